@@ -0,0 +1,8 @@
+// intel_tex_2 links a prebuilt C++ object, without the C++ runtime it needs
+fn main() {
+    match std::env::var("CARGO_CFG_TARGET_OS").as_deref() {
+        Ok("linux") | Ok("android") => println!("cargo:rustc-link-lib=stdc++"),
+        Ok("macos") | Ok("ios") => println!("cargo:rustc-link-lib=c++"),
+        _ => {}
+    }
+}
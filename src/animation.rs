@@ -0,0 +1,58 @@
+// Animations grouped from numbered frames, `run_0.png` ... `run_7.png` make the animation `run`
+//
+// Sprite names without their extension are matched against a pattern whose `name` group names the
+// animation and `frame` group numbers the frame. Frames are ordered by number, not by name, so
+// `run_10` follows `run_9`. Sprites that don't match are left out of every animation.
+
+use globset::GlobMatcher;
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+
+use crate::{strip_extension, AtlasRecord};
+
+// Frames named <name>_<number>, <name>-<number> or <name><number>
+pub const DEFAULT_PATTERN: &str = r"^(?P<name>.*?)[_-]?(?P<frame>\d+)$";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AnimationRecord {
+    pub name: String,
+    // Record names of the frames in playing order
+    pub frames: Vec<String>,
+    // Milliseconds every frame is shown for, None when no --frame-duration matched
+    pub duration: Option<u32>
+}
+
+// Whether `pattern` has the groups animations are grouped by
+pub fn check_pattern(pattern: &Regex) -> Result<(), String> {
+    for group in ["name", "frame"] {
+        if !pattern.capture_names().any(|name| name == Some(group)) {
+            return Err(format!("animation pattern {} has no (?P<{}>...) group", pattern, group));
+        }
+    }
+    Ok(())
+}
+
+// The animations among `records` in name order, with the duration of the first rule matching their name
+pub fn group(records: &[AtlasRecord], pattern: &Regex, durations: &[(GlobMatcher, u32)]) -> Vec<AnimationRecord> {
+    let mut frames: Vec<(String, u64, &str)> = records.iter()
+        .filter_map(|record| {
+            let captures = pattern.captures(strip_extension(&record.name))?;
+            let name = captures.name("name")?.as_str();
+            let frame = captures.name("frame")?.as_str().parse().ok()?;
+            (!name.is_empty()).then(|| (name.to_string(), frame, record.name.as_str()))
+        })
+        .collect();
+    frames.sort();
+
+    let mut animations: Vec<AnimationRecord> = Vec::new();
+    for (name, _, record) in frames {
+        match animations.last_mut() {
+            Some(animation) if animation.name == name => animation.frames.push(record.to_string()),
+            _ => {
+                let duration = durations.iter().find(|(glob, _)| glob.is_match(&name)).map(|&(_, duration)| duration);
+                animations.push(AnimationRecord { name, frames: vec![record.to_string()], duration });
+            }
+        }
+    }
+    animations
+}
@@ -0,0 +1,45 @@
+// Aseprite files, packed as they are saved without exporting them first
+//
+// Every frame is flattened the way Aseprite shows it, visible layers blended in order, and becomes
+// a sprite of the file's full canvas size. Tags become animations of their frames in playing order,
+// ping-pong tags going back down without repeating their ends.
+
+use asefile::{AnimationDirection, AsepriteFile};
+
+pub const EXTENSIONS: [&str; 2] = ["ase", "aseprite"];
+
+pub struct Aseprite {
+    pub width: u32,
+    pub height: u32,
+    // RGBA8 pixels of every frame
+    pub frames: Vec<Vec<u8>>,
+    // Milliseconds every frame is shown for
+    pub durations: Vec<u32>,
+    // Names of the tags and their frame numbers in playing order
+    pub tags: Vec<(String, Vec<u32>)>
+}
+
+pub fn read(bytes: &[u8]) -> Result<Aseprite, String> {
+    let file = AsepriteFile::read(bytes).map_err(|err| err.to_string())?;
+    let (width, height) = (file.width() as u32, file.height() as u32);
+    if width == 0 || height == 0 || file.num_frames() == 0 {
+        return Err(format!("empty sprite ({}x{}, {} frames)", width, height, file.num_frames()));
+    }
+
+    let frames = (0..file.num_frames()).map(|frame| file.frame(frame).image().into_raw()).collect();
+    let durations = (0..file.num_frames()).map(|frame| file.frame(frame).duration()).collect();
+
+    let mut tags = Vec::new();
+    for id in 0..file.num_tags() {
+        let tag = file.tag(id);
+        let (from, to) = (tag.from_frame(), tag.to_frame().min(file.num_frames() - 1));
+        let frames: Vec<u32> = match tag.animation_direction() {
+            AnimationDirection::Forward => (from..=to).collect(),
+            AnimationDirection::Reverse => (from..=to).rev().collect(),
+            AnimationDirection::PingPong => (from..=to).chain((from + 1..to).rev()).collect()
+        };
+        tags.push((tag.name().to_string(), frames));
+    }
+
+    Ok(Aseprite { width, height, frames, durations, tags })
+}
@@ -0,0 +1,117 @@
+// ASTC 4x4 block encoder
+//
+// Every block is a single partition with direct LDR endpoints at full 8 bit precision: RGB with
+// 3 bit weights for opaque blocks, RGBA with 2 bit weights otherwise. The endpoints span the
+// block's texels along their principal axis. Far from what dedicated encoders reach, but every
+// ASTC decoder reads it.
+
+// Color endpoint modes
+const CEM_RGB_DIRECT: u128 = 8;
+const CEM_RGBA_DIRECT: u128 = 12;
+
+// Block modes of a 4x4 weight grid without dual planes, with 3 and 2 bit weights
+const MODE_WEIGHTS_3_BITS: u128 = 83;
+const MODE_WEIGHTS_2_BITS: u128 = 66;
+
+// Endpoints start after the block mode, partition count and endpoint mode
+const ENDPOINTS_OFFSET: u32 = 17;
+
+// Direction along which the texels spread the most, found by power iteration on their covariance
+fn principal_axis(texels: &[[f32; 4]], mean: [f32; 4], channels: usize) -> [f32; 4] {
+    let mut covariance = [[0.0f32; 4]; 4];
+    for texel in texels {
+        for i in 0..channels {
+            for j in 0..channels {
+                covariance[i][j] += (texel[i] - mean[i]) * (texel[j] - mean[j]);
+            }
+        }
+    }
+
+    let mut axis = [1.0f32; 4];
+    for _ in 0..8 {
+        let mut next = [0.0f32; 4];
+        for i in 0..channels {
+            next[i] = (0..channels).map(|j| covariance[i][j] * axis[j]).sum();
+        }
+        let length = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if length < 1e-6 {
+            return [0.0; 4];
+        }
+        axis = next.map(|v| v / length);
+    }
+    axis
+}
+
+// Encode the 16 RGBA texels of a block, row by row
+fn encode_block(block: &[[u8; 4]; 16]) -> [u8; 16] {
+    let opaque = block.iter().all(|texel| texel[3] == 255);
+    let (channels, levels, mode, cem) = match opaque {
+        true => (3, 8, MODE_WEIGHTS_3_BITS, CEM_RGB_DIRECT),
+        false => (4, 4, MODE_WEIGHTS_2_BITS, CEM_RGBA_DIRECT)
+    };
+
+    let texels: Vec<[f32; 4]> = block.iter().map(|texel| texel.map(|v| v as f32)).collect();
+    let mut mean = [0.0f32; 4];
+    for texel in texels.iter() {
+        for c in 0..4 {
+            mean[c] += texel[c] / 16.0;
+        }
+    }
+    let axis = principal_axis(&texels, mean, channels);
+
+    let project = |texel: &[f32; 4], origin: [f32; 4], direction: [f32; 4]| -> f32 {
+        (0..channels).map(|c| (texel[c] - origin[c]) * direction[c]).sum()
+    };
+    let (low, high) = texels.iter()
+        .map(|texel| project(texel, mean, axis))
+        .fold((f32::MAX, f32::MIN), |(low, high), t| (low.min(t), high.max(t)));
+    let endpoint = |t: f32| -> [u8; 4] {
+        let mut color = [255u8; 4];
+        for c in 0..channels {
+            color[c] = (mean[c] + t * axis[c]).round().clamp(0.0, 255.0) as u8;
+        }
+        color
+    };
+    let (mut first, mut second) = (endpoint(low), endpoint(high));
+
+    // The decoder swaps endpoints whose second color is darker, so keep the brighter one second
+    let brightness = |color: [u8; 4]| color[..3].iter().map(|&v| v as u32).sum::<u32>();
+    if brightness(second) < brightness(first) {
+        std::mem::swap(&mut first, &mut second);
+    }
+
+    let span: [f32; 4] = std::array::from_fn(|c| second[c] as f32 - first[c] as f32);
+    let span_length: f32 = span.iter().map(|v| v * v).sum();
+    let origin = first.map(|v| v as f32);
+    let mut weights: u128 = 0;
+    for (i, texel) in texels.iter().enumerate() {
+        let t = if span_length > 0.0 { project(texel, origin, span) / span_length } else { 0.0 };
+        let weight = (t.clamp(0.0, 1.0) * (levels - 1) as f32).round() as u128;
+        weights |= weight << (i as u32 * (levels as u32).trailing_zeros());
+    }
+
+    let mut bits = mode | cem << 13;
+    for c in 0..channels {
+        bits |= (first[c] as u128) << (ENDPOINTS_OFFSET + 16 * c as u32);
+        bits |= (second[c] as u128) << (ENDPOINTS_OFFSET + 16 * c as u32 + 8);
+    }
+    // Weights are stored from the top of the block down, bit reversed
+    bits |= weights.reverse_bits();
+    bits.to_le_bytes()
+}
+
+// Compress a `width` x `height` RGBA8 image, both multiples of 4, block row by block row
+pub fn compress(rgba: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let mut blocks = Vec::with_capacity((width / 4 * height / 4 * 16) as usize);
+    for block_y in (0..height).step_by(4) {
+        for block_x in (0..width).step_by(4) {
+            let block = std::array::from_fn(|i| {
+                let (x, y) = (block_x + i as u32 % 4, block_y + i as u32 / 4);
+                let index = ((y * width + x) * 4) as usize;
+                [rgba[index], rgba[index + 1], rgba[index + 2], rgba[index + 3]]
+            });
+            blocks.extend(encode_block(&block));
+        }
+    }
+    blocks
+}
@@ -0,0 +1,169 @@
+// Finding packed sprites that no source file refers to
+//
+// Source files are scanned for quoted strings. A string refers to a sprite when it, or its last
+// path component, is the sprite name with or without the extension ("hero.png", "hero",
+// "sprites/hero.png"). Quoted strings naming any image atlast packs (.png, .jpg, .aseprite, ...)
+// that match no sprite are reported too. In Rust sources single quotes are char literals,
+// lifetimes and loop labels, never strings.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::cubemap;
+
+pub struct Reference {
+    pub path: PathBuf,
+    pub line: usize,
+    pub text: String
+}
+
+pub struct Audit {
+    // Sprites no source file refers to, sorted
+    pub unreferenced: Vec<String>,
+    // Image references that match no sprite
+    pub missing: Vec<Reference>,
+    pub files: usize
+}
+
+// Length of a Rust char literal up to its closing quote, `None` for lifetimes and loop labels
+fn char_literal(rest: &str) -> Option<usize> {
+    let mut chars = rest.chars();
+    let body = match chars.next()? {
+        '\\' => 1 + chars.next()?.len_utf8() + chars.as_str().find('\'')?,
+        c => c.len_utf8()
+    };
+    rest[body..].starts_with('\'').then_some(body)
+}
+
+// Quoted strings on a line, a quote left open runs to the end of the line and is ignored.
+// With `rust` single quotes open no strings, char literals are skipped whole.
+fn literals(line: &str, rust: bool) -> Vec<&str> {
+    let mut found = Vec::new();
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((start, quote)) = chars.next() {
+        if quote == '\'' && rust {
+            if let Some(body) = char_literal(&line[start + 1..]) {
+                while chars.next_if(|&(i, _)| i <= start + 1 + body).is_some() {}
+            }
+            continue;
+        }
+        if quote != '"' && quote != '\'' {
+            continue;
+        }
+
+        let mut escaped = false;
+        for (i, c) in chars.by_ref() {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == quote {
+                found.push(&line[start + 1..i]);
+                break;
+            }
+        }
+    }
+
+    found
+}
+
+fn stem(name: &str) -> &str {
+    name.rsplit_once('.').map_or(name, |(stem, _)| stem)
+}
+
+pub fn scan(code_dir: &Path, names: &[String]) -> Audit {
+    // Every spelling that refers to a sprite
+    let mut keys: HashMap<&str, Vec<&str>> = HashMap::new();
+    for name in names {
+        keys.entry(name).or_default().push(name);
+        keys.entry(stem(name)).or_default().push(name);
+        if let Some((cube, _)) = cubemap::face(name) {
+            keys.entry(cube).or_default().push(name);
+        }
+    }
+
+    let mut referenced: HashSet<&str> = HashSet::new();
+    let mut missing = Vec::new();
+    let mut files = 0;
+
+    for entry in WalkDir::new(code_dir).into_iter().filter_map(|entry| entry.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        // Binary files are not source
+        let text = match std::fs::read_to_string(entry.path()) {
+            Ok(text) => text,
+            Err(_) => continue
+        };
+        files += 1;
+        let rust = entry.path().extension().is_some_and(|ext| ext == "rs");
+
+        for (number, line) in text.lines().enumerate() {
+            for literal in literals(line, rust) {
+                let file_name = literal.rsplit(['/', '\\']).next().unwrap();
+                let sprites = keys.get(literal).or_else(|| keys.get(file_name));
+
+                if let Some(sprites) = sprites {
+                    referenced.extend(sprites.iter().copied());
+                } else if crate::is_image(Path::new(file_name)) || crate::is_aseprite(Path::new(file_name)) {
+                    missing.push(Reference {
+                        path: entry.path().to_path_buf(),
+                        line: number + 1,
+                        text: literal.to_string()
+                    });
+                }
+            }
+        }
+    }
+
+    let mut unreferenced: Vec<String> = names.iter()
+        .filter(|name| !referenced.contains(name.as_str()))
+        .cloned()
+        .collect();
+    unreferenced.sort();
+    unreferenced.dedup();
+
+    Audit {
+        unreferenced,
+        missing,
+        files
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{literals, scan};
+
+    #[test]
+    fn lifetimes_and_char_literals_open_no_strings() {
+        let line = "fn sprite<'a>(atlas: &'a Atlas) -> &'a Sprite { atlas.get(\"hero.png\") }";
+        assert_eq!(literals(line, true), ["hero.png"]);
+        // Elsewhere single quotes are strings
+        assert_eq!(literals("load('hero.png', \"ui/button\")", false), ["hero.png", "ui/button"]);
+
+        // A quote inside a char literal does not open a string either
+        let line = r#"let (quote, tick, escaped) = ('"', '\'', '\u{22}'); 'outer: loop { load("coin") }"#;
+        assert_eq!(literals(line, true), ["coin"]);
+    }
+
+    #[test]
+    fn references_to_any_input_format_can_be_missing() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-audit-formats", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("game.rs"), concat!(
+            "fn sprites<'a>(atlas: &'a Atlas) -> [&'a Sprite; 4] {\n",
+            "    [atlas.get(\"hero.png\"), atlas.get(\"ui/title.jpg\"), atlas.get(\"boss.aseprite\"), atlas.get(\"level.json\")]\n",
+            "}\n"
+        )).unwrap();
+
+        let audit = scan(&dir, &["hero.png".to_string(), "coin.png".to_string()]);
+        assert_eq!(audit.unreferenced, ["coin.png"]);
+        let missing: Vec<_> = audit.missing.iter().map(|reference| (reference.line, reference.text.as_str())).collect();
+        assert_eq!(missing, [(2, "ui/title.jpg"), (2, "boss.aseprite")]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
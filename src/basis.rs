@@ -0,0 +1,67 @@
+// Basis Universal textures, a single file that transcodes to whichever GPU format the device has
+//
+// Atlases are encoded as UASTC 4x4, the high quality Basis mode, as a 2D texture holding one image
+// per array layer or cubemap face. Mip levels come from `mipmap` rather than the encoder so they
+// stay within their sprites.
+
+use std::io::{self, Write};
+
+use basis_universal::{BasisTextureFormat, ColorSpace, Compressor, CompressorParams, TranscodeParameters, Transcoder,
+                      TranscoderTextureFormat};
+
+// Write the `images` of `width` x `height` RGBA8 `levels`, the base level first and every level
+// holding all images
+pub fn write<W: Write>(w: &mut W, width: u32, height: u32, images: u32, levels: &[Vec<u8>]) -> io::Result<()> {
+    let mut params = CompressorParams::new();
+    params.set_basis_format(BasisTextureFormat::UASTC4x4);
+    params.set_color_space(ColorSpace::Srgb);
+    params.set_generate_mipmaps(false);
+
+    for image in 0..images {
+        for (level, pixels) in levels.iter().enumerate() {
+            let (level_width, level_height) = ((width >> level).max(1), (height >> level).max(1));
+            let size = pixels.len() / images as usize;
+            let pixels = &pixels[image as usize * size..(image as usize + 1) * size];
+            match level {
+                0 => params.source_image_mut(image).init(pixels, level_width, level_height, 4),
+                _ => params.source_mipmap_image_mut(image, level as u32 - 1).init(pixels, level_width, level_height, 4)
+            }
+        }
+    }
+
+    let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get() as u32);
+    let mut compressor = Compressor::new(threads);
+    // Safety: the parameters only hold complete RGBA8 images of matching sizes
+    unsafe {
+        if !compressor.init(&params) {
+            return Err(io::Error::other("could not set up the Basis Universal encoder"));
+        }
+        compressor.process().map_err(|err| io::Error::other(format!("Basis Universal encoding failed: {:?}", err)))?;
+    }
+    w.write_all(compressor.basis_file())
+}
+
+// Transcode the base level of every image of a texture written by `write` back to RGBA8, with its
+// number of images (0 for a single one)
+pub fn read(bytes: &[u8]) -> Option<(u32, u32, u32, Vec<u8>)> {
+    let mut transcoder = Transcoder::new();
+    if !transcoder.validate_header(bytes) {
+        return None;
+    }
+    let images = transcoder.image_count(bytes);
+    let description = transcoder.image_level_description(bytes, 0, 0)?;
+    transcoder.prepare_transcoding(bytes).ok()?;
+
+    let mut pixels = Vec::new();
+    for image in 0..images {
+        let parameters = TranscodeParameters {
+            image_index: image,
+            ..Default::default()
+        };
+        pixels.extend(transcoder.transcode_image_level(bytes, TranscoderTextureFormat::RGBA32, parameters).ok()?);
+    }
+    transcoder.end_transcoding();
+
+    let layers = if images == 1 { 0 } else { images };
+    Some((description.original_width, description.original_height, layers, pixels))
+}
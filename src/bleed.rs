@@ -0,0 +1,63 @@
+// Alpha bleeding, spreading the colors of visible texels into the fully transparent ones around them
+//
+// Bilinear filtering blends the edge of a sprite with its transparent neighbors, whose color is
+// usually black, and leaves dark halos. Transparent texels take the average color of their visible
+// or already filled 8 neighbors, wave after wave, and keep their alpha of 0. Texels of a sprite only
+// take colors from the same sprite, the padding between sprites from any of them.
+
+// Fill the transparent texels of the RGBA `pixels` of a `width` x `height` canvas, `owners` being
+// the sprite of every texel as `mipmap::owners` gives them
+pub fn bleed(width: u32, height: u32, pixels: &mut [u8], owners: &[u32]) {
+    let (width, height) = (width as i64, height as i64);
+    let mut filled: Vec<bool> = pixels.chunks(4).map(|px| px[3] != 0).collect();
+
+    let neighbors = move |i: usize| {
+        let (x, y) = (i as i64 % width, i as i64 / width);
+        (-1..=1).flat_map(move |dy| (-1..=1).map(move |dx| (x + dx, y + dy)))
+            .filter(move |&(nx, ny)| (nx, ny) != (x, y) && (0..width).contains(&nx) && (0..height).contains(&ny))
+            .map(move |(nx, ny)| (ny * width + nx) as usize)
+    };
+    let usable = |texel: usize, neighbor: usize| owners[texel] == 0 || owners[neighbor] == owners[texel];
+
+    let mut queued = filled.clone();
+    let mut wave: Vec<usize> = Vec::new();
+    for i in (0..filled.len()).filter(|&i| !filled[i]) {
+        if neighbors(i).any(|n| filled[n] && usable(i, n)) {
+            queued[i] = true;
+            wave.push(i);
+        }
+    }
+
+    while !wave.is_empty() {
+        // Colors of the whole wave are taken before any of it is filled
+        let colors: Vec<[u8; 3]> = wave.iter()
+            .map(|&i| {
+                let mut sum = [0u32; 3];
+                let mut count = 0;
+                for n in neighbors(i).filter(|&n| filled[n] && usable(i, n)) {
+                    for channel in 0..3 {
+                        sum[channel] += pixels[n * 4 + channel] as u32;
+                    }
+                    count += 1;
+                }
+                sum.map(|sum| ((sum + count / 2) / count) as u8)
+            })
+            .collect();
+
+        for (&i, color) in wave.iter().zip(colors) {
+            pixels[i * 4..i * 4 + 3].copy_from_slice(&color);
+            filled[i] = true;
+        }
+
+        let mut next = Vec::new();
+        for &i in wave.iter() {
+            for n in neighbors(i) {
+                if !queued[n] && usable(n, i) {
+                    queued[n] = true;
+                    next.push(n);
+                }
+            }
+        }
+        wave = next;
+    }
+}
@@ -0,0 +1,169 @@
+// Incremental rebuilds
+//
+// The decoded inputs, the layout and the composed pixels of a build are kept in a cache file. The
+// next build with the same settings only decodes files whose contents changed, keeps the layout
+// when every sprite has the same name and size in the same order, and then only composes the
+// sprites whose pixels changed over the previous pixels. The texture is still encoded whole.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bincode::{serialize, deserialize};
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use log::warn;
+
+use crate::effects::Effects;
+use crate::nineslice;
+use crate::pivot;
+use crate::userdata;
+use crate::{AtlasError, Image, Rect};
+
+// Content hash and decoded image of every input by path, None for left out placeholders
+type Files = HashMap<PathBuf, ([u8; 32], Option<Image>)>;
+
+#[derive(Serialize, Deserialize, Default)]
+struct CacheData {
+    // Hash of the settings the cache was written with, other settings start over
+    settings: u64,
+    files: Files,
+    layout: Option<Layout>,
+    canvas: Option<Canvas>
+}
+
+#[derive(Serialize, Deserialize)]
+struct Layout {
+    // Name, width and height of every sprite in placement order
+    sprites: Vec<(String, u32, u32)>,
+    width: u32,
+    rects: Vec<Rect>
+}
+
+#[derive(Serialize, Deserialize)]
+struct Canvas {
+    width: u32,
+    height: u32,
+    rects: Vec<Rect>,
+    // Hash of the pixels drawn at each rect
+    sprites: Vec<[u8; 32]>,
+    pixels: Vec<u8>
+}
+
+pub struct Cache {
+    path: PathBuf,
+    previous: CacheData,
+    // Inputs of this build, replacing the previous ones when saved
+    files: Mutex<Files>,
+    // Inputs that were decoded rather than taken from the cache
+    decoded: AtomicUsize,
+    // Whether the previous layout was kept
+    pub kept_layout: bool,
+    layout: Option<Layout>,
+    canvas: Option<Canvas>
+}
+
+impl Cache {
+    // The cache at `path`, empty when there is none or it was written with other settings
+    pub fn open(path: &Path, settings: u64) -> Cache {
+        let previous = match std::fs::read(path) {
+            Ok(bytes) => match deserialize::<CacheData>(&bytes) {
+                Ok(data) if data.settings == settings => data,
+                Ok(_) => CacheData { settings, ..CacheData::default() },
+                Err(err) => {
+                    warn!("ignoring unreadable cache {:?}: {}", path, err);
+                    CacheData { settings, ..CacheData::default() }
+                }
+            },
+            Err(_) => CacheData { settings, ..CacheData::default() }
+        };
+
+        Cache {
+            path: path.to_path_buf(),
+            previous,
+            files: Mutex::new(HashMap::new()),
+            decoded: AtomicUsize::new(0),
+            kept_layout: false,
+            layout: None,
+            canvas: None
+        }
+    }
+
+    // Hash of an input, covering its effects sidecar which changes how it is decoded
+    pub fn hash_file(path: &Path, bytes: &[u8]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(bytes);
+        for sidecar in [Effects::sidecar(path), nineslice::sidecar(path), pivot::sidecar(path), userdata::sidecar(path)] {
+            if let Ok(sidecar) = std::fs::read(sidecar) {
+                hasher.update(sidecar);
+            }
+        }
+        hasher.finalize().into()
+    }
+
+    // The image decoded from the same contents by the previous build
+    pub fn image(&self, path: &Path, hash: &[u8; 32]) -> Option<Option<Image>> {
+        let (_, image) = self.previous.files.get(path).filter(|(previous, _)| previous == hash)?;
+        self.remember(path, hash, image, false);
+        Some(image.clone())
+    }
+
+    pub fn remember(&self, path: &Path, hash: &[u8; 32], image: &Option<Image>, decoded: bool) {
+        if decoded {
+            self.decoded.fetch_add(1, Ordering::Relaxed);
+        }
+        self.files.lock().unwrap().insert(path.to_path_buf(), (*hash, image.clone()));
+    }
+
+    // Inputs decoded by this build and those taken from the cache
+    pub fn counts(&self) -> (usize, usize) {
+        let decoded = self.decoded.load(Ordering::Relaxed);
+        (decoded, self.files.lock().unwrap().len() - decoded)
+    }
+
+    fn sprites(images: &[Image]) -> Vec<(String, u32, u32)> {
+        images.iter().map(|image| (image.name.clone(), image.width, image.height)).collect()
+    }
+
+    // Width and rects of the previous layout if `images` are the sprites it placed
+    pub fn layout(&mut self, images: &[Image]) -> Option<(u32, Vec<Rect>)> {
+        let sprites = Cache::sprites(images);
+        let layout = self.previous.layout.take().filter(|layout| layout.sprites == sprites)?;
+        self.kept_layout = true;
+        let found = (layout.width, layout.rects.clone());
+        self.layout = Some(layout);
+        Some(found)
+    }
+
+    pub fn remember_layout(&mut self, images: &[Image], width: u32, rects: &[Rect]) {
+        self.layout = Some(Layout { sprites: Cache::sprites(images), width, rects: rects.to_vec() });
+    }
+
+    // Pixels of the previous build and the hash of every sprite drawn into them, if composed
+    // `width` by `height` with sprites at `rects`
+    pub fn canvas(&self, width: u32, height: u32, rects: &[Rect]) -> Option<(&[[u8; 32]], Vec<u8>)> {
+        self.previous.canvas.as_ref()
+            .filter(|canvas| (canvas.width, canvas.height) == (width, height) && canvas.rects == rects)
+            .map(|canvas| (canvas.sprites.as_slice(), canvas.pixels.clone()))
+    }
+
+    pub fn remember_canvas(&mut self, width: u32, height: u32, rects: &[Rect], sprites: Vec<[u8; 32]>, pixels: Vec<u8>) {
+        self.canvas = Some(Canvas { width, height, rects: rects.to_vec(), sprites, pixels });
+    }
+
+    pub fn hash_pixels(data: &[u8]) -> [u8; 32] {
+        Sha256::digest(data).into()
+    }
+
+    // Write what this build decoded, placed and composed
+    pub fn save(self) -> Result<(), AtlasError> {
+        let data = CacheData {
+            settings: self.previous.settings,
+            files: self.files.into_inner().unwrap(),
+            layout: self.layout,
+            canvas: self.canvas
+        };
+        std::fs::write(&self.path, serialize(&data)?).map_err(|err| AtlasError::Io(self.path.clone(), err))
+    }
+}
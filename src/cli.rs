@@ -0,0 +1,3251 @@
+// The atlast command line tool
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use std::ffi::{OsStr, OsString};
+use std::sync::mpsc;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use indicatif::ParallelProgressIterator;
+use log::{debug, error, info, trace, warn, LevelFilter};
+use notify::{EventKind, RecursiveMode, Watcher};
+use notify::event::{MetadataKind, ModifyKind};
+
+use crate::*;
+use crate::conflict::{Conflicts, Resolution};
+use crate::preset::Preset;
+use crate::progress;
+
+// Exit statuses scripts can tell apart
+const EXIT_FAILED: i32 = 1;
+const EXIT_EMPTY: i32 = 2;
+// `diff` follows diff(1), 1 when the atlases differ and 2 when they could not be compared
+const EXIT_DIFFERENT: i32 = 1;
+const EXIT_TROUBLE: i32 = 2;
+
+
+fn positive_integer(value: String) -> Result<(), String> {
+    match value.parse::<u32>() {
+        Ok(n) if n > 0 => Ok(()),
+        _ => Err(format!("expected a positive integer, got {}", value))
+    }
+}
+
+fn non_negative_integer(value: String) -> Result<(), String> {
+    match value.parse::<u32>() {
+        Ok(_) => Ok(()),
+        _ => Err(format!("expected a non-negative integer, got {}", value))
+    }
+}
+
+fn positive_number(value: String) -> Result<(), String> {
+    match value.parse::<f32>() {
+        Ok(f) if f > 0.0 && f.is_finite() => Ok(()),
+        _ => Err(format!("expected a positive number, got {}", value))
+    }
+}
+
+fn group_rule(value: String) -> Result<(), String> {
+    match value.split_once('=') {
+        Some((pattern, output)) if !output.is_empty() => Glob::new(pattern).map(|_| ()).map_err(|err| err.to_string()),
+        _ => Err(format!("expected PATTERN=FILE, got {}", value))
+    }
+}
+
+fn scale_rule(value: String) -> Result<(), String> {
+    match value.rsplit_once('=') {
+        Some((pattern, scale)) => {
+            Glob::new(pattern).map_err(|err| err.to_string())?;
+            positive_number(scale.to_string())
+        }
+        None => Err(format!("expected PATTERN=FACTOR, got {}", value))
+    }
+}
+
+fn animation_pattern(value: String) -> Result<(), String> {
+    let pattern = regex::Regex::new(&value).map_err(|err| err.to_string())?;
+    animation::check_pattern(&pattern)
+}
+
+fn duration_rule(value: String) -> Result<(), String> {
+    match value.rsplit_once('=') {
+        Some((pattern, duration)) => {
+            Glob::new(pattern).map_err(|err| err.to_string())?;
+            positive_integer(duration.to_string())
+        }
+        None => Err(format!("expected PATTERN=MILLISECONDS, got {}", value))
+    }
+}
+
+fn nine_slice_rule(value: String) -> Result<(), String> {
+    match value.rsplit_once('=') {
+        Some((pattern, borders)) => {
+            Glob::new(pattern).map_err(|err| err.to_string())?;
+            NineSlice::parse(borders).map(|_| ())
+        }
+        None => Err(format!("expected PATTERN=LEFT,TOP,RIGHT,BOTTOM, got {}", value))
+    }
+}
+
+fn pivot_point(value: String) -> Result<(), String> {
+    pivot::parse(&value).map(|_| ())
+}
+
+fn pivot_rule(value: String) -> Result<(), String> {
+    match value.rsplit_once('=') {
+        Some((pattern, point)) => {
+            Glob::new(pattern).map_err(|err| err.to_string())?;
+            pivot::parse(point).map(|_| ())
+        }
+        None => Err(format!("expected PATTERN=X,Y, got {}", value))
+    }
+}
+
+// JSON has equals signs of its own, so the pattern ends at the first
+fn user_data_rule(value: String) -> Result<(), String> {
+    match value.split_once('=') {
+        Some((pattern, data)) => {
+            Glob::new(pattern).map_err(|err| err.to_string())?;
+            userdata::check(data).map(|_| ())
+        }
+        None => Err(format!("expected PATTERN=JSON, got {}", value))
+    }
+}
+
+fn scale_set(value: String) -> Result<(), String> {
+    value.split(',').try_for_each(|scale| positive_number(scale.trim().to_string()))
+}
+
+fn glob(value: String) -> Result<(), String> {
+    Glob::new(&value).map(|_| ()).map_err(|err| err.to_string())
+}
+
+// Read "<sprite name> <uses>" lines of a usage report, also accepting commas as separators
+fn read_usage(path: &Path) -> Result<HashMap<String, f64>, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut usage = HashMap::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let entry = line.rsplit_once(|c: char| c.is_whitespace() || c == ',')
+            .and_then(|(name, uses)| Some((name.trim().trim_end_matches(',').trim_end().to_string(), uses.parse::<f64>().ok()?)));
+        match entry {
+            Some((name, uses)) if uses.is_finite() => {
+                usage.insert(name, uses);
+            }
+            _ => return Err(format!("line {}: expected a sprite name and a number, got {:?}", number + 1, line))
+        }
+    }
+
+    Ok(usage)
+}
+
+// Read "<pattern> <priority>" lines, skipping blank lines and # comments
+fn read_priorities(path: &Path) -> Result<Vec<(GlobMatcher, i32)>, String> {
+    let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut rules = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let rule = line.rsplit_once(char::is_whitespace)
+            .and_then(|(pattern, priority)| Some((Glob::new(pattern.trim()).ok()?, priority.parse().ok()?)));
+        match rule {
+            Some((glob, priority)) => rules.push((glob.compile_matcher(), priority)),
+            None => return Err(format!("line {}: expected a pattern and a priority, got {:?}", number + 1, line))
+        }
+    }
+
+    Ok(rules)
+}
+
+// "4MB", "512KB", "1GB" or plain bytes, in powers of 1024
+fn parse_bytes(value: &str) -> Option<u64> {
+    let upper = value.trim().to_ascii_uppercase();
+    let (number, unit) = match upper.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => upper.split_at(i),
+        None => (upper.as_str(), "")
+    };
+
+    let unit = match unit.trim() {
+        "" | "B" => 1,
+        "KB" | "K" | "KIB" => 1 << 10,
+        "MB" | "M" | "MIB" => 1 << 20,
+        "GB" | "G" | "GIB" => 1 << 30,
+        _ => return None
+    };
+    number.parse::<u64>().ok()?.checked_mul(unit)
+}
+
+// "WIDTHxHEIGHT" or "WIDTHxHEIGHTxPAGES" as a number of pixels
+fn parse_pixels(value: &str) -> Option<u64> {
+    let dims: Vec<u64> = value.split('x').map(|dim| dim.parse().ok()).collect::<Option<_>>()?;
+    match dims.as_slice() {
+        [width, height] => width.checked_mul(*height),
+        [width, height, pages] => width.checked_mul(*height)?.checked_mul(*pages),
+        _ => None
+    }
+}
+
+fn byte_budget(value: String) -> Result<(), String> {
+    match parse_bytes(&value) {
+        Some(_) => Ok(()),
+        None => Err(format!("expected a size such as 4MB, got {}", value))
+    }
+}
+
+fn pixel_budget(value: String) -> Result<(), String> {
+    match parse_pixels(&value) {
+        Some(_) => Ok(()),
+        None => Err(format!("expected WIDTHxHEIGHT or WIDTHxHEIGHTxPAGES, got {}", value))
+    }
+}
+
+fn fraction(value: String) -> Result<(), String> {
+    match value.parse::<f64>() {
+        Ok(f) if f > 0.0 && f <= 1.0 => Ok(()),
+        _ => Err(format!("expected a number in (0, 1], got {}", value))
+    }
+}
+
+
+// Flags are set with ATLAST_<FLAG>=1, options get their ATLAST_<OPTION> value from clap
+const ENV_FLAGS: [&str; 30] = [
+    "per-directory", "ktx", "texture-array", "composite-layers", "append", "to-srgb", "no-normalize", "sdf",
+    "height-to-normal", "msdf", "allow-empty", "verify", "seam-test", "strict", "yes", "allow-rotation", "trim",
+    "pretty", "pot", "square", "dedupe", "watch", "quiet", "dry-run", "mipmaps",
+    "premultiply-alpha", "alpha-bleed", "animations", "polygons", "linear-filtering"
+];
+
+// Environment variable for an argument, `pixel-format` is ATLAST_PIXEL_FORMAT
+fn env_name(arg: &str) -> String {
+    format!("ATLAST_{}", arg.to_uppercase().replace('-', "_"))
+}
+
+// Given on the command line or through the environment, rather than left at its default
+fn explicit(matches: &ArgMatches, arg: &str) -> bool {
+    matches.occurrences_of(arg) > 0 || std::env::var_os(env_name(arg)).is_some()
+}
+
+// Commands taking the packing options, the flags in ENV_FLAGS belong to them
+const PACK_COMMANDS: [&str; 4] = ["pack", "compare", "doctor", "merge"];
+const COMMANDS: [&str; 9] = ["pack", "compare", "doctor", "merge", "inspect", "unpack", "verify", "diff", "audit"];
+
+// Command line arguments with the flags enabled in the environment added, unless already given
+//
+// Arguments that do not start with a command are packing options from before there were commands,
+// so `atlast -d sprites` still packs.
+fn with_env_flags(mut args: Vec<OsString>) -> Vec<OsString> {
+    let first = args.get(1).and_then(|arg| arg.to_str());
+    let named = first.is_some_and(|arg| COMMANDS.contains(&arg) || ["help", "-h", "--help", "-V", "--version"].contains(&arg));
+    if !named {
+        args.insert(1.min(args.len()), OsString::from("pack"));
+    }
+    if !args.get(1).and_then(|arg| arg.to_str()).is_some_and(|arg| PACK_COMMANDS.contains(&arg)) {
+        return args;
+    }
+
+    for flag in ENV_FLAGS.iter() {
+        let enabled = std::env::var(env_name(flag))
+            .is_ok_and(|value| ["1", "true", "yes"].contains(&value.to_lowercase().as_str()));
+        let long = format!("--{}", flag);
+        let given = args.iter().any(|arg| arg.to_string_lossy().split('=').next() == Some(long.as_str()));
+        // Last, so a flag like --append that may take a value never takes a positional argument
+        if enabled && !given {
+            args.push(OsString::from(long));
+        }
+    }
+
+    args
+}
+
+fn app<'a, 'b>() -> App<'a, 'b> {
+    App::new("atlast")
+        .version("1.0")
+        .author("Devin Vander Stelt")
+        .about("Create texture atlases that last")
+        .subcommand(pack_args(SubCommand::with_name("pack")
+             .about("Pack the images under a directory into an atlas")))
+        .subcommand(pack_args(SubCommand::with_name("compare")
+             .about("Pack the same input with every sort order and print the resulting sizes")))
+        .subcommand(pack_args(SubCommand::with_name("doctor")
+             .about("Report problems with the input images without packing them")))
+        .subcommand(pack_args(SubCommand::with_name("merge")
+             .about("Repack the sprites of existing atlases into one")
+             .arg(Arg::with_name("atlases")
+                  .value_name("ATLAS")
+                  .multiple(true)
+                  .required(true)
+                  .help("Atlases to merge, sprites of the same name only once if they look the same"))))
+        .subcommand(SubCommand::with_name("inspect")
+             .about("Print the sprites of an atlas with their rects and UVs, its size and how much of it they fill")
+             .arg(Arg::with_name("atlas")
+                  .value_name("ATLAS")
+                  .default_value("output.atlas")
+                  .help("Atlas to inspect"))
+             .arg(Arg::with_name("json")
+                  .long("json")
+                  .help("Print the same as JSON"))
+             .arg(key_arg()))
+        .subcommand(SubCommand::with_name("unpack")
+             .about("Write every sprite of an atlas back out as a png named after it")
+             .arg(Arg::with_name("atlas")
+                  .value_name("ATLAS")
+                  .default_value("output.atlas")
+                  .help("Atlas to unpack"))
+             .arg(Arg::with_name("output-dir")
+                  .short("o")
+                  .takes_value(true)
+                  .env("ATLAST_OUTPUT_DIR")
+                  .value_name("DIR")
+                  .default_value("./")
+                  .help("Directory to write the sprites into"))
+             .arg(key_arg()))
+        .subcommand(SubCommand::with_name("verify")
+             .about("Check that an atlas is intact and every sprite lies within its image")
+             .arg(Arg::with_name("atlas")
+                  .value_name("ATLAS")
+                  .default_value("output.atlas")
+                  .help("Atlas to verify"))
+             .arg(Arg::with_name("source-dir")
+                  .long("source-dir")
+                  .takes_value(true)
+                  .env("ATLAST_SOURCE_DIR")
+                  .value_name("DIR")
+                  .help("Also compare the pixels of every sprite against the images it was packed from"))
+             .arg(key_arg()))
+        .subcommand(SubCommand::with_name("diff")
+             .about("Report the sprites added, removed, moved or changed between two atlases")
+             .arg(Arg::with_name("old")
+                  .value_name("OLD")
+                  .required(true)
+                  .help("Atlas to compare against"))
+             .arg(Arg::with_name("new")
+                  .value_name("NEW")
+                  .required(true)
+                  .help("Atlas to compare"))
+             .arg(Arg::with_name("json")
+                  .long("json")
+                  .help("Print the same as JSON"))
+             .arg(key_arg()))
+        .subcommand(SubCommand::with_name("audit")
+             .about("Report packed sprites that no source file refers to")
+             .arg(Arg::with_name("atlas")
+                  .value_name("ATLAS")
+                  .default_value("output.atlas")
+                  .help("Atlas to audit"))
+             .arg(Arg::with_name("code-dir")
+                  .long("code-dir")
+                  .takes_value(true)
+                  .env("ATLAST_CODE_DIR")
+                  .value_name("DIR")
+                  .required(true)
+                  .help("Directory of source files to search for sprite names"))
+             .arg(key_arg()))
+}
+
+// The atlases to build, each with the name of its target and the command line arguments with the
+// options of the config file added
+fn builds(matches: &ArgMatches, args: &[OsString]) -> Vec<(Option<String>, Vec<OsString>)> {
+    let path = match matches.value_of("config") {
+        Some(path) => PathBuf::from(paths::normalize(path).as_ref()),
+        None if Path::new(config::DEFAULT).is_file() => PathBuf::from(config::DEFAULT),
+        None if matches.is_present("target") => {
+            error!("--target selects targets of a config file, and there is none");
+            std::process::exit(EXIT_FAILED);
+        }
+        None => return vec![(None, args.to_vec())]
+    };
+
+    let config = match config::read(&path) {
+        Ok(config) => config,
+        Err(err) => {
+            error!("unable to read {:?}: {}", path, err);
+            std::process::exit(EXIT_FAILED);
+        }
+    };
+
+    // Checked whole, so a mistake is reported even when the command line overrides it
+    check_config(&path, None, &config.options);
+    for (name, _) in config.targets.iter() {
+        check_config(&path, Some(name), &config.target(name).unwrap());
+    }
+
+    let selected: Vec<&str> = match matches.values_of("target") {
+        Some(names) => names.collect(),
+        None => config.targets.iter().map(|(name, _)| name.as_str()).collect()
+    };
+    if let Some(name) = selected.iter().find(|name| config.target(name).is_none()) {
+        error!("{:?} has no target named {:?}", path, name);
+        std::process::exit(EXIT_FAILED);
+    }
+
+    // Options given on the command line or through the environment override the file
+    let with_options = |options: config::Options| {
+        let given = options.into_iter().filter(|(name, _)| !explicit(matches, name));
+        args.iter().cloned().chain(given.flat_map(|(_, args)| args)).collect()
+    };
+    if selected.is_empty() {
+        return vec![(None, with_options(config.options))];
+    }
+    selected.into_iter()
+        .map(|name| (Some(name.to_string()), with_options(config.target(name).unwrap())))
+        .collect()
+}
+
+// Exit when clap rejects the options of a config file or one of its targets
+fn check_config(path: &Path, target: Option<&str>, options: &config::Options) {
+    let name = match target {
+        Some(target) => format!("{} targets.{}", path.display(), target),
+        None => path.display().to_string()
+    };
+    let args = options.iter().flat_map(|(_, args)| args.iter().cloned());
+    if let Err(err) = pack_args(App::new(name.as_str())).get_matches_from_safe(std::iter::once(OsString::from(&name)).chain(args)) {
+        error!("invalid options in {}", name);
+        err.exit();
+    }
+}
+
+// Messages shown for --quiet and -v, commands without them show everything but -v's
+fn log_level(matches: &ArgMatches) -> LevelFilter {
+    if matches.is_present("quiet") {
+        return LevelFilter::Error;
+    }
+    match matches.occurrences_of("verbose") {
+        0 => LevelFilter::Info,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace
+    }
+}
+
+pub fn main() {
+    let args = with_env_flags(std::env::args_os().collect());
+    let matches = app().get_matches_from(&args);
+
+    // with_env_flags names a command when none is given
+    let (command, matches) = matches.subcommand();
+    let matches = matches.unwrap();
+    progress::init(log_level(matches));
+
+    match command {
+        "audit" => std::process::exit(audit(matches)),
+        "inspect" => std::process::exit(inspect(matches)),
+        "unpack" => std::process::exit(unpack(matches)),
+        "verify" => std::process::exit(verify(matches)),
+        "diff" => std::process::exit(diff(matches)),
+        _ => {}
+    }
+
+    // Targets are built one after the other, sharing the decoding threads
+    let builds = builds(matches, &args);
+    let mut status = 0;
+    for (i, (target, args)) in builds.iter().enumerate() {
+        let matches = app().get_matches_from(args);
+        let matches = matches.subcommand_matches(command).unwrap();
+
+        if i == 0 {
+            if let Some(jobs) = matches.value_of("jobs") {
+                let pool = rayon::ThreadPoolBuilder::new().num_threads(jobs.parse().unwrap()).build_global();
+                if let Err(err) = pool {
+                    warn!("unable to limit the thread count: {}", err);
+                }
+            }
+            if matches.is_present("watch") && command == "merge" {
+                error!("--watch rebuilds when the asset directory changes, merge reads atlases instead");
+                std::process::exit(EXIT_FAILED);
+            }
+            if matches.value_of("append").is_some() && ["per-directory", "group", "scale-set"].iter().any(|arg| matches.is_present(arg)) {
+                error!("--append ATLAS keeps the sprites of one atlas, --append alone appends to every output");
+                std::process::exit(EXIT_FAILED);
+            }
+            if matches.is_present("watch") && builds.len() > 1 {
+                error!("--watch rebuilds a single target, select one with --target");
+                std::process::exit(EXIT_FAILED);
+            }
+        }
+
+        if let Some(target) = target {
+            info!("Target {}", target);
+        }
+        let result = match command {
+            "compare" => compare(matches, args),
+            "doctor" => doctor(matches),
+            _ => pack(matches, args)
+        };
+        if status == 0 {
+            status = result;
+        }
+    }
+    std::process::exit(status);
+}
+
+// Build the atlases the arguments ask for, and with --watch keep rebuilding them
+fn pack(matches: &ArgMatches, args: &[OsString]) -> i32 {
+    let asset_dir = paths::normalize(matches.value_of("asset-directory").unwrap());
+    let asset_dir = Path::new(asset_dir.as_ref());
+    let output_file = paths::normalize(matches.value_of("output-file").unwrap());
+    let output_file = output_file.as_ref();
+
+    let status = run_scales(matches, args, asset_dir, output_file);
+    if matches.is_present("watch") {
+        return watch(matches, args, asset_dir, output_file);
+    }
+    status.err().unwrap_or(0)
+}
+
+// Build the atlases once per factor of --scale-set, or once at --scale
+fn run_scales(matches: &ArgMatches, args: &[OsString], asset_dir: &Path, output_file: &str) -> Result<(), i32> {
+    let scales: Vec<Option<f32>> = match matches.value_of("scale-set") {
+        Some(set) => set.split(',').map(|scale| Some(scale.trim().parse().unwrap())).collect(),
+        None => vec![None]
+    };
+
+    let mut status = None;
+    for scale in scales {
+        if let Err(err) = run(matches, args, asset_dir, output_file, scale) {
+            status = status.or(Some(err));
+        }
+    }
+    status.map_or(Ok(()), Err)
+}
+
+// "sprites.atlas" at a scale of 0.5 is "sprites@0.5x.atlas"
+fn scaled_path(path: &str, scale: f32) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+    path.with_file_name(format!("{}@{}x{}", stem, scale, ext)).to_string_lossy().into_owned()
+}
+
+// Build every atlas the arguments ask for, the first failure decides the exit status. A `scale`
+// from --scale-set replaces --scale and marks every output with it.
+fn run(matches: &ArgMatches, args: &[OsString], asset_dir: &Path, output_file: &str, scale: Option<f32>) -> Result<(), i32> {
+    if let Some(rules) = matches.values_of("group") {
+        let groups: Vec<(GlobMatcher, Cow<str>)> = rules
+            .map(|rule| {
+                let (pattern, output) = rule.split_once('=').unwrap();
+                (Glob::new(pattern).unwrap().compile_matcher(), paths::normalize(output))
+            })
+            .collect();
+        let duplicate = matches.value_of("shared").unwrap() == "duplicate";
+
+        // Sprites no group claims would silently go missing
+        let unclaimed = walk(matches, asset_dir)
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| is_image(entry.path()) || is_aseprite(entry.path()))
+            .filter(|entry| {
+                let relative = entry.path().strip_prefix(asset_dir).unwrap();
+                !groups.iter().any(|(pattern, _)| pattern.is_match(relative))
+            })
+            .count();
+        if unclaimed > 0 {
+            warn!("{} image(s) match no group and are not packed", unclaimed);
+        }
+
+        let mut status = None;
+        let mut stats = Vec::new();
+        for (i, (pattern, output)) in groups.iter().enumerate() {
+            info!("Building {:?}", output);
+
+            let select = |relative: &Path| {
+                pattern.is_match(relative)
+                    && (duplicate || !groups[..i].iter().any(|(earlier, _)| earlier.is_match(relative)))
+            };
+
+            match build(matches, args, asset_dir, output, &select, scale) {
+                Ok(atlas) => stats.push(atlas),
+                Err(err) => {
+                    error!("failed to build {:?}", output);
+                    status = status.or(Some(err));
+                }
+            }
+        }
+
+        if let Err(err) = write_stats(matches, &stats) {
+            status = status.or(Some(err));
+        }
+        return status.map_or(Ok(()), Err);
+    }
+
+    if !matches.is_present("per-directory") {
+        return build(matches, args, asset_dir, output_file, &|_| true, scale)
+            .and_then(|stats| write_stats(matches, &[stats]));
+    }
+
+    // Atlases go into the -o directory, or the current one when it was not given
+    let output_dir = if explicit(matches, "output-file") {
+        Path::new(output_file)
+    } else {
+        Path::new(".")
+    };
+    if !matches.is_present("dry-run") {
+        if let Err(err) = std::fs::create_dir_all(output_dir) {
+            error!("unable to create {:?}: {}", output_dir, err);
+            return Err(EXIT_FAILED);
+        }
+    }
+
+    let mut directories: Vec<PathBuf> = match std::fs::read_dir(asset_dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+            .map(|entry| entry.path())
+            .collect(),
+        Err(err) => {
+            error!("unable to read {:?}: {}", asset_dir, err);
+            return Err(EXIT_FAILED);
+        }
+    };
+    directories.sort();
+
+    // Images directly in the asset directory go into an atlas named after it
+    let loose = walk(matches, asset_dir)
+        .filter_map(|entry| entry.ok())
+        .any(|entry| entry.depth() == 1 && (is_image(entry.path()) || is_aseprite(entry.path())));
+    let root_name = asset_dir.canonicalize().ok()
+        .and_then(|dir| dir.file_name().map(|name| name.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "assets".to_string());
+    if loose && directories.iter().any(|directory| directory.file_name().unwrap().to_string_lossy() == root_name) {
+        error!("images in {:?} and in its subdirectory {:?} would both be packed into {}.atlas", asset_dir, root_name, root_name);
+        return Err(EXIT_FAILED);
+    }
+
+    if directories.is_empty() && !loose && !matches.is_present("allow-empty") {
+        error!("no subdirectories in directory");
+        return Err(EXIT_EMPTY);
+    }
+
+    // The root atlas only takes the images at its top level, the others everything under them
+    let mut atlases: Vec<(&Path, Cow<str>, bool)> = Vec::new();
+    if loose {
+        atlases.push((asset_dir, Cow::from(root_name.as_str()), true));
+    }
+    for directory in directories.iter() {
+        atlases.push((directory, directory.file_name().unwrap().to_string_lossy(), false));
+    }
+
+    // Every directory is attempted, the first failure decides the exit status
+    let mut status = None;
+    let mut stats = Vec::new();
+    for (directory, name, top_level) in atlases {
+        let output = output_dir.join(format!("{}.atlas", name));
+        info!("Building {:?}", output);
+
+        let select = |relative: &Path| !top_level || relative.components().count() == 1;
+        match build(matches, args, directory, &output.to_string_lossy(), &select, scale) {
+            Ok(atlas) => stats.push(atlas),
+            Err(err) => {
+                error!("failed to build {:?}", output);
+                status = status.or(Some(err));
+            }
+        }
+    }
+
+    if let Err(err) = write_stats(matches, &stats) {
+        status = status.or(Some(err));
+    }
+    status.map_or(Ok(()), Err)
+}
+
+// Rebuild whenever an image or font under the asset directory changes, until watching fails
+fn watch(matches: &ArgMatches, args: &[OsString], asset_dir: &Path, output_file: &str) -> i32 {
+    let debounce = Duration::from_millis(matches.value_of("debounce").unwrap().parse().unwrap());
+    let (sender, events) = mpsc::channel();
+    let watcher = notify::recommended_watcher(sender)
+        .and_then(|mut watcher| watcher.watch(asset_dir, RecursiveMode::Recursive).map(|_| watcher));
+    // Dropping the watcher would stop the events
+    let _watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(err) => {
+            error!("unable to watch {:?}: {}", asset_dir, err);
+            return EXIT_FAILED;
+        }
+    };
+
+    loop {
+        info!("Watching {:?} for changes", asset_dir);
+        loop {
+            match events.recv() {
+                Ok(Ok(event)) if changes_input(&event) => break,
+                Ok(Ok(_)) => {}
+                Ok(Err(err)) => warn!("{}", err),
+                Err(_) => return EXIT_FAILED
+            }
+        }
+
+        // Wait for the changes to settle, a copy of many files is one rebuild
+        loop {
+            match events.recv_timeout(debounce) {
+                Ok(_) => {}
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return EXIT_FAILED
+            }
+        }
+
+        info!("Rebuilding after a change in {:?}", asset_dir);
+        if run_scales(matches, args, asset_dir, output_file).is_err() {
+            warn!("build failed, waiting for the next change");
+        }
+    }
+}
+
+// Whether `event` adds, changes or removes a packed file, reading them while building does not
+fn changes_input(event: &notify::Event) -> bool {
+    let kind = matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_))
+        && !matches!(event.kind, EventKind::Modify(ModifyKind::Metadata(MetadataKind::AccessTime)));
+    let fonts = [Some(OsStr::new("ttf")), Some(OsStr::new("otf"))];
+    kind && event.paths.iter().any(|path| is_image(path) || is_aseprite(path) || fonts.contains(&path.extension()))
+}
+
+// Options of the commands that pack: pack, compare and doctor
+fn pack_args<'a, 'b>(command: App<'a, 'b>) -> App<'a, 'b> {
+    command
+        .arg(Arg::with_name("asset-directory")
+             .short("d")
+             .value_name("DIR_NAME")
+             .takes_value(true)
+             .env("ATLAST_ASSET_DIRECTORY")
+             .default_value("./"))
+        .arg(Arg::with_name("output-file")
+             .short("o")
+             .takes_value(true)
+             .env("ATLAST_OUTPUT_FILE")
+             .value_name("FILE_NAME")
+             .default_value("output.atlas"))
+        .arg(Arg::with_name("config")
+             .long("config")
+             .takes_value(true)
+             .env("ATLAST_CONFIG")
+             .value_name("FILE")
+             .help("Read the options not given on the command line or in the environment from FILE, atlast.toml when it exists"))
+        .arg(Arg::with_name("target")
+             .long("target")
+             .takes_value(true)
+             .multiple(true)
+             .number_of_values(1)
+             .env("ATLAST_TARGET")
+             .value_name("NAME")
+             .help("Build only the named target of the config file, all of them by default"))
+        .arg(Arg::with_name("per-directory")
+             .long("per-directory")
+             .help("Write one atlas per immediate subdirectory of the asset directory into the -o directory"))
+        .arg(Arg::with_name("group")
+             .long("group")
+             .takes_value(true)
+             .env("ATLAST_GROUP")
+             .multiple(true)
+             .number_of_values(1)
+             .value_name("PATTERN=FILE")
+             .validator(group_rule)
+             .conflicts_with("per-directory")
+             .help("Pack sprites whose path under the asset directory matches PATTERN into FILE, repeatable"))
+        .arg(Arg::with_name("include")
+             .long("include")
+             .takes_value(true)
+             .env("ATLAST_INCLUDE")
+             .multiple(true)
+             .number_of_values(1)
+             .value_name("GLOB")
+             .validator(glob)
+             .help("Pack only files whose path under the asset directory matches GLOB, repeatable"))
+        .arg(Arg::with_name("exclude")
+             .long("exclude")
+             .takes_value(true)
+             .env("ATLAST_EXCLUDE")
+             .multiple(true)
+             .number_of_values(1)
+             .value_name("GLOB")
+             .validator(glob)
+             .help("Leave out files whose path under the asset directory matches GLOB, repeatable"))
+        .arg(Arg::with_name("max-depth")
+             .long("max-depth")
+             .takes_value(true)
+             .env("ATLAST_MAX_DEPTH")
+             .value_name("N")
+             .validator(positive_integer)
+             .help("Only look N directories deep, 1 takes only the files directly in the asset directory"))
+        .arg(Arg::with_name("shared")
+             .long("shared")
+             .takes_value(true)
+             .env("ATLAST_SHARED")
+             .value_name("POLICY")
+             .possible_values(&["first", "duplicate"])
+             .default_value("first")
+             .help("Pack sprites matching several groups into the first matching group only, or into all of them"))
+        .arg(Arg::with_name("preset")
+             .long("preset")
+             .takes_value(true)
+             .env("ATLAST_PRESET")
+             .value_name("TARGET")
+             .possible_values(&["web", "mobile", "desktop", "console"])
+             .help("Size limits and output format suited to a target, explicit flags take precedence"))
+        .arg(Arg::with_name("max-atlas-size")
+             .long("max-atlas-size")
+             .takes_value(true)
+             .env("ATLAST_MAX_ATLAS_SIZE")
+             .value_name("PIXELS")
+             .validator(positive_integer)
+             .help("Fail when the atlas is wider or taller than this"))
+        .arg(Arg::with_name("packer")
+             .long("packer")
+             .takes_value(true)
+             .env("ATLAST_PACKER")
+             .value_name("ALGORITHM")
+             .possible_values(&["scan", "maxrects", "skyline"])
+             .default_value("scan")
+             .help("Place sprites at the first free position, with MaxRects or on a skyline, see the compare subcommand"))
+        .arg(Arg::with_name("trim")
+             .long("trim")
+             .conflicts_with_all(&["texture-array", "cubemap"])
+             .help("Pack only the opaque bounding box of every sprite, recording its source size and offset"))
+        .arg(Arg::with_name("jobs")
+             .long("jobs")
+             .short("j")
+             .takes_value(true)
+             .env("ATLAST_JOBS")
+             .value_name("N")
+             .validator(positive_integer)
+             .help("Decode images on at most this many threads, one per core by default"))
+        .arg(Arg::with_name("watch")
+             .long("watch")
+             .conflicts_with("append")
+             .help("Keep running and rebuild whenever an image under the asset directory is added, changed or removed"))
+        .arg(Arg::with_name("debounce")
+             .long("debounce")
+             .takes_value(true)
+             .env("ATLAST_DEBOUNCE")
+             .value_name("MS")
+             .validator(positive_integer)
+             .default_value("300")
+             .help("With --watch, rebuild once no change has been seen for this long so copying many files builds once"))
+        .arg(Arg::with_name("cache")
+             .long("cache")
+             .takes_value(true)
+             .env("ATLAST_CACHE")
+             .value_name("DIR")
+             .conflicts_with_all(&["append", "texture-array", "cubemap"])
+             .help("Keep decoded inputs and the layout in DIR so rebuilds only decode changed files and keep the layout when sizes did not change"))
+        .arg(Arg::with_name("dedupe")
+             .long("dedupe")
+             .conflicts_with_all(&["texture-array", "cubemap"])
+             .help("Pack pixel-identical images once, recording the shared rect under each of their names"))
+        .arg(Arg::with_name("padding")
+             .long("padding")
+             .takes_value(true)
+             .env("ATLAST_PADDING")
+             .value_name("PIXELS")
+             .validator(non_negative_integer)
+             .default_value("0")
+             .help("Transparent pixels to keep between sprites so filtering does not bleed neighbors into each other"))
+        .arg(Arg::with_name("format")
+             .long("format")
+             .takes_value(true)
+             .env("ATLAST_FORMAT")
+             .value_name("FORMAT")
+             .possible_values(&["bincode", "json", "tp-json-hash", "tp-json-array", "godot", "libgdx", "rust"])
+             .default_value("bincode")
+             .help("Encode the atlas data with bincode as atlas.data, as JSON in atlas.json in atlast's own or TexturePacker's layouts, or as atlas.data with Godot resources, a libGDX pack file or Rust constants"))
+        .arg(Arg::with_name("pretty")
+             .long("pretty")
+             .help("Indent JSON atlas data for reading"))
+        .arg(Arg::with_name("animations")
+             .long("animations")
+             .help("Group numbered sprites like run_0.png ... run_7.png into animations in the atlas data"))
+        .arg(Arg::with_name("animation-pattern")
+             .long("animation-pattern")
+             .takes_value(true)
+             .env("ATLAST_ANIMATION_PATTERN")
+             .value_name("REGEX")
+             .requires("animations")
+             .validator(animation_pattern)
+             .help("Regex matching frame names without extension, its name group naming the animation and frame group numbering the frame, by default run_0, run-0 or run0"))
+        .arg(Arg::with_name("frame-duration")
+             .long("frame-duration")
+             .takes_value(true)
+             .multiple(true)
+             .number_of_values(1)
+             .env("ATLAST_FRAME_DURATION")
+             .value_name("PATTERN=MILLISECONDS")
+             .requires("animations")
+             .validator(duration_rule)
+             .help("Record how long every frame of the animations whose name matches is shown, the first matching rule wins"))
+        .arg(Arg::with_name("nine-slice")
+             .long("nine-slice")
+             .takes_value(true)
+             .multiple(true)
+             .number_of_values(1)
+             .env("ATLAST_NINE_SLICE")
+             .value_name("PATTERN=LEFT,TOP,RIGHT,BOTTOM")
+             .validator(nine_slice_rule)
+             .help("Record nine-slice borders for the sprites whose path matches without a .9.png border or .9.json sidecar"))
+        .arg(Arg::with_name("pivot")
+             .long("pivot")
+             .takes_value(true)
+             .env("ATLAST_PIVOT")
+             .value_name("X,Y")
+             .default_value("0.5,0.5")
+             .validator(pivot_point)
+             .help("Pivot of every sprite as a fraction of its source from the top left corner"))
+        .arg(Arg::with_name("sprite-pivot")
+             .long("sprite-pivot")
+             .takes_value(true)
+             .multiple(true)
+             .number_of_values(1)
+             .env("ATLAST_SPRITE_PIVOT")
+             .value_name("PATTERN=X,Y")
+             .validator(pivot_rule)
+             .help("Pivot of the sprites whose path matches without a .pivot.json sidecar, the first matching rule wins"))
+        .arg(Arg::with_name("user-data")
+             .long("user-data")
+             .takes_value(true)
+             .multiple(true)
+             .number_of_values(1)
+             .env("ATLAST_USER_DATA")
+             .value_name("PATTERN=JSON")
+             .validator(user_data_rule)
+             .help("Record this JSON with the sprites whose path matches without a .meta.json sidecar, the first matching rule wins"))
+        .arg(Arg::with_name("extrude")
+             .long("extrude")
+             .takes_value(true)
+             .env("ATLAST_EXTRUDE")
+             .value_name("PIXELS")
+             .validator(non_negative_integer)
+             .default_value("0")
+             .conflicts_with_all(&["texture-array", "cubemap"])
+             .help("Repeat the edge pixels of every sprite this far outwards to prevent seams when filtering"))
+        .arg(Arg::with_name("allow-rotation")
+             .long("allow-rotation")
+             .help("Let the packer turn sprites 90° clockwise when that fits them better, marking their records rotated"))
+        .arg(Arg::with_name("polygons")
+             .long("polygons")
+             .conflicts_with_all(&["texture-array", "cubemap"])
+             .help("Record an outline polygon of every sprite's opaque pixels, letting the scan packer overlap rects around it"))
+        .arg(Arg::with_name("polygon-tolerance")
+             .long("polygon-tolerance")
+             .takes_value(true)
+             .env("ATLAST_POLYGON_TOLERANCE")
+             .value_name("PIXELS")
+             .validator(non_negative_integer)
+             .default_value("2")
+             .help("How far outlines may stray from the opaque pixels to get by with fewer vertices"))
+        .arg(Arg::with_name("heuristic")
+             .long("heuristic")
+             .takes_value(true)
+             .env("ATLAST_HEURISTIC")
+             .value_name("HEURISTIC")
+             .possible_values(&["bl", "min-waste"])
+             .default_value("bl")
+             .help("Put skyline sprites at the lowest position or where they leave the least space unusable below them"))
+        .arg(Arg::with_name("sort")
+             .long("sort")
+             .takes_value(true)
+             .env("ATLAST_SORT")
+             .value_name("ORDER")
+             .possible_values(&["area", "height", "width", "perimeter", "max-side", "name"])
+             .default_value("area")
+             .help("Place sprites largest first by this measure, or by name, see the compare subcommand"))
+        .arg(Arg::with_name("optimize")
+             .long("optimize")
+             .takes_value(true)
+             .env("ATLAST_OPTIMIZE")
+             .value_name("ITERATIONS")
+             .validator(non_negative_integer)
+             .conflicts_with_all(&["texture-array", "cubemap"])
+             .help("Pack with every packer, sort order and a few widths, then try this many variations of the smallest layout"))
+        .arg(Arg::with_name("width")
+             .long("width")
+             .takes_value(true)
+             .env("ATLAST_WIDTH")
+             .value_name("PIXELS")
+             .validator(positive_integer)
+             .help("Width of the atlas, picked automatically when omitted"))
+        .arg(Arg::with_name("pixel-format")
+             .long("pixel-format")
+             .takes_value(true)
+             .env("ATLAST_PIXEL_FORMAT")
+             .value_name("FORMAT")
+             .possible_values(&["rgba8888", "rgba4444", "rgb565"])
+             .default_value("rgba8888")
+             .help("Pixel format of the packed atlas"))
+        .arg(Arg::with_name("dither")
+             .long("dither")
+             .takes_value(true)
+             .env("ATLAST_DITHER")
+             .value_name("MODE")
+             .possible_values(&["none", "ordered", "floyd-steinberg"])
+             .default_value("none")
+             .help("Dithering used when reducing bit depth"))
+        .arg(Arg::with_name("ktx")
+             .long("ktx")
+             .conflicts_with("texture-format")
+             .help("Write the atlas as a KTX2 texture instead of png/raw pixels, short for --texture-format ktx2"))
+        .arg(Arg::with_name("texture-format")
+             .long("texture-format")
+             .takes_value(true)
+             .env("ATLAST_TEXTURE_FORMAT")
+             .value_name("CONTAINER")
+             .possible_values(&["png", "ktx2", "dds", "basis"])
+             .default_value("png")
+             .help("Container of the atlas images, png meaning png or raw pixels for reduced pixel formats and basis a Basis Universal UASTC texture"))
+        .arg(Arg::with_name("compression")
+             .long("compression")
+             .takes_value(true)
+             .env("ATLAST_COMPRESSION")
+             .value_name("FORMAT")
+             .possible_values(&["bc1", "bc3", "bc7", "etc2", "astc"])
+             .help("GPU block compression of KTX2 and DDS textures, in place of the pixel format"))
+        .arg(Arg::with_name("mipmaps")
+             .long("mipmaps")
+             .conflicts_with("virtual-tiles")
+             .help("Also write mip levels down to 1x1, filtered within each sprite, as atlas_mip1.png, ... or into the KTX2, DDS or Basis texture"))
+        .arg(Arg::with_name("premultiply-alpha")
+             .long("premultiply-alpha")
+             .help("Multiply the color of every pixel by its alpha and record it in the atlas data, for ONE, ONE_MINUS_SRC_ALPHA blending"))
+        .arg(Arg::with_name("alpha-bleed")
+             .long("alpha-bleed")
+             .conflicts_with("premultiply-alpha")
+             .help("Spread the colors of the sprites into the fully transparent texels around them, keeping their alpha"))
+        .arg(Arg::with_name("texture-array")
+             .long("texture-array")
+             .help("Write equally sized sprites as the layers of a KTX2 texture array instead of packing them"))
+        .arg(Arg::with_name("cubemap")
+             .long("cubemap")
+             .takes_value(true)
+             .env("ATLAST_CUBEMAP")
+             .value_name("LAYOUT")
+             .possible_values(&["cross", "ktx2"])
+             .conflicts_with("texture-array")
+             .help("Assemble name_px/_nx/_py/_ny/_pz/_nz sprites into cubemaps, as crosses or a KTX2 cubemap"))
+        .arg(Arg::with_name("udim")
+             .long("udim")
+             .takes_value(true)
+             .env("ATLAST_UDIM")
+             .value_name("PIXELS")
+             .validator(positive_integer)
+             .conflicts_with_all(&["texture-array", "cubemap"])
+             .help("Split the atlas into square UDIM tiles 1001, 1002, ... of this size"))
+        .arg(Arg::with_name("scale")
+             .long("scale")
+             .takes_value(true)
+             .env("ATLAST_SCALE")
+             .value_name("FACTOR")
+             .validator(positive_number)
+             .conflicts_with("scale-set")
+             .help("Resize every sprite by this factor before packing"))
+        .arg(Arg::with_name("sprite-scale")
+             .long("sprite-scale")
+             .takes_value(true)
+             .multiple(true)
+             .number_of_values(1)
+             .env("ATLAST_SPRITE_SCALE")
+             .value_name("PATTERN=FACTOR")
+             .validator(scale_rule)
+             .help("Also resize the sprites whose path matches by this factor, the first matching rule wins"))
+        .arg(Arg::with_name("scale-set")
+             .long("scale-set")
+             .takes_value(true)
+             .env("ATLAST_SCALE_SET")
+             .value_name("FACTORS")
+             .validator(scale_set)
+             .help("Build the atlas once per comma separated factor, e.g. 1,0.5 writes atlas@1x and atlas@0.5x"))
+        .arg(Arg::with_name("scale-filter")
+             .long("scale-filter")
+             .takes_value(true)
+             .env("ATLAST_SCALE_FILTER")
+             .value_name("FILTER")
+             .possible_values(&["nearest", "triangle", "lanczos"])
+             .default_value("triangle")
+             .help("Filter sprites are resized with, nearest keeps pixel art crisp"))
+        .arg(Arg::with_name("linear-filtering")
+             .long("linear-filtering")
+             .help("Resize sprites and generate mip levels in linear light rather than in sRGB, where they darken"))
+        .arg(Arg::with_name("max-width")
+             .long("max-width")
+             .takes_value(true)
+             .env("ATLAST_MAX_WIDTH")
+             .value_name("PIXELS")
+             .validator(positive_integer)
+             .conflicts_with_all(&["texture-array", "cubemap", "udim"])
+             .help("Never make the atlas wider than this"))
+        .arg(Arg::with_name("max-height")
+             .long("max-height")
+             .takes_value(true)
+             .env("ATLAST_MAX_HEIGHT")
+             .value_name("PIXELS")
+             .validator(positive_integer)
+             .conflicts_with_all(&["texture-array", "cubemap", "udim", "virtual-tiles", "append"])
+             .help("Spill sprites that do not fit under this height onto further pages atlas_0, atlas_1, ..."))
+        .arg(Arg::with_name("pot")
+             .long("pot")
+             .conflicts_with_all(&["texture-array", "cubemap", "udim", "virtual-tiles"])
+             .help("Round the atlas dimensions up to powers of two, padding with transparent pixels"))
+        .arg(Arg::with_name("square")
+             .long("square")
+             .conflicts_with_all(&["texture-array", "cubemap", "udim", "virtual-tiles"])
+             .help("Make the atlas as tall as it is wide, padding with transparent pixels"))
+        .arg(Arg::with_name("composite-layers")
+             .long("composite-layers")
+             .help("Flatten name__layer.png images into a single name.png sprite, layers stacked in name order"))
+        .arg(Arg::with_name("variant-suffix")
+             .long("variant-suffix")
+             .takes_value(true)
+             .env("ATLAST_VARIANT_SUFFIX")
+             .multiple(true)
+             .number_of_values(1)
+             .value_name("SUFFIX")
+             .conflicts_with_all(&["texture-array", "cubemap", "udim", "virtual-tiles", "append"])
+             .help("Place name<SUFFIX>.png at the rect of name.png in a companion atlas<SUFFIX> texture, repeatable"))
+        .arg(Arg::with_name("exclude-list")
+             .long("exclude-list")
+             .takes_value(true)
+             .env("ATLAST_EXCLUDE_LIST")
+             .value_name("FILE")
+             .help("Leave out the sprites whose record names are listed in FILE, one per line"))
+        .arg(Arg::with_name("append")
+             .long("append")
+             .takes_value(true)
+             .min_values(0)
+             .max_values(1)
+             .value_name("ATLAS")
+             .conflicts_with_all(&["texture-array", "cubemap", "udim"])
+             .help("Keep the sprites of an existing atlas, the output unless ATLAS is given, in place and only pack new \
+                    images around them"))
+        .arg(Arg::with_name("priorities")
+             .long("priorities")
+             .takes_value(true)
+             .env("ATLAST_PRIORITIES")
+             .value_name("FILE")
+             .help("Manifest of \"<pattern> <priority>\" lines, positive priorities are pinned to the first page"))
+        .arg(Arg::with_name("virtual-tiles")
+             .long("virtual-tiles")
+             .takes_value(true)
+             .env("ATLAST_VIRTUAL_TILES")
+             .value_name("PIXELS")
+             .validator(positive_integer)
+             .conflicts_with_all(&["texture-array", "cubemap", "udim"])
+             .help("Store the atlas as square tiles of this size with an indirection table, for virtual texturing"))
+        .arg(Arg::with_name("usage")
+             .long("usage")
+             .takes_value(true)
+             .env("ATLAST_USAGE")
+             .value_name("FILE")
+             .help("Usage report of \"<sprite name> <uses>\" lines, frequently used sprites are placed first"))
+        .arg(Arg::with_name("color-chunks")
+             .long("color-chunks")
+             .takes_value(true)
+             .env("ATLAST_COLOR_CHUNKS")
+             .value_name("POLICY")
+             .possible_values(&["strip", "preserve"])
+             .default_value("strip")
+             .help("Strip or preserve the iCCP/gAMA/cHRM/sRGB chunks of the inputs in the atlas png"))
+        .arg(Arg::with_name("to-srgb")
+             .long("to-srgb")
+             .help("Convert inputs with embedded color profiles to sRGB before packing"))
+        .arg(Arg::with_name("non-utf8")
+             .long("non-utf8")
+             .takes_value(true)
+             .env("ATLAST_NON_UTF8")
+             .value_name("POLICY")
+             .possible_values(&["lossy", "skip"])
+             .default_value("lossy")
+             .help("Record non UTF-8 file names lossily or skip those files"))
+        .arg(Arg::with_name("no-normalize")
+             .long("no-normalize")
+             .help("Keep record names as found instead of normalizing them to NFC"))
+        .arg(Arg::with_name("name-style")
+             .long("name-style")
+             .takes_value(true)
+             .env("ATLAST_NAME_STYLE")
+             .value_name("STYLE")
+             .possible_values(&["relative", "filename", "stem"])
+             .default_value("relative")
+             .help("Name sprites by their path under the asset directory, their file name, or their file name without extension"))
+        .arg(Arg::with_name("placeholders")
+             .long("placeholders")
+             .takes_value(true)
+             .env("ATLAST_PLACEHOLDERS")
+             .value_name("POLICY")
+             .possible_values(&["keep", "skip", "reject"])
+             .default_value("keep")
+             .help("Keep, skip, or reject 1x1 placeholder images"))
+        .arg(Arg::with_name("oversize")
+             .long("oversize")
+             .takes_value(true)
+             .env("ATLAST_OVERSIZE")
+             .value_name("POLICY")
+             .possible_values(&["error", "own-page", "downscale"])
+             .default_value("error")
+             .help("Fail on sprites too large for --max-width, --max-height or --page-size, pack each into an atlas of its own, or scale them down to fit"))
+        .arg(Arg::with_name("max-sprite-size")
+             .long("max-sprite-size")
+             .takes_value(true)
+             .env("ATLAST_MAX_SPRITE_SIZE")
+             .value_name("PIXELS")
+             .validator(positive_integer)
+             .default_value("4096")
+             .help("Warn about sources wider or taller than this"))
+        .arg(Arg::with_name("max-sprite-share")
+             .long("max-sprite-share")
+             .takes_value(true)
+             .env("ATLAST_MAX_SPRITE_SHARE")
+             .value_name("FRACTION")
+             .validator(fraction)
+             .help("Warn about sources taking more than this share of the total sprite area"))
+        .arg(Arg::with_name("max-pack-iterations")
+             .long("max-pack-iterations")
+             .takes_value(true)
+             .env("ATLAST_MAX_PACK_ITERATIONS")
+             .value_name("N")
+             .validator(positive_integer)
+             .help("Give up when placing sprites takes more than N steps"))
+        .arg(Arg::with_name("pack-timeout")
+             .long("pack-timeout")
+             .takes_value(true)
+             .env("ATLAST_PACK_TIMEOUT")
+             .value_name("SECONDS")
+             .validator(positive_integer)
+             .help("Give up when packing takes longer than this"))
+        .arg(Arg::with_name("sdf")
+             .long("sdf")
+             .help("Convert sprites into single channel signed distance fields before packing"))
+        .arg(Arg::with_name("sdf-spread")
+             .long("sdf-spread")
+             .takes_value(true)
+             .env("ATLAST_SDF_SPREAD")
+             .value_name("PIXELS")
+             .validator(positive_integer)
+             .default_value("8")
+             .help("Distance covered by the signed distance field on each side of an edge"))
+        .arg(Arg::with_name("palette")
+             .long("palette")
+             .takes_value(true)
+             .env("ATLAST_PALETTE")
+             .value_name("FILE")
+             .help("Remap sprite colors through a palette of old/new pairs (text) or a 2 pixel tall png"))
+        .arg(Arg::with_name("height-to-normal")
+             .long("height-to-normal")
+             .conflicts_with("sdf")
+             .help("Convert grayscale sprites from heightmaps into tangent-space normal maps"))
+        .arg(Arg::with_name("normal-strength")
+             .long("normal-strength")
+             .takes_value(true)
+             .env("ATLAST_NORMAL_STRENGTH")
+             .value_name("STRENGTH")
+             .validator(positive_number)
+             .default_value("1.0")
+             .help("Scale of height slopes when generating normal maps"))
+        .arg(Arg::with_name("normal-map")
+             .long("normal-map")
+             .takes_value(true)
+             .env("ATLAST_NORMAL_MAP")
+             .multiple(true)
+             .number_of_values(1)
+             .value_name("GLOB")
+             .validator(glob)
+             .help("Treat sprites whose path matches GLOB as normal maps: never rotated or color converted, renormalized when scaled, repeatable"))
+        .arg(Arg::with_name("msdf")
+             .long("msdf")
+             .help("Pack the glyphs of .ttf/.otf fonts as multi-channel signed distance fields"))
+        .arg(Arg::with_name("msdf-size")
+             .long("msdf-size")
+             .takes_value(true)
+             .env("ATLAST_MSDF_SIZE")
+             .value_name("PIXELS")
+             .validator(positive_integer)
+             .default_value("32")
+             .help("Pixels per em of MSDF glyphs"))
+        .arg(Arg::with_name("msdf-range")
+             .long("msdf-range")
+             .takes_value(true)
+             .env("ATLAST_MSDF_RANGE")
+             .value_name("PIXELS")
+             .validator(positive_integer)
+             .default_value("4")
+             .help("Distance range of MSDF glyphs in pixels"))
+        .arg(Arg::with_name("charset")
+             .long("charset")
+             .takes_value(true)
+             .env("ATLAST_CHARSET")
+             .value_name("CHARS")
+             .help("Characters to render from fonts, printable ASCII by default"))
+        .arg(Arg::with_name("budget")
+             .long("budget")
+             .takes_value(true)
+             .env("ATLAST_BUDGET")
+             .value_name("SIZE")
+             .validator(byte_budget)
+             .help("Fail when the written atlas file is larger than this, e.g. 4MB"))
+        .arg(Arg::with_name("budget-pixels")
+             .long("budget-pixels")
+             .takes_value(true)
+             .env("ATLAST_BUDGET_PIXELS")
+             .value_name("WxH[xPAGES]")
+             .validator(pixel_budget)
+             .help("Fail before writing when the atlas has more pixels than this, e.g. 2048x2048x2"))
+        .arg(Arg::with_name("allow-empty")
+             .long("allow-empty")
+             .help("Write an empty atlas instead of failing when no images are found"))
+        .arg(Arg::with_name("dry-run")
+             .long("dry-run")
+             .help("Pack and report the layout without writing the atlas, the cache or the -o directory"))
+        .arg(Arg::with_name("verify")
+             .long("verify")
+             .help("Re-read the written atlas and check every sprite against its source"))
+        .arg(Arg::with_name("seam-test")
+             .long("seam-test")
+             .help("Report colors bled in from neighboring sprites under bilinear filtering"))
+        .arg(Arg::with_name("seam-test-out")
+             .long("seam-test-out")
+             .takes_value(true)
+             .env("ATLAST_SEAM_TEST_OUT")
+             .value_name("FILE_NAME")
+             .help("Run the seam test and write the atlas with contaminated texels marked to a png"))
+        .arg(Arg::with_name("debug-preview")
+             .long("debug-preview")
+             .takes_value(true)
+             .env("ATLAST_DEBUG_PREVIEW")
+             .value_name("FILE_NAME")
+             .help("Write the atlas with every rect outlined in its own color to a png, and a legend of the colors beside it"))
+        .arg(Arg::with_name("strict")
+             .long("strict")
+             .help("Fail instead of skipping unreadable, duplicate, or oversized images"))
+        .arg(Arg::with_name("on-conflict")
+             .long("on-conflict")
+             .takes_value(true)
+             .env("ATLAST_ON_CONFLICT")
+             .value_name("ACTION")
+             .possible_values(&["rename", "skip", "overwrite", "abort"])
+             .help("Resolve duplicate names, an existing output and oversized sprites without asking"))
+        .arg(Arg::with_name("quiet")
+             .short("q")
+             .long("quiet")
+             .conflicts_with("verbose")
+             .help("Print only errors and draw no progress bars"))
+        .arg(Arg::with_name("verbose")
+             .short("v")
+             .long("verbose")
+             .multiple(true)
+             .help("Also print every input added, twice to also print where every sprite was placed"))
+        .arg(Arg::with_name("yes")
+             .short("y")
+             .long("yes")
+             .help("Take the default resolution of every conflict without asking"))
+        .arg(Arg::with_name("stats-out")
+             .long("stats-out")
+             .takes_value(true)
+             .env("ATLAST_STATS_OUT")
+             .value_name("FILE")
+             .help("Write sprite counts, page fill, wasted pixels, output size and timings of every atlas built as JSON"))
+        .arg(key_arg())
+}
+
+// --key, shared by every command that reads or writes atlases
+fn key_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("key")
+        .long("key")
+        .takes_value(true)
+        .value_name("HEX")
+        .env("ATLAST_KEY")
+        .hide_env_values(true)
+        .validator(|value| crypt::parse_key(&value).map(|_| ()))
+        .help("AES-256 key of 64 hex digits to encrypt the written atlas with and decrypt existing ones")
+}
+
+// Pack the input once per packer and sort order with the remaining settings given
+fn compare(matches: &ArgMatches, args: &[OsString]) -> i32 {
+    let asset_dir = paths::normalize(matches.value_of("asset-directory").unwrap());
+    let asset_dir = Path::new(asset_dir.as_ref());
+    let output_file = paths::normalize(matches.value_of("output-file").unwrap());
+
+    let mut atlas = match load(matches, args, asset_dir, &output_file, &|_| true, None) {
+        Ok(atlas) => atlas,
+        Err(status) => return status
+    };
+
+    println!("{:<10} {:<10} {:>12} {:>6} {:>7} {:>10}", "packer", "sort", "size", "pages", "fill", "time");
+    for trial in trials(&mut atlas) {
+        match trial.layout {
+            Ok(((width, height), pages, fill)) => {
+                println!("{:<10} {:<10} {:>12} {:>6} {:>6.1}% {:>8}ms", trial.packer.name(), trial.order.name(),
+                         format!("{}x{}", width, height), pages, 100.0 * fill, trial.elapsed.as_millis());
+            }
+            Err(err) => println!("{:<10} {:<10} {}", trial.packer.name(), trial.order.name(), err)
+        }
+    }
+
+    0
+}
+
+// One layout tried by `compare` and how long packing it took
+struct Trial {
+    packer: Packer,
+    order: SortOrder,
+    // Texture size, page count and the share of it covered by sprites, or why packing failed
+    layout: Result<((u32, u32), u32, f64), AtlasError>,
+    elapsed: Duration
+}
+
+// Pack the loaded images with every packer and sort order in turn
+fn trials(atlas: &mut Atlas) -> Vec<Trial> {
+    let sprite_pixels: u64 = atlas.images.iter().map(|img| img.area() as u64).sum();
+    // Every row would show the same optimized layout
+    atlas.optimize = None;
+
+    let mut trials = Vec::new();
+    for (packer, order) in Packer::ALL.iter().flat_map(|&packer| SortOrder::ALL.map(|order| (packer, order))) {
+        atlas.packer = packer;
+        atlas.sort = order;
+        atlas.records.clear();
+
+        let started = Instant::now();
+        let result = atlas.pack();
+        let elapsed = started.elapsed();
+
+        let layout = result.map(|_| {
+            let pages = atlas.page_height().map_or(1, |page| atlas.height().div_ceil(page));
+            (atlas.texture_size(), pages, sprite_pixels as f64 / atlas.output_pixels() as f64)
+        });
+        trials.push(Trial { packer, order, layout, elapsed });
+    }
+    trials
+}
+
+// Check every png under the asset directory, failing when any problem is found
+fn doctor(matches: &ArgMatches) -> i32 {
+    let asset_dir = paths::normalize(matches.value_of("asset-directory").unwrap());
+    let asset_dir = Path::new(asset_dir.as_ref());
+    let mut atlas = configure(matches);
+    atlas.name_root = Some(asset_dir.to_path_buf());
+
+    let mut corrupt = Vec::new();
+    let mut unsupported = Vec::new();
+    let mut non_utf8 = Vec::new();
+
+    for entry in walk(matches, asset_dir) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                corrupt.push((PathBuf::new(), err.to_string()));
+                continue;
+            }
+        };
+
+        let path = entry.path();
+        if !entry.file_type().is_file() || !is_image(path) {
+            continue;
+        }
+        if path.to_str().is_none() {
+            non_utf8.push(path.to_path_buf());
+        }
+
+        // The header as stored, before the decoder expands or strips anything
+        let header = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png")) {
+            File::open(path)
+                .map_err(png::DecodingError::from)
+                .and_then(|file| png::Decoder::new(file).read_info())
+                .map(|(_, reader)| Some((reader.info().color_type, reader.info().bit_depth)))
+        } else {
+            Ok(None)
+        };
+        match header {
+            Ok(Some((color_type, png::BitDepth::Sixteen))) => {
+                let kind = format!("16-bit {:?}, packing keeps only 8 bits per channel", color_type);
+                unsupported.push((path.to_path_buf(), kind));
+            }
+            Ok(_) => {
+                if let Err(err) = atlas.add_image(path) {
+                    corrupt.push((path.to_path_buf(), err.reason()));
+                }
+            }
+            Err(err) => corrupt.push((path.to_path_buf(), err.to_string()))
+        }
+    }
+
+    let duplicates = atlas.drop_duplicate_names();
+    let oversized = atlas.oversized();
+
+    println!("{} corrupt file(s)", corrupt.len());
+    for (path, err) in corrupt.iter() {
+        println!("  {:?}: {}", path, err);
+    }
+    println!("{} unsupported color type(s)", unsupported.len());
+    for (path, kind) in unsupported.iter() {
+        println!("  {:?}: {}", path, kind);
+    }
+    println!("{} suspiciously large image(s)", oversized.len());
+    for image in oversized.iter() {
+        println!("  {:?} ({}x{})", image.path, image.width, image.height);
+    }
+    println!("{} duplicate name(s)", duplicates.len());
+    for (name, kept, dropped) in duplicates.iter() {
+        println!("  {:?}: {:?} and {:?}", name, kept, dropped);
+    }
+    println!("{} non-UTF-8 path(s)", non_utf8.len());
+    for path in non_utf8.iter() {
+        println!("  {:?}", path);
+    }
+
+    // Area alone gives a lower bound, gaps between sprites only add to it
+    let area: u64 = atlas.images.iter().map(|img| img.area() as u64).sum();
+    let width = atlas.pick_width().max(1);
+    let height = area.div_ceil(width as u64);
+    println!("estimated atlas size: {} image(s) need at least {}x{}, {} bytes as RGBA8",
+             atlas.images.len(), width, height, width as u64 * height * 4);
+
+    let problems = corrupt.len() + unsupported.len() + oversized.len() + duplicates.len() + non_utf8.len();
+    if problems > 0 {
+        EXIT_FAILED
+    } else {
+        0
+    }
+}
+
+// Compare the sprites of an atlas with the names quoted in source code, failing when they disagree
+fn audit(matches: &ArgMatches) -> i32 {
+    let atlas = paths::normalize(matches.value_of("atlas").unwrap());
+    let atlas = Path::new(atlas.as_ref());
+    let code_dir = paths::normalize(matches.value_of("code-dir").unwrap());
+    let code_dir = Path::new(code_dir.as_ref());
+
+    let key = matches.value_of("key").map(|key| crypt::parse_key(key).unwrap());
+    let data = match reader::read_data(atlas, key.as_ref()) {
+        Ok(data) => data,
+        Err(err) => {
+            error!("unable to read {:?}: {}", atlas, err);
+            return EXIT_FAILED;
+        }
+    };
+    if !code_dir.is_dir() {
+        error!("{:?} is not a directory", code_dir);
+        return EXIT_FAILED;
+    }
+
+    let names: Vec<String> = data.records.into_iter().map(|record| record.name).collect();
+    let report = audit::scan(code_dir, &names);
+
+    for name in report.unreferenced.iter() {
+        println!("unreferenced sprite {:?}", name);
+    }
+    for reference in report.missing.iter() {
+        println!("{}:{}: no sprite named {:?}", reference.path.display(), reference.line, reference.text);
+    }
+    println!("Searched {} file(s): {} of {} sprite(s) unreferenced, {} reference(s) to missing sprites",
+             report.files, report.unreferenced.len(), names.len(), report.missing.len());
+
+    if report.unreferenced.is_empty() && report.missing.is_empty() {
+        0
+    } else {
+        EXIT_FAILED
+    }
+}
+
+fn inspect(matches: &ArgMatches) -> i32 {
+    let atlas = paths::normalize(matches.value_of("atlas").unwrap());
+    let atlas = Path::new(atlas.as_ref());
+
+    let key = matches.value_of("key").map(|key| crypt::parse_key(key).unwrap());
+    let data = match reader::read_data(atlas, key.as_ref()) {
+        Ok(data) => data,
+        Err(err) => {
+            error!("unable to read {:?}: {}", atlas, err);
+            return EXIT_FAILED;
+        }
+    };
+    let inspection = inspect::inspect(&data);
+
+    if matches.is_present("json") {
+        println!("{}", serde_json::to_string_pretty(&inspection).unwrap());
+        return 0;
+    }
+
+    let name_width = inspection.sprites.iter().map(|sprite| sprite.name.chars().count()).max().unwrap_or(0).max(4);
+    let image_width = inspection.sprites.iter().map(|sprite| sprite.image.len()).max().unwrap_or(0).max(5);
+    println!("{:<name_width$}  {:<image_width$}  {:>5} {:>5} {:>5} {:>5}  {:>6} {:>6} {:>6} {:>6}  rotated",
+             "name", "image", "x", "y", "w", "h", "u0", "v0", "u1", "v1");
+    for sprite in inspection.sprites.iter() {
+        let [u0, v0, u1, v1] = sprite.uv;
+        println!("{:<name_width$}  {:<image_width$}  {:>5} {:>5} {:>5} {:>5}  {:>6.4} {:>6.4} {:>6.4} {:>6.4}  {}",
+                 sprite.name, sprite.image, sprite.x, sprite.y, sprite.width, sprite.height, u0, v0, u1, v1,
+                 if sprite.rotated { "yes" } else { "no" });
+    }
+
+    println!();
+    println!("{} sprite(s) in {} image(s) of {}x{}", inspection.sprites.len(), inspection.images, inspection.width, inspection.height);
+    println!("fill {:.1}%, wasted {:.1}% ({} of {} pixels covered)",
+             100.0 * inspection.fill, inspection.wasted_percent, inspection.sprite_pixels, inspection.total_pixels);
+    0
+}
+
+// Compare two atlases sprite by sprite, failing when they differ
+fn diff(matches: &ArgMatches) -> i32 {
+    let key = matches.value_of("key").map(|key| crypt::parse_key(key).unwrap());
+    let mut files = Vec::new();
+    for arg in ["old", "new"] {
+        let atlas = paths::normalize(matches.value_of(arg).unwrap());
+        match reader::read(Path::new(atlas.as_ref()), key.as_ref()) {
+            Ok(file) => files.push(file),
+            Err(err) => {
+                error!("unable to read {:?}: {}", atlas, err);
+                return EXIT_TROUBLE;
+            }
+        }
+    }
+    let diff = match diff::diff(&files[0], &files[1]) {
+        Ok(diff) => diff,
+        Err(err) => {
+            error!("unable to compare the atlases: {}", err);
+            return EXIT_TROUBLE;
+        }
+    };
+
+    if matches.is_present("json") {
+        println!("{}", serde_json::to_string_pretty(&diff).unwrap());
+    } else {
+        for name in diff.added.iter() {
+            println!("added    {}", name);
+        }
+        for name in diff.removed.iter() {
+            println!("removed  {}", name);
+        }
+        for moved in diff.moved.iter() {
+            let place = |to: &diff::Placement| format!("{},{} {}x{}{}{}{}", to.x, to.y, to.width, to.height,
+                if to.page > 0 { format!(" page {}", to.page) } else { String::new() },
+                if to.layer > 0 { format!(" layer {}", to.layer) } else { String::new() },
+                if to.rotated { " rotated" } else { "" });
+            println!("moved    {}  {} -> {}", moved.name, place(&moved.from), place(&moved.to));
+        }
+        for name in diff.changed.iter() {
+            println!("changed  {}", name);
+        }
+        if diff.old_size != diff.new_size {
+            println!("resized  {}x{} -> {}x{}", diff.old_size.0, diff.old_size.1, diff.new_size.0, diff.new_size.1);
+        }
+        println!("{} added, {} removed, {} moved, {} changed", diff.added.len(), diff.removed.len(), diff.moved.len(), diff.changed.len());
+    }
+
+    if diff.is_empty() { 0 } else { EXIT_DIFFERENT }
+}
+
+fn unpack(matches: &ArgMatches) -> i32 {
+    let atlas = paths::normalize(matches.value_of("atlas").unwrap());
+    let atlas = Path::new(atlas.as_ref());
+    let output_dir = paths::normalize(matches.value_of("output-dir").unwrap());
+    let output_dir = Path::new(output_dir.as_ref());
+
+    let key = matches.value_of("key").map(|key| crypt::parse_key(key).unwrap());
+    let sprites = match reader::read(atlas, key.as_ref()).map_err(|err| err.to_string()).and_then(|file| unpack::extract(&file)) {
+        Ok(sprites) => sprites,
+        Err(err) => {
+            error!("unable to unpack {:?}: {}", atlas, err);
+            return EXIT_FAILED;
+        }
+    };
+
+    let mut status = 0;
+    let mut count = 0;
+    for sprite in sprites.iter() {
+        // Names are relative paths, they must not lead out of the output directory
+        let relative = Path::new(&sprite.name);
+        if !relative.components().all(|part| matches!(part, std::path::Component::Normal(_))) {
+            warn!("skipping {:?}: not a relative file name", sprite.name);
+            status = EXIT_FAILED;
+            continue;
+        }
+
+        // Sprites packed from other formats or rendered from fonts are still written as pngs
+        let is_png = relative.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+        let path = if is_png { output_dir.join(relative) } else { output_dir.join(format!("{}.png", sprite.name)) };
+
+        let written = std::fs::create_dir_all(path.parent().unwrap())
+            .map_err(|err| AtlasError::Io(path.clone(), err))
+            .and_then(|_| write_png(&path.to_string_lossy(), sprite.width, sprite.height, &sprite.data));
+        match written {
+            Ok(()) => count += 1,
+            Err(err) => {
+                error!("unable to write {:?}: {}", path, err.reason());
+                status = EXIT_FAILED;
+            }
+        }
+    }
+
+    println!("Wrote {} of {} sprite(s) to {:?}", count, sprites.len(), output_dir);
+    status
+}
+
+// Check the atlas against the hashes stored with it, decode it, check its rects and texture
+// coordinates and cut out every sprite, comparing them against their sources if given
+fn verify(matches: &ArgMatches) -> i32 {
+    let atlas = paths::normalize(matches.value_of("atlas").unwrap());
+    let atlas = Path::new(atlas.as_ref());
+
+    let key = matches.value_of("key").map(|key| crypt::parse_key(key).unwrap());
+    // Atlases from before the seal have nothing to hash against, their records and pixels are still checked
+    let sealed = match reader::verify(atlas, key.as_ref()) {
+        Err(ReadError::Unsealed) => {
+            warn!("{:?} has no {}, skipping the integrity check", atlas, integrity::ENTRY);
+            Ok(())
+        }
+        result => result
+    };
+    let file = sealed
+        .and_then(|_| reader::read(atlas, key.as_ref()))
+        .map_err(|err| err.to_string());
+
+    let checked = file.and_then(|file| {
+        let mut problems = validate::check(&file);
+        if !problems.is_empty() {
+            return Ok((0, problems));
+        }
+
+        let sprites = unpack::extract(&file)?;
+        if let Some(dir) = matches.value_of("source-dir") {
+            let mut sources = Atlas::new();
+            sources.add_dir(Path::new(paths::normalize(dir).as_ref())).map_err(|err| err.to_string())?;
+            problems.extend(validate::compare(&file, &sprites, &sources.images)?);
+        }
+        Ok((sprites.len(), problems))
+    });
+
+    match checked {
+        Ok((count, problems)) if problems.is_empty() => {
+            println!("Verified {} sprites", count);
+            0
+        }
+        Ok((_, problems)) => {
+            for problem in problems.iter() {
+                error!("{}", problem);
+            }
+            error!("Verification of {:?} failed: {} problem(s)", atlas, problems.len());
+            EXIT_FAILED
+        }
+        Err(err) => {
+            error!("Verification of {:?} failed: {}", atlas, err);
+            EXIT_FAILED
+        }
+    }
+}
+
+// Settings shared by every atlas built in one invocation
+fn configure(matches: &ArgMatches) -> Atlas {
+    let mut atlas = Atlas::new();
+
+    // Presets only fill in what was not given explicitly
+    let preset = matches.value_of("preset").map(|name| Preset::from_name(name).unwrap());
+    let preset = |name: &str| preset.as_ref().filter(|_| !explicit(matches, name));
+
+    atlas.fixed_width = matches.value_of("width").map(|width| width.parse().unwrap());
+    atlas.pixel_format = match preset("pixel-format") {
+        Some(preset) => preset.pixel_format,
+        None => PixelFormat::from_name(matches.value_of("pixel-format").unwrap()).unwrap()
+    };
+    atlas.dither = match preset("dither") {
+        Some(preset) => preset.dither,
+        None => Dither::from_name(matches.value_of("dither").unwrap()).unwrap()
+    };
+    atlas.texture_array = matches.is_present("texture-array");
+    atlas.page_size = matches.value_of("udim").map(|size| size.parse().unwrap());
+    atlas.max_width = matches.value_of("max-width").map(|width| width.parse().unwrap());
+    atlas.max_height = matches.value_of("max-height").map(|height| height.parse().unwrap());
+    if let (Some(width), Some(max)) = (atlas.fixed_width, atlas.max_width) {
+        if width > max {
+            error!("--width {} is over --max-width {}", width, max);
+            std::process::exit(EXIT_FAILED);
+        }
+    }
+    atlas.tile_size = matches.value_of("virtual-tiles").map(|size| size.parse().unwrap());
+    atlas.cubemap = matches.value_of("cubemap").map(|layout| CubeLayout::from_name(layout).unwrap());
+    // Presets leave the fixed layouts alone, the flags conflict with them
+    let fixed_layout = atlas.texture_array || atlas.cubemap.is_some() || atlas.page_size.is_some() || atlas.tile_size.is_some();
+    atlas.pot = match preset("pot") {
+        Some(preset) => preset.pot && !fixed_layout,
+        None => matches.is_present("pot")
+    };
+    atlas.square = matches.is_present("square");
+    // An explicit pixel format also picks the container, and arrays and cubemaps need one
+    let ktx = matches.is_present("ktx") || preset("pixel-format").is_some_and(|preset| preset.ktx) && !explicit(matches, "texture-format");
+    atlas.texture_format = match TextureFormat::from_name(matches.value_of("texture-format").unwrap()).unwrap() {
+        TextureFormat::Png if ktx || atlas.texture_array || atlas.cubemap == Some(CubeLayout::Ktx2) => TextureFormat::Ktx2,
+        format => format
+    };
+    atlas.compression = matches.value_of("compression").map(|name| Compression::from_name(name).unwrap());
+    if atlas.texture_format == TextureFormat::Basis && explicit(matches, "pixel-format") && atlas.pixel_format != PixelFormat::Rgba8888 {
+        error!("Basis textures are encoded from rgba8888, not --pixel-format {}", atlas.pixel_format.name());
+        std::process::exit(EXIT_FAILED);
+    }
+    if let Some(compression) = atlas.compression {
+        if matches!(atlas.texture_format, TextureFormat::Png | TextureFormat::Basis) {
+            error!("--compression {} needs --texture-format ktx2 or dds", compression.name());
+            std::process::exit(EXIT_FAILED);
+        }
+        if explicit(matches, "pixel-format") && atlas.pixel_format != PixelFormat::Rgba8888 {
+            error!("--compression {} replaces --pixel-format {}", compression.name(), atlas.pixel_format.name());
+            std::process::exit(EXIT_FAILED);
+        }
+        if atlas.texture_format == TextureFormat::Dds && !dds::supports(compress::Texels::Blocks(compression)) {
+            error!("DDS has no {} format, it needs --texture-format ktx2", compression.name());
+            std::process::exit(EXIT_FAILED);
+        }
+    }
+    atlas.mipmaps = matches.is_present("mipmaps");
+    atlas.premultiply_alpha = matches.is_present("premultiply-alpha");
+    atlas.alpha_bleed = matches.is_present("alpha-bleed");
+    atlas.color_chunks = ChunkPolicy::from_name(matches.value_of("color-chunks").unwrap()).unwrap();
+    atlas.to_srgb = matches.is_present("to-srgb");
+    atlas.max_sprite_size = match preset("max-sprite-size") {
+        Some(preset) => preset.max_sprite_size,
+        None => matches.value_of("max-sprite-size").unwrap().parse().unwrap()
+    };
+    atlas.max_atlas_size = match preset("max-atlas-size") {
+        Some(preset) => Some(preset.max_atlas_size),
+        None => matches.value_of("max-atlas-size").map(|size| size.parse().unwrap())
+    };
+    atlas.max_sprite_share = matches.value_of("max-sprite-share").map(|share| share.parse().unwrap());
+    atlas.normalize_names = !matches.is_present("no-normalize");
+    atlas.key = matches.value_of("key").map(|key| crypt::parse_key(key).unwrap());
+    if let Some(max) = matches.value_of("max-pack-iterations") {
+        atlas.max_iterations = max.parse().unwrap();
+    }
+    if matches.is_present("sdf") {
+        atlas.sdf_spread = Some(matches.value_of("sdf-spread").unwrap().parse().unwrap());
+    }
+    if let Some(path) = matches.value_of("palette") {
+        match Palette::read(Path::new(path)) {
+            Ok(palette) => atlas.palette = Some(palette),
+            Err(err) => {
+                error!("unable to read palette {:?}: {}", path, err);
+                std::process::exit(EXIT_FAILED);
+            }
+        }
+    }
+    if let Some(path) = matches.value_of("priorities") {
+        match read_priorities(Path::new(path)) {
+            Ok(rules) => atlas.priorities = rules,
+            Err(err) => {
+                error!("unable to read priorities {:?}: {}", path, err);
+                std::process::exit(EXIT_FAILED);
+            }
+        }
+    }
+    if let Some(path) = matches.value_of("usage") {
+        match read_usage(Path::new(path)) {
+            Ok(usage) => {
+                atlas.usage = usage.into_iter()
+                    .map(|(name, uses)| (if atlas.normalize_names { name.nfc().collect() } else { name }, uses))
+                    .collect();
+            }
+            Err(err) => {
+                error!("unable to read usage report {:?}: {}", path, err);
+                std::process::exit(EXIT_FAILED);
+            }
+        }
+    }
+    if matches.is_present("height-to-normal") {
+        atlas.normal_strength = Some(matches.value_of("normal-strength").unwrap().parse().unwrap());
+    }
+    if let Some(globs) = matches.values_of("normal-map") {
+        atlas.normal_maps = globs.map(|glob| Glob::new(glob).unwrap().compile_matcher()).collect();
+    }
+    atlas.msdf_size = matches.value_of("msdf-size").unwrap().parse().unwrap();
+    atlas.msdf_range = matches.value_of("msdf-range").unwrap().parse().unwrap();
+    if let Some(charset) = matches.value_of("charset") {
+        atlas.charset = charset.to_string();
+    }
+    atlas.time_limit = matches.value_of("pack-timeout").map(|secs| Duration::from_secs(secs.parse().unwrap()));
+    atlas.name_style = match matches.value_of("name-style").unwrap() {
+        "filename" => NameStyle::Filename,
+        "stem" => NameStyle::Stem,
+        _ => NameStyle::Relative
+    };
+    atlas.placeholders = match matches.value_of("placeholders").unwrap() {
+        "skip" => Placeholders::Skip,
+        "reject" => Placeholders::Reject,
+        _ => Placeholders::Keep
+    };
+    if matches.is_present("animations") {
+        let pattern = matches.value_of("animation-pattern").unwrap_or(animation::DEFAULT_PATTERN);
+        atlas.animations = Some(regex::Regex::new(pattern).unwrap());
+    }
+    if let Some(rules) = matches.values_of("frame-duration") {
+        atlas.frame_durations = rules
+            .map(|rule| {
+                let (pattern, duration) = rule.rsplit_once('=').unwrap();
+                (Glob::new(pattern).unwrap().compile_matcher(), duration.parse().unwrap())
+            })
+            .collect();
+    }
+    if let Some(rules) = matches.values_of("nine-slice") {
+        atlas.nine_slices = rules
+            .map(|rule| {
+                let (pattern, borders) = rule.rsplit_once('=').unwrap();
+                (Glob::new(pattern).unwrap().compile_matcher(), NineSlice::parse(borders).unwrap())
+            })
+            .collect();
+    }
+    atlas.pivot = pivot::parse(matches.value_of("pivot").unwrap()).unwrap();
+    if let Some(rules) = matches.values_of("sprite-pivot") {
+        atlas.sprite_pivots = rules
+            .map(|rule| {
+                let (pattern, point) = rule.rsplit_once('=').unwrap();
+                (Glob::new(pattern).unwrap().compile_matcher(), pivot::parse(point).unwrap())
+            })
+            .collect();
+    }
+    if let Some(rules) = matches.values_of("user-data") {
+        atlas.user_data = rules
+            .map(|rule| {
+                let (pattern, data) = rule.split_once('=').unwrap();
+                (Glob::new(pattern).unwrap().compile_matcher(), userdata::check(data).unwrap().to_string())
+            })
+            .collect();
+    }
+    atlas.scale = matches.value_of("scale").map_or(1.0, |scale| scale.parse().unwrap());
+    if let Some(rules) = matches.values_of("sprite-scale") {
+        atlas.sprite_scales = rules
+            .map(|rule| {
+                let (pattern, scale) = rule.rsplit_once('=').unwrap();
+                (Glob::new(pattern).unwrap().compile_matcher(), scale.parse().unwrap())
+            })
+            .collect();
+    }
+    atlas.scale_filter = match matches.value_of("scale-filter").unwrap() {
+        "nearest" => FilterType::Nearest,
+        "lanczos" => FilterType::Lanczos3,
+        _ => FilterType::Triangle
+    };
+    atlas.linear_filtering = matches.is_present("linear-filtering");
+    atlas.oversize = match matches.value_of("oversize").unwrap() {
+        "own-page" => Oversize::OwnPage,
+        "downscale" => Oversize::Downscale,
+        _ => Oversize::Error
+    };
+    atlas.packer = Packer::from_name(matches.value_of("packer").unwrap()).unwrap();
+    atlas.heuristic = Heuristic::from_name(matches.value_of("heuristic").unwrap()).unwrap();
+    atlas.allow_rotation = matches.is_present("allow-rotation");
+    atlas.padding = match preset("padding") {
+        Some(preset) => preset.padding,
+        None => matches.value_of("padding").unwrap().parse().unwrap()
+    };
+    atlas.extrude = matches.value_of("extrude").unwrap().parse().unwrap();
+    if matches.is_present("polygons") {
+        // Extrusion would paint over the sprites overlapping a rect
+        if atlas.extrude > 0 {
+            error!("--polygons cannot be combined with --extrude");
+            std::process::exit(EXIT_FAILED);
+        }
+        atlas.polygons = Some(matches.value_of("polygon-tolerance").unwrap().parse().unwrap());
+    }
+    atlas.data_format = DataFormat::from_name(matches.value_of("format").unwrap()).unwrap();
+    // TexturePacker sheets describe a single image
+    if atlas.data_format.is_texturepacker() && (atlas.texture_array || atlas.cubemap.is_some() || atlas.page_size.is_some() || atlas.max_height.is_some() || atlas.tile_size.is_some()) {
+        error!("--format {} cannot describe texture arrays, cubemaps, pages or tiles", matches.value_of("format").unwrap());
+        std::process::exit(EXIT_FAILED);
+    }
+    // Godot's AtlasTextures are plain rects, and libGDX turns rotated regions the other way. Both
+    // engines only load images they can import.
+    if matches!(atlas.data_format, DataFormat::Godot | DataFormat::Libgdx) && (atlas.allow_rotation || atlas.texture_format != TextureFormat::Png
+        || atlas.pixel_format != PixelFormat::Rgba8888 || atlas.texture_array || atlas.cubemap.is_some() || atlas.tile_size.is_some()) {
+        error!("--format {} needs unrotated sprites in png images, without texture arrays, cubemaps or tiles",
+                 matches.value_of("format").unwrap());
+        std::process::exit(EXIT_FAILED);
+    }
+    atlas.pretty = matches.is_present("pretty");
+    atlas.sort = SortOrder::from_name(matches.value_of("sort").unwrap()).unwrap();
+    atlas.optimize = matches.value_of("optimize").map(|iterations| iterations.parse().unwrap());
+
+    atlas
+}
+
+// Pack the images under `asset_dir` for which `select` accepts the path relative to it
+// into `output_file`, failing with an exit status
+// Write the stats of the atlases built when --stats-out was given
+fn write_stats(matches: &ArgMatches, atlases: &[Stats]) -> Result<(), i32> {
+    let path = match matches.value_of("stats-out") {
+        Some(path) => Path::new(path),
+        None => return Ok(())
+    };
+
+    stats::write(path, atlases).map_err(|err| {
+        error!("unable to write stats to {:?}: {}", path, err);
+        EXIT_FAILED
+    })
+}
+
+// Files and directories under the asset directory that --include, --exclude and --max-depth leave in
+fn walk<'a>(matches: &ArgMatches, asset_dir: &'a Path) -> impl Iterator<Item = walkdir::Result<walkdir::DirEntry>> + 'a {
+    let globs = |arg| matches.values_of(arg).map(|globs| {
+        let mut set = GlobSetBuilder::new();
+        for glob in globs {
+            set.add(Glob::new(glob).unwrap());
+        }
+        set.build().unwrap()
+    });
+    let (include, exclude): (Option<GlobSet>, Option<GlobSet>) = (globs("include"), globs("exclude"));
+
+    let mut walk = WalkDir::new(asset_dir).sort_by_file_name();
+    if let Some(depth) = matches.value_of("max-depth") {
+        walk = walk.max_depth(depth.parse().unwrap());
+    }
+
+    // Directories are still descended into, a glob like `ui/**/*.png` only matches their files
+    walk.into_iter().filter(move |entry| {
+        let Ok(entry) = entry else { return true };
+        let relative = entry.path().strip_prefix(asset_dir).unwrap();
+        entry.file_type().is_dir()
+            || (include.as_ref().is_none_or(|set| set.is_match(relative)) && !exclude.as_ref().is_some_and(|set| set.is_match(relative)))
+    })
+}
+
+// The atlas --append keeps the sprites of, the output itself unless one is named
+fn appended<'a>(matches: &'a ArgMatches, output_file: &'a str) -> Option<Cow<'a, str>> {
+    matches.is_present("append").then(|| matches.value_of("append").map_or(Cow::Borrowed(output_file), paths::normalize))
+}
+
+// Read the selected images under `asset_dir` and everything that decides how they are packed
+fn load(matches: &ArgMatches, args: &[OsString], asset_dir: &Path, output_file: &str, select: &dyn Fn(&Path) -> bool, scale: Option<f32>) -> Result<Atlas, i32> {
+    let mut atlas = configure(matches);
+    if let Some(scale) = scale {
+        atlas.scale = scale;
+    }
+    atlas.name_root = Some(asset_dir.to_path_buf());
+
+    // One cache per output atlas, so groups and directories do not evict each other.
+    // A dry run writes nothing, the cache included.
+    if let Some(dir) = matches.value_of("cache").filter(|_| !matches.is_present("dry-run")) {
+        if let Err(err) = std::fs::create_dir_all(dir) {
+            error!("unable to create the cache directory {:?}: {}", dir, err);
+            return Err(EXIT_FAILED);
+        }
+        let name = Path::new(output_file).file_name().unwrap_or_default().to_string_lossy();
+        atlas.cache = Some(Cache::open(&Path::new(dir).join(format!("{}.cache", name)), cache_settings(matches, args)));
+    }
+
+    // Images that failed to load, reported once the walk is done
+    let mut failed = Vec::new();
+
+    let skip_non_utf8 = matches.value_of("non-utf8").unwrap() == "skip";
+    let msdf = matches.is_present("msdf");
+
+    // Image and font files in walk order, fonts flagged
+    let mut inputs: Vec<(PathBuf, bool)> = Vec::new();
+
+    // Merging repacks the given atlases instead of the asset directory
+    let walked = if matches.is_present("atlases") { None } else { Some(walk(matches, asset_dir)) };
+    for entry in walked.into_iter().flatten() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                warn!("skipping: {}", err);
+                continue;
+            }
+        };
+
+        if entry.file_type().is_file() {
+
+            let path = entry.path();
+            if !select(path.strip_prefix(asset_dir).unwrap()) {
+                continue;
+            }
+
+            let font = msdf && [Some(OsStr::new("ttf")), Some(OsStr::new("otf"))].contains(&path.extension());
+
+            let aseprite = is_aseprite(path);
+
+            if is_image(path) || font || aseprite {
+                if skip_non_utf8 && entry.file_name().to_str().is_none() {
+                    warn!("skipping {:?}: file name is not valid UTF-8", path);
+                    continue;
+                }
+
+                debug!("adding {:?}", path);
+                inputs.push((path.to_path_buf(), font || aseprite));
+            }
+        }
+    }
+
+    // Images are decoded in parallel, then added in walk order along with the fonts and Aseprite files
+    let decoded: Vec<Option<Result<Option<Image>, AtlasError>>> = inputs.par_iter()
+        .progress_with(progress::bar("decoding", inputs.len() as u64))
+        .map(|(path, sequential)| if *sequential { None } else { Some(atlas.decode(path)) })
+        .collect();
+    for ((path, _), decoded) in inputs.into_iter().zip(decoded) {
+        let result = match decoded {
+            Some(decoded) => decoded.map(|image| atlas.images.extend(image)),
+            None if is_aseprite(&path) => atlas.add_aseprite(&path),
+            None => atlas.add_font(&path)
+        };
+
+        if let Err(err) = result {
+            warn!("skipping {:?}: {}", path, err.reason());
+            failed.push(path);
+        }
+    }
+
+    for input in matches.values_of("atlases").into_iter().flatten() {
+        let input = paths::normalize(input);
+        match atlas.add_atlas(Path::new(input.as_ref())) {
+            Ok(0) => {}
+            Ok(identical) => info!("left out {} sprite(s) of {:?} identical to ones added before", identical, input),
+            Err(err) => {
+                error!("unable to merge {:?}: {}", input, err);
+                return Err(EXIT_FAILED);
+            }
+        }
+    }
+
+    if let Some(cache) = &atlas.cache {
+        let (decoded, unchanged) = cache.counts();
+        info!("decoded {} changed input(s), {} unchanged", decoded, unchanged);
+    }
+
+    if matches.is_present("composite-layers") {
+        for (path, err) in atlas.composite_layers() {
+            warn!("skipping {:?}: {}", path, err);
+            failed.push(path);
+        }
+    }
+
+    if let Some(suffixes) = matches.values_of("variant-suffix") {
+        atlas.variant_suffixes = suffixes.map(String::from).collect();
+        for (path, err) in atlas.attach_variants() {
+            warn!("skipping {:?}: {}", path, err);
+            failed.push(path);
+        }
+        for (i, suffix) in atlas.variant_suffixes.iter().enumerate() {
+            let missing = atlas.images.iter().filter(|image| image.variants[i].is_none()).count();
+            if missing > 0 {
+                warn!("{} sprite(s) have no {} variant, their rect stays transparent in atlas{}", missing, suffix, suffix);
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        let paths: String = failed.iter().map(|path| format!("\n  {:?}", path)).collect();
+        warn!("{} image(s) could not be packed:{}", failed.len(), paths);
+
+        if matches.is_present("strict") {
+            return Err(EXIT_FAILED);
+        }
+    }
+
+    if let Some(list) = matches.value_of("exclude-list") {
+        let text = match std::fs::read_to_string(list) {
+            Ok(text) => text,
+            Err(err) => {
+                error!("unable to read exclude list {:?}: {}", list, err);
+                return Err(EXIT_FAILED);
+            }
+        };
+
+        let names: Vec<String> = text.lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|name| if atlas.normalize_names { name.nfc().collect() } else { name.to_string() })
+            .collect();
+
+        let before = atlas.images.len();
+        for name in atlas.exclude(&names) {
+            warn!("excluded sprite {:?} is not among the inputs", name);
+        }
+        info!("excluded {} sprite(s)", before - atlas.images.len());
+    }
+
+    // Without an existing output there is nothing to append to yet, but a named atlas has to exist
+    let existing = appended(matches, output_file);
+    if let Some(existing) = existing.as_deref().filter(|existing| matches.value_of("append").is_some() || Path::new(existing).exists()) {
+        match atlas.keep_existing(Path::new(existing)) {
+            Ok(unchanged) => {
+                info!("keeping {} sprite(s) of {:?} in place, {} input(s) already packed", atlas.base.len(), existing, unchanged);
+            }
+            Err(err) => {
+                error!("unable to append to {:?}: {}", existing, err);
+                return Err(EXIT_FAILED);
+            }
+        }
+    }
+
+    if atlas.images.is_empty() && atlas.base.is_empty() && !matches.is_present("allow-empty") {
+        error!("no images in directory");
+        return Err(EXIT_EMPTY);
+    }
+
+    atlas.assign_priorities(asset_dir);
+
+    let unused = atlas.usage.keys().filter(|name| !atlas.images.iter().any(|img| img.name == **name)).count();
+    if unused > 0 {
+        warn!("{} sprite(s) in the usage report are not among the inputs", unused);
+    }
+
+    let conflicts = conflicts(matches);
+    let strict = matches.is_present("strict");
+
+    for (name, paths) in atlas.duplicate_names() {
+        let question = format!("{} images are named {:?}: {:?}", paths.len(), name, paths);
+        let options = [Resolution::Rename, Resolution::Skip, Resolution::Overwrite, Resolution::Abort];
+        let resolution = if strict { Resolution::Abort } else { conflicts.resolve(&question, &options, Resolution::Abort) };
+
+        // Skipping keeps the first image in path order, overwriting the last
+        let (kept, dropped) = match resolution {
+            Resolution::Rename => {
+                for path in paths[1..].iter() {
+                    let renamed = atlas.rename_image(path);
+                    info!("renamed {:?} to {:?}", path, renamed);
+                }
+                continue;
+            }
+            Resolution::Abort => {
+                error!("duplicate name {:?}: {:?}", name, paths);
+                return Err(EXIT_FAILED);
+            }
+            Resolution::Overwrite => (&paths[paths.len() - 1], &paths[..paths.len() - 1]),
+            _ => (&paths[0], &paths[1..])
+        };
+
+        let skipped: String = dropped.iter().map(|path| format!("\n  skipped {:?}", path)).collect();
+        warn!("duplicate name {:?}, keeping {:?}{}", name, kept, skipped);
+        atlas.images.retain(|img| !dropped.contains(&img.path));
+    }
+
+    match atlas.collect_credits(asset_dir) {
+        Ok(0) => {}
+        Ok(attributed) => info!("credited {} sprite(s) in {} attribution(s)", attributed, atlas.credits.len()),
+        Err(err) => {
+            error!("unable to read license: {}", err);
+            return Err(EXIT_FAILED);
+        }
+    }
+
+    let oversized: Vec<(PathBuf, u32, u32)> = atlas.oversized().iter()
+        .map(|image| (image.path.clone(), image.width, image.height))
+        .collect();
+    if !oversized.is_empty() {
+        let largest: String = oversized.iter().take(10)
+            .map(|(path, width, height)| format!("\n  {:?} ({}x{})", path, width, height))
+            .collect();
+        warn!("{} oversized source image(s), largest first:{}", oversized.len(), largest);
+
+        if strict {
+            return Err(EXIT_FAILED);
+        }
+    }
+
+    for (path, width, height) in oversized {
+        let question = format!("{:?} is {}x{}, over the size limits", path, width, height);
+        let options = [Resolution::Keep, Resolution::Skip, Resolution::Abort];
+        match conflicts.resolve(&question, &options, Resolution::Keep) {
+            Resolution::Skip => {
+                info!("skipping {:?}", path);
+                atlas.images.retain(|img| img.path != path);
+            }
+            Resolution::Abort => return Err(EXIT_FAILED),
+            _ => {}
+        }
+    }
+
+    let scaled = atlas.scale_images(asset_dir);
+    if scaled > 0 {
+        info!("scaled {} sprite(s)", scaled);
+    }
+
+    if atlas.oversize == Oversize::Downscale {
+        let scaled: String = atlas.downscale_oversized().iter()
+            .map(|(path, from, to)| format!("\n  {:?} ({}x{} to {}x{})", path, from.0, from.1, to.0, to.1))
+            .collect();
+        if !scaled.is_empty() {
+            warn!("downscaled {} sprite(s) to fit the size limits:{}", scaled.matches('\n').count(), scaled);
+        }
+    }
+
+    // Outside the log macros, which skip their arguments below the log level
+    if matches.is_present("dedupe") {
+        let folded = atlas.dedupe();
+        info!("folded {} duplicate sprite(s)", folded);
+    }
+
+    if matches.is_present("trim") {
+        let trimmed = atlas.trim();
+        info!("trimmed {} transparent pixel(s)", trimmed);
+    }
+
+    Ok(atlas)
+}
+
+// Arguments that do not change what is written, so changing them keeps the cache
+const CACHE_NEUTRAL: [&str; 9] = ["watch", "debounce", "jobs", "cache", "yes", "config", "target", "quiet", "verbose"];
+
+// Those of them that are flags, without a value to skip
+const NEUTRAL_FLAGS: [&str; 4] = ["watch", "yes", "quiet", "verbose"];
+
+// Options naming files that are read while packing, whose contents the cache depends on as well
+const SETTINGS_FILES: [&str; 4] = ["palette", "exclude-list", "priorities", "usage"];
+
+// Hash of the arguments, the files they name and the environment a cache is valid for
+fn cache_settings(matches: &ArgMatches, args: &[OsString]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+
+    // A file that cannot be read fails the build before the cache is saved
+    for path in SETTINGS_FILES.iter().filter_map(|option| matches.values_of(option)).flatten() {
+        std::fs::read(path).unwrap_or_default().hash(&mut hasher);
+    }
+
+    let mut args = args.iter().skip(1).map(|arg| arg.to_string_lossy().into_owned());
+    while let Some(arg) = args.next() {
+        let (name, separate_value) = match arg.strip_prefix("--") {
+            Some(long) => (long.split('=').next().unwrap(), !long.contains('=')),
+            None if arg.starts_with("-j") => ("jobs", arg == "-j"),
+            None if arg == "-y" => ("yes", false),
+            None if arg == "-q" => ("quiet", false),
+            None if arg.starts_with("-v") && arg[1..].chars().all(|c| c == 'v') => ("verbose", false),
+            None => ("", false)
+        };
+        if !CACHE_NEUTRAL.contains(&name) {
+            arg.hash(&mut hasher);
+        } else if separate_value && !NEUTRAL_FLAGS.contains(&name) {
+            args.next();
+        }
+    }
+
+    let mut vars: Vec<(String, String)> = std::env::vars()
+        .filter(|(var, _)| var.starts_with("ATLAST_") && !CACHE_NEUTRAL.iter().any(|arg| env_name(arg) == *var))
+        .collect();
+    vars.sort();
+    vars.hash(&mut hasher);
+
+    hasher.finish()
+}
+
+fn conflicts(matches: &ArgMatches) -> Conflicts {
+    let policy = matches.value_of("on-conflict").map(|name| Resolution::from_name(name).unwrap());
+    // Nobody is there to answer while watching
+    Conflicts::new(policy, matches.is_present("yes") || matches.is_present("watch"))
+}
+
+// The first of "<stem>-2.<ext>", "<stem>-3.<ext>", ... that does not exist yet
+fn unused_path(path: &str) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+
+    (2..)
+        .map(|n| path.with_file_name(format!("{}-{}{}", stem, n, ext)))
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+        .to_string_lossy()
+        .into_owned()
+}
+
+// Pack every image split off by --oversize own-page into an atlas of its own beside `output_file`,
+// "sprites.atlas" gets "sprites_own_1.atlas" and so on
+fn build_own_pages(matches: &ArgMatches, output_file: &str, images: Vec<Image>) -> Result<(), i32> {
+    let path = Path::new(output_file);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+
+    for (i, image) in images.into_iter().enumerate() {
+        let output = path.with_file_name(format!("{}_own_{}{}", stem, i + 1, ext));
+        let name = image.name.clone();
+
+        // The limits are what the sprite is over
+        let mut atlas = configure(matches);
+        atlas.max_width = None;
+        atlas.max_height = None;
+        atlas.page_size = None;
+        atlas.fixed_width = None;
+        atlas.images.push(image);
+        if let Err(err) = atlas.pack() {
+            error!("{}", err);
+            return Err(EXIT_FAILED);
+        }
+
+        if matches.is_present("dry-run") {
+            info!("Would write {:?} holding {:?}: {}x{}", output, name, atlas.width(), atlas.height());
+            continue;
+        }
+        if let Err(err) = atlas.write(&output) {
+            error!("{}", err);
+            return Err(EXIT_FAILED);
+        }
+        info!("Packed {:?} on its own into {:?}", name, output);
+    }
+    Ok(())
+}
+
+// Percentage of the atlas pages covered by sprites
+// Print the sprites of the seam test that pull in neighboring colors, out of `sprites`
+fn report_seams(reports: &[SeamReport], sprites: usize, out: Option<&str>) {
+    if reports.is_empty() {
+        println!("Seam test: no bleeding between {} sprites", sprites);
+    } else {
+        println!("Seam test: {} of {} sprites pull in neighboring colors:", reports.len(), sprites);
+        for report in reports {
+            println!("  {} (max error {}, {} of {} samples)",
+                report.name, report.max_error, report.contaminated, report.samples);
+        }
+    }
+    if let Some(path) = out {
+        println!("Seam test visualization written to {}", path);
+    }
+}
+
+// Print the sprites taking the most area, worth shrinking when the atlas is over a limit
+fn report_largest(atlas: &Atlas) {
+    println!("largest contributors:");
+    for (image, share) in atlas.largest(10) {
+        println!("  {} ({}x{}, {:.1}% of sprite area)", image.name, image.width, image.height, share);
+    }
+}
+
+fn occupancy(stats: &Stats) -> f64 {
+    100.0 * stats.sprite_pixels as f64 / (stats.sprite_pixels + stats.wasted_pixels).max(1) as f64
+}
+
+fn build(matches: &ArgMatches, args: &[OsString], asset_dir: &Path, output_file: &str, select: &dyn Fn(&Path) -> bool, scale: Option<f32>) -> Result<Stats, i32> {
+    let started = Instant::now();
+    let mut timings = Timings::default();
+    let output_file = &scale.map_or_else(|| output_file.to_string(), |scale| scaled_path(output_file, scale));
+
+    if Path::new(output_file).is_dir() {
+        error!("{:?} is a directory, -o names the atlas file", output_file);
+        return Err(EXIT_FAILED);
+    }
+
+    let dry_run = matches.is_present("dry-run");
+    let mut output_file = output_file.to_string();
+    // Appending to the output replaces it on purpose
+    let replaced = appended(matches, &output_file).is_some_and(|existing| Path::new(existing.as_ref()) == Path::new(&output_file));
+    if Path::new(&output_file).exists() && !replaced && !dry_run {
+        let question = format!("{:?} already exists", output_file);
+        let options = [Resolution::Rename, Resolution::Skip, Resolution::Overwrite, Resolution::Abort];
+        match conflicts(matches).resolve(&question, &options, Resolution::Overwrite) {
+            Resolution::Rename => {
+                output_file = unused_path(&output_file);
+                info!("writing {:?} instead", output_file);
+            }
+            Resolution::Skip => {
+                warn!("skipping {:?}: it already exists", output_file);
+                return Err(EXIT_FAILED);
+            }
+            Resolution::Abort => return Err(EXIT_FAILED),
+            _ => {}
+        }
+    }
+    let output_file = output_file.as_str();
+    let mut atlas = load(matches, args, asset_dir, output_file, select, scale)?;
+    let own_pages = if atlas.oversize == Oversize::OwnPage { atlas.split_oversized() } else { Vec::new() };
+    if !own_pages.is_empty() {
+        let paths: String = own_pages.iter().map(|image| format!("\n  {:?} ({}x{})", image.path, image.width, image.height)).collect();
+        warn!("{} sprite(s) are too large for the size limits and get an atlas of their own:{}", own_pages.len(), paths);
+    }
+
+    info!("Packing...");
+    timings.load = started.elapsed().as_millis() as u64;
+    let spinner = progress::spinner("packing");
+    if let Err(err) = atlas.pack() {
+        error!("{}", err);
+        return Err(EXIT_FAILED);
+    }
+    drop(spinner);
+    timings.pack = started.elapsed().as_millis() as u64 - timings.load;
+    for record in atlas.records() {
+        trace!("placed {:?} at {},{} ({}x{})", record.name, record.x, record.y, record.width, record.height);
+    }
+    if atlas.cache.as_ref().is_some_and(|cache| cache.kept_layout) {
+        info!("sprite sizes are unchanged, keeping the previous layout");
+    }
+
+    if matches.is_present("seam-test") || matches.is_present("seam-test-out") {
+        let out = matches.value_of("seam-test-out");
+        match atlas.seam_test(out) {
+            Ok(reports) => report_seams(&reports, atlas.images.len(), out),
+            Err(err) => warn!("{}", err)
+        }
+    }
+
+    // Written before the limits are checked, an atlas over them is the one worth looking at
+    if let Some(path) = matches.value_of("debug-preview") {
+        match atlas.debug_preview(path) {
+            Ok(legend) => info!("Preview written to {} with its legend in {:?}", path, legend),
+            Err(err) => warn!("{}", err)
+        }
+    }
+
+    if let Some(max) = atlas.max_atlas_size {
+        let (width, height) = atlas.texture_size();
+        if width > max || height > max {
+            error!("the atlas is {}x{}, over the {}x{} limit", width, height, max, max);
+            report_largest(&atlas);
+            return Err(EXIT_FAILED);
+        }
+    }
+
+    if let Some(budget) = matches.value_of("budget-pixels") {
+        let pixels = atlas.output_pixels();
+        if pixels > parse_pixels(budget).unwrap() {
+            error!("the atlas has {} pixels, over the {} budget", pixels, budget);
+            report_largest(&atlas);
+            return Err(EXIT_FAILED);
+        }
+    }
+
+    build_own_pages(matches, output_file, own_pages)?;
+
+    if dry_run {
+        timings.total = started.elapsed().as_millis() as u64;
+        let stats = atlas.stats(output_file, 0, timings);
+        info!("Would write {:?}: {} sprite(s), {}x{} in {} page(s), {:.1}% occupied",
+              output_file, stats.sprites, stats.width, stats.height, stats.pages.len(), occupancy(&stats));
+        return Ok(stats);
+    }
+
+    info!("Writing...");
+    let writing = Instant::now();
+    let spinner = progress::spinner("writing");
+    if let Err(err) = atlas.write(output_file) {
+        error!("{}", err);
+        return Err(EXIT_FAILED);
+    }
+    drop(spinner);
+    timings.write = writing.elapsed().as_millis() as u64;
+
+    // A cache that cannot be written only makes the next build slower
+    if let Some(Err(err)) = atlas.cache.take().map(|cache| cache.save()) {
+        warn!("unable to write the cache: {}", err);
+    }
+
+    let size = std::fs::metadata(output_file).map(|meta| meta.len()).unwrap_or(0);
+    if let Some(budget) = matches.value_of("budget") {
+        if size > parse_bytes(budget).unwrap() {
+            error!("{:?} is {} bytes, over the {} budget", output_file, size, budget);
+            report_largest(&atlas);
+            return Err(EXIT_FAILED);
+        }
+    }
+
+    if matches.is_present("verify") {
+        info!("Verifying...");
+        match atlas.verify(output_file) {
+            Ok(problems) if problems.is_empty() => {
+                info!("Verified {} sprites", atlas.sprite_count());
+            }
+            Ok(problems) => {
+                let listed: String = problems.iter().map(|problem| format!("\n  {}", problem)).collect();
+                error!("verification failed with {} problem(s):{}", problems.len(), listed);
+                return Err(EXIT_FAILED);
+            }
+            Err(err) => {
+                error!("verification failed: {}", err);
+                return Err(EXIT_FAILED);
+            }
+        }
+    }
+
+    timings.total = started.elapsed().as_millis() as u64;
+    let stats = atlas.stats(output_file, size, timings);
+    info!("Packed {} sprite(s) into {:?}: {}x{} in {} page(s), {:.1}% occupied, {} bytes",
+          stats.sprites, output_file, stats.width, stats.height, stats.pages.len(), occupancy(&stats), size);
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn environment_variables_add_the_flags_not_given() {
+        let args = |list: &[&str]| list.iter().map(OsString::from).collect::<Vec<_>>();
+        std::env::set_var("ATLAST_SDF", "Yes");
+        std::env::set_var("ATLAST_KTX", "0");
+        std::env::set_var("ATLAST_STRICT", "true");
+
+        assert_eq!(env_name("pixel-format"), "ATLAST_PIXEL_FORMAT");
+        assert_eq!(
+            with_env_flags(args(&["atlast", "-d", "sprites", "--strict"])),
+            args(&["atlast", "pack", "-d", "sprites", "--strict", "--sdf"])
+        );
+        // Only the packing commands take the flags
+        assert_eq!(with_env_flags(args(&["atlast", "inspect", "ui.atlas"])), args(&["atlast", "inspect", "ui.atlas"]));
+
+        for var in ["ATLAST_SDF", "ATLAST_KTX", "ATLAST_STRICT"] {
+            std::env::remove_var(var);
+        }
+    }
+
+    #[test]
+    fn extended_length_paths_use_backslashes() {
+        assert_eq!(paths::normalize_extended(r"\\?\C:\art/ui/icon.png"), r"\\?\C:\art\ui\icon.png");
+        assert_eq!(paths::normalize_extended(r"\\?\UNC\server\share/art"), r"\\?\UNC\server\share\art");
+    }
+
+    #[test]
+    fn other_paths_are_left_alone() {
+        assert_eq!(paths::normalize_extended(r"C:\art/ui"), r"C:\art/ui");
+        assert_eq!(paths::normalize_extended(r"\\server\share/art"), r"\\server\share/art");
+        assert_eq!(paths::normalize_extended("assets/ui"), "assets/ui");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn sprite_names_from_windows_paths() {
+        for path in [r"\\?\C:\art/ui/icon.png", r"\\?\UNC\server\share/art\icon.png", r"\\server\share/art\ui/icon.png", r"C:/art\icon.png"] {
+            let normalized = paths::normalize(path);
+            assert_eq!(Path::new(normalized.as_ref()).file_name().unwrap(), "icon.png", "{}", path);
+        }
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn walked_paths_strip_the_asset_directory() {
+        let root = paths::normalize(r"\\?\UNC\server\share/art");
+        let root = Path::new(root.as_ref());
+        let walked = root.join("ui").join("icon.png");
+        assert_eq!(walked.strip_prefix(root).unwrap(), Path::new(r"ui\icon.png"));
+    }
+
+    #[test]
+    fn config_values_become_arguments() {
+        let table: toml::Table = "output-file = \"ui.atlas\"\npadding = 2\ntrim = true\nsquare = false\ngroup = [\"ui/**=ui.atlas\", \"**=game.atlas\"]"
+            .parse().unwrap();
+        let options = config::args(&table).unwrap();
+        let args = |name: &str| options.iter().find(|(option, _)| option == name).unwrap().1.clone();
+
+        assert_eq!(args("output-file"), ["-o", "ui.atlas"]);
+        assert_eq!(args("padding"), ["--padding", "2"]);
+        assert_eq!(args("trim"), ["--trim"]);
+        assert!(args("square").is_empty());
+        assert_eq!(args("group"), ["--group", "ui/**=ui.atlas", "--group", "**=game.atlas"]);
+
+        let nested: toml::Table = "[ui]\npadding = 2".parse().unwrap();
+        assert!(config::args(&nested).is_err());
+    }
+
+    #[test]
+    fn targets_override_the_shared_options() {
+        let config = config::parse("padding = 2\ntrim = true\n[targets.ui]\noutput-file = \"ui.atlas\"\n[targets.chars]\npadding = 0").unwrap();
+        let names: Vec<&str> = config.targets.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["chars", "ui"]);
+
+        let args = |name: &str| -> Vec<OsString> { config.target(name).unwrap().into_iter().flat_map(|(_, args)| args).collect() };
+        assert_eq!(args("chars"), ["--trim", "--padding", "0"]);
+        assert_eq!(args("ui"), ["--padding", "2", "--trim", "-o", "ui.atlas"]);
+        assert!(config.target("tiles").is_none());
+
+        assert!(config::parse("[targets.ui]\njobs = 2").is_err());
+    }
+
+    #[test]
+    fn walk_applies_the_globs_and_depth() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-walk", std::process::id()));
+        for file in ["a.png", "a@2x.png", "ui/b.png", "ui/deep/c.png"] {
+            std::fs::create_dir_all(dir.join(file).parent().unwrap()).unwrap();
+            std::fs::write(dir.join(file), []).unwrap();
+        }
+
+        let files = |args: &[&str]| -> Vec<String> {
+            let matches = pack_args(App::new("atlast")).get_matches_from(std::iter::once("atlast").chain(args.iter().copied()));
+            let mut files: Vec<String> = walk(&matches, &dir)
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| entry.path().strip_prefix(&dir).unwrap().to_string_lossy().replace('\\', "/"))
+                .collect();
+            files.sort();
+            files
+        };
+
+        assert_eq!(files(&["--exclude", "*@2x.png"]), ["a.png", "ui/b.png", "ui/deep/c.png"]);
+        assert_eq!(files(&["--include", "ui/**", "--exclude", "**/deep/**"]), ["ui/b.png"]);
+        assert_eq!(files(&["--max-depth", "2"]), ["a.png", "a@2x.png", "ui/b.png"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn verbosity_picks_the_log_level_and_keeps_the_cache() {
+        let level = |args: &[&str]| log_level(&pack_args(App::new("atlast")).get_matches_from(std::iter::once("atlast").chain(args.iter().copied())));
+        assert_eq!(level(&[]), LevelFilter::Info);
+        assert_eq!(level(&["-q"]), LevelFilter::Error);
+        assert_eq!(level(&["-v"]), LevelFilter::Debug);
+        assert_eq!(level(&["-vv"]), LevelFilter::Trace);
+        assert_eq!(level(&["--verbose", "-v"]), LevelFilter::Trace);
+
+        let settings = |args: &[&str]| {
+            let args: Vec<OsString> = args.iter().map(OsString::from).collect();
+            cache_settings(&pack_args(App::new("atlast")).get_matches_from(args.iter().filter(|arg| *arg != "pack")), &args)
+        };
+        assert_eq!(settings(&["atlast", "pack", "-vv", "--padding", "2"]), settings(&["atlast", "pack", "--padding", "2"]));
+        assert_eq!(settings(&["atlast", "pack", "--quiet", "--padding", "2"]), settings(&["atlast", "pack", "--padding", "2"]));
+    }
+
+    #[test]
+    fn dry_runs_pack_without_writing() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-dry", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_png(&dir.join("a.png").to_string_lossy(), 4, 2, &[255; 4 * 2 * 4]).unwrap();
+        let output = dir.join("out").join("a.atlas");
+
+        let args: Vec<OsString> = ["atlast", "--dry-run", "--cache", &dir.join("cache").to_string_lossy()].iter().map(OsString::from).collect();
+        let matches = pack_args(App::new("atlast")).get_matches_from(&args);
+        let stats = build(&matches, &args, &dir, &output.to_string_lossy(), &|_| true, None).unwrap();
+        assert_eq!((stats.sprites, stats.sprite_pixels, stats.output_bytes), (1, 8, 0));
+        assert!(!dir.join("out").exists() && !dir.join("cache").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn scale_sets_mark_every_output() {
+        assert_eq!(scaled_path("build/sprites.atlas", 0.5), "build/sprites@0.5x.atlas");
+        assert_eq!(scaled_path("sprites.atlas", 1.0), "sprites@1x.atlas");
+        assert!(scale_set("1, 0.5,0.25".to_string()).is_ok());
+        assert!(scale_set("1,0".to_string()).is_err());
+        assert!(scale_rule("ui/**=0.5".to_string()).is_ok());
+        assert!(scale_rule("ui/**".to_string()).is_err());
+    }
+
+    #[test]
+    fn append_keeps_the_named_atlas_or_the_output() {
+        let matches = |args: &[&str]| pack_args(App::new("atlast")).get_matches_from(args);
+        let named = matches(&["atlast", "--append", "shipped.atlas", "-d", "art"]);
+        assert_eq!(appended(&named, "out.atlas").as_deref(), Some("shipped.atlas"));
+        assert_eq!(named.value_of("asset-directory"), Some("art"));
+        assert_eq!(appended(&matches(&["atlast", "--append", "-d", "art"]), "out.atlas").as_deref(), Some("out.atlas"));
+        assert_eq!(appended(&matches(&["atlast", "-d", "art"]), "out.atlas"), None);
+    }
+
+    #[test]
+    fn on_conflict_resolves_duplicate_names_and_existing_outputs() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-conflict", std::process::id()));
+        for (sub, color) in [("a", [255, 0, 0, 255]), ("b", [0, 0, 255, 255])] {
+            std::fs::create_dir_all(dir.join("art").join(sub)).unwrap();
+            write_png(&dir.join("art").join(sub).join("icon.png").to_string_lossy(), 2, 2, &color.repeat(4)).unwrap();
+        }
+        let cache = dir.join("cache").to_string_lossy().into_owned();
+        let pack = |policy: &str, output: &str| {
+            let args: Vec<OsString> = ["atlast", "--name-style", "filename", "--on-conflict", policy, "--cache", &cache]
+                .iter()
+                .map(OsString::from)
+                .collect();
+            let matches = pack_args(App::new("atlast")).get_matches_from(&args);
+            build(&matches, &args, &dir.join("art"), &dir.join(output).to_string_lossy(), &|_| true, None)
+        };
+        let sprites = |output: &str| {
+            let file = reader::read(&dir.join(output), None).unwrap();
+            let mut sprites: Vec<_> = unpack::extract(&file).unwrap().into_iter().map(|sprite| (sprite.name, sprite.data[..4].to_vec())).collect();
+            sprites.sort();
+            sprites
+        };
+        let (red, blue) = (vec![255, 0, 0, 255], vec![0, 0, 255, 255]);
+
+        // Skipping keeps the first image in path order and overwriting the last
+        pack("rename", "rename.atlas").unwrap();
+        assert_eq!(sprites("rename.atlas"), [("icon.png".to_string(), red.clone()), ("icon~2.png".to_string(), blue.clone())]);
+        pack("skip", "skip.atlas").unwrap();
+        assert_eq!(sprites("skip.atlas"), [("icon.png".to_string(), red.clone())]);
+        pack("overwrite", "overwrite.atlas").unwrap();
+        assert_eq!(sprites("overwrite.atlas"), [("icon.png".to_string(), blue.clone())]);
+        assert_eq!(pack("abort", "abort.atlas").err(), Some(EXIT_FAILED));
+        assert!(!dir.join("abort.atlas").exists());
+
+        // The same policy decides about an output that already exists
+        pack("rename", "skip.atlas").unwrap();
+        assert_eq!(sprites("skip-2.atlas").len(), 2);
+        assert_eq!(sprites("skip.atlas").len(), 1);
+        assert_eq!(pack("skip", "skip.atlas").err(), Some(EXIT_FAILED));
+
+        // A policy that is not on offer falls back to the default
+        let conflicts = Conflicts::new(Some(Resolution::Rename), false);
+        assert_eq!(conflicts.resolve("too large", &[Resolution::Keep, Resolution::Skip, Resolution::Abort], Resolution::Keep), Resolution::Keep);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dedupe_folds_identical_inputs_whatever_the_log_level() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-dedupe", std::process::id()));
+        std::fs::create_dir_all(dir.join("art")).unwrap();
+        for name in ["a.png", "b.png"] {
+            write_png(&dir.join("art").join(name).to_string_lossy(), 2, 2, &[255; 2 * 2 * 4]).unwrap();
+        }
+        let output = dir.join("dedupe.atlas");
+
+        // Tests run without a logger, so nothing is logged at all
+        let args: Vec<OsString> = ["atlast", "--dedupe", "--cache", &dir.join("cache").to_string_lossy()].iter().map(OsString::from).collect();
+        let matches = pack_args(App::new("atlast")).get_matches_from(&args);
+        build(&matches, &args, &dir.join("art"), &output.to_string_lossy(), &|_| true, None).unwrap();
+        let records = reader::read(&output, None).unwrap().data.records;
+        assert_eq!(records.len(), 2);
+        assert_eq!((records[0].x, records[0].y), (records[1].x, records[1].y));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn trim_records_the_source_size_whatever_the_log_level() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-trim", std::process::id()));
+        std::fs::create_dir_all(dir.join("art")).unwrap();
+        // An opaque texel with a transparent border
+        let mut pixels = vec![0; 3 * 3 * 4];
+        pixels[16..20].copy_from_slice(&[255; 4]);
+        write_png(&dir.join("art").join("dot.png").to_string_lossy(), 3, 3, &pixels).unwrap();
+        let output = dir.join("trim.atlas");
+
+        let args: Vec<OsString> = ["atlast", "--trim", "--cache", &dir.join("cache").to_string_lossy()].iter().map(OsString::from).collect();
+        let matches = pack_args(App::new("atlast")).get_matches_from(&args);
+        build(&matches, &args, &dir.join("art"), &output.to_string_lossy(), &|_| true, None).unwrap();
+        let record = &reader::read(&output, None).unwrap().data.records[0];
+        assert_eq!((record.width, record.height, record.source_width, record.source_height, record.offset_x, record.offset_y), (1, 1, 3, 3, 1, 1));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn caches_start_over_when_the_palette_changes() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-palette-cache", std::process::id()));
+        std::fs::create_dir_all(dir.join("art")).unwrap();
+        write_png(&dir.join("art").join("a.png").to_string_lossy(), 1, 1, &[255, 0, 0, 255]).unwrap();
+        let (palette, output) = (dir.join("palette.txt"), dir.join("a.atlas"));
+
+        let args: Vec<OsString> = ["atlast", "--palette", &palette.to_string_lossy(), "--cache", &dir.join("cache").to_string_lossy()]
+            .iter()
+            .map(OsString::from)
+            .collect();
+        let pack = |swap: &str| {
+            std::fs::write(&palette, format!("ff0000 {}\n", swap)).unwrap();
+            let matches = pack_args(App::new("atlast")).get_matches_from(&args);
+            build(&matches, &args, &dir.join("art"), &output.to_string_lossy(), &|_| true, None).unwrap();
+            reader::read(&output, None).unwrap().pixels
+        };
+        assert_eq!(pack("00ff00"), [0, 255, 0, 255]);
+        // The input is unchanged but decodes to other pixels
+        assert_eq!(pack("0000ff"), [0, 0, 255, 255]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn verify_checks_unsealed_atlases_without_their_hashes() {
+        use std::io::{Cursor, Read, Write};
+        use zip::{ZipArchive, ZipWriter};
+
+        let dir = std::env::temp_dir().join(format!("atlast-{}-verify-unsealed", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let verify_path = |path: &Path| {
+            let matches = app().get_matches_from(["atlast", "verify", path.to_str().unwrap()]);
+            verify(matches.subcommand_matches("verify").unwrap())
+        };
+
+        // Written before atlases were sealed, so it has no atlas.hash to check
+        let fixture: &[u8] = include_bytes!("../tests/fixtures/v1.atlas");
+        let intact = dir.join("intact.atlas");
+        std::fs::write(&intact, fixture).unwrap();
+        assert_eq!(verify_path(&intact), 0);
+
+        // The same atlas with a rect past the right edge of its image still fails
+        let mut png = Vec::new();
+        ZipArchive::new(Cursor::new(fixture)).unwrap().by_name("atlas.png").unwrap().read_to_end(&mut png).unwrap();
+        let data = bincode::serialize(&(vec![(3u32, 0u32, 2u32, 2u32, "blue")], 4u32, 2u32)).unwrap();
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in [("atlas.png", &png), ("atlas.data", &data)] {
+            zip.start_file(name, zip::write::FileOptions::default()).unwrap();
+            zip.write_all(contents).unwrap();
+        }
+        let outside = dir.join("outside.atlas");
+        std::fs::write(&outside, zip.finish().unwrap().into_inner()).unwrap();
+        assert_eq!(verify_path(&outside), EXIT_FAILED);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn diff_exits_like_diff_1() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-diff-status", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, color: u8| {
+            let path = dir.join(name);
+            let mut atlas = AtlasBuilder::new().build();
+            atlas.add(Image::new("a", 2, 2, vec![color; 2 * 2 * 4]).unwrap());
+            atlas.pack().unwrap();
+            atlas.write(&path).unwrap();
+            path.to_str().unwrap().to_string()
+        };
+        let (old, same, changed) = (write("old.atlas", 255), write("same.atlas", 255), write("changed.atlas", 128));
+        let missing = dir.join("missing.atlas").to_str().unwrap().to_string();
+        let status = |new: &str| {
+            let matches = app().get_matches_from(["atlast", "diff", "--json", &old, new]);
+            diff(matches.subcommand_matches("diff").unwrap())
+        };
+
+        assert_eq!(status(&same), 0);
+        assert_eq!(status(&changed), EXIT_DIFFERENT);
+        assert_eq!(status(&missing), EXIT_TROUBLE);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn per_directory_packs_loose_images_into_an_atlas_named_after_the_root() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-per-directory", std::process::id()));
+        let assets = dir.join("scenes");
+        std::fs::create_dir_all(assets.join("forest")).unwrap();
+        write_png(&assets.join("title.png").to_string_lossy(), 2, 2, &[255; 16]).unwrap();
+        write_png(&assets.join("forest").join("tree.png").to_string_lossy(), 2, 2, &[255; 16]).unwrap();
+        let output = dir.join("out").to_string_lossy().into_owned();
+        let pack = || {
+            let args: Vec<OsString> = ["atlast", "--per-directory", "-o", &output].iter().map(OsString::from).collect();
+            let matches = pack_args(App::new("atlast")).get_matches_from(&args);
+            run(&matches, &args, &assets, &output, None)
+        };
+        let names = |atlas: &str| -> Vec<String> {
+            reader::read_data(&dir.join("out").join(atlas), None).unwrap().records.into_iter().map(|record| record.name).collect()
+        };
+
+        pack().unwrap();
+        assert_eq!(names("scenes.atlas"), ["title.png"]);
+        assert_eq!(names("forest.atlas"), ["tree.png"]);
+
+        // A subdirectory named like the root would write the same atlas
+        std::fs::create_dir_all(assets.join("scenes")).unwrap();
+        write_png(&assets.join("scenes").join("sky.png").to_string_lossy(), 2, 2, &[255; 16]).unwrap();
+        assert_eq!(pack().err(), Some(EXIT_FAILED));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn corrupt_images_are_skipped_unless_strict() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-corrupt", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_png(&dir.join("good.png").to_string_lossy(), 2, 2, &[255; 16]).unwrap();
+        // A png cut off after its signature
+        std::fs::write(dir.join("truncated.png"), b"\x89PNG\r\n\x1a\n").unwrap();
+        let output = dir.join("out.atlas");
+        let pack = |flags: &[&str]| {
+            let args: Vec<OsString> = ["atlast"].iter().chain(flags).map(OsString::from).collect();
+            let matches = pack_args(App::new("atlast")).get_matches_from(&args);
+            build(&matches, &args, &dir, &output.to_string_lossy(), &|_| true, None)
+        };
+
+        assert_eq!(pack(&[]).unwrap().sprites, 1);
+        let names: Vec<String> = reader::read_data(&output, None).unwrap().records.into_iter().map(|record| record.name).collect();
+        assert_eq!(names, ["good.png"]);
+
+        std::fs::remove_file(&output).unwrap();
+        assert_eq!(pack(&["--strict"]).err(), Some(EXIT_FAILED));
+        assert!(!output.exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_file_names_are_recorded_lossily_or_skipped() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = std::env::temp_dir().join(format!("atlast-{}-non-utf8", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_png(&dir.join("plain.png").to_string_lossy(), 2, 2, &[255; 16]).unwrap();
+        // "café.png" in Latin-1
+        let latin1 = dir.join(OsStr::from_bytes(b"caf\xe9.png"));
+        std::fs::write(&latin1, std::fs::read(dir.join("plain.png")).unwrap()).unwrap();
+        let output = dir.join("out.atlas");
+        let names = |policy: &str| {
+            let args: Vec<OsString> = ["atlast", "--non-utf8", policy].iter().map(OsString::from).collect();
+            let matches = pack_args(App::new("atlast")).get_matches_from(&args);
+            build(&matches, &args, &dir, &output.to_string_lossy(), &|_| true, None).unwrap();
+            let mut names: Vec<String> = reader::read_data(&output, None).unwrap().records.into_iter().map(|record| record.name).collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(names("lossy"), ["caf\u{fffd}.png", "plain.png"]);
+        assert_eq!(names("skip"), ["plain.png"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn empty_inputs_exit_with_their_own_status_or_write_an_empty_atlas() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-empty", std::process::id()));
+        std::fs::create_dir_all(dir.join("art")).unwrap();
+        std::fs::write(dir.join("art").join("notes.txt"), "no images here").unwrap();
+        let output = dir.join("out.atlas");
+        let pack = |flags: &[&str]| {
+            let args: Vec<OsString> = ["atlast"].iter().chain(flags).map(OsString::from).collect();
+            let matches = pack_args(App::new("atlast")).get_matches_from(&args);
+            build(&matches, &args, &dir.join("art"), &output.to_string_lossy(), &|_| true, None)
+        };
+
+        assert_eq!(pack(&[]).err(), Some(EXIT_EMPTY));
+        assert!(!output.exists());
+
+        assert_eq!(pack(&["--allow-empty"]).unwrap().sprites, 0);
+        let file = reader::read(&output, None).unwrap();
+        assert!(file.data.records.is_empty());
+        assert_eq!((file.width, file.height), (1, 1));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn oversized_sources_only_fail_strict_builds() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-oversized", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_png(&dir.join("screenshot.png").to_string_lossy(), 64, 8, &[255; 64 * 8 * 4]).unwrap();
+        write_png(&dir.join("icon.png").to_string_lossy(), 4, 4, &[255; 4 * 4 * 4]).unwrap();
+        let output = dir.join("out.atlas");
+        let pack = |flags: &[&str]| {
+            let args: Vec<OsString> = ["atlast", "--max-sprite-size", "32"].iter().chain(flags).map(OsString::from).collect();
+            let matches = pack_args(App::new("atlast")).get_matches_from(&args);
+            build(&matches, &args, &dir, &output.to_string_lossy(), &|_| true, None)
+        };
+
+        assert_eq!(pack(&[]).unwrap().sprites, 2);
+        assert_eq!(pack(&["--strict"]).err(), Some(EXIT_FAILED));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+
+    #[test]
+    fn groups_route_sprites_into_their_own_atlases() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-groups", std::process::id()));
+        let assets = dir.join("art");
+        for path in ["ui/button.png", "ui/fx/glow.png", "fx/spark.png", "loose.png"] {
+            std::fs::create_dir_all(assets.join(path).parent().unwrap()).unwrap();
+            write_png(&assets.join(path).to_string_lossy(), 2, 2, &[255; 16]).unwrap();
+        }
+        let ui = dir.join("ui.atlas").to_string_lossy().into_owned();
+        let fx = dir.join("fx.atlas").to_string_lossy().into_owned();
+        let pack = |shared: &str| {
+            let args: Vec<OsString> = ["atlast", "--group", &format!("ui/**={}", ui), "--group", &format!("**/fx/**={}", fx), "--shared", shared]
+                .iter()
+                .map(OsString::from)
+                .collect();
+            let matches = pack_args(App::new("atlast")).get_matches_from(&args);
+            run(&matches, &args, &assets, "unused.atlas", None).unwrap();
+        };
+        let names = |atlas: &str| -> Vec<String> {
+            let mut names: Vec<String> = reader::read_data(Path::new(atlas), None).unwrap().records.into_iter().map(|record| record.name).collect();
+            names.sort();
+            names
+        };
+
+        // A sprite matching both groups goes into the first, one matching none into neither
+        pack("first");
+        assert_eq!(names(&ui), ["ui/button.png", "ui/fx/glow.png"]);
+        assert_eq!(names(&fx), ["fx/spark.png"]);
+
+        pack("duplicate");
+        assert_eq!(names(&ui), ["ui/button.png", "ui/fx/glow.png"]);
+        assert_eq!(names(&fx), ["fx/spark.png", "ui/fx/glow.png"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+
+    #[test]
+    fn presets_fill_in_what_the_flags_leave_out() {
+        let atlas = |args: &[&str]| configure(&pack_args(App::new("atlast")).get_matches_from(args));
+
+        let mobile = atlas(&["atlast", "--preset", "mobile"]);
+        assert_eq!((mobile.max_atlas_size, mobile.max_sprite_size, mobile.padding), (Some(2048), 1024, 2));
+        assert_eq!((mobile.pixel_format, mobile.dither), (PixelFormat::Rgba4444, Dither::FloydSteinberg));
+        assert_eq!(mobile.texture_format, TextureFormat::Ktx2);
+        assert!(mobile.pot);
+
+        // Explicit flags win, and an explicit pixel format keeps the container it asks for
+        let overridden = atlas(&["atlast", "--preset", "mobile", "--padding", "0", "--pixel-format", "rgba8888", "--max-atlas-size", "512"]);
+        assert_eq!((overridden.max_atlas_size, overridden.max_sprite_size, overridden.padding), (Some(512), 1024, 0));
+        assert_eq!((overridden.pixel_format, overridden.texture_format), (PixelFormat::Rgba8888, TextureFormat::Png));
+
+        // Fixed layouts are never rounded up to powers of two
+        assert!(!atlas(&["atlast", "--preset", "web", "--virtual-tiles", "64"]).pot);
+    }
+
+
+    #[test]
+    fn exclude_lists_leave_out_sprites_by_record_name() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-exclude", std::process::id()));
+        for path in ["ui/ok.png", "ui/broken.png", "fx/broken.png"] {
+            std::fs::create_dir_all(dir.join("art").join(path).parent().unwrap()).unwrap();
+            write_png(&dir.join("art").join(path).to_string_lossy(), 2, 2, &[255; 16]).unwrap();
+        }
+        // Matched by the whole record name, so fx/broken.png stays
+        let list = dir.join("exclude.txt");
+        std::fs::write(&list, "# pulled until the art is fixed\n  ui/broken.png  \n\nui/missing.png\n").unwrap();
+        let output = dir.join("out.atlas");
+        let pack = |flags: &[&str]| {
+            let args: Vec<OsString> = ["atlast"].iter().chain(flags).map(OsString::from).collect();
+            let matches = pack_args(App::new("atlast")).get_matches_from(&args);
+            build(&matches, &args, &dir.join("art"), &output.to_string_lossy(), &|_| true, None)
+        };
+
+        pack(&["--exclude-list", &list.to_string_lossy()]).unwrap();
+        let mut names: Vec<String> = reader::read_data(&output, None).unwrap().records.into_iter().map(|record| record.name).collect();
+        names.sort();
+        assert_eq!(names, ["fx/broken.png", "ui/ok.png"]);
+
+        assert_eq!(pack(&["--exclude-list", &dir.join("missing.txt").to_string_lossy()]).err(), Some(EXIT_FAILED));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+
+    #[test]
+    fn usage_reports_put_hot_sprites_on_the_first_pages() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-usage", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let report = dir.join("usage.txt");
+        std::fs::write(&report, "# frame counts from a play session\nhud/coin.png 9000\nhud/heart.png, 120.5\nboss/roar.png 3\n").unwrap();
+
+        let args = ["atlast", "--usage", &report.to_string_lossy(), "--max-width", "16", "--max-height", "16"].map(String::from);
+        let mut atlas = configure(&pack_args(App::new("atlast")).get_matches_from(&args));
+        assert_eq!(atlas.usage.get("hud/heart.png"), Some(&120.5));
+        for name in ["boss/roar.png", "a.png", "b.png", "c.png", "hud/heart.png", "hud/coin.png"] {
+            atlas.add(Image::new(name, 8, 8, vec![255; 8 * 8 * 4]).unwrap());
+        }
+        atlas.pack().unwrap();
+
+        // Reported sprites come first by their uses, unreported ones after them
+        let page = |name: &str| atlas.records().into_iter().find(|record| record.name == name).unwrap().page;
+        assert_eq!((page("hud/coin.png"), page("hud/heart.png"), page("boss/roar.png")), (0, 0, 0));
+        assert_eq!(atlas.records().iter().filter(|record| record.page == 1).count(), 2);
+
+        std::fs::write(&report, "hud/coin.png often\n").unwrap();
+        assert_eq!(read_usage(&report).unwrap_err(), "line 1: expected a sprite name and a number, got \"hud/coin.png often\"");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+
+    #[test]
+    fn stats_out_reports_fill_waste_and_trim_savings() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-stats", std::process::id()));
+        std::fs::create_dir_all(dir.join("art")).unwrap();
+        // An 8x8 sprite inside a transparent 10x10 border
+        let mut framed = vec![0; 10 * 10 * 4];
+        for y in 1..9 {
+            framed[(y * 10 + 1) * 4..(y * 10 + 9) * 4].fill(255);
+        }
+        write_png(&dir.join("art").join("framed.png").to_string_lossy(), 10, 10, &framed).unwrap();
+        write_png(&dir.join("art").join("solid.png").to_string_lossy(), 8, 8, &[255; 8 * 8 * 4]).unwrap();
+        let output = dir.join("out.atlas");
+        let report = dir.join("stats.json");
+
+        let args: Vec<OsString> = ["atlast", "--trim", "--max-width", "8", "--max-height", "8", "--stats-out", &report.to_string_lossy()]
+            .iter()
+            .map(OsString::from)
+            .collect();
+        let matches = pack_args(App::new("atlast")).get_matches_from(&args);
+        let stats = build(&matches, &args, &dir.join("art"), &output.to_string_lossy(), &|_| true, None).unwrap();
+        write_stats(&matches, &[stats]).unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&report).unwrap()).unwrap();
+        let atlas = &json["atlases"][0];
+        assert_eq!(atlas["output"], output.to_string_lossy().as_ref());
+        assert_eq!((atlas["sprites"].as_u64(), atlas["sprite_pixels"].as_u64()), (Some(2), Some(128)));
+        assert_eq!((atlas["wasted_pixels"].as_u64(), atlas["trim_saved_pixels"].as_u64()), (Some(0), Some(36)));
+        let pages = atlas["pages"].as_array().unwrap();
+        assert_eq!(pages.len(), 2);
+        assert!(pages.iter().all(|page| page["fill"] == 1.0 && page["total_pixels"] == 64));
+        assert!(atlas["output_bytes"].as_u64().unwrap() > 0);
+        assert!(atlas["timings_ms"]["total"].is_u64());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+
+    #[test]
+    fn compare_packs_every_strategy_as_a_fresh_build_would() {
+        let sizes = [(3, 7), (8, 2), (5, 5), (1, 9), (6, 4), (2, 2)];
+        let images = || sizes.iter().enumerate().map(|(i, &(width, height))| {
+            Image::new(&i.to_string(), width, height, vec![255; (width * height * 4) as usize]).unwrap()
+        });
+        let mut atlas = AtlasBuilder::new().padding(1).build();
+        images().for_each(|image| atlas.add(image));
+
+        let trials = trials(&mut atlas);
+        assert_eq!(trials.len(), Packer::ALL.len() * SortOrder::ALL.len());
+        let sprite_pixels: u32 = sizes.iter().map(|(width, height)| width * height).sum();
+        // Reusing the atlas carries nothing over from the previous layout
+        for trial in trials {
+            let mut fresh = AtlasBuilder::new().padding(1).packer(trial.packer).build();
+            fresh.sort = trial.order;
+            images().for_each(|image| fresh.add(image));
+            fresh.pack().unwrap();
+
+            let ((width, height), pages, fill) = trial.layout.unwrap();
+            assert_eq!((width, height), fresh.texture_size(), "{} {}", trial.packer.name(), trial.order.name());
+            assert_eq!(pages, 1);
+            assert_eq!(fill, sprite_pixels as f64 / (width * height) as f64);
+        }
+    }
+
+
+    #[test]
+    fn doctor_fails_on_any_problem_it_finds() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-doctor", std::process::id()));
+        for sub in ["ui", "fx"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        write_png(&dir.join("ui").join("icon.png").to_string_lossy(), 2, 2, &[255; 16]).unwrap();
+        let doctor = |flags: &[&str]| {
+            let dir = dir.to_string_lossy();
+            let args: Vec<&str> = ["atlast", "doctor", "-d", &dir].iter().chain(flags).copied().collect();
+            let matches = app().get_matches_from(args);
+            super::doctor(matches.subcommand_matches("doctor").unwrap())
+        };
+        assert_eq!(doctor(&[]), 0);
+
+        // Each kind of problem fails the check on its own
+        write_png(&dir.join("fx").join("icon.png").to_string_lossy(), 2, 2, &[255; 16]).unwrap();
+        assert_eq!(doctor(&[]), 0);
+        assert_eq!(doctor(&["--name-style", "filename"]), EXIT_FAILED);
+        assert_eq!(doctor(&["--max-sprite-size", "1"]), EXIT_FAILED);
+
+        let mut encoder = png::Encoder::new(File::create(dir.join("fx").join("deep.png")).unwrap(), 1, 1);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Sixteen);
+        encoder.write_header().unwrap().write_image_data(&[255; 8]).unwrap();
+        assert_eq!(doctor(&[]), EXIT_FAILED);
+        std::fs::remove_file(dir.join("fx").join("deep.png")).unwrap();
+
+        std::fs::write(dir.join("fx").join("broken.png"), b"\x89PNG\r\n\x1a\n").unwrap();
+        assert_eq!(doctor(&[]), EXIT_FAILED);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+
+    #[test]
+    fn watch_rebuilds_only_for_changes_to_packed_files() {
+        use notify::event::{AccessKind, CreateKind, DataChange, RemoveKind};
+
+        let event = |kind: EventKind, path: &str| notify::Event::new(kind).add_path(PathBuf::from(path));
+        let edit = EventKind::Modify(ModifyKind::Data(DataChange::Content));
+        assert!(changes_input(&event(EventKind::Create(CreateKind::File), "art/new.png")));
+        assert!(changes_input(&event(edit, "art/hero.JPG")));
+        assert!(changes_input(&event(EventKind::Remove(RemoveKind::File), "art/boss.aseprite")));
+        assert!(changes_input(&event(edit, "art/fonts/title.ttf")));
+
+        // Reading the inputs while building, or touching other files, would rebuild forever
+        assert!(!changes_input(&event(EventKind::Access(AccessKind::Read), "art/hero.png")));
+        assert!(!changes_input(&event(EventKind::Modify(ModifyKind::Metadata(MetadataKind::AccessTime)), "art/hero.png")));
+        assert!(!changes_input(&event(edit, "art/notes.txt")));
+        assert!(!changes_input(&event(EventKind::Create(CreateKind::File), "art/output.atlas")));
+    }
+
+
+    #[test]
+    fn each_command_takes_only_its_own_options() {
+        let args = |list: &[&str]| list.iter().map(OsString::from).collect::<Vec<_>>();
+        let parse = |list: &[&str]| app().get_matches_from_safe(with_env_flags(args(list)));
+
+        for command in COMMANDS {
+            let err = parse(&["atlast", command, "--help"]).unwrap_err();
+            assert_eq!(err.kind, clap::ErrorKind::HelpDisplayed, "{}", command);
+        }
+        // Packing options without a command still pack, the environment may add flags after them
+        assert_eq!(with_env_flags(args(&["atlast", "-d", "sprites"]))[..4], args(&["atlast", "pack", "-d", "sprites"])[..]);
+        assert_eq!(with_env_flags(args(&["atlast", "--version"])), args(&["atlast", "--version"]));
+
+        let matches = parse(&["atlast", "unpack", "ui.atlas", "-o", "sprites"]).unwrap();
+        assert_eq!(matches.subcommand_name(), Some("unpack"));
+        let unpack = matches.subcommand_matches("unpack").unwrap();
+        assert_eq!((unpack.value_of("atlas"), unpack.value_of("output-dir")), (Some("ui.atlas"), Some("sprites")));
+
+        // Packing options are not accepted by the commands that read atlases
+        for command in ["inspect", "unpack", "verify"] {
+            let err = parse(&["atlast", command, "ui.atlas", "--padding", "2"]).unwrap_err();
+            assert_eq!(err.kind, clap::ErrorKind::UnknownArgument, "{}", command);
+        }
+        assert!(parse(&["atlast", "pack", "--padding", "2"]).is_ok());
+    }
+}
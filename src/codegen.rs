@@ -0,0 +1,77 @@
+// Rust source with a constant for every sprite, for games that include!() it and refer to sprites
+// by identifier so a missing sprite fails to compile
+//
+// Constants are the sprite names without extension in upper snake case, "ui/play-button.png" is
+// UI_PLAY_BUTTON. `SPRITES` lists them by name in sorted order so `sprite` can binary search it.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::{strip_extension, AtlasData};
+
+// Identifier of a sprite constant, unique among `taken`
+fn identifier(name: &str, taken: &mut HashSet<String>) -> String {
+    let mut ident: String = strip_extension(name).chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    if !ident.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        ident.insert(0, '_');
+    }
+
+    let unique = (1..)
+        .map(|n| if n == 1 { ident.clone() } else { format!("{}_{}", ident, n) })
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap();
+    taken.insert(unique.clone());
+    unique
+}
+
+// Source of the module for `data`, written for the atlas `atlas`
+pub fn rust(data: &AtlasData, atlas: &str) -> String {
+    let mut records: Vec<_> = data.records.iter().collect();
+    records.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut text = String::new();
+    writeln!(text, "// Generated by atlast from {}, do not edit", atlas).unwrap();
+    writeln!(text).unwrap();
+    writeln!(text, "#[derive(Debug, Clone, Copy, PartialEq, Eq)]").unwrap();
+    writeln!(text, "pub struct SpriteRect {{").unwrap();
+    writeln!(text, "    pub x: u32,").unwrap();
+    writeln!(text, "    pub y: u32,").unwrap();
+    writeln!(text, "    pub w: u32,").unwrap();
+    writeln!(text, "    pub h: u32,").unwrap();
+    writeln!(text, "    // Page or texture array layer holding the sprite").unwrap();
+    writeln!(text, "    pub page: u32,").unwrap();
+    writeln!(text, "    // Stored turned 90° clockwise, w and h are those of the rect in the atlas").unwrap();
+    writeln!(text, "    pub rotated: bool").unwrap();
+    writeln!(text, "}}").unwrap();
+    writeln!(text).unwrap();
+    writeln!(text, "pub const ATLAS_WIDTH: u32 = {};", data.width).unwrap();
+    writeln!(text, "pub const ATLAS_HEIGHT: u32 = {};", data.height).unwrap();
+    writeln!(text, "// Blend with ONE, ONE_MINUS_SRC_ALPHA when true").unwrap();
+    writeln!(text, "pub const PREMULTIPLIED_ALPHA: bool = {};", data.premultiplied_alpha).unwrap();
+    writeln!(text).unwrap();
+
+    let mut taken = HashSet::new();
+    let mut listed = Vec::new();
+    for record in records {
+        let ident = identifier(&record.name, &mut taken);
+        writeln!(text, "// {}", record.name).unwrap();
+        writeln!(text, "pub const {}: SpriteRect = SpriteRect {{ x: {}, y: {}, w: {}, h: {}, page: {}, rotated: {} }};",
+                 ident, record.x, record.y, record.width, record.height, record.page.max(record.layer), record.rotated).unwrap();
+        listed.push((&record.name, ident));
+    }
+
+    writeln!(text).unwrap();
+    writeln!(text, "pub const SPRITES: [(&str, SpriteRect); {}] = [", listed.len()).unwrap();
+    for (i, (name, ident)) in listed.iter().enumerate() {
+        let separator = if i + 1 < listed.len() { "," } else { "" };
+        writeln!(text, "    ({:?}, {}){}", name, ident, separator).unwrap();
+    }
+    writeln!(text, "];").unwrap();
+    writeln!(text).unwrap();
+    writeln!(text, "pub fn sprite(name: &str) -> Option<SpriteRect> {{").unwrap();
+    writeln!(text, "    SPRITES.binary_search_by_key(&name, |(name, _)| *name).ok().map(|i| SPRITES[i].1)").unwrap();
+    writeln!(text, "}}").unwrap();
+    text
+}
@@ -0,0 +1,239 @@
+// Color space chunks (iCCP, gAMA, cHRM, sRGB) of png inputs, and the sRGB transfer function for
+// filtering in linear light
+
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+use serde::{Serialize, Deserialize};
+use qcms::{DataType, Intent, Profile, Transform, CIE_xyY, CIE_xyYTRIPLE};
+
+// What to do with the color chunks of the inputs when writing the atlas png
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkPolicy {
+    Strip,
+    Preserve
+}
+
+impl ChunkPolicy {
+    pub fn from_name(name: &str) -> Option<ChunkPolicy> {
+        match name {
+            "strip" => Some(ChunkPolicy::Strip),
+            "preserve" => Some(ChunkPolicy::Preserve),
+            _ => None
+        }
+    }
+}
+
+// Raw chunk data as found in the source file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColorChunks {
+    iccp: Option<Vec<u8>>,
+    gama: Option<Vec<u8>>,
+    chrm: Option<Vec<u8>>,
+    srgb: Option<Vec<u8>>
+}
+
+impl ColorChunks {
+    // Scan the chunks preceding the image data of an encoded png
+    pub fn read(png: &[u8]) -> ColorChunks {
+        let mut chunks = ColorChunks::default();
+        let mut pos = 8;
+
+        while pos + 8 <= png.len() {
+            let len = u32::from_be_bytes([png[pos], png[pos+1], png[pos+2], png[pos+3]]) as usize;
+            let kind = &png[pos+4..pos+8];
+            let start = pos + 8;
+
+            if kind == b"IDAT" || kind == b"IEND" || start + len > png.len() {
+                break;
+            }
+
+            let data = Some(png[start..start+len].to_vec());
+            match kind {
+                b"iCCP" => chunks.iccp = data,
+                b"gAMA" => chunks.gama = data,
+                b"cHRM" => chunks.chrm = data,
+                b"sRGB" => chunks.srgb = data,
+                _ => {}
+            }
+
+            // Skip the data and crc
+            pos = start + len + 4;
+        }
+
+        chunks
+    }
+
+    // Chunks describing plain sRGB with perceptual intent
+    pub fn srgb() -> ColorChunks {
+        ColorChunks {
+            srgb: Some(vec![0]),
+            ..ColorChunks::default()
+        }
+    }
+
+    pub fn write<W: std::io::Write>(&self, writer: &mut png::Writer<W>) -> Result<(), png::EncodingError> {
+        let chunks = [
+            (*b"iCCP", &self.iccp),
+            (*b"gAMA", &self.gama),
+            (*b"cHRM", &self.chrm),
+            (*b"sRGB", &self.srgb)
+        ];
+
+        for (kind, data) in chunks.iter() {
+            if let Some(data) = data {
+                writer.write_chunk(*kind, data)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Profile the pixels are encoded in, None when they are already sRGB or untagged
+    pub fn profile(&self) -> Option<Box<Profile>> {
+        if self.srgb.is_some() {
+            return None;
+        }
+
+        if let Some(iccp) = &self.iccp {
+            // Profile name, null separator, compression method, zlib data
+            let name_end = iccp.iter().position(|&b| b == 0)?;
+            let mut icc = Vec::new();
+            ZlibDecoder::new(iccp.get(name_end+2..)?).read_to_end(&mut icc).ok()?;
+            return Profile::new_from_slice(&icc, false);
+        }
+
+        let gamma = self.gama.as_ref()
+            .filter(|gama| gama.len() == 4 && be_u32(gama, 0) > 0)
+            .map(|gama| 100000.0 / be_u32(gama, 0) as f32);
+
+        let chromaticities = self.chrm.as_ref().filter(|chrm| chrm.len() == 32).map(|chrm| {
+            let xy = |i: usize| CIE_xyY {
+                x: be_u32(chrm, i * 8) as f64 / 100000.0,
+                y: be_u32(chrm, i * 8 + 4) as f64 / 100000.0,
+                Y: 1.0
+            };
+            (xy(0), CIE_xyYTRIPLE { red: xy(1), green: xy(2), blue: xy(3) })
+        });
+
+        if gamma.is_none() && chromaticities.is_none() {
+            return None;
+        }
+
+        let gamma = gamma.unwrap_or(2.2);
+        let (white, primaries) = chromaticities.unwrap_or_else(|| {
+            let xy = |x, y| CIE_xyY { x, y, Y: 1.0 };
+            (xy(0.3127, 0.3290), CIE_xyYTRIPLE {
+                red: xy(0.64, 0.33),
+                green: xy(0.30, 0.60),
+                blue: xy(0.15, 0.06)
+            })
+        });
+
+        Profile::new_rgb_with_gamma_set(white, primaries, gamma, gamma, gamma)
+    }
+}
+
+fn be_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([data[offset], data[offset+1], data[offset+2], data[offset+3]])
+}
+
+// Convert RGBA pixels from the given profile to sRGB in place
+pub fn convert_to_srgb(rgba: &mut [u8], profile: &Profile, grayscale: bool) -> bool {
+    let mut srgb = Profile::new_sRGB();
+    srgb.precache_output_transform();
+
+    if grayscale {
+        // Gray profiles only transform gray pixels
+        let gray: Vec<u8> = rgba.chunks(4).flat_map(|px| [px[0], px[3]]).collect();
+        let transform = Transform::new_to(profile, &srgb, DataType::GrayA8, DataType::RGBA8, Intent::default());
+        if let Some(transform) = transform {
+            transform.convert(&gray, rgba);
+            return true;
+        }
+    }
+
+    match Transform::new(profile, &srgb, DataType::RGBA8, Intent::default()) {
+        Some(transform) => {
+            transform.apply(rgba);
+            true
+        }
+        None => false
+    }
+}
+
+// An sRGB encoded channel in linear light, 0 to 1
+pub fn to_linear(channel: u8) -> f32 {
+    let c = channel as f32 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+// A channel in linear light encoded as sRGB, clamped to 0 to 1 first
+pub fn from_linear(linear: f32) -> u8 {
+    let l = linear.clamp(0.0, 1.0);
+    let c = if l <= 0.0031308 { l * 12.92 } else { 1.055 * l.powf(1.0 / 2.4) - 0.055 };
+    (c * 255.0).round() as u8
+}
+
+// RGBA pixels with the colors in linear light and alpha from 0 to 1
+pub fn linearize(rgba: &[u8]) -> Vec<f32> {
+    rgba.chunks_exact(4)
+        .flat_map(|px| [to_linear(px[0]), to_linear(px[1]), to_linear(px[2]), px[3] as f32 / 255.0])
+        .collect()
+}
+
+// The inverse of `linearize`
+pub fn delinearize(rgba: &[f32]) -> Vec<u8> {
+    rgba.chunks_exact(4)
+        .flat_map(|px| [from_linear(px[0]), from_linear(px[1]), from_linear(px[2]), (px[3].clamp(0.0, 1.0) * 255.0).round() as u8])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{convert_to_srgb, ColorChunks};
+
+    // A 1x1 png with `chunks` written before its pixels
+    fn png(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, 1, 1);
+        encoder.set_color(png::ColorType::RGBA);
+        let mut writer = encoder.write_header().unwrap();
+        for (kind, data) in chunks {
+            writer.write_chunk(**kind, data).unwrap();
+        }
+        writer.write_image_data(&[0; 4]).unwrap();
+        drop(writer);
+        bytes
+    }
+
+    #[test]
+    fn chunks_are_read_and_written_back_unchanged() {
+        let gama = 45455u32.to_be_bytes();
+        let source = png(&[(b"gAMA", &gama), (b"tEXt", b"Title\0hero")]);
+        let chunks = ColorChunks::read(&source);
+        assert_eq!(chunks, ColorChunks { gama: Some(gama.to_vec()), ..ColorChunks::default() });
+
+        let mut bytes = Vec::new();
+        let encoder = png::Encoder::new(&mut bytes, 1, 1);
+        let mut writer = encoder.write_header().unwrap();
+        chunks.write(&mut writer).unwrap();
+        writer.write_image_data(&[0]).unwrap();
+        drop(writer);
+        assert_eq!(ColorChunks::read(&bytes), chunks);
+    }
+
+    #[test]
+    fn only_tagged_pixels_are_converted_to_srgb() {
+        // sRGB and untagged pixels need no profile
+        assert!(ColorChunks::srgb().profile().is_none());
+        assert!(ColorChunks::default().profile().is_none());
+
+        // A gAMA of 1.0 marks linear pixels, half as bright being 188 in sRGB
+        let linear = ColorChunks::read(&png(&[(b"gAMA", &100000u32.to_be_bytes())]));
+        let mut pixels = [128, 128, 128, 255, 0, 0, 0, 0];
+        assert!(convert_to_srgb(&mut pixels, &linear.profile().unwrap(), false));
+        assert!(pixels[..3].iter().all(|&c| c.abs_diff(188) <= 2), "{:?}", pixels);
+        assert_eq!(pixels[3..], [255, 0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,156 @@
+// GPU block compression, so KTX2 and DDS atlases upload as they are without decoding or recompressing
+//
+// Every format works on 4x4 blocks, images are padded to whole blocks by repeating their last row
+// and column. BC1 and ETC2 are opaque, BC3, BC7 and ASTC keep alpha. ETC2 blocks are encoded in
+// the ETC1 subset of ETC2, which every ETC2 decoder reads, and ASTC by atlast's own encoder.
+
+use intel_tex_2::{bc1, bc3, bc7, etc1, RgbaSurface};
+
+use crate::astc;
+use crate::quantize::{expand, quantize, Dither, PixelFormat};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Bc1,
+    Bc3,
+    Bc7,
+    Etc2,
+    Astc
+}
+
+impl Compression {
+    pub fn from_name(name: &str) -> Option<Compression> {
+        match name {
+            "bc1" => Some(Compression::Bc1),
+            "bc3" => Some(Compression::Bc3),
+            "bc7" => Some(Compression::Bc7),
+            "etc2" => Some(Compression::Etc2),
+            "astc" => Some(Compression::Astc),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compression::Bc1 => "bc1",
+            Compression::Bc3 => "bc3",
+            Compression::Bc7 => "bc7",
+            Compression::Etc2 => "etc2",
+            Compression::Astc => "astc"
+        }
+    }
+
+    // Bytes of every 4x4 block
+    pub fn block_bytes(&self) -> usize {
+        match self {
+            Compression::Bc1 | Compression::Etc2 => 8,
+            Compression::Bc3 | Compression::Bc7 | Compression::Astc => 16
+        }
+    }
+
+    // Compress a `width` x `height` RGBA8 image
+    pub fn compress(&self, rgba: &[u8], width: u32, height: u32) -> Vec<u8> {
+        let (padded_width, padded_height) = (width.next_multiple_of(4), height.next_multiple_of(4));
+        let mut padded = Vec::with_capacity((padded_width * padded_height * 4) as usize);
+        for y in 0..padded_height {
+            let row = (y.min(height - 1) * width * 4) as usize;
+            padded.extend_from_slice(&rgba[row..row + (width * 4) as usize]);
+            for _ in width..padded_width {
+                padded.extend_from_slice(&rgba[row + ((width - 1) * 4) as usize..row + (width * 4) as usize]);
+            }
+        }
+
+        let surface = RgbaSurface {
+            data: &padded,
+            width: padded_width,
+            height: padded_height,
+            stride: padded_width * 4
+        };
+        let opaque = rgba.chunks(4).all(|px| px[3] == 255);
+        match self {
+            Compression::Bc1 => bc1::compress_blocks(&surface),
+            Compression::Bc3 => bc3::compress_blocks(&surface),
+            Compression::Bc7 if opaque => bc7::compress_blocks(&bc7::opaque_basic_settings(), &surface),
+            Compression::Bc7 => bc7::compress_blocks(&bc7::alpha_basic_settings(), &surface),
+            Compression::Etc2 => etc1::compress_blocks(&etc1::slow_settings(), &surface),
+            Compression::Astc => astc::compress(&padded, padded_width, padded_height)
+        }
+    }
+
+    // Decode a `width` x `height` image back to RGBA8, None when `blocks` is too short
+    pub fn decompress(&self, blocks: &[u8], width: u32, height: u32) -> Option<Vec<u8>> {
+        let (width, height) = (width as usize, height as usize);
+        let mut texels = vec![0u32; width * height];
+        match self {
+            Compression::Bc1 => texture2ddecoder::decode_bc1(blocks, width, height, &mut texels),
+            Compression::Bc3 => texture2ddecoder::decode_bc3(blocks, width, height, &mut texels),
+            Compression::Bc7 => texture2ddecoder::decode_bc7(blocks, width, height, &mut texels),
+            Compression::Etc2 => texture2ddecoder::decode_etc2_rgb(blocks, width, height, &mut texels),
+            Compression::Astc => texture2ddecoder::decode_astc_4_4(blocks, width, height, &mut texels)
+        }.ok()?;
+
+        // The decoder packs texels as BGRA
+        Some(texels.iter()
+            .flat_map(|texel| {
+                let [b, g, r, a] = texel.to_le_bytes();
+                [r, g, b, a]
+            })
+            .collect())
+    }
+}
+
+// How the texels of a written image are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Texels {
+    Pixels(PixelFormat),
+    Blocks(Compression),
+    // Basis Universal UASTC, kept as RGBA8 until `basis` encodes the whole texture and transcoded
+    // back to it when read
+    Basis
+}
+
+impl Texels {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Texels::Pixels(format) => format.name(),
+            Texels::Blocks(compression) => compression.name(),
+            Texels::Basis => "uastc"
+        }
+    }
+
+    // Bytes of a `width` x `height` image
+    pub fn size(&self, width: u32, height: u32) -> usize {
+        match self {
+            Texels::Pixels(PixelFormat::Rgba8888) | Texels::Basis => (width * height * 4) as usize,
+            Texels::Pixels(_) => (width * height * 2) as usize,
+            Texels::Blocks(compression) => (width.div_ceil(4) * height.div_ceil(4)) as usize * compression.block_bytes()
+        }
+    }
+
+    // Encode `images` RGBA8 images of `width` x `height` following each other. Blocks never span two images.
+    pub fn encode(&self, rgba: &[u8], width: u32, height: u32, images: u32, dither: Dither) -> Vec<u8> {
+        match self {
+            Texels::Pixels(PixelFormat::Rgba8888) | Texels::Basis => rgba.to_vec(),
+            Texels::Pixels(format) => quantize(rgba, width, height * images, *format, dither),
+            Texels::Blocks(compression) => rgba.chunks((width * height * 4) as usize)
+                .flat_map(|image| compression.compress(image, width, height))
+                .collect()
+        }
+    }
+
+    // Decode `images` images of `width` x `height` following each other back to RGBA8
+    pub fn decode(&self, data: &[u8], width: u32, height: u32, images: u32) -> Option<Vec<u8>> {
+        match self {
+            Texels::Pixels(format) => Some(expand(data, *format)),
+            Texels::Basis => Some(data.to_vec()),
+            Texels::Blocks(compression) => {
+                let size = self.size(width, height);
+                let mut rgba = Vec::with_capacity((width * height * images * 4) as usize);
+                for i in 0..images as usize {
+                    rgba.extend(compression.decompress(data.get(i * size..(i + 1) * size)?, width, height)?);
+                }
+                Some(rgba)
+            }
+        }
+    }
+}
@@ -0,0 +1,110 @@
+// Packing options read from atlast.toml
+//
+// Keys are the names of the pack options and take the values they take on the command line:
+// `padding = 2`, `allow-rotation = true`, `group = ["ui/**=ui.atlas", "**=game.atlas"]`. They are
+// turned back into arguments so clap checks and parses them like everything else.
+//
+// Tables under `targets` name atlases built by one run, each with options of its own that override
+// those set for all of them:
+//
+//     padding = 2
+//
+//     [targets.ui]
+//     asset-directory = "assets/ui"
+//     output-file = "build/ui.atlas"
+
+use std::ffi::OsString;
+use std::path::Path;
+
+use toml::{Table, Value};
+
+// Read from the working directory when no --config is given
+pub const DEFAULT: &str = "atlast.toml";
+
+// Options that only have a short form
+const SHORT: [(&str, &str); 2] = [("asset-directory", "-d"), ("output-file", "-o")];
+
+// Options that apply to the whole run rather than to one atlas
+const RUN_WIDE: [&str; 5] = ["jobs", "watch", "debounce", "quiet", "verbose"];
+
+// The arguments of every option by its name
+pub type Options = Vec<(String, Vec<OsString>)>;
+
+pub struct Config {
+    pub options: Options,
+    // Named atlases and the options they set, by name
+    pub targets: Vec<(String, Options)>
+}
+
+impl Config {
+    // Options of a target, overriding those set for all of them
+    pub fn target(&self, name: &str) -> Option<Options> {
+        let (_, own) = self.targets.iter().find(|(target, _)| target == name)?;
+        let shared = self.options.iter().filter(|(option, _)| !own.iter().any(|(name, _)| name == option));
+        Some(shared.chain(own.iter()).cloned().collect())
+    }
+}
+
+pub fn read(path: &Path) -> Result<Config, String> {
+    parse(&std::fs::read_to_string(path).map_err(|err| err.to_string())?)
+}
+
+pub fn parse(text: &str) -> Result<Config, String> {
+    let mut table = text.parse::<Table>().map_err(|err| err.to_string())?;
+
+    let targets = match table.remove("targets") {
+        Some(Value::Table(targets)) => targets.into_iter()
+            .map(|(name, options)| match options {
+                Value::Table(options) => {
+                    let options = args(&options).map_err(|err| format!("targets.{}: {}", name, err))?;
+                    match options.iter().find(|(option, _)| RUN_WIDE.contains(&option.as_str())) {
+                        Some((option, _)) => Err(format!("targets.{}: {} applies to the whole run", name, option)),
+                        None => Ok((name, options))
+                    }
+                }
+                _ => Err(format!("targets.{} is not a table of options", name))
+            })
+            .collect::<Result<_, String>>()?,
+        Some(_) => return Err("targets is not a table of named targets".to_string()),
+        None => Vec::new()
+    };
+
+    Ok(Config { options: args(&table)?, targets })
+}
+
+// The arguments every key of `table` stands for
+pub fn args(table: &Table) -> Result<Options, String> {
+    table.iter()
+        .map(|(name, value)| {
+            if name == "config" || name == "target" {
+                return Err(format!("{} cannot be set in a config file", name));
+            }
+            let flag = SHORT.iter()
+                .find(|(long, _)| long == name)
+                .map_or_else(|| format!("--{}", name), |(_, short)| short.to_string());
+
+            let values = match value {
+                Value::Array(values) => values.iter().collect(),
+                value => vec![value]
+            };
+            let mut args = Vec::new();
+            for value in values {
+                let text = match value {
+                    Value::String(text) => text.clone(),
+                    Value::Integer(number) => number.to_string(),
+                    Value::Float(number) => number.to_string(),
+                    // Flags are given or left out, there is nothing to pass
+                    Value::Boolean(true) => {
+                        args.push(OsString::from(&flag));
+                        continue;
+                    }
+                    Value::Boolean(false) => continue,
+                    _ => return Err(format!("{} takes a string, number, boolean or a list of them", name))
+                };
+                args.push(OsString::from(&flag));
+                args.push(OsString::from(text));
+            }
+            Ok((name.clone(), args))
+        })
+        .collect()
+}
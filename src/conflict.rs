@@ -0,0 +1,87 @@
+// Deciding what to do about duplicate names, existing outputs and oversized sprites
+//
+// In a terminal the user is asked about every conflict, otherwise `--on-conflict` picks the
+// resolution, falling back to the default of each kind of conflict.
+
+use std::io::{self, BufRead, IsTerminal, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Rename,
+    Skip,
+    Overwrite,
+    // Use the sprite as it is, only offered for oversized sprites
+    Keep,
+    Abort
+}
+
+impl Resolution {
+    pub fn from_name(name: &str) -> Option<Resolution> {
+        match name {
+            "rename" => Some(Resolution::Rename),
+            "skip" => Some(Resolution::Skip),
+            "overwrite" => Some(Resolution::Overwrite),
+            "abort" => Some(Resolution::Abort),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Resolution::Rename => "rename",
+            Resolution::Skip => "skip",
+            Resolution::Overwrite => "overwrite",
+            Resolution::Keep => "keep",
+            Resolution::Abort => "abort"
+        }
+    }
+}
+
+pub struct Conflicts {
+    policy: Option<Resolution>,
+    interactive: bool
+}
+
+impl Conflicts {
+    // `assume_yes` takes the default of every conflict without asking
+    pub fn new(policy: Option<Resolution>, assume_yes: bool) -> Conflicts {
+        Conflicts {
+            policy,
+            interactive: policy.is_none() && !assume_yes && io::stdin().is_terminal() && io::stdout().is_terminal()
+        }
+    }
+
+    // Pick one of `options`. A policy that is not among them, like rename for an oversized
+    // sprite, resolves to `default` as does an empty answer or a closed stdin.
+    pub fn resolve(&self, question: &str, options: &[Resolution], default: Resolution) -> Resolution {
+        if let Some(policy) = self.policy {
+            return if options.contains(&policy) { policy } else { default };
+        }
+        if !self.interactive {
+            return default;
+        }
+
+        let choices: Vec<String> = options.iter()
+            .map(|option| if *option == default { format!("[{}]", option.name()) } else { option.name().to_string() })
+            .collect();
+
+        let stdin = io::stdin();
+        loop {
+            print!("{} {}? ", question, choices.join("/"));
+            io::stdout().flush().unwrap();
+
+            let mut answer = String::new();
+            if stdin.lock().read_line(&mut answer).unwrap_or(0) == 0 {
+                return default;
+            }
+
+            let answer = answer.trim().to_lowercase();
+            if answer.is_empty() {
+                return default;
+            }
+            if let Some(option) = options.iter().find(|option| option.name().starts_with(&answer)) {
+                return *option;
+            }
+        }
+    }
+}
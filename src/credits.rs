@@ -0,0 +1,131 @@
+// License and attribution metadata carried from the art into the atlas
+//
+// A sprite's attribution is read from its `<stem>.license` sidecar, or else from the nearest
+// `atlast.license` in its directory or a parent directory up to the asset directory:
+//
+//     # applies to everything under this directory
+//     author: Jane Doe
+//     license: CC-BY-4.0
+//     source: https://example.com/pack
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, Deserialize};
+
+pub const DIRECTORY_FILE: &str = "atlast.license";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Attribution {
+    pub author: Option<String>,
+    pub license: Option<String>,
+    pub source: Option<String>
+}
+
+// Sprites sharing the same attribution
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Credit {
+    pub attribution: Attribution,
+    pub sprites: Vec<String>
+}
+
+impl Attribution {
+    pub fn read(path: &Path) -> Result<Attribution, String> {
+        let text = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let mut attribution = Attribution::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line.split_once(':')
+                .ok_or_else(|| format!("line {}: expected \"field: value\", got {:?}", number + 1, line))?;
+            let value = Some(value.trim().to_string());
+            match key.trim() {
+                "author" => attribution.author = value,
+                "license" => attribution.license = value,
+                "source" => attribution.source = value,
+                other => return Err(format!("line {}: unknown field {:?}", number + 1, other))
+            }
+        }
+
+        Ok(attribution)
+    }
+}
+
+// Find the attribution file for the sprite at `path`, looking no higher than `root`
+pub fn sidecar(path: &Path, root: &Path) -> Option<PathBuf> {
+    let own = path.with_extension("license");
+    if own.is_file() {
+        return Some(own);
+    }
+
+    path.ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(root))
+        .map(|dir| dir.join(DIRECTORY_FILE))
+        .find(|file| file.is_file())
+}
+
+// Group sprites by attribution, keeping the order attributions were first seen in
+pub fn group(sprites: Vec<(String, Attribution)>) -> Vec<Credit> {
+    let mut credits: Vec<Credit> = Vec::new();
+    let mut index: HashMap<Attribution, usize> = HashMap::new();
+
+    for (name, attribution) in sprites {
+        let i = *index.entry(attribution.clone()).or_insert_with(|| {
+            credits.push(Credit { attribution, sprites: Vec::new() });
+            credits.len() - 1
+        });
+        credits[i].sprites.push(name);
+    }
+
+    credits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{group, sidecar, Attribution, DIRECTORY_FILE};
+
+    #[test]
+    fn sprites_take_their_own_sidecar_or_the_nearest_directory_file() {
+        let root = std::env::temp_dir().join(format!("atlast-{}-credits", std::process::id()));
+        std::fs::create_dir_all(root.join("pack").join("trees")).unwrap();
+        std::fs::write(root.join(DIRECTORY_FILE), "author: Studio\n").unwrap();
+        std::fs::write(root.join("pack").join(DIRECTORY_FILE), "# third party\nauthor: Jane Doe\nlicense: CC-BY-4.0\n").unwrap();
+        std::fs::write(root.join("pack").join("trees").join("oak.license"), "author: Ann\nsource: https://example.com/oak\n").unwrap();
+
+        let oak = root.join("pack").join("trees").join("oak.png");
+        assert_eq!(sidecar(&oak, &root), Some(root.join("pack").join("trees").join("oak.license")));
+        let pine = root.join("pack").join("trees").join("pine.png");
+        assert_eq!(sidecar(&pine, &root), Some(root.join("pack").join(DIRECTORY_FILE)));
+        // Nothing above the asset directory is read
+        assert_eq!(sidecar(&pine, &root.join("pack").join("trees")), None);
+
+        let attribution = Attribution::read(&sidecar(&pine, &root).unwrap()).unwrap();
+        assert_eq!(attribution.author.as_deref(), Some("Jane Doe"));
+        assert_eq!(attribution.license.as_deref(), Some("CC-BY-4.0"));
+        assert_eq!(attribution.source, None);
+
+        std::fs::write(root.join(DIRECTORY_FILE), "copyright 2024\n").unwrap();
+        assert_eq!(Attribution::read(&root.join(DIRECTORY_FILE)).unwrap_err(), "line 1: expected \"field: value\", got \"copyright 2024\"");
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn sprites_sharing_an_attribution_are_credited_together() {
+        let by = |author: &str| Attribution { author: Some(author.to_string()), license: None, source: None };
+        let credits = group(vec![
+            ("oak".to_string(), by("Ann")),
+            ("coin".to_string(), by("Studio")),
+            ("pine".to_string(), by("Ann"))
+        ]);
+
+        let grouped: Vec<(&str, Vec<&str>)> = credits.iter()
+            .map(|credit| (credit.attribution.author.as_deref().unwrap(), credit.sprites.iter().map(String::as_str).collect()))
+            .collect();
+        assert_eq!(grouped, [("Ann", vec!["oak", "pine"]), ("Studio", vec!["coin"])]);
+    }
+}
@@ -0,0 +1,57 @@
+// Encrypting whole atlas archives so shipped builds do not expose the art
+//
+// An encrypted atlas is the magic bytes, a 12 byte nonce and the AES-256-GCM encrypted zip.
+// The nonce is derived from the key and the archive, so the same inputs still produce the same
+// file and different archives never share a nonce.
+
+use aes_gcm::{Aes256Gcm, Key as AesKey, KeyInit, Nonce};
+use aes_gcm::aead::Aead;
+use sha2::{Digest, Sha256};
+
+pub const MAGIC: &[u8; 8] = b"ATLASTE\x01";
+
+pub type Key = [u8; 32];
+
+// Keys are 64 hex digits
+pub fn parse_key(value: &str) -> Result<Key, String> {
+    let value = value.trim();
+    if value.len() != 64 || !value.is_ascii() {
+        return Err("expected a key of 64 hex digits".to_string());
+    }
+
+    let mut key = [0; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&value[2 * i..2 * i + 2], 16)
+            .map_err(|_| "expected a key of 64 hex digits".to_string())?;
+    }
+    Ok(key)
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+pub fn encrypt(key: &Key, archive: &[u8]) -> Vec<u8> {
+    let digest = Sha256::new().chain_update(key).chain_update(archive).finalize();
+    let nonce = Nonce::from_slice(&digest[..12]);
+
+    let cipher = Aes256Gcm::new(AesKey::<Aes256Gcm>::from_slice(key));
+    let sealed = cipher.encrypt(nonce, archive).unwrap();
+
+    let mut out = Vec::with_capacity(MAGIC.len() + 12 + sealed.len());
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(nonce);
+    out.extend_from_slice(&sealed);
+    out
+}
+
+pub fn decrypt(key: &Key, data: &[u8]) -> Result<Vec<u8>, String> {
+    if !is_encrypted(data) || data.len() < MAGIC.len() + 12 {
+        return Err("not an encrypted atlas".to_string());
+    }
+
+    let (nonce, sealed) = data[MAGIC.len()..].split_at(12);
+    let cipher = Aes256Gcm::new(AesKey::<Aes256Gcm>::from_slice(key));
+    cipher.decrypt(Nonce::from_slice(nonce), sealed)
+        .map_err(|_| "wrong key or corrupted atlas".to_string())
+}
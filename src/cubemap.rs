@@ -0,0 +1,37 @@
+// Assembling `name_px.png`, `name_nx.png`, ... face images into cubemaps
+
+// Face suffixes in KTX2 / Vulkan face order: +X, -X, +Y, -Y, +Z, -Z
+pub const FACES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+// Column and row of each face in a horizontal cross, in face size units:
+//
+//        +Y
+//    -X  +Z  +X  -Z
+//        -Y
+pub const CROSS: [(u32, u32); 6] = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CubeLayout {
+    // Faces laid out as a horizontal cross per cube in a png
+    Cross,
+    // A KTX2 cubemap, or cubemap array for several cubes
+    Ktx2
+}
+
+impl CubeLayout {
+    pub fn from_name(name: &str) -> Option<CubeLayout> {
+        match name {
+            "cross" => Some(CubeLayout::Cross),
+            "ktx2" => Some(CubeLayout::Ktx2),
+            _ => None
+        }
+    }
+}
+
+// Split a sprite name like "sky_px.png" into the cube name and face index
+pub fn face(name: &str) -> Option<(&str, usize)> {
+    let stem = name.rsplit_once('.').map_or(name, |(stem, _)| stem);
+    let (cube, suffix) = stem.rsplit_once('_')?;
+    let face = FACES.iter().position(|&face| face.eq_ignore_ascii_case(suffix))?;
+    Some((cube, face))
+}
@@ -0,0 +1,157 @@
+// Minimal DDS container reader and writer
+//
+// Textures are always written with the DX10 header, the only one describing BC7, sRGB and texture
+// arrays. Unlike KTX2, every array layer or face is stored with all its mip levels before the next.
+// DXGI has no ETC2 or ASTC formats, those need KTX2.
+
+use std::io::{self, Write};
+
+use crate::compress::{Compression, Texels};
+use crate::quantize::PixelFormat;
+
+const MAGIC: &[u8; 4] = b"DDS ";
+const HEADER_SIZE: u32 = 124;
+const PIXEL_FORMAT_SIZE: u32 = 32;
+
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_LINEARSIZE: u32 = 0x80000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x400000;
+const DDSCAPS2_CUBEMAP_ALL_FACES: u32 = 0xFE00;
+const RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+const RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+
+const DXGI_FORMAT_R8G8B8A8_UNORM_SRGB: u32 = 29;
+const DXGI_FORMAT_BC1_UNORM_SRGB: u32 = 72;
+const DXGI_FORMAT_BC3_UNORM_SRGB: u32 = 78;
+const DXGI_FORMAT_B5G6R5_UNORM: u32 = 85;
+const DXGI_FORMAT_BC7_UNORM_SRGB: u32 = 99;
+const DXGI_FORMAT_A4B4G4R4_UNORM: u32 = 191;
+
+// Offset of the texture data, after the magic, header and DX10 header
+const DATA_OFFSET: usize = 4 + HEADER_SIZE as usize + 20;
+
+fn dxgi_format(texels: Texels) -> Option<u32> {
+    match texels {
+        Texels::Pixels(PixelFormat::Rgba8888) => Some(DXGI_FORMAT_R8G8B8A8_UNORM_SRGB),
+        Texels::Pixels(PixelFormat::Rgba4444) => Some(DXGI_FORMAT_A4B4G4R4_UNORM),
+        Texels::Pixels(PixelFormat::Rgb565) => Some(DXGI_FORMAT_B5G6R5_UNORM),
+        Texels::Blocks(Compression::Bc1) => Some(DXGI_FORMAT_BC1_UNORM_SRGB),
+        Texels::Blocks(Compression::Bc3) => Some(DXGI_FORMAT_BC3_UNORM_SRGB),
+        Texels::Blocks(Compression::Bc7) => Some(DXGI_FORMAT_BC7_UNORM_SRGB),
+        Texels::Blocks(Compression::Etc2 | Compression::Astc) | Texels::Basis => None
+    }
+}
+
+// Whether DDS can hold `texels`
+pub fn supports(texels: Texels) -> bool {
+    dxgi_format(texels).is_some()
+}
+
+// Write a 2D texture whose `levels` are already encoded as `texels`, the base level first and every
+// level holding all images. A non-zero `layers` makes it a texture array and 6 `faces` a cubemap.
+pub fn write<W: Write>(w: &mut W, texels: Texels, width: u32, height: u32, layers: u32, faces: u32, levels: &[Vec<u8>]) -> io::Result<()> {
+    let format = dxgi_format(texels)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("DDS has no {} format", texels.name())))?;
+    let images = (layers.max(1) * faces) as usize;
+
+    let mut flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT;
+    let pitch_or_size = match texels {
+        Texels::Pixels(_) => {
+            flags |= DDSD_PITCH;
+            texels.size(width, 1) as u32
+        }
+        Texels::Blocks(_) | Texels::Basis => {
+            flags |= DDSD_LINEARSIZE;
+            texels.size(width, height) as u32
+        }
+    };
+    let mut caps = DDSCAPS_TEXTURE;
+    if levels.len() > 1 {
+        flags |= DDSD_MIPMAPCOUNT;
+        caps |= DDSCAPS_COMPLEX | DDSCAPS_MIPMAP;
+    }
+    let (caps2, misc) = match faces {
+        6 => {
+            caps |= DDSCAPS_COMPLEX;
+            (DDSCAPS2_CUBEMAP_ALL_FACES, RESOURCE_MISC_TEXTURECUBE)
+        }
+        _ => (0, 0)
+    };
+
+    w.write_all(MAGIC)?;
+    for value in [HEADER_SIZE, flags, height, width, pitch_or_size, 0, levels.len() as u32] {
+        w.write_all(&value.to_le_bytes())?;
+    }
+    w.write_all(&[0; 11 * 4])?;
+    // Pixel format, only pointing at the DX10 header
+    for value in [PIXEL_FORMAT_SIZE, DDPF_FOURCC, u32::from_le_bytes(*b"DX10"), 0, 0, 0, 0, 0] {
+        w.write_all(&value.to_le_bytes())?;
+    }
+    for value in [caps, caps2, 0, 0, 0] {
+        w.write_all(&value.to_le_bytes())?;
+    }
+    // DX10 header, cubemap arrays count cubes rather than faces
+    for value in [format, RESOURCE_DIMENSION_TEXTURE2D, misc, layers.max(1), 0] {
+        w.write_all(&value.to_le_bytes())?;
+    }
+
+    for image in 0..images {
+        for level in levels {
+            let size = level.len() / images;
+            w.write_all(&level[image * size..(image + 1) * size])?;
+        }
+    }
+    Ok(())
+}
+
+fn le_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(offset..offset+4)?.try_into().ok()?))
+}
+
+// Read the base level of a texture written by `write`, with its number of images
+// (layers times faces, 0 for a plain 2D texture)
+pub fn read(bytes: &[u8]) -> Option<(Texels, u32, u32, u32, Vec<u8>)> {
+    if bytes.get(..4)? != MAGIC || le_u32(bytes, 84)? != u32::from_le_bytes(*b"DX10") {
+        return None;
+    }
+
+    let height = le_u32(bytes, 12)?;
+    let width = le_u32(bytes, 16)?;
+    let level_count = le_u32(bytes, 28)?.max(1);
+    let texels = match le_u32(bytes, 128)? {
+        DXGI_FORMAT_R8G8B8A8_UNORM_SRGB => Texels::Pixels(PixelFormat::Rgba8888),
+        DXGI_FORMAT_A4B4G4R4_UNORM => Texels::Pixels(PixelFormat::Rgba4444),
+        DXGI_FORMAT_B5G6R5_UNORM => Texels::Pixels(PixelFormat::Rgb565),
+        DXGI_FORMAT_BC1_UNORM_SRGB => Texels::Blocks(Compression::Bc1),
+        DXGI_FORMAT_BC3_UNORM_SRGB => Texels::Blocks(Compression::Bc3),
+        DXGI_FORMAT_BC7_UNORM_SRGB => Texels::Blocks(Compression::Bc7),
+        _ => return None
+    };
+    let cube = le_u32(bytes, 136)? & RESOURCE_MISC_TEXTURECUBE != 0;
+    let layers = match (le_u32(bytes, 140)?, cube) {
+        (1, false) => 0,
+        (layers, false) => layers,
+        (cubes, true) => cubes * 6
+    };
+
+    // Skip the mip levels following the base level of every image
+    let base = texels.size(width, height);
+    let stride: usize = (0..level_count as usize)
+        .map(|level| texels.size((width >> level).max(1), (height >> level).max(1)))
+        .sum();
+    let mut data = Vec::with_capacity(base * layers.max(1) as usize);
+    for image in 0..layers.max(1) as usize {
+        let offset = DATA_OFFSET + image * stride;
+        data.extend_from_slice(bytes.get(offset..offset + base)?);
+    }
+
+    Some((texels, width, height, layers, data))
+}
@@ -0,0 +1,110 @@
+// Differences between two atlases, printed by the diff subcommand
+//
+// Sprites are matched by name. One in both atlases has moved when its rect, page, layer or rotation
+// changed, and changed when its pixels did. Pixels are compared as they were before packing, so
+// moving, rotating or trimming a sprite differently is no change, and the way verify hashes them,
+// so neither is alpha bleeding into its transparent texels.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::reader::AtlasFile;
+use crate::unpack::{self, Sprite};
+use crate::validate;
+use crate::AtlasRecord;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Placement {
+    pub(crate) page: u32,
+    pub(crate) layer: u32,
+    pub(crate) x: u32,
+    pub(crate) y: u32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) rotated: bool
+}
+
+impl Placement {
+    fn of(record: &AtlasRecord) -> Placement {
+        Placement {
+            page: record.page,
+            layer: record.layer,
+            x: record.x,
+            y: record.y,
+            width: record.width,
+            height: record.height,
+            rotated: record.rotated
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct Moved {
+    pub(crate) name: String,
+    pub(crate) from: Placement,
+    pub(crate) to: Placement
+}
+
+#[derive(Serialize)]
+pub(crate) struct Diff {
+    // Sizes of the old and the new atlas
+    pub(crate) old_size: (u32, u32),
+    pub(crate) new_size: (u32, u32),
+    // Sprite names in name order, a sprite can both move and change
+    pub(crate) added: Vec<String>,
+    pub(crate) removed: Vec<String>,
+    pub(crate) moved: Vec<Moved>,
+    pub(crate) changed: Vec<String>
+}
+
+impl Diff {
+    // Whether both atlases are the same size with the same sprites in the same places, texture
+    // coordinates changing along with the size
+    pub(crate) fn is_empty(&self) -> bool {
+        self.old_size == self.new_size && self.added.is_empty() && self.removed.is_empty() && self.moved.is_empty() && self.changed.is_empty()
+    }
+}
+
+pub(crate) fn diff(old: &AtlasFile, new: &AtlasFile) -> Result<Diff, String> {
+    // Sprites sharing a name could be matched either way round
+    let sprites = |file: &AtlasFile, which: &str| -> Result<BTreeMap<String, (AtlasRecord, Sprite)>, String> {
+        let mut sprites = BTreeMap::new();
+        for (record, sprite) in file.data.records.iter().cloned().zip(unpack::extract(file)?) {
+            if sprites.contains_key(&record.name) {
+                return Err(format!("the {} atlas has more than one sprite named {:?}", which, record.name));
+            }
+            sprites.insert(record.name.clone(), (record, sprite));
+        }
+        Ok(sprites)
+    };
+    let (old_sprites, new_sprites) = (sprites(old, "old")?, sprites(new, "new")?);
+
+    let mut diff = Diff {
+        old_size: (old.width, old.height),
+        new_size: (new.width, new.height),
+        added: new_sprites.keys().filter(|name| !old_sprites.contains_key(*name)).cloned().collect(),
+        removed: old_sprites.keys().filter(|name| !new_sprites.contains_key(*name)).cloned().collect(),
+        moved: Vec::new(),
+        changed: Vec::new()
+    };
+
+    let hash = |(record, sprite): &(AtlasRecord, Sprite)| {
+        (sprite.width, sprite.height, validate::hash(sprite.width, sprite.height, &sprite.data, record.polygon.as_ref()))
+    };
+    for (name, old_sprite) in old_sprites.iter() {
+        let new_sprite = match new_sprites.get(name) {
+            Some(new_sprite) => new_sprite,
+            None => continue
+        };
+
+        let (from, to) = (Placement::of(&old_sprite.0), Placement::of(&new_sprite.0));
+        if from != to {
+            diff.moved.push(Moved { name: name.clone(), from, to });
+        }
+        if hash(old_sprite) != hash(new_sprite) {
+            diff.changed.push(name.clone());
+        }
+    }
+    Ok(diff)
+}
@@ -0,0 +1,218 @@
+// Outline and drop shadow effects baked into sprites before packing
+//
+// Effects are read from a sidecar next to the sprite, `icon.png` uses `icon.effects`:
+//
+//     # 2 pixel black outline
+//     outline 2 000000
+//     # shadow offset 3,3 with a 2 pixel blur at half opacity
+//     shadow 3 3 2 00000080
+
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Outline {
+    pub width: u32,
+    pub color: [u8; 4]
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Shadow {
+    pub dx: i32,
+    pub dy: i32,
+    pub blur: u32,
+    pub color: [u8; 4]
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Effects {
+    pub outline: Option<Outline>,
+    pub shadow: Option<Shadow>
+}
+
+fn parse_color(value: &str) -> Result<[u8; 4], String> {
+    let channel = |i: usize| u8::from_str_radix(&value[i..i+2], 16);
+    let parsed = match value.len() {
+        6 if value.is_ascii() => (channel(0), channel(2), channel(4), Ok(255)),
+        8 if value.is_ascii() => (channel(0), channel(2), channel(4), channel(6)),
+        _ => return Err(format!("expected RRGGBB or RRGGBBAA, got {:?}", value))
+    };
+
+    match parsed {
+        (Ok(r), Ok(g), Ok(b), Ok(a)) => Ok([r, g, b, a]),
+        _ => Err(format!("expected RRGGBB or RRGGBBAA, got {:?}", value))
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("expected a number, got {:?}", value))
+}
+
+impl Effects {
+    pub fn sidecar(path: &Path) -> std::path::PathBuf {
+        path.with_extension("effects")
+    }
+
+    // Read the sidecar for the sprite at `path`, no sidecar means no effects
+    pub fn read(path: &Path) -> Result<Effects, String> {
+        let text = match std::fs::read_to_string(Effects::sidecar(path)) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Effects::default()),
+            Err(err) => return Err(err.to_string())
+        };
+
+        let mut effects = Effects::default();
+        for (number, line) in text.lines().enumerate() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let context = |err: String| format!("{:?} line {}: {}", Effects::sidecar(path), number + 1, err);
+
+            match fields.as_slice() {
+                [] => {}
+                [comment, ..] if comment.starts_with('#') => {}
+                ["outline", width, color] => {
+                    effects.outline = Some(Outline {
+                        width: parse_number(width).map_err(context)?,
+                        color: parse_color(color).map_err(context)?
+                    });
+                }
+                ["shadow", dx, dy, blur, color] => {
+                    effects.shadow = Some(Shadow {
+                        dx: parse_number(dx).map_err(context)?,
+                        dy: parse_number(dy).map_err(context)?,
+                        blur: parse_number(blur).map_err(context)?,
+                        color: parse_color(color).map_err(context)?
+                    });
+                }
+                _ => return Err(context(format!("unrecognized effect {:?}", line.trim())))
+            }
+        }
+
+        Ok(effects)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outline.is_none() && self.shadow.is_none()
+    }
+}
+
+// Straight alpha "source over destination"
+pub fn over(src: [u8; 4], dst: [u8; 4]) -> [u8; 4] {
+    let sa = src[3] as f32 / 255.0;
+    let da = dst[3] as f32 / 255.0;
+    let out_a = sa + da * (1.0 - sa);
+    if out_a == 0.0 {
+        return [0; 4];
+    }
+
+    let mut out = [0; 4];
+    for c in 0..3 {
+        out[c] = ((src[c] as f32 * sa + dst[c] as f32 * da * (1.0 - sa)) / out_a).round() as u8;
+    }
+    out[3] = (out_a * 255.0).round() as u8;
+    out
+}
+
+// Horizontal then vertical box blur of an alpha mask
+fn blur(alpha: &[f32], width: usize, height: usize, radius: usize) -> Vec<f32> {
+    let window = (2 * radius + 1) as f32;
+    let pass = |input: &[f32], along_x: bool| {
+        let mut out = vec![0.0; input.len()];
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = 0.0;
+                for offset in 0..=2 * radius {
+                    let (sx, sy) = if along_x {
+                        ((x + offset).checked_sub(radius), Some(y))
+                    } else {
+                        (Some(x), (y + offset).checked_sub(radius))
+                    };
+                    if let (Some(sx), Some(sy)) = (sx, sy) {
+                        if sx < width && sy < height {
+                            sum += input[sy * width + sx];
+                        }
+                    }
+                }
+                out[y * width + x] = sum / window;
+            }
+        }
+        out
+    };
+
+    pass(&pass(alpha, true), false)
+}
+
+// Apply the effects, growing the sprite so nothing is clipped
+pub fn apply(rgba: &[u8], width: u32, height: u32, effects: &Effects) -> (u32, u32, Vec<u8>) {
+    let outline = effects.outline.map_or(0, |outline| outline.width as i32);
+
+    // Margin on the left, top, right and bottom
+    let mut margin = [outline; 4];
+    if let Some(shadow) = effects.shadow {
+        let blur = shadow.blur as i32;
+        margin[0] = margin[0].max(outline + blur - shadow.dx);
+        margin[1] = margin[1].max(outline + blur - shadow.dy);
+        margin[2] = margin[2].max(outline + blur + shadow.dx);
+        margin[3] = margin[3].max(outline + blur + shadow.dy);
+    }
+
+    let out_w = (width as i32 + margin[0] + margin[2]) as usize;
+    let out_h = (height as i32 + margin[1] + margin[3]) as usize;
+
+    // The sprite placed on the grown canvas
+    let mut layer = vec![[0u8; 4]; out_w * out_h];
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            let i = (y * width as usize + x) * 4;
+            let o = (y + margin[1] as usize) * out_w + x + margin[0] as usize;
+            layer[o].copy_from_slice(&rgba[i..i+4]);
+        }
+    }
+
+    if let Some(Outline { width: radius, color }) = effects.outline {
+        let radius = radius as i64;
+        let mut outlined = vec![[0u8; 4]; out_w * out_h];
+        for y in 0..out_h as i64 {
+            for x in 0..out_w as i64 {
+                // Largest sprite alpha within a disk of the outline width
+                let mut coverage = 0;
+                for dy in -radius..=radius {
+                    for dx in -radius..=radius {
+                        let (sx, sy) = (x + dx, y + dy);
+                        if dx * dx + dy * dy <= radius * radius && sx >= 0 && sy >= 0 && (sx as usize) < out_w && (sy as usize) < out_h {
+                            coverage = coverage.max(layer[sy as usize * out_w + sx as usize][3]);
+                        }
+                    }
+                }
+
+                let i = y as usize * out_w + x as usize;
+                let alpha = (coverage as u32 * color[3] as u32 / 255) as u8;
+                outlined[i] = over(layer[i], [color[0], color[1], color[2], alpha]);
+            }
+        }
+        layer = outlined;
+    }
+
+    if let Some(shadow) = effects.shadow {
+        let alpha: Vec<f32> = layer.iter().map(|px| px[3] as f32 / 255.0).collect();
+        let alpha = blur(&alpha, out_w, out_h, shadow.blur as usize);
+
+        let mut shadowed = vec![[0u8; 4]; out_w * out_h];
+        for y in 0..out_h as i64 {
+            for x in 0..out_w as i64 {
+                let (sx, sy) = (x - shadow.dx as i64, y - shadow.dy as i64);
+                let coverage = if sx >= 0 && sy >= 0 && (sx as usize) < out_w && (sy as usize) < out_h {
+                    alpha[sy as usize * out_w + sx as usize]
+                } else {
+                    0.0
+                };
+
+                let i = y as usize * out_w + x as usize;
+                let color = shadow.color;
+                let shade = [color[0], color[1], color[2], (coverage * color[3] as f32).round() as u8];
+                shadowed[i] = over(layer[i], shade);
+            }
+        }
+        layer = shadowed;
+    }
+
+    (out_w as u32, out_h as u32, layer.concat())
+}
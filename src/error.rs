@@ -0,0 +1,100 @@
+// Failures of building an atlas that callers can act on, in place of panicking
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use zip::result::ZipError;
+
+use crate::PackError;
+
+#[derive(Debug)]
+pub enum AtlasError {
+    // Reading or writing the file failed
+    Io(PathBuf, io::Error),
+    // The file is not an image or font that can be packed
+    Decode(PathBuf, String),
+    // A sprite did not fit within the atlas limits
+    Pack(PackError),
+    // The archive could not be assembled
+    Write(String)
+}
+
+impl AtlasError {
+    // The file the error happened on
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            AtlasError::Io(path, _) | AtlasError::Decode(path, _) => Some(path),
+            _ => None
+        }
+    }
+
+    // The failure without the file it happened on
+    pub fn reason(&self) -> String {
+        match self {
+            AtlasError::Io(_, err) => err.to_string(),
+            AtlasError::Decode(_, reason) => reason.clone(),
+            err => err.to_string()
+        }
+    }
+}
+
+impl fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AtlasError::Io(path, err) => write!(f, "{:?}: {}", path, err),
+            AtlasError::Decode(path, reason) => write!(f, "{:?}: {}", path, reason),
+            AtlasError::Pack(err) => write!(f, "{}", err),
+            AtlasError::Write(reason) => write!(f, "unable to write the atlas: {}", reason)
+        }
+    }
+}
+
+impl Error for AtlasError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AtlasError::Io(_, err) => Some(err),
+            AtlasError::Pack(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<PackError> for AtlasError {
+    fn from(err: PackError) -> AtlasError {
+        AtlasError::Pack(err)
+    }
+}
+
+// Everything below happens while assembling the archive in memory
+
+impl From<ZipError> for AtlasError {
+    fn from(err: ZipError) -> AtlasError {
+        AtlasError::Write(err.to_string())
+    }
+}
+
+impl From<io::Error> for AtlasError {
+    fn from(err: io::Error) -> AtlasError {
+        AtlasError::Write(err.to_string())
+    }
+}
+
+impl From<png::EncodingError> for AtlasError {
+    fn from(err: png::EncodingError) -> AtlasError {
+        AtlasError::Write(err.to_string())
+    }
+}
+
+impl From<bincode::Error> for AtlasError {
+    fn from(err: bincode::Error) -> AtlasError {
+        AtlasError::Write(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for AtlasError {
+    fn from(err: serde_json::Error) -> AtlasError {
+        AtlasError::Write(err.to_string())
+    }
+}
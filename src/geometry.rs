@@ -0,0 +1,40 @@
+// Axis-aligned rects in pixels and how they relate to each other
+//
+// Rects are half-open, covering the columns from `x` up to but not including `x + width` and the
+// rows likewise, so rects that share an edge neither overlap nor leave a gap between them. Two
+// rects overlap when their columns and their rows both do, whichever corners end up inside the
+// other.
+
+use serde::{Serialize, Deserialize};
+
+// x, y, width, height
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32
+}
+
+impl Rect {
+    pub(crate) fn intersects(&self, other: &Rect) -> bool {
+        overlap(self.x, self.width, other.x, other.width) && overlap(self.y, self.height, other.y, other.height)
+    }
+
+    // Whether `other` lies entirely within this rect
+    pub(crate) fn contains(&self, other: &Rect) -> bool {
+        within(other.x, other.width, self.x, self.width) && within(other.y, other.height, self.y, self.height)
+    }
+}
+
+// Whether the interval of `length` from `start` shares any pixel with the one of `other_length`
+// from `other_start`
+fn overlap(start: u32, length: u32, other_start: u32, other_length: u32) -> bool {
+    start < other_start + other_length && other_start < start + length
+}
+
+// Whether the interval of `length` from `start` lies within the one of `outer_length` from
+// `outer_start`
+fn within(start: u32, length: u32, outer_start: u32, outer_length: u32) -> bool {
+    start >= outer_start && start + length <= outer_start + outer_length
+}
@@ -0,0 +1,43 @@
+// Godot 4 AtlasTexture resources, one `<sprite>.tres` per sprite next to the atlas images
+//
+// Godot resolves relative `ext_resource` paths from the resource's own directory, so the archive can
+// be extracted anywhere in a project. Trimmed sprites get a margin restoring their source size.
+
+use std::collections::HashSet;
+
+use crate::{strip_extension, AtlasData};
+
+// Name and contents of the resource of every record, the atlas images having `extension`
+pub fn resources(data: &AtlasData, extension: &str) -> Result<Vec<(String, String)>, String> {
+    let mut names = HashSet::new();
+    data.records.iter()
+        .map(|record| {
+            // "ui/button.png" is "ui/button.tres"
+            let name = format!("{}.tres", strip_extension(&record.name));
+            if !names.insert(name.clone()) {
+                return Err(format!("{} is the resource of two sprites", name));
+            }
+
+            let image = match data.pages.get(record.page as usize) {
+                Some(page) => format!("{}.{}", page.stem, extension),
+                None => format!("atlas.{}", extension)
+            };
+            let path = format!("{}{}", "../".repeat(name.matches('/').count()), image)
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"");
+
+            let mut text = format!(
+                "[gd_resource type=\"AtlasTexture\" load_steps=2 format=3]\n\n\
+                 [ext_resource type=\"Texture2D\" path=\"{}\" id=\"1\"]\n\n\
+                 [resource]\n\
+                 atlas = ExtResource(\"1\")\n\
+                 region = Rect2({}, {}, {}, {})\n",
+                path, record.x, record.y, record.width, record.height);
+            if (record.width, record.height) != (record.source_width, record.source_height) {
+                text.push_str(&format!("margin = Rect2({}, {}, {}, {})\n", record.offset_x, record.offset_y,
+                                       record.source_width - record.width, record.source_height - record.height));
+            }
+            Ok((name, text))
+        })
+        .collect()
+}
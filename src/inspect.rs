@@ -0,0 +1,92 @@
+// Contents of an existing atlas, printed by the inspect subcommand
+
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::AtlasData;
+
+#[derive(Serialize)]
+pub struct SpriteInfo {
+    pub name: String,
+    // Image the sprite is stored in, its page or layer
+    pub image: String,
+    pub page: u32,
+    pub layer: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub rotated: bool,
+    // Left, top, right and bottom of the rect in texture coordinates, UDIM tiles offset by their tile
+    pub uv: [f64; 4]
+}
+
+#[derive(Serialize)]
+pub struct Inspection {
+    pub width: u32,
+    pub height: u32,
+    // Pages or texture array layers of `width` x `height`, 1 for a single image
+    pub images: u32,
+    pub sprites: Vec<SpriteInfo>,
+    pub sprite_pixels: u64,
+    pub total_pixels: u64,
+    // Share of the images covered by sprites, 0 to 1
+    pub fill: f64,
+    // Percentage of the images covered by no sprite
+    pub wasted_percent: f64
+}
+
+pub fn inspect(data: &AtlasData) -> Inspection {
+    let images = data.layers.max(data.pages.len() as u32).max(1);
+    let (width, height) = (data.width.max(1) as f64, data.height.max(1) as f64);
+
+    let sprites: Vec<SpriteInfo> = data.records.iter()
+        .map(|record| {
+            let page = data.pages.get(record.page as usize);
+            let (image, u, v) = match page {
+                Some(page) if page.tile > 0 => (page.stem.clone(), page.u_offset as f64, page.v_offset as f64),
+                Some(page) => (page.stem.clone(), 0.0, 0.0),
+                None if data.layers > 0 => (format!("atlas layer {}", record.layer), 0.0, 0.0),
+                None => ("atlas".to_string(), 0.0, 0.0)
+            };
+
+            SpriteInfo {
+                name: record.name.clone(),
+                image,
+                page: record.page,
+                layer: record.layer,
+                x: record.x,
+                y: record.y,
+                width: record.width,
+                height: record.height,
+                rotated: record.rotated,
+                uv: [
+                    u + record.x as f64 / width,
+                    v + record.y as f64 / height,
+                    u + (record.x + record.width) as f64 / width,
+                    v + (record.y + record.height) as f64 / height
+                ]
+            }
+        })
+        .collect();
+
+    // Deduplicated sprites share a rect, which only covers its pixels once
+    let rects: HashSet<(u32, u32, u32, u32, u32, u32)> = data.records.iter()
+        .map(|record| (record.page, record.layer, record.x, record.y, record.width, record.height))
+        .collect();
+    let sprite_pixels: u64 = rects.iter().map(|rect| rect.4 as u64 * rect.5 as u64).sum();
+    let total_pixels = data.width as u64 * data.height as u64 * images as u64;
+    let fill = sprite_pixels as f64 / total_pixels.max(1) as f64;
+
+    Inspection {
+        width: data.width,
+        height: data.height,
+        images,
+        sprites,
+        sprite_pixels,
+        total_pixels,
+        fill,
+        wasted_percent: 100.0 * (1.0 - fill)
+    }
+}
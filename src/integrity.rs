@@ -0,0 +1,128 @@
+// Hashes stored in the atlas so corrupted or tampered files can be detected at load time
+//
+// `atlas.hash` is the last entry of the archive. It holds the SHA-256 of every other entry's
+// contents in archive order, and an archive hash over those names and hashes, so a missing,
+// added, reordered or modified entry is caught.
+
+use std::io::{self, Cursor, Read, Write};
+
+use bincode::{serialize, deserialize};
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+use zip::{ZipArchive, ZipWriter};
+use zip::result::ZipResult;
+
+pub const ENTRY: &str = "atlas.hash";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Integrity {
+    pub archive: [u8; 32],
+    pub entries: Vec<(String, [u8; 32])>
+}
+
+fn archive_hash(entries: &[(String, [u8; 32])]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for (name, hash) in entries {
+        hasher.update(name.as_bytes());
+        hasher.update([0]);
+        hasher.update(hash);
+    }
+    hasher.finalize().into()
+}
+
+// Hash every entry of the archive except `atlas.hash` itself
+fn hash_entries<R: Read + io::Seek>(zip: &mut ZipArchive<R>) -> ZipResult<Vec<(String, [u8; 32])>> {
+    let mut entries = Vec::new();
+    for i in 0..zip.len() {
+        let mut entry = zip.by_index(i)?;
+        if entry.name() == ENTRY {
+            continue;
+        }
+
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents)?;
+        entries.push((entry.name().to_string(), Sha256::digest(&contents).into()));
+    }
+    Ok(entries)
+}
+
+// Append `atlas.hash` to a finished archive
+pub fn seal(archive: Vec<u8>) -> ZipResult<Vec<u8>> {
+    let entries = hash_entries(&mut ZipArchive::new(Cursor::new(archive.as_slice()))?)?;
+    let integrity = Integrity {
+        archive: archive_hash(&entries),
+        entries
+    };
+
+    let mut zip = ZipWriter::new_append(Cursor::new(archive))?;
+    zip.start_file(ENTRY, crate::entry_options())?;
+    zip.write_all(&serialize(&integrity).unwrap())?;
+    Ok(zip.finish()?.into_inner())
+}
+
+// Compare the archive against its `atlas.hash`, returning every mismatch
+pub fn check<R: Read + io::Seek>(zip: &mut ZipArchive<R>, stored: &[u8]) -> Result<Vec<String>, String> {
+    let integrity: Integrity = deserialize(stored).map_err(|err| format!("invalid {}: {}", ENTRY, err))?;
+    let entries = hash_entries(zip).map_err(|err| err.to_string())?;
+    let mut problems = Vec::new();
+
+    for (name, hash) in integrity.entries.iter() {
+        match entries.iter().find(|(entry, _)| entry == name) {
+            Some((_, actual)) if actual != hash => problems.push(format!("{} does not match its hash", name)),
+            Some(_) => {}
+            None => problems.push(format!("{} is missing", name))
+        }
+    }
+    for (name, _) in entries.iter() {
+        if !integrity.entries.iter().any(|(entry, _)| entry == name) {
+            problems.push(format!("{} is not covered by {}", name, ENTRY));
+        }
+    }
+    if problems.is_empty() && archive_hash(&entries) != integrity.archive {
+        problems.push("archive does not match its hash".to_string());
+    }
+
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Write};
+
+    use zip::write::FileOptions;
+    use zip::{ZipArchive, ZipWriter};
+
+    use super::{check, seal, ENTRY};
+
+    fn archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in entries {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(contents).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn every_changed_missing_or_extra_entry_is_reported() {
+        let sealed = seal(archive(&[("atlas.png", b"pixels"), ("atlas.data", b"records")])).unwrap();
+        let mut zip = ZipArchive::new(Cursor::new(sealed)).unwrap();
+        assert_eq!(zip.by_index(zip.len() - 1).unwrap().name(), ENTRY);
+        let mut stored = Vec::new();
+        zip.by_name(ENTRY).unwrap().read_to_end(&mut stored).unwrap();
+        assert_eq!(check(&mut zip, &stored).unwrap(), Vec::<String>::new());
+
+        let mut tampered = ZipArchive::new(Cursor::new(archive(&[("atlas.png", b"PIXELS"), ("extra.txt", b"")]))).unwrap();
+        assert_eq!(check(&mut tampered, &stored).unwrap(), [
+            "atlas.png does not match its hash",
+            "atlas.data is missing",
+            "extra.txt is not covered by atlas.hash"
+        ]);
+
+        // Same entries in another order only change the archive hash
+        let mut reordered = ZipArchive::new(Cursor::new(archive(&[("atlas.data", b"records"), ("atlas.png", b"pixels")]))).unwrap();
+        assert_eq!(check(&mut reordered, &stored).unwrap(), ["archive does not match its hash"]);
+
+        assert!(check(&mut reordered, b"junk").unwrap_err().starts_with("invalid atlas.hash"));
+    }
+}
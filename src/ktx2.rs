@@ -0,0 +1,238 @@
+// Minimal KTX2 container reader and writer
+//
+// Only what atlast emits is supported: uncompressed packed formats, the
+// block compressed formats of `compress` and no supercompression.
+
+use std::io::{self, Write};
+
+use crate::compress::{Compression, Texels};
+use crate::quantize::PixelFormat;
+
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A
+];
+
+// Vulkan format enum values
+const VK_FORMAT_R4G4B4A4_UNORM_PACK16: u32 = 2;
+const VK_FORMAT_R5G6B5_UNORM_PACK16: u32 = 4;
+const VK_FORMAT_R8G8B8A8_SRGB: u32 = 43;
+const VK_FORMAT_BC1_RGB_SRGB_BLOCK: u32 = 132;
+const VK_FORMAT_BC3_SRGB_BLOCK: u32 = 138;
+const VK_FORMAT_BC7_SRGB_BLOCK: u32 = 146;
+const VK_FORMAT_ETC2_R8G8B8_SRGB_BLOCK: u32 = 148;
+const VK_FORMAT_ASTC_4X4_SRGB_BLOCK: u32 = 158;
+
+// Data format descriptor constants
+const KHR_DF_MODEL_RGBSDA: u8 = 1;
+const KHR_DF_MODEL_BC1A: u8 = 128;
+const KHR_DF_MODEL_BC3: u8 = 130;
+const KHR_DF_MODEL_BC7: u8 = 134;
+const KHR_DF_MODEL_ETC2: u8 = 161;
+const KHR_DF_MODEL_ASTC: u8 = 162;
+const KHR_DF_PRIMARIES_BT709: u8 = 1;
+const KHR_DF_TRANSFER_LINEAR: u8 = 1;
+const KHR_DF_TRANSFER_SRGB: u8 = 2;
+const KHR_DF_SAMPLE_DATATYPE_LINEAR: u8 = 0x10;
+
+const CHANNEL_R: u8 = 0;
+const CHANNEL_G: u8 = 1;
+const CHANNEL_B: u8 = 2;
+const CHANNEL_A: u8 = 15;
+// The color channel of BC1, BC7 and ASTC blocks is 0, that of ETC2 blocks 2
+const CHANNEL_ETC2_COLOR: u8 = 2;
+
+struct Layout {
+    vk_format: u32,
+    type_size: u32,
+    model: u8,
+    // Side of the square texel blocks
+    block: u8,
+    bytes_per_block: u8,
+    transfer: u8,
+    // (channel, bit offset, bit length) from least significant bit up
+    samples: &'static [(u8, u16, u8)]
+}
+
+fn layout(texels: Texels) -> Option<Layout> {
+    let pixels = |vk_format, bytes_per_block, transfer, samples| Layout {
+        vk_format,
+        type_size: bytes_per_block as u32,
+        model: KHR_DF_MODEL_RGBSDA,
+        block: 1,
+        bytes_per_block,
+        transfer,
+        samples
+    };
+    let blocks = |vk_format, model, bytes_per_block, samples| Layout {
+        vk_format,
+        type_size: 1,
+        model,
+        block: 4,
+        bytes_per_block,
+        transfer: KHR_DF_TRANSFER_SRGB,
+        samples
+    };
+
+    Some(match texels {
+        Texels::Pixels(PixelFormat::Rgba8888) => Layout {
+            type_size: 1,
+            ..pixels(VK_FORMAT_R8G8B8A8_SRGB, 4, KHR_DF_TRANSFER_SRGB,
+                     &[(CHANNEL_R, 0, 8), (CHANNEL_G, 8, 8), (CHANNEL_B, 16, 8), (CHANNEL_A, 24, 8)])
+        },
+        Texels::Pixels(PixelFormat::Rgba4444) => pixels(VK_FORMAT_R4G4B4A4_UNORM_PACK16, 2, KHR_DF_TRANSFER_LINEAR,
+                                                        &[(CHANNEL_A, 0, 4), (CHANNEL_B, 4, 4), (CHANNEL_G, 8, 4), (CHANNEL_R, 12, 4)]),
+        Texels::Pixels(PixelFormat::Rgb565) => pixels(VK_FORMAT_R5G6B5_UNORM_PACK16, 2, KHR_DF_TRANSFER_LINEAR,
+                                                      &[(CHANNEL_B, 0, 5), (CHANNEL_G, 5, 6), (CHANNEL_R, 11, 5)]),
+        Texels::Blocks(Compression::Bc1) => blocks(VK_FORMAT_BC1_RGB_SRGB_BLOCK, KHR_DF_MODEL_BC1A, 8, &[(0, 0, 64)]),
+        Texels::Blocks(Compression::Bc3) => blocks(VK_FORMAT_BC3_SRGB_BLOCK, KHR_DF_MODEL_BC3, 16, &[(CHANNEL_A, 0, 64), (0, 64, 64)]),
+        Texels::Blocks(Compression::Bc7) => blocks(VK_FORMAT_BC7_SRGB_BLOCK, KHR_DF_MODEL_BC7, 16, &[(0, 0, 128)]),
+        Texels::Blocks(Compression::Etc2) => blocks(VK_FORMAT_ETC2_R8G8B8_SRGB_BLOCK, KHR_DF_MODEL_ETC2, 8, &[(CHANNEL_ETC2_COLOR, 0, 64)]),
+        Texels::Blocks(Compression::Astc) => blocks(VK_FORMAT_ASTC_4X4_SRGB_BLOCK, KHR_DF_MODEL_ASTC, 16, &[(0, 0, 128)]),
+        Texels::Basis => return None
+    })
+}
+
+fn data_format_descriptor(layout: &Layout) -> Vec<u8> {
+    let block_size = 24 + 16 * layout.samples.len() as u16;
+    let mut dfd = Vec::new();
+
+    dfd.extend_from_slice(&(4 + block_size as u32).to_le_bytes());
+    // Khronos vendor, basic descriptor type
+    dfd.extend_from_slice(&0u32.to_le_bytes());
+    dfd.extend_from_slice(&2u16.to_le_bytes());
+    dfd.extend_from_slice(&block_size.to_le_bytes());
+    dfd.extend_from_slice(&[layout.model, KHR_DF_PRIMARIES_BT709, layout.transfer, 0]);
+    dfd.extend_from_slice(&[layout.block - 1, layout.block - 1, 0, 0]);
+    dfd.extend_from_slice(&[layout.bytes_per_block, 0, 0, 0, 0, 0, 0, 0]);
+
+    for &(channel, offset, length) in layout.samples {
+        // Alpha is never sRGB encoded
+        let qualifiers = if channel == CHANNEL_A && layout.transfer == KHR_DF_TRANSFER_SRGB {
+            KHR_DF_SAMPLE_DATATYPE_LINEAR
+        } else {
+            0
+        };
+
+        dfd.extend_from_slice(&offset.to_le_bytes());
+        dfd.extend_from_slice(&[length - 1, channel | qualifiers]);
+        dfd.extend_from_slice(&[0, 0, 0, 0]);
+        dfd.extend_from_slice(&0u32.to_le_bytes());
+        dfd.extend_from_slice(&(u32::MAX >> (32 - length.min(32))).to_le_bytes());
+    }
+
+    dfd
+}
+
+// Write a 2D texture whose `levels` are already encoded as `texels`, the base level first.
+// A non-zero `layers` makes it a texture array and 6 `faces` a cubemap, the images of a level
+// follow each other layer by layer, face by face.
+pub fn write<W: Write>(w: &mut W, texels: Texels, width: u32, height: u32, layers: u32, faces: u32, levels: &[Vec<u8>]) -> io::Result<()> {
+    let layout = layout(texels)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("KTX2 has no {} format", texels.name())))?;
+    let dfd = data_format_descriptor(&layout);
+
+    let level_count = levels.len() as u32;
+    let header_size = 80;
+    let level_index_size = 24 * level_count;
+    let dfd_offset = header_size + level_index_size;
+
+    // Level data must be aligned to lcm(texel block size, 4), and is stored smallest level first
+    let align = |offset: u64| (offset + 3) & !3;
+    let mut offsets = vec![0; levels.len()];
+    let mut end = dfd_offset as u64 + dfd.len() as u64;
+    for (level, data) in levels.iter().enumerate().rev() {
+        offsets[level] = align(end);
+        end = offsets[level] + data.len() as u64;
+    }
+
+    w.write_all(&IDENTIFIER)?;
+    for value in [layout.vk_format, layout.type_size, width, height, 0, layers, faces, level_count, 0] {
+        w.write_all(&value.to_le_bytes())?;
+    }
+
+    // Index: dfd, key/value data, supercompression global data
+    w.write_all(&dfd_offset.to_le_bytes())?;
+    w.write_all(&(dfd.len() as u32).to_le_bytes())?;
+    w.write_all(&0u32.to_le_bytes())?;
+    w.write_all(&0u32.to_le_bytes())?;
+    w.write_all(&0u64.to_le_bytes())?;
+    w.write_all(&0u64.to_le_bytes())?;
+
+    // Level index
+    for (offset, data) in offsets.iter().zip(levels) {
+        w.write_all(&offset.to_le_bytes())?;
+        w.write_all(&(data.len() as u64).to_le_bytes())?;
+        w.write_all(&(data.len() as u64).to_le_bytes())?;
+    }
+
+    w.write_all(&dfd)?;
+    let mut written = dfd_offset as u64 + dfd.len() as u64;
+    for (offset, data) in offsets.iter().zip(levels).rev() {
+        w.write_all(&vec![0; (offset - written) as usize])?;
+        w.write_all(data)?;
+        written = offset + data.len() as u64;
+    }
+    Ok(())
+}
+
+fn le_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(offset..offset+4)?.try_into().ok()?))
+}
+
+fn le_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(bytes.get(offset..offset+8)?.try_into().ok()?))
+}
+
+// Read the base level of a texture written by `write`, with its number of images
+// (layers times faces, 0 for a plain 2D texture)
+pub fn read(bytes: &[u8]) -> Option<(Texels, u32, u32, u32, Vec<u8>)> {
+    if bytes.get(..12)? != IDENTIFIER {
+        return None;
+    }
+
+    let texels = match le_u32(bytes, 12)? {
+        VK_FORMAT_R8G8B8A8_SRGB => Texels::Pixels(PixelFormat::Rgba8888),
+        VK_FORMAT_R4G4B4A4_UNORM_PACK16 => Texels::Pixels(PixelFormat::Rgba4444),
+        VK_FORMAT_R5G6B5_UNORM_PACK16 => Texels::Pixels(PixelFormat::Rgb565),
+        VK_FORMAT_BC1_RGB_SRGB_BLOCK => Texels::Blocks(Compression::Bc1),
+        VK_FORMAT_BC3_SRGB_BLOCK => Texels::Blocks(Compression::Bc3),
+        VK_FORMAT_BC7_SRGB_BLOCK => Texels::Blocks(Compression::Bc7),
+        VK_FORMAT_ETC2_R8G8B8_SRGB_BLOCK => Texels::Blocks(Compression::Etc2),
+        VK_FORMAT_ASTC_4X4_SRGB_BLOCK => Texels::Blocks(Compression::Astc),
+        _ => return None
+    };
+    let width = le_u32(bytes, 20)?;
+    let height = le_u32(bytes, 24)?;
+    let layers = match (le_u32(bytes, 32)?, le_u32(bytes, 36)?) {
+        (0, 1) => 0,
+        (layers, faces) => layers.max(1) * faces
+    };
+
+    let offset = le_u64(bytes, 80)? as usize;
+    let length = le_u64(bytes, 88)? as usize;
+
+    Some((texels, width, height, layers, bytes.get(offset..offset+length)?.to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write, Texels, VK_FORMAT_R5G6B5_UNORM_PACK16};
+    use crate::quantize::PixelFormat;
+
+    #[test]
+    fn the_level_follows_the_header_aligned_to_four_bytes() {
+        let data = [0x20, 0xfc, 0x1f, 0x00, 0xe0, 0x07, 0xff, 0xff];
+        let mut ktx = Vec::new();
+        write(&mut ktx, Texels::Pixels(PixelFormat::Rgb565), 2, 2, 0, 1, &[data.to_vec()]).unwrap();
+
+        let word = |offset: usize| u32::from_le_bytes(ktx[offset..offset + 4].try_into().unwrap());
+        let long = |offset: usize| u64::from_le_bytes(ktx[offset..offset + 8].try_into().unwrap()) as usize;
+        assert_eq!(&ktx[..12], b"\xabKTX 20\xbb\r\n\x1a\n");
+        // vkFormat, typeSize, pixel size and a single level
+        assert_eq!((word(12), word(16), word(20), word(24), word(40)), (VK_FORMAT_R5G6B5_UNORM_PACK16, 2, 2, 2, 1));
+
+        let (offset, length) = (long(80), long(88));
+        assert_eq!((offset % 4, length), (0, data.len()));
+        assert_eq!(&ktx[offset..], &data);
+    }
+}
@@ -0,0 +1,5264 @@
+// Packing sprites into texture atlases, for the atlast command line tool and asset pipelines
+//
+// Build scripts pack a directory with `pack`, or set up an `Atlas` through `AtlasBuilder` and add
+// images to it one by one.
+
+// Without the command line tool some of the settings it offers are never read
+#![cfg_attr(not(feature = "cli"), allow(dead_code))]
+
+mod animation;
+mod aseprite;
+mod astc;
+#[cfg(feature = "cli")]
+mod audit;
+mod basis;
+mod bleed;
+mod cache;
+#[cfg(feature = "cli")]
+pub mod cli;
+mod codegen;
+mod color;
+mod compress;
+#[cfg(feature = "cli")]
+mod config;
+#[cfg(feature = "cli")]
+mod conflict;
+mod credits;
+mod crypt;
+mod cubemap;
+mod dds;
+mod diff;
+mod effects;
+mod error;
+mod geometry;
+mod godot;
+mod inspect;
+mod integrity;
+mod ktx2;
+mod libgdx;
+mod loader;
+mod maxrects;
+mod mipmap;
+mod msdf;
+mod nineslice;
+mod normal;
+mod palette;
+mod pivot;
+mod polygon;
+#[cfg(feature = "cli")]
+mod paths;
+#[cfg(feature = "cli")]
+mod preset;
+mod preview;
+#[cfg(feature = "cli")]
+mod progress;
+mod quantize;
+mod reader;
+mod sdf;
+mod skyline;
+mod tiles;
+mod unpack;
+mod userdata;
+mod validate;
+mod seam;
+mod stats;
+mod texturepacker;
+
+use walkdir::WalkDir;
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashMap};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::fmt;
+use std::error::Error;
+use std::time::{Duration, Instant};
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::io::Cursor;
+use std::io;
+use std::borrow::Cow;
+
+use serde::{Serialize, Deserialize};
+use bincode::serialize;
+use rayon::prelude::*;
+use log::{info, warn};
+
+use unicode_normalization::UnicodeNormalization;
+use globset::GlobMatcher;
+use image::imageops::FilterType;
+
+use zip::ZipWriter;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, DateTime};
+
+pub use error::AtlasError;
+pub use loader::{load, load_verified, LoadedAtlas};
+pub use reader::ReadError;
+pub use quantize::PixelFormat;
+pub use compress::Compression;
+pub use animation::AnimationRecord;
+pub use nineslice::NineSlice;
+pub use polygon::Polygon;
+pub use geometry::Rect;
+use quantize::Dither;
+use compress::Texels;
+use color::{ChunkPolicy, ColorChunks, convert_to_srgb};
+use effects::Effects;
+use cubemap::CubeLayout;
+use palette::Palette;
+use tiles::TileTable;
+use maxrects::MaxRects;
+pub use skyline::Heuristic;
+use skyline::Skyline;
+use credits::{Attribution, Credit};
+use stats::{PageStats, Stats, Timings};
+use cache::Cache;
+use seam::SeamReport;
+
+
+// Free space of a packing algorithm that sprites are placed into
+trait Bin {
+    // With `rotate` the sprite may also be placed turned, as a `height` x `width` rect
+    fn insert(&mut self, width: u32, height: u32, rotate: bool) -> Option<Rect>;
+    // Mark `used` as taken by a sprite placed beforehand
+    fn occupy(&mut self, used: &Rect);
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AtlasRecord {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub name: String,
+    // Texture array layer holding the sprite, always 0 for a regular atlas.
+    // For KTX2 cubemaps this is the layer-face index, cube * 6 + face.
+    pub layer: u32,
+    // Index into the atlas pages, always 0 for a single page atlas
+    pub page: u32,
+    // The sprite is stored turned 90° clockwise, so the rect is as wide as the source is tall
+    pub rotated: bool,
+    // Size of the source before transparent borders were trimmed and where the stored pixels sit in it
+    pub source_width: u32,
+    pub source_height: u32,
+    pub offset_x: u32,
+    pub offset_y: u32,
+    // Insets of the corners that keep their size when the sprite is stretched as a nine-slice
+    pub nine_slice: Option<NineSlice>,
+    // Point the sprite is placed and rotated by, as a fraction of the source from its top left corner
+    pub pivot_x: f32,
+    pub pivot_y: f32,
+    // JSON text attached to the sprite by a sidecar or --user-data, passed through untouched
+    pub user_data: Option<String>,
+    // Outline of the opaque pixels traced with --polygons, in the source like the nine-slice borders
+    pub polygon: Option<Polygon>
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct AtlasData {
+    records: Vec<AtlasRecord>,
+    width: u32,
+    height: u32,
+    fonts: Vec<FontRecord>,
+    // Number of texture array layers (times 6 faces for cubemaps) of `width` x `height`, 0 for a regular atlas
+    layers: u32,
+    cubes: Vec<CubeRecord>,
+    // Pages of `width` x `height`, empty for a single page atlas
+    pages: Vec<PageRecord>,
+    // Indirection table of a virtual texture atlas stored as tiles
+    tiles: Option<TileTable>,
+    // Authors and licenses of the packed sprites
+    credits: Vec<Credit>,
+    // The color of every pixel is already multiplied by its alpha
+    premultiplied_alpha: bool,
+    // Numbered sprites grouped with --animations
+    animations: Vec<AnimationRecord>
+}
+
+// A page stored as <stem>.<ext>. UDIM tiles are atlas.<tile> and cover UVs from their offset to
+// offset + 1, pages spilled over the maximum height are atlas_<index> with tile and offsets 0.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct PageRecord {
+    stem: String,
+    tile: u32,
+    u_offset: u32,
+    v_offset: u32
+}
+
+impl PageRecord {
+    fn udim(index: u32) -> PageRecord {
+        PageRecord {
+            stem: format!("atlas.{}", 1001 + index),
+            tile: 1001 + index,
+            u_offset: index % 10,
+            v_offset: index / 10
+        }
+    }
+
+    fn numbered(index: u32) -> PageRecord {
+        PageRecord {
+            stem: format!("atlas_{}", index),
+            tile: 0,
+            u_offset: 0,
+            v_offset: 0
+        }
+    }
+}
+
+// Record names of a cube's faces in +X, -X, +Y, -Y, +Z, -Z order
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CubeRecord {
+    name: String,
+    faces: Vec<String>
+}
+
+// Metrics of a font packed as MSDF glyphs, in ems unless noted
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct FontRecord {
+    name: String,
+    // Pixels per em and distance range in pixels the glyphs were rendered with
+    size: f32,
+    distance_range: f32,
+    ascender: f32,
+    descender: f32,
+    line_height: f32,
+    glyphs: Vec<GlyphRecord>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct GlyphRecord {
+    codepoint: u32,
+    advance: f32,
+    // Name of the glyph's record, None for glyphs without an outline
+    sprite: Option<String>,
+    // Left, bottom, right, top of the sprite quad relative to the pen position, y up
+    plane_bounds: Option<[f32; 4]>
+}
+
+
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
+pub struct Image {
+    name: String,
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+    // Source only carried a single gray channel
+    grayscale: bool,
+    // Color space chunks of the source png
+    color_chunks: ColorChunks,
+    // Higher priorities are placed first, positive ones are pinned to page 0
+    priority: i32,
+    // Turned 90° clockwise to fit the atlas better
+    rotated: bool,
+    // Untrimmed size of the source and the position of the trimmed pixels in it
+    source_size: (u32, u32),
+    offset: (u32, u32),
+    // Nine-slice borders within the source
+    nine_slice: Option<NineSlice>,
+    // Pivot within the source, None for the atlas default
+    pivot: Option<(f32, f32)>,
+    // JSON text recorded with the sprite
+    user_data: Option<String>,
+    // Outline of the opaque pixels within the source
+    outline: Option<Polygon>,
+    // Names of identical sprites recorded at the same rect
+    aliases: Vec<String>,
+    // Companion images of the same size placed at the same rect, by variant suffix
+    variants: Vec<Option<Image>>,
+    // Holds normal vectors rather than colors, which are never turned or color converted and are
+    // renormalized when resampled
+    normal_map: bool
+}
+
+impl Image {
+    // An RGBA8 sprite made in code rather than read from a file, the error naming it as its path
+    pub fn new(name: &str, width: u32, height: u32, data: Vec<u8>) -> Result<Image, AtlasError> {
+        if data.len() != width as usize * height as usize * 4 {
+            return Err(AtlasError::Decode(PathBuf::from(name), format!("{} bytes are not {}x{} RGBA8", data.len(), width, height)));
+        }
+        Ok(Image {
+            name: name.to_string(),
+            path: PathBuf::new(),
+            width,
+            height,
+            data,
+            grayscale: false,
+            color_chunks: ColorChunks::default(),
+            priority: 0,
+            rotated: false,
+            source_size: (width, height),
+            offset: (0, 0),
+            nine_slice: None,
+            pivot: None,
+            user_data: None,
+            outline: None,
+            aliases: Vec::new(),
+            variants: Vec::new(),
+            normal_map: false
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn area(&self) -> u32 {
+        self.width * self.height
+    }
+
+    // Turn the pixels 90° clockwise
+    fn rotate(&mut self) {
+        let (width, height) = (self.height, self.width);
+        let mut data = Vec::with_capacity(self.data.len());
+        for row in 0..height {
+            for col in 0..width {
+                let src = (((self.height - 1 - col) * self.width + row) * 4) as usize;
+                data.extend_from_slice(&self.data[src..src + 4]);
+            }
+        }
+
+        self.width = width;
+        self.height = height;
+        self.data = data;
+        self.rotated = !self.rotated;
+
+        for variant in self.variants.iter_mut().flatten() {
+            variant.rotate();
+        }
+    }
+
+    // Row spans of the outline placed upright or `turned`, the whole rect without an outline
+    fn outline_spans(&self, turned: bool) -> Spans {
+        // Size of the trimmed source and of it as placed
+        let (width, height) = if self.rotated { (self.height, self.width) } else { (self.width, self.height) };
+        let placed = if turned { (height, width) } else { (width, height) };
+        match &self.outline {
+            Some(outline) => polygon::placed_spans(outline, self.offset, placed, turned),
+            None => vec![(0, placed.0); placed.1 as usize]
+        }
+    }
+
+    // Resample to `width` x `height`, which becomes the untrimmed source size. With `linear` the
+    // colors are filtered in linear light, except those of normal maps and variants, which hold data.
+    fn resize(&mut self, width: u32, height: u32, filter: FilterType, linear: bool) {
+        let data = std::mem::take(&mut self.data);
+        self.data = if linear && !self.normal_map {
+            let source = image::Rgba32FImage::from_raw(self.width, self.height, color::linearize(&data)).unwrap();
+            color::delinearize(&image::imageops::resize(&source, width, height, filter).into_raw())
+        } else {
+            let source = image::RgbaImage::from_raw(self.width, self.height, data).unwrap();
+            image::imageops::resize(&source, width, height, filter).into_raw()
+        };
+        self.nine_slice = self.nine_slice.map(|borders| borders.scale((self.width, self.height), (width, height)));
+        self.width = width;
+        self.height = height;
+        self.source_size = (width, height);
+        self.offset = (0, 0);
+        if self.normal_map {
+            normal::renormalize(&mut self.data);
+        }
+
+        for variant in self.variants.iter_mut().flatten() {
+            variant.resize(width, height, filter, false);
+        }
+    }
+
+    // Whether the sprite or one of its variants holds normal vectors, which turning would rotate
+    // away from the surface they describe
+    fn holds_normals(&self) -> bool {
+        self.normal_map || self.variants.iter().flatten().any(|variant| variant.normal_map)
+    }
+
+    // Crop off fully transparent rows and columns, keeping a single pixel of an empty image
+    fn trim(&mut self) {
+        let opaque = |x: u32, y: u32| self.data[((y * self.width + x) * 4 + 3) as usize] != 0;
+        let rows: Vec<u32> = (0..self.height).filter(|&y| (0..self.width).any(|x| opaque(x, y))).collect();
+        let cols: Vec<u32> = (0..self.width).filter(|&x| (0..self.height).any(|y| opaque(x, y))).collect();
+
+        let (left, top, width, height) = match (rows.first(), rows.last(), cols.first(), cols.last()) {
+            (Some(&top), Some(&bottom), Some(&left), Some(&right)) => (left, top, right - left + 1, bottom - top + 1),
+            _ => (0, 0, 1, 1)
+        };
+        if (width, height) != (self.width, self.height) {
+            self.crop(left, top, width, height);
+        }
+    }
+
+    // Keep the `width` by `height` pixels at `left`, `top`, cropping the variants the same way
+    fn crop(&mut self, left: u32, top: u32, width: u32, height: u32) {
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+        for row in top..top + height {
+            let start = ((row * self.width + left) * 4) as usize;
+            data.extend_from_slice(&self.data[start..start + (width * 4) as usize]);
+        }
+
+        self.width = width;
+        self.height = height;
+        self.data = data;
+        self.offset = (self.offset.0 + left, self.offset.1 + top);
+
+        for variant in self.variants.iter_mut().flatten() {
+            variant.crop(left, top, width, height);
+        }
+    }
+}
+
+
+pub struct Atlas {
+    records: Vec<Rect>,
+    images: Vec<Image>,
+    width: u32,
+    // Width requested by the user, otherwise picked when packing
+    fixed_width: Option<u32>,
+    // Key the written atlas is encrypted with, and existing atlases are read with
+    key: Option<crypt::Key>,
+    pixel_format: PixelFormat,
+    dither: Dither,
+    // Wrap the pixel data in a KTX2 or DDS container instead of png/raw
+    texture_format: TextureFormat,
+    // Block compression of KTX2 and DDS textures, replacing the pixel format
+    compression: Option<Compression>,
+    // Write downscaled mip levels of every image, into the KTX2 or DDS texture or next to it
+    mipmaps: bool,
+    // Multiply the color of every pixel by its alpha when writing the atlas images
+    premultiply_alpha: bool,
+    // Spread the colors of the sprites into the transparent texels around them
+    alpha_bleed: bool,
+    color_chunks: ChunkPolicy,
+    // Convert inputs with embedded color profiles to sRGB
+    to_srgb: bool,
+    placeholders: Placeholders,
+    oversize: Oversize,
+    packer: Packer,
+    heuristic: Heuristic,
+    // Let sprites be turned 90° when that fits them better
+    allow_rotation: bool,
+    // Transparent pixels kept free between sprites
+    padding: u32,
+    // Repeat the edge pixels of every sprite this far outwards
+    extrude: u32,
+    // Trace an outline polygon of every sprite straying at most this many pixels from its opaque
+    // pixels, which the scan packer lets rects overlap around
+    polygons: Option<u32>,
+    data_format: DataFormat,
+    // Indent JSON atlas data
+    pretty: bool,
+    sort: SortOrder,
+    // Search the packers, sort orders and widths for the smallest layout, then try this many
+    // variations of it
+    optimize: Option<u32>,
+    // Sources larger than this in either dimension are reported
+    max_sprite_size: u32,
+    // Sources taking more than this share of the total sprite area are reported
+    max_sprite_share: Option<f64>,
+    // Normalize record names to NFC
+    normalize_names: bool,
+    name_style: NameStyle,
+    // Directory relative names start from, file names are used without one
+    name_root: Option<PathBuf>,
+    // Watchdog limits for placing sprites
+    max_iterations: u64,
+    time_limit: Option<Duration>,
+    // Replace sprites with distance fields spreading this many pixels
+    sdf_spread: Option<u32>,
+    // Recolor sprites through this palette as they are read
+    palette: Option<Palette>,
+    // Turn grayscale sprites into normal maps, treating them as heightmaps of this strength
+    normal_strength: Option<f32>,
+    // Sprites whose path matches one of these patterns are normal maps already
+    normal_maps: Vec<GlobMatcher>,
+    fonts: Vec<FontRecord>,
+    // Write equally sized sprites as the layers of a KTX2 texture array
+    texture_array: bool,
+    // Assemble cube face sprites into cubemaps
+    cubemap: Option<CubeLayout>,
+    cubes: Vec<CubeRecord>,
+    // Split the atlas into square UDIM tiles of this size
+    page_size: Option<u32>,
+    // Largest atlas size, sprites spill onto further pages of `max_height` past it
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+    // Round the written texture up to power-of-two and/or equal dimensions
+    pot: bool,
+    square: bool,
+    // Sprite priorities by path pattern, the first match wins
+    priorities: Vec<(GlobMatcher, i32)>,
+    // Factor every sprite is resized by, times that of the first pattern its path matches
+    scale: f32,
+    sprite_scales: Vec<(GlobMatcher, f32)>,
+    // Nine-slice borders of the sprites without a nine-patch or sidecar of their own, by path
+    // pattern, the first match wins
+    nine_slices: Vec<(GlobMatcher, NineSlice)>,
+    // Pivot of every sprite, and by path pattern for those without a sidecar of their own, the first
+    // match wins
+    pivot: (f32, f32),
+    sprite_pivots: Vec<(GlobMatcher, (f32, f32))>,
+    // JSON text recorded with the sprites without a sidecar of their own by path pattern, the first
+    // match wins
+    user_data: Vec<(GlobMatcher, String)>,
+    // Pattern grouping numbered sprites into animations, and the frame duration in milliseconds of
+    // the first animation name pattern matching
+    animations: Option<regex::Regex>,
+    frame_durations: Vec<(GlobMatcher, u32)>,
+    // Animations of the tags of Aseprite files
+    tags: Vec<AnimationRecord>,
+    // Resampling filter of scaled and downscaled sprites
+    scale_filter: FilterType,
+    // Resample scaled sprites and mip levels in linear light rather than in sRGB
+    linear_filtering: bool,
+    // Fail when the atlas (or one of its pages) is wider or taller than this
+    max_atlas_size: Option<u32>,
+    // Store the atlas as tiles of this size for virtual texturing
+    tile_size: Option<u32>,
+    // How often the game uses each sprite by name, hot sprites are placed first
+    usage: HashMap<String, f64>,
+    credits: Vec<Credit>,
+    // Sprites carried over from an existing atlas, placed before anything else
+    base: Vec<(Image, Rect)>,
+    // Pixels per em, distance range and characters of MSDF font glyphs
+    msdf_size: f64,
+    msdf_range: f64,
+    charset: String,
+    // Decoded inputs, layout and pixels of the previous build
+    cache: Option<Cache>,
+    // File name suffixes of companion images written as further atlases with the same layout,
+    // "_n" pairs hero_n.png with hero.png and writes atlas_n.png
+    variant_suffixes: Vec<String>
+}
+
+// A sprite that could not be placed and the constraint that stopped it
+#[derive(Debug)]
+pub struct PackError {
+    sprite: String,
+    constraint: Constraint
+}
+
+#[derive(Debug)]
+enum Constraint {
+    Width { width: u32, atlas_width: u32 },
+    Iterations(u64),
+    Time(Duration),
+    LayerSize { size: (u32, u32), layer: (u32, u32) },
+    Page { size: (u32, u32), page: (u32, u32) },
+    Pinned(i32),
+    Cube(String)
+}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "could not place {}: ", self.sprite)?;
+        match &self.constraint {
+            Constraint::Width { width, atlas_width } => {
+                write!(f, "it is {} pixels wide but the atlas is {} pixels wide", width, atlas_width)
+            }
+            Constraint::Iterations(max) => write!(f, "placement exceeded {} iterations", max),
+            Constraint::Time(limit) => write!(f, "packing exceeded the {:?} time limit", limit),
+            Constraint::LayerSize { size, layer } => {
+                write!(f, "it is {}x{} but the texture array layers are {}x{}", size.0, size.1, layer.0, layer.1)
+            }
+            Constraint::Cube(problem) => write!(f, "{}", problem),
+            Constraint::Pinned(priority) => write!(f, "it has priority {} but page 0 is full", priority),
+            Constraint::Page { size, page } => {
+                write!(f, "it is {}x{} but pages are {}x{}", size.0, size.1, page.0, page.1)
+            }
+        }
+    }
+}
+
+impl Error for PackError {}
+
+// What to do with 1x1 placeholder images
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholders {
+    Keep,
+    Skip,
+    // Treat them like unreadable images
+    Reject
+}
+
+// What to do with sprites that fit on no page of the maximum size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Oversize {
+    // Fail to pack
+    Error,
+    // Pack each into an atlas of its own next to the output
+    OwnPage,
+    // Scale them down until they fit
+    Downscale
+}
+
+// Path of a downscaled image with its size before and after
+type Downscaled = (PathBuf, (u32, u32), (u32, u32));
+
+// First column and the one past the last a sprite covers in each of its rows
+type Spans = Vec<(u32, u32)>;
+
+// What sprites are named after
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NameStyle {
+    // "slime.png"
+    Filename,
+    // "enemies/slime.png", the path under the asset directory
+    Relative,
+    // "slime"
+    Stem
+}
+
+// Encoding of the atlas data entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataFormat {
+    Bincode,
+    Json,
+    // TexturePacker's layouts, frames keyed by name or listed
+    TpJsonHash,
+    TpJsonArray,
+    // Bincode along with a Godot AtlasTexture resource per sprite
+    Godot,
+    // Bincode along with a libGDX pack file
+    Libgdx,
+    // Bincode, and Rust constants for every sprite written next to the atlas
+    Rust
+}
+
+impl DataFormat {
+    pub fn from_name(name: &str) -> Option<DataFormat> {
+        match name {
+            "bincode" => Some(DataFormat::Bincode),
+            "json" => Some(DataFormat::Json),
+            "tp-json-hash" => Some(DataFormat::TpJsonHash),
+            "tp-json-array" => Some(DataFormat::TpJsonArray),
+            "godot" => Some(DataFormat::Godot),
+            "libgdx" => Some(DataFormat::Libgdx),
+            "rust" => Some(DataFormat::Rust),
+            _ => None
+        }
+    }
+
+    fn is_texturepacker(&self) -> bool {
+        matches!(self, DataFormat::TpJsonHash | DataFormat::TpJsonArray)
+    }
+}
+
+// Container the atlas images are written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    // Png, or raw pixels for the reduced pixel formats
+    Png,
+    Ktx2,
+    Dds,
+    // Basis Universal UASTC, transcoded to a GPU format at load time
+    Basis
+}
+
+impl TextureFormat {
+    pub fn from_name(name: &str) -> Option<TextureFormat> {
+        match name {
+            "png" => Some(TextureFormat::Png),
+            "ktx2" => Some(TextureFormat::Ktx2),
+            "dds" => Some(TextureFormat::Dds),
+            "basis" => Some(TextureFormat::Basis),
+            _ => None
+        }
+    }
+}
+
+// Algorithm placing new sprites
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Packer {
+    // First free position, scanning rows top to bottom
+    Scan,
+    // MaxRects with the best short side fit heuristic
+    MaxRects,
+    // Skyline with a choice of heuristics
+    Skyline
+}
+
+impl Packer {
+    pub const ALL: [Packer; 3] = [Packer::Scan, Packer::MaxRects, Packer::Skyline];
+
+    pub fn from_name(name: &str) -> Option<Packer> {
+        Packer::ALL.iter().copied().find(|packer| packer.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Packer::Scan => "scan",
+            Packer::MaxRects => "maxrects",
+            Packer::Skyline => "skyline"
+        }
+    }
+}
+
+// Order new sprites are placed in, after priority and usage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOrder {
+    Area,
+    Height,
+    Width,
+    Perimeter,
+    // Longer side first
+    MaxSide,
+    // Alphabetically, leaving it to the names
+    Name
+}
+
+impl SortOrder {
+    pub const ALL: [SortOrder; 6] = [SortOrder::Area, SortOrder::Height, SortOrder::Width, SortOrder::Perimeter, SortOrder::MaxSide, SortOrder::Name];
+
+    pub fn from_name(name: &str) -> Option<SortOrder> {
+        SortOrder::ALL.iter().copied().find(|order| order.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SortOrder::Area => "area",
+            SortOrder::Height => "height",
+            SortOrder::Width => "width",
+            SortOrder::Perimeter => "perimeter",
+            SortOrder::MaxSide => "max-side",
+            SortOrder::Name => "name"
+        }
+    }
+
+    // Larger keys are placed first
+    fn key(&self, image: &Image) -> u64 {
+        let (width, height) = (image.width as u64, image.height as u64);
+        match self {
+            SortOrder::Area => width * height,
+            SortOrder::Height => height,
+            SortOrder::Width => width,
+            SortOrder::Perimeter => width + height,
+            SortOrder::MaxSide => width.max(height),
+            // Every key ties, so sprites go by name
+            SortOrder::Name => 0
+        }
+    }
+}
+
+impl Atlas {
+    pub fn new() -> Atlas {
+        Atlas {
+            records: Vec::new(),
+            images: Vec::new(),
+            width: 0,
+            fixed_width: None,
+            key: None,
+            pixel_format: PixelFormat::Rgba8888,
+            dither: Dither::None,
+            texture_format: TextureFormat::Png,
+            compression: None,
+            mipmaps: false,
+            premultiply_alpha: false,
+            alpha_bleed: false,
+            color_chunks: ChunkPolicy::Strip,
+            to_srgb: false,
+            placeholders: Placeholders::Keep,
+            oversize: Oversize::Error,
+            packer: Packer::Scan,
+            heuristic: Heuristic::BottomLeft,
+            allow_rotation: false,
+            padding: 0,
+            extrude: 0,
+            polygons: None,
+            data_format: DataFormat::Bincode,
+            pretty: false,
+            sort: SortOrder::Area,
+            optimize: None,
+            max_sprite_size: 4096,
+            max_sprite_share: None,
+            normalize_names: true,
+            name_style: NameStyle::Relative,
+            name_root: None,
+            max_iterations: 1_000_000_000,
+            time_limit: None,
+            sdf_spread: None,
+            palette: None,
+            normal_strength: None,
+            normal_maps: Vec::new(),
+            fonts: Vec::new(),
+            texture_array: false,
+            cubemap: None,
+            cubes: Vec::new(),
+            page_size: None,
+            max_width: None,
+            max_height: None,
+            pot: false,
+            square: false,
+            priorities: Vec::new(),
+            scale: 1.0,
+            sprite_scales: Vec::new(),
+            nine_slices: Vec::new(),
+            pivot: (0.5, 0.5),
+            sprite_pivots: Vec::new(),
+            user_data: Vec::new(),
+            animations: None,
+            frame_durations: Vec::new(),
+            tags: Vec::new(),
+            scale_filter: FilterType::Triangle,
+            linear_filtering: false,
+            base: Vec::new(),
+            usage: HashMap::new(),
+            credits: Vec::new(),
+            max_atlas_size: None,
+            tile_size: None,
+            msdf_size: 32.0,
+            msdf_range: 4.0,
+            charset: (' '..='~').collect(),
+            cache: None,
+            variant_suffixes: Vec::new()
+        }
+    }
+
+    // Whether the 1x1 image at `path` is to be left out
+    fn skip_placeholder(&self, path: &Path) -> Result<bool, png::DecodingError> {
+        match self.placeholders {
+            Placeholders::Keep => warn!("{:?} is a 1x1 placeholder", path),
+            Placeholders::Skip => {
+                info!("skipping {:?}: 1x1 placeholder", path);
+                return Ok(true);
+            }
+            Placeholders::Reject => return Err("1x1 placeholder".to_string().into())
+        }
+        Ok(false)
+    }
+
+    pub fn add_image(&mut self, path: &Path) -> Result<(), AtlasError> {
+        let image = self.decode(path)?;
+        self.images.extend(image);
+        Ok(())
+    }
+
+    // Decode `paths` in parallel and add the images in the order given, returning the errors of
+    // those that failed
+    pub fn add_images(&mut self, paths: &[PathBuf]) -> Vec<AtlasError> {
+        let decoded: Vec<Result<Option<Image>, AtlasError>> = paths.par_iter().map(|path| self.decode(path)).collect();
+
+        let mut failed = Vec::new();
+        for result in decoded {
+            match result {
+                Ok(image) => self.images.extend(image),
+                Err(err) => failed.push(err)
+            }
+        }
+        failed
+    }
+
+    // The image at `path` as it will be packed, None for a placeholder that is left out
+    fn decode(&self, path: &Path) -> Result<Option<Image>, AtlasError> {
+        let bytes = std::fs::read(path).map_err(|err| AtlasError::Io(path.to_path_buf(), err))?;
+
+        // Unchanged files are taken from the cache
+        let hash = self.cache.as_ref().map(|_| Cache::hash_file(path, &bytes));
+        if let (Some(cache), Some(hash)) = (&self.cache, &hash) {
+            if let Some(image) = cache.image(path, hash) {
+                return Ok(image);
+            }
+        }
+
+        let image = self.read_image(path, &bytes).map_err(|err| match err {
+            png::DecodingError::IoError(err) => AtlasError::Io(path.to_path_buf(), err),
+            err => AtlasError::Decode(path.to_path_buf(), err.to_string())
+        })?;
+        if let (Some(cache), Some(hash)) = (&self.cache, &hash) {
+            cache.remember(path, hash, &image, true);
+        }
+        Ok(image)
+    }
+
+    fn read_image(&self, path: &Path, bytes: &[u8]) -> Result<Option<Image>, png::DecodingError> {
+        let is_png = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+
+        // Pngs are decoded here to keep their color chunks, other formats by the image crate
+        let (mut width, mut height, mut data, grayscale, heightmap) = if is_png {
+            let (info, mut reader) = png_decoder(bytes).read_info()?;
+
+            if info.width == 0 || info.height == 0 {
+                return Err(format!("zero-size image ({}x{})", info.width, info.height).into());
+            }
+
+            if info.width == 1 && info.height == 1 && self.skip_placeholder(path)? {
+                return Ok(None);
+            }
+
+            let mut buf = vec![0; info.buffer_size()];
+            reader.next_frame(&mut buf)?;
+
+            let grayscale = info.color_type == png::ColorType::Grayscale;
+            let heightmap = matches!(info.color_type, png::ColorType::Grayscale | png::ColorType::GrayscaleAlpha);
+            let data = to_rgba(buf, info.color_type);
+
+            let expected = info.width as usize * info.height as usize * 4;
+            if data.len() != expected {
+                return Err(format!("decoded {} bytes but the header implies {}", data.len(), expected).into());
+            }
+
+            (info.width, info.height, data, grayscale, heightmap)
+        } else {
+            let decoded = decode_image(path, bytes)?;
+            if decoded.0 == 1 && decoded.1 == 1 && self.skip_placeholder(path)? {
+                return Ok(None);
+            }
+            decoded
+        };
+
+        // Nine-patches lose the border marking their stretchable area, and the ".9" of their name
+        let mut name = self.image_name(path);
+        let mut patch = None;
+        if nineslice::is_nine_patch(path) {
+            let (inner_width, inner_height, inner, borders) = nineslice::strip_border(width, height, &data)?;
+            (width, height, data) = (inner_width, inner_height, inner);
+            name = nineslice::sprite_name(&name);
+            patch = Some(borders);
+        }
+
+        let color_chunks = if is_png { ColorChunks::read(bytes) } else { ColorChunks::default() };
+        let mut image = self.finish_image(path, name, (width, height, data, grayscale, heightmap), color_chunks)?;
+        image.nine_slice = patch.or(image.nine_slice);
+        Ok(Some(image))
+    }
+
+    // The sprite `name` of RGBA `pixels` decoded from `path` with its size, whether they were a single
+    // gray channel and whether they were gray at all, after the palette, distance field, normal map,
+    // color conversion and effects options are applied
+    fn finish_image(&self, path: &Path, name: String, pixels: (u32, u32, Vec<u8>, bool, bool), color_chunks: ColorChunks)
+        -> Result<Image, png::DecodingError> {
+        let (mut width, mut height, mut data, mut grayscale, heightmap) = pixels;
+        if let Some(palette) = &self.palette {
+            palette.remap(&mut data);
+            grayscale &= data.chunks(4).all(|px| px[0] == px[1] && px[1] == px[2]);
+        }
+
+        if let Some(spread) = self.sdf_spread {
+            let (sdf_width, sdf_height, sdf) = sdf::generate(&data, width, height, spread);
+            width = sdf_width;
+            height = sdf_height;
+            data = sdf;
+            grayscale = true;
+        }
+
+        // Normal maps hold vectors rather than colors, so they carry no color chunks
+        let relative = self.name_root.as_ref().and_then(|root| path.strip_prefix(root).ok()).unwrap_or(path);
+        let normal_strength = self.normal_strength.filter(|_| heightmap);
+        let normal_map = normal_strength.is_some() || self.normal_maps.iter().any(|pattern| pattern.is_match(relative));
+        let mut color_chunks = color_chunks;
+        if let Some(strength) = normal_strength {
+            data = normal::from_height(&data, width, height, strength);
+            grayscale = false;
+        }
+        if normal_map {
+            color_chunks = ColorChunks::default();
+        }
+
+        // Untagged inputs are assumed to already be sRGB
+        if self.to_srgb && !normal_map {
+            match color_chunks.profile() {
+                Some(profile) if !convert_to_srgb(&mut data, &profile, grayscale) => {
+                    warn!("unable to convert {:?} to sRGB, packing unconverted", path);
+                }
+                _ => color_chunks = ColorChunks::srgb()
+            }
+        }
+
+        let effects = Effects::read(path)?;
+        if !effects.is_empty() {
+            let (effects_width, effects_height, baked) = effects::apply(&data, width, height, &effects);
+            width = effects_width;
+            height = effects_height;
+            data = baked;
+            grayscale = false;
+        }
+
+        let nine_slice = match nineslice::read(path)? {
+            Some(borders) => Some(borders),
+            None => self.nine_slices.iter().find(|(pattern, _)| pattern.is_match(relative)).map(|&(_, borders)| borders)
+        };
+        if let Some(borders) = nine_slice.filter(|borders| !borders.fits(width, height)) {
+            return Err(format!("nine-slice borders {} do not fit {}x{}", borders, width, height).into());
+        }
+        let pivot = match pivot::read(path)? {
+            Some(pivot) => Some(pivot),
+            None => self.sprite_pivots.iter().find(|(pattern, _)| pattern.is_match(relative)).map(|&(_, pivot)| pivot)
+        };
+        let user_data = match userdata::read(path)? {
+            Some(data) => Some(data),
+            None => self.user_data.iter().find(|(pattern, _)| pattern.is_match(relative)).map(|(_, data)| data.clone())
+        };
+
+        Ok(Image {
+            name,
+            path: path.to_path_buf(),
+            width,
+            height,
+            data,
+            grayscale,
+            color_chunks,
+            priority: 0,
+            rotated: false,
+            source_size: (width, height),
+            offset: (0, 0),
+            nine_slice,
+            pivot,
+            user_data,
+            outline: None,
+            aliases: Vec::new(),
+            variants: Vec::new(),
+            normal_map
+        })
+    }
+
+    // Name of the sprite read from `path` as it is recorded
+    fn image_name(&self, path: &Path) -> String {
+        let (name, lossy) = self.sprite_name(path);
+        if lossy {
+            warn!("{:?} is not valid UTF-8, recording it as {:?}", path, name);
+        }
+
+        // Names authored on different platforms may use different unicode forms
+        if self.normalize_names {
+            name.nfc().collect()
+        } else {
+            name
+        }
+    }
+
+    // Name of the sprite read from `path` and whether the path had to be decoded lossily
+    fn sprite_name(&self, path: &Path) -> (String, bool) {
+        let name = match self.name_style {
+            NameStyle::Filename => Path::new(path.file_name().unwrap()),
+            NameStyle::Stem => Path::new(path.file_stem().unwrap()),
+            NameStyle::Relative => self.name_root.as_ref()
+                .and_then(|root| path.strip_prefix(root).ok())
+                .filter(|relative| !relative.as_os_str().is_empty())
+                .unwrap_or(Path::new(path.file_name().unwrap()))
+        };
+
+        // Separated by `/` on every platform
+        let parts: Vec<Cow<str>> = name.components().map(|part| part.as_os_str().to_string_lossy()).collect();
+        let lossy = parts.iter().any(|part| matches!(part, Cow::Owned(_)));
+        (parts.join("/"), lossy)
+    }
+
+    pub fn add(&mut self, image: Image) {
+        self.images.push(image);
+    }
+
+    // Add every image under `dir`, named by their path under it, returning how many were added
+    pub fn add_dir(&mut self, dir: &Path) -> Result<usize, AtlasError> {
+        self.name_root = Some(dir.to_path_buf());
+        let mut paths = Vec::new();
+        let mut aseprite_paths = Vec::new();
+        for entry in WalkDir::new(dir).sort_by_file_name() {
+            let entry = entry.map_err(|err| AtlasError::Io(err.path().unwrap_or(dir).to_path_buf(), err.into()))?;
+            if entry.file_type().is_file() && is_image(entry.path()) {
+                paths.push(entry.into_path());
+            } else if entry.file_type().is_file() && is_aseprite(entry.path()) {
+                aseprite_paths.push(entry.into_path());
+            }
+        }
+
+        let before = self.images.len();
+        if let Some(err) = self.add_images(&paths).into_iter().next() {
+            return Err(err);
+        }
+        for path in aseprite_paths {
+            self.add_aseprite(&path)?;
+        }
+        Ok(self.images.len() - before)
+    }
+
+    // Render the glyphs of a font as MSDF sprites named "<font file>/U+<codepoint>"
+    fn add_font(&mut self, path: &Path) -> Result<(), AtlasError> {
+        let bytes = std::fs::read(path).map_err(|err| AtlasError::Io(path.to_path_buf(), err))?;
+        let font = msdf::generate(&bytes, &self.charset, self.msdf_size, self.msdf_range)
+            .map_err(|err| AtlasError::Decode(path.to_path_buf(), err.to_string()))?;
+        let font_name: String = self.sprite_name(path).0.nfc().collect();
+
+        let mut glyphs = Vec::new();
+        for glyph in font.glyphs {
+            let sprite = glyph.image.map(|(width, height, data)| {
+                let name = format!("{}/U+{:04X}", font_name, glyph.codepoint);
+                self.images.push(Image {
+                    name: name.clone(),
+                    path: path.to_path_buf(),
+                    width,
+                    height,
+                    data,
+                    grayscale: false,
+                    color_chunks: ColorChunks::default(),
+                    priority: 0,
+                    rotated: false,
+                    source_size: (width, height),
+                    offset: (0, 0),
+                    nine_slice: None,
+                    pivot: None,
+                    user_data: None,
+                    outline: None,
+                    aliases: Vec::new(),
+                    variants: Vec::new(),
+                    normal_map: false
+                });
+                name
+            });
+
+            glyphs.push(GlyphRecord {
+                codepoint: glyph.codepoint,
+                advance: glyph.advance,
+                sprite,
+                plane_bounds: glyph.plane
+            });
+        }
+
+        self.fonts.push(FontRecord {
+            name: font_name,
+            size: self.msdf_size as f32,
+            distance_range: self.msdf_range as f32,
+            ascender: font.ascender,
+            descender: font.descender,
+            line_height: font.line_height,
+            glyphs
+        });
+
+        Ok(())
+    }
+
+    // Add every frame of an Aseprite file as a sprite, `<file>_<frame>` past the first one, and its
+    // tags as animations named "<file>/<tag>"
+    fn add_aseprite(&mut self, path: &Path) -> Result<(), AtlasError> {
+        let bytes = std::fs::read(path).map_err(|err| AtlasError::Io(path.to_path_buf(), err))?;
+        let file = aseprite::read(&bytes).map_err(|err| AtlasError::Decode(path.to_path_buf(), err))?;
+        let name = self.image_name(path);
+        let stem = strip_extension(&name);
+        let frame_name = |frame: u32| match file.frames.len() {
+            1 => name.clone(),
+            _ => format!("{}_{}{}", stem, frame, &name[stem.len()..])
+        };
+
+        for (frame, pixels) in file.frames.iter().enumerate() {
+            let pixels = (file.width, file.height, pixels.clone(), false, false);
+            let image = self.finish_image(path, frame_name(frame as u32), pixels, ColorChunks::default())
+                .map_err(|err| AtlasError::Decode(path.to_path_buf(), err.to_string()))?;
+            self.images.push(image);
+        }
+
+        for (tag, frames) in &file.tags {
+            let Some(&first) = frames.first() else { continue };
+            let duration = file.durations[first as usize];
+            if frames.iter().any(|&frame| file.durations[frame as usize] != duration) {
+                warn!("{:?}: frames of tag {:?} last differently long, recording {} ms", path, tag, duration);
+            }
+            self.tags.push(AnimationRecord {
+                name: format!("{}/{}", stem, tag),
+                frames: frames.iter().map(|&frame| frame_name(frame)).collect(),
+                duration: Some(duration)
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn pack(&mut self) -> Result<(), AtlasError> {
+        if self.texture_array {
+            return Ok(self.pack_layers()?);
+        }
+        if let Some(layout) = self.cubemap {
+            return Ok(self.pack_cubes(layout)?);
+        }
+
+        // Priority first, then usage, then the sort order. Ties go by name and path so the order the
+        // images were added in, which follows the file system, never changes the layout.
+        let mut images = std::mem::take(&mut self.images);
+        images.sort_unstable_by(|a, b| self.placement_order(a, b, self.sort));
+        self.images = images;
+
+        if let Some(tolerance) = self.polygons {
+            // Outlines are traced upright, sprites turned before keep theirs
+            for image in self.images.iter_mut().filter(|image| image.outline.is_none() && !image.rotated) {
+                let outline = polygon::trace(image.width, image.height, &image.data, tolerance);
+                image.outline = Some(outline.translate(image.offset.0, image.offset.1));
+            }
+        }
+
+        let (max_width, _) = self.size_limits();
+        self.width = self.page_size.unwrap_or_else(|| self.pick_width().min(max_width.unwrap_or(u32::MAX)));
+
+        // The same sprites at the same sizes as in the previous build keep their rects
+        let layout = match &mut self.cache {
+            Some(cache) if self.base.is_empty() => cache.layout(&self.images),
+            _ => None
+        };
+        let base = match layout {
+            Some((width, rects)) => {
+                self.width = width;
+                self.records = rects;
+                0
+            }
+            None => {
+                let base = match self.optimize {
+                    Some(iterations) => self.place_optimized(iterations)?,
+                    None => self.place()?
+                };
+                // A picked width counts every sprite's padding, so the sprites may not reach across it
+                if self.fixed_width.is_none() && self.page_size.is_none() && !self.records.is_empty() {
+                    self.width = self.width.min(self.used_width());
+                }
+                if let Some(cache) = &mut self.cache {
+                    cache.remember_layout(&self.images[base..], self.width, &self.records[base..]);
+                }
+                base
+            }
+        };
+
+        for (image, slot) in self.images.iter_mut().zip(self.records.iter()).skip(base) {
+            if slot.width != image.width {
+                image.rotate();
+            }
+        }
+
+        Ok(self.check_pinned(base)?)
+    }
+
+    // Whether `a` is placed before `b`: priority first, then usage, then the sort order
+    fn placement_order(&self, a: &Image, b: &Image, sort: SortOrder) -> std::cmp::Ordering {
+        let usage = |img: &Image| self.usage.get(&img.name).copied().unwrap_or(0.0);
+        b.priority.cmp(&a.priority)
+            .then(usage(b).total_cmp(&usage(a)))
+            .then(sort.key(b).cmp(&sort.key(a)))
+            .then_with(|| a.name.cmp(&b.name))
+            .then_with(|| a.path.cmp(&b.path))
+    }
+
+    // Fail when a pinned sprite placed after the first `base` ended up past the first page
+    fn check_pinned(&self, base: usize) -> Result<(), PackError> {
+        for (image, slot) in self.images.iter().zip(self.records.iter()).skip(base) {
+            if image.priority > 0 && self.page_limit().is_some_and(|page| slot.y >= page) {
+                return Err(PackError {
+                    sprite: image.name.clone(),
+                    constraint: Constraint::Pinned(image.priority)
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    // Place the sprites with every packer, heuristic and sort order at a few widths around the picked
+    // one, then `iterations` times more with the best layout so far changed a little, accepting
+    // somewhat worse ones early on as simulated annealing does. Keeps the layout needing the fewest
+    // texture pixels and, like `place`, returns how many sprites were carried over.
+    fn place_optimized(&mut self, iterations: u32) -> Result<usize, PackError> {
+        let base = self.base.len();
+        let (widest, picked) = (self.widest(), self.width);
+        let widths: Vec<u32> = match self.fixed_width.or(self.page_size) {
+            Some(_) => vec![picked],
+            None => {
+                let max = self.size_limits().0.unwrap_or(u32::MAX).max(widest);
+                let mut widths: Vec<u32> = [6, 7, 8, 9, 10].iter().map(|eighths| (picked * eighths / 8).clamp(widest, max)).collect();
+                widths.dedup();
+                widths
+            }
+        };
+
+        // Images are moved between orders as they are, `ids` naming each by where pack() sorted it
+        let mut images = std::mem::take(&mut self.images);
+        let mut ids: Vec<usize> = (0..images.len()).collect();
+        let priorities: Vec<i32> = images.iter().map(|image| image.priority).collect();
+        let arrange = |images: &mut Vec<Image>, ids: &mut Vec<usize>, order: &[usize]| {
+            let mut slots: Vec<Option<Image>> = (0..ids.len()).map(|_| None).collect();
+            for (image, &id) in std::mem::take(images).into_iter().zip(ids.iter()) {
+                slots[id] = Some(image);
+            }
+            *images = order.iter().map(|&id| slots[id].take().unwrap()).collect();
+            *ids = order.to_vec();
+        };
+
+        // The configured layout comes first, so it is kept on ties and its error reported when no
+        // layout works out
+        let configured = (self.packer, self.heuristic, picked);
+        let first = self.try_layout(&mut images, base);
+        let mut best = first.as_ref().ok().map(|&pixels| (pixels, ids.clone(), configured, self.records.clone()));
+        let mut tried = 1;
+
+        for packer in Packer::ALL {
+            let heuristics = match packer {
+                Packer::Skyline => vec![Heuristic::BottomLeft, Heuristic::MinWaste],
+                _ => vec![self.heuristic]
+            };
+            for (heuristic, sort) in heuristics.into_iter().flat_map(|heuristic| SortOrder::ALL.map(|sort| (heuristic, sort))) {
+                let mut order: Vec<usize> = (0..images.len()).collect();
+                order.sort_by(|&a, &b| self.placement_order(&images[a], &images[b], sort));
+                let order: Vec<usize> = order.into_iter().map(|position| ids[position]).collect();
+                arrange(&mut images, &mut ids, &order);
+
+                for &width in widths.iter() {
+                    (self.packer, self.heuristic, self.width) = (packer, heuristic, width);
+                    tried += 1;
+                    if let Ok(pixels) = self.try_layout(&mut images, base) {
+                        if best.as_ref().is_none_or(|best| pixels < best.0) {
+                            best = Some((pixels, ids.clone(), (packer, heuristic, width), self.records.clone()));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Anneal from the best layout, swapping two sprites of the same priority or nudging the
+        // width. The random numbers are always the same so builds are reproducible.
+        if let Some((pixels, order, (packer, heuristic, width), _)) = best.clone() {
+            (self.packer, self.heuristic) = (packer, heuristic);
+            let mut current = (pixels, order, width);
+            let mut state = 0x9e37_79b9_7f4a_7c15_u64;
+            let mut random = |bound: usize| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state % bound.max(1) as u64) as usize
+            };
+
+            for step in 0..iterations {
+                let (mut order, mut width) = (current.1.clone(), current.2);
+                if widths.len() > 1 && random(4) == 0 {
+                    let nudge = random((picked / 16).max(1) as usize) as u32 + 1;
+                    width = if random(2) == 0 { width.saturating_sub(nudge).max(widest) } else { width + nudge };
+                } else {
+                    let (a, b) = (random(order.len()), random(order.len()));
+                    if a == b || priorities[order[a]] != priorities[order[b]] {
+                        continue;
+                    }
+                    order.swap(a, b);
+                }
+
+                arrange(&mut images, &mut ids, &order);
+                self.width = width;
+                tried += 1;
+                if let Ok(pixels) = self.try_layout(&mut images, base) {
+                    let temperature = 0.05 * (1.0 - step as f64 / iterations as f64);
+                    let worse = (pixels as f64 - current.0 as f64) / current.0 as f64;
+                    if worse <= 0.0 || (random(1 << 20) as f64 / (1 << 20) as f64) < (-worse / temperature).exp() {
+                        current = (pixels, order.clone(), width);
+                    }
+                    if best.as_ref().is_some_and(|best| pixels < best.0) {
+                        best = Some((pixels, order, (packer, heuristic, width), self.records.clone()));
+                    }
+                }
+            }
+        }
+
+        let (pixels, order, (packer, heuristic, width), records) = match best {
+            Some(best) => best,
+            None => {
+                self.images = images;
+                return first.map(|_| base);
+            }
+        };
+        arrange(&mut images, &mut ids, &order);
+        (self.packer, self.heuristic, self.width) = (packer, heuristic, width);
+        let base_images: Vec<Image> = std::mem::take(&mut self.base).into_iter().map(|(image, _)| image).collect();
+        self.images = base_images.into_iter().chain(images).collect();
+        self.records = records;
+
+        let saved = match first {
+            Ok(configured) => format!(", {:.1}% fewer pixels than the configured one", 100.0 * (1.0 - pixels as f64 / configured as f64)),
+            Err(_) => String::new()
+        };
+        info!("kept the smallest of {} layouts, {}x{} with the {} packer{}", tried, self.texture_size().0, self.texture_size().1, packer.name(), saved);
+        Ok(base)
+    }
+
+    // Place `images` in their order around the sprites carried over, then take them back with the
+    // first `base` carried over ones, returning the texture pixels the layout needs
+    fn try_layout(&mut self, images: &mut Vec<Image>, base: usize) -> Result<u64, PackError> {
+        self.images = std::mem::take(images);
+        self.records.clear();
+        let placed = self.place().and_then(|_| self.check_pinned(base)).map(|_| self.output_pixels());
+
+        let mut placed_images = std::mem::take(&mut self.images);
+        *images = placed_images.split_off(base);
+        self.base = placed_images.into_iter().zip(self.records.iter().copied()).collect();
+        placed
+    }
+
+    // Place the sprites around those carried over, returning how many were carried over
+    fn place(&mut self) -> Result<usize, PackError> {
+        // Carried over sprites keep their rects, new ones are placed around them
+        let (base_images, base_rects): (Vec<Image>, Vec<Rect>) = std::mem::take(&mut self.base).into_iter().unzip();
+        let images = std::mem::replace(&mut self.images, base_images);
+        self.records = base_rects;
+
+        // Sprites are placed with their extrusion all around them and the padding to their right and
+        // below them, which may hang over the atlas edge. Records only cover the sprite.
+        let base = self.records.len();
+        let base_rects = self.records.clone();
+        let (extrude, gutter) = (self.extrude, self.gutter());
+        for rect in self.records.iter_mut() {
+            *rect = Rect {
+                x: rect.x.saturating_sub(extrude),
+                y: rect.y.saturating_sub(extrude),
+                width: rect.width + gutter,
+                height: rect.height + gutter
+            };
+        }
+        let placed = match self.packer {
+            Packer::Scan => self.place_scan(&images),
+            Packer::MaxRects => self.place_maxrects(&images),
+            Packer::Skyline => self.place_skyline(&images)
+        };
+        self.records[..base].copy_from_slice(&base_rects);
+        for rect in self.records[base..].iter_mut() {
+            *rect = Rect {
+                x: rect.x + extrude,
+                y: rect.y + extrude,
+                width: rect.width - gutter,
+                height: rect.height - gutter
+            };
+        }
+        // Kept even on failure so the sprites can be packed again with other settings
+        self.images.extend(images);
+        placed?;
+        Ok(base)
+    }
+
+    fn place_scan(&mut self, images: &[Image]) -> Result<(), PackError> {
+        let started = Instant::now();
+        let mut iterations = 0;
+        let gutter = self.gutter();
+
+        // With polygons rects may overlap where the outlines of their sprites stay apart
+        let mut outlines: Option<Vec<Spans>> = self.polygons.map(|_| {
+            self.images.iter().map(|image| image.outline_spans(image.rotated)).collect()
+        });
+
+        for image in images {
+            self.check_size(image)?;
+
+            // Whichever way up the sprite ends higher
+            let upright = if image.width + 2 * self.extrude <= self.width {
+                let spans = outlines.as_ref().map(|_| image.outline_spans(false));
+                let placed = outlines.as_deref().zip(spans.as_ref());
+                Some((self.next_slot(image, image.width + gutter, image.height + gutter, placed, started, &mut iterations)?, spans))
+            } else {
+                None
+            };
+            let turned = if self.may_turn(image) && image.width != image.height && image.height + 2 * self.extrude <= self.width {
+                let spans = outlines.as_ref().map(|_| image.outline_spans(true));
+                let placed = outlines.as_deref().zip(spans.as_ref());
+                Some((self.next_slot(image, image.height + gutter, image.width + gutter, placed, started, &mut iterations)?, spans))
+            } else {
+                None
+            };
+            let (slot, spans) = upright.into_iter().chain(turned)
+                .min_by_key(|(slot, _)| (slot.y + slot.height, slot.x))
+                .unwrap();
+            self.records.push(slot);
+            if let (Some(outlines), Some(spans)) = (&mut outlines, spans) {
+                outlines.push(spans);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Fill pages one after another, or without pages find the lowest atlas height that fits everything
+    fn place_maxrects(&mut self, images: &[Image]) -> Result<(), PackError> {
+        for image in images {
+            self.check_size(image)?;
+        }
+
+        if let Some(page) = self.page_limit() {
+            let (width, padding) = (self.width, self.padding);
+            return self.place_paged(images, page, || MaxRects::new(width + padding, page + padding));
+        }
+
+        let started = Instant::now();
+        let mut iterations = 0;
+
+        // Start from the height the sprites would cover without any waste, grow it until they fit
+        // and then narrow down on the lowest height they fit in
+        let area: u64 = self.records.iter().map(|rect| rect.width as u64 * rect.height as u64)
+            .chain(images.iter().map(|img| img.area() as u64))
+            .sum();
+        let shortest_side = |img: &Image| if self.may_turn(img) { img.width.min(img.height) } else { img.height };
+        let tallest = images.iter().map(shortest_side).max().unwrap_or(0).max(self.height());
+        let mut low = tallest.max(area.div_ceil(self.width.max(1) as u64) as u32);
+        let mut placed = self.maxrects_bin(images, low, started, &mut iterations)?;
+        let mut high = low;
+        while placed.is_none() {
+            low = high + 1;
+            high += (high / 8).max(1);
+            placed = self.maxrects_bin(images, high, started, &mut iterations)?;
+        }
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match self.maxrects_bin(images, mid, started, &mut iterations)? {
+                Some(rects) => {
+                    placed = Some(rects);
+                    high = mid;
+                }
+                None => low = mid + 1
+            }
+        }
+
+        self.records.extend(placed.unwrap());
+        Ok(())
+    }
+
+    // Rects for `images` in a bin of the atlas width and `height` around the placed sprites,
+    // None when they do not all fit
+    fn maxrects_bin(&self, images: &[Image], height: u32, started: Instant, iterations: &mut u64)
+                    -> Result<Option<Vec<Rect>>, PackError> {
+        let mut bin = MaxRects::new(self.width + self.padding, height + self.padding);
+        for rect in self.records.iter() {
+            bin.occupy(rect);
+        }
+
+        let mut rects = Vec::with_capacity(images.len());
+        for image in images {
+            self.watchdog(image, started, iterations)?;
+            match bin.insert(image.width + self.gutter(), image.height + self.gutter(), self.may_turn(image)) {
+                Some(rect) => rects.push(rect),
+                None => return Ok(None)
+            }
+        }
+
+        Ok(Some(rects))
+    }
+
+    // The skyline grows downwards as needed when the atlas has no pages
+    fn place_skyline(&mut self, images: &[Image]) -> Result<(), PackError> {
+        for image in images {
+            self.check_size(image)?;
+        }
+
+        let (width, padding, heuristic) = (self.width, self.padding, self.heuristic);
+        let page = self.page_limit().unwrap_or(u32::MAX - padding);
+        self.place_paged(images, page, || Skyline::new(width + padding, page + padding, heuristic))
+    }
+
+    // Place sprites on pages `page` high stacked top to bottom, each sprite on the first page with
+    // room for it, opening a new page when none has
+    fn place_paged<B: Bin>(&mut self, images: &[Image], page: u32, new_bin: impl Fn() -> B) -> Result<(), PackError> {
+        let started = Instant::now();
+        let mut iterations = 0;
+        let gutter = self.gutter();
+
+        let mut pages: Vec<B> = Vec::new();
+        for rect in self.records.iter() {
+            let index = (rect.y / page) as usize;
+            while pages.len() <= index {
+                pages.push(new_bin());
+            }
+            pages[index].occupy(&Rect { y: rect.y % page, ..*rect });
+        }
+
+        for image in images {
+            self.watchdog(image, started, &mut iterations)?;
+            let rotate = self.may_turn(image);
+            let placed = pages.iter_mut().enumerate()
+                .find_map(|(index, bin)| Some((index, bin.insert(image.width + gutter, image.height + gutter, rotate)?)));
+            let (index, rect) = match placed {
+                Some(placed) => placed,
+                None => {
+                    // Sprites were checked to fit on a page
+                    let mut bin = new_bin();
+                    let rect = bin.insert(image.width + gutter, image.height + gutter, rotate).unwrap();
+                    pages.push(bin);
+                    (pages.len() - 1, rect)
+                }
+            };
+            self.records.push(Rect { y: rect.y + index as u32 * page, ..rect });
+        }
+
+        Ok(())
+    }
+
+    // Stop placement that runs past the iteration or time limit
+    fn watchdog(&self, image: &Image, started: Instant, iterations: &mut u64) -> Result<(), PackError> {
+        *iterations += 1;
+        if *iterations > self.max_iterations {
+            return Err(PackError { sprite: image.name.clone(), constraint: Constraint::Iterations(self.max_iterations) });
+        }
+        if let Some(limit) = self.time_limit {
+            if started.elapsed() > limit {
+                return Err(PackError { sprite: image.name.clone(), constraint: Constraint::Time(limit) });
+            }
+        }
+
+        Ok(())
+    }
+
+    // Stack equally sized sprites on top of each other, one texture array layer each, in name order
+    fn pack_layers(&mut self) -> Result<(), PackError> {
+        self.images.sort_by(|a, b| a.name.cmp(&b.name));
+
+        // The most common size is taken to be the intended one so the odd sprite out is reported
+        let mut sizes: BTreeMap<(u32, u32), usize> = BTreeMap::new();
+        for image in self.images.iter() {
+            *sizes.entry((image.width, image.height)).or_default() += 1;
+        }
+        let layer = sizes.iter().rev().max_by_key(|(_, &count)| count).map_or((0, 0), |(&size, _)| size);
+        self.width = layer.0;
+
+        for (i, image) in self.images.iter().enumerate() {
+            if (image.width, image.height) != layer {
+                return Err(PackError {
+                    sprite: image.name.clone(),
+                    constraint: Constraint::LayerSize { size: (image.width, image.height), layer }
+                });
+            }
+
+            self.records.push(Rect {
+                x: 0,
+                y: i as u32 * layer.1,
+                width: layer.0,
+                height: layer.1
+            });
+        }
+
+        Ok(())
+    }
+
+    // Group face sprites into cubes in name order and lay every cube out as a cross, or its faces
+    // stacked in KTX2 order
+    fn pack_cubes(&mut self, layout: CubeLayout) -> Result<(), PackError> {
+        let fail = |sprite: &str, problem: String| PackError {
+            sprite: sprite.to_string(),
+            constraint: Constraint::Cube(problem)
+        };
+
+        let mut cubes: BTreeMap<String, [Option<usize>; 6]> = BTreeMap::new();
+        for (i, image) in self.images.iter().enumerate() {
+            let (cube, face) = cubemap::face(&image.name)
+                .ok_or_else(|| fail(&image.name, "it is not named like a cube face (_px, _nx, _py, _ny, _pz, _nz)".to_string()))?;
+
+            let slot = &mut cubes.entry(cube.to_string()).or_default()[face];
+            if let Some(other) = slot {
+                return Err(fail(&image.name, format!("{} is the same face", self.images[*other].name)));
+            }
+            *slot = Some(i);
+        }
+
+        let size = self.images.first().map_or(0, |img| img.width);
+        for image in self.images.iter() {
+            if (image.width, image.height) != (size, size) {
+                return Err(fail(&image.name, format!("it is {}x{} but cube faces are {}x{}", image.width, image.height, size, size)));
+            }
+        }
+
+        let mut order = Vec::new();
+        for (name, faces) in cubes.iter() {
+            for (face, slot) in faces.iter().enumerate() {
+                match slot {
+                    Some(i) => order.push(*i),
+                    None => return Err(fail(name, format!("it has no _{} face", cubemap::FACES[face])))
+                }
+            }
+        }
+
+        let mut images: Vec<Option<Image>> = std::mem::take(&mut self.images).into_iter().map(Some).collect();
+        self.images = order.iter().map(|&i| images[i].take().unwrap()).collect();
+
+        self.width = match layout {
+            CubeLayout::Cross => 4 * size,
+            CubeLayout::Ktx2 => size
+        };
+
+        for (i, image) in self.images.iter().enumerate() {
+            let (cube, face) = (i as u32 / 6, i % 6);
+            let (col, row) = match layout {
+                CubeLayout::Cross => (cubemap::CROSS[face].0, cube * 3 + cubemap::CROSS[face].1),
+                CubeLayout::Ktx2 => (0, i as u32)
+            };
+
+            self.records.push(Rect {
+                x: col * size,
+                y: row * size,
+                width: image.width,
+                height: image.height
+            });
+        }
+
+        self.cubes = self.images.chunks(6)
+            .map(|faces| CubeRecord {
+                name: cubemap::face(&faces[0].name).unwrap().0.to_string(),
+                faces: faces.iter().map(|img| img.name.clone()).collect()
+            })
+            .collect();
+
+        Ok(())
+    }
+
+    // Carry the sprites of the atlas at `path` over at their current positions, dropping inputs
+    // with the same names. Returns how many inputs were dropped.
+    fn keep_existing(&mut self, path: &Path) -> Result<usize, ReadError> {
+        let file = reader::read(path, self.key.as_ref())?;
+        if file.layers > 0 || file.pages > 0 || !file.data.cubes.is_empty() {
+            return Err(ReadError::Format("only single page atlases can be appended to".to_string()));
+        }
+
+        // Growing the width keeps every position valid
+        self.fixed_width = Some(self.fixed_width.unwrap_or(0).max(file.width));
+        self.fonts.extend(file.data.fonts);
+        self.credits.extend(file.data.credits);
+        self.carry_animations(&file.data.records, file.data.animations);
+
+        for record in file.data.records {
+            let mut data = Vec::with_capacity((record.width * record.height * 4) as usize);
+            for row in record.y..record.y + record.height {
+                let start = ((row * file.width + record.x) * 4) as usize;
+                data.extend_from_slice(&file.pixels[start..start + (record.width * 4) as usize]);
+            }
+            if file.data.premultiplied_alpha {
+                unpremultiply(&mut data);
+            }
+
+            let grayscale = data.chunks(4).all(|px| px[0] == px[1] && px[1] == px[2] && px[3] == 255);
+            let normal_map = self.normal_maps.iter().any(|pattern| pattern.is_match(&record.name));
+            let image = Image {
+                name: record.name,
+                path: path.to_path_buf(),
+                width: record.width,
+                height: record.height,
+                data,
+                grayscale,
+                color_chunks: ColorChunks::default(),
+                priority: 0,
+                rotated: record.rotated,
+                source_size: (record.source_width, record.source_height),
+                offset: (record.offset_x, record.offset_y),
+                nine_slice: record.nine_slice,
+                pivot: Some((record.pivot_x, record.pivot_y)),
+                user_data: record.user_data,
+                outline: record.polygon,
+                aliases: Vec::new(),
+                variants: Vec::new(),
+                normal_map
+            };
+            let rect = Rect {
+                x: record.x,
+                y: record.y,
+                width: record.width,
+                height: record.height
+            };
+            self.base.push((image, rect));
+        }
+
+        let before = self.images.len();
+        let base = &self.base;
+        self.images.retain(|img| !base.iter().any(|(kept, _)| kept.name == img.name));
+        Ok(before - self.images.len())
+    }
+
+    // Keep the `animations` of an existing atlas with `records`, except those --animations groups from
+    // the numbered sprites again, new frames included, and those the inputs already tag
+    fn carry_animations(&mut self, records: &[AtlasRecord], animations: Vec<AnimationRecord>) {
+        let regrouped = self.animations.as_ref()
+            .map_or_else(Vec::new, |pattern| animation::group(records, pattern, &self.frame_durations));
+        for kept in animations {
+            let name = &kept.name;
+            if !regrouped.iter().chain(self.tags.iter()).any(|animation| animation.name == *name) {
+                self.tags.push(kept);
+            }
+        }
+    }
+
+    // Add the sprites of the atlas at `path` as they were before packing, with its animations, fonts
+    // and credits. Sprites named and looking like one added before are left out, returning how many.
+    fn add_atlas(&mut self, path: &Path) -> Result<usize, ReadError> {
+        let file = reader::read(path, self.key.as_ref())?;
+        let sprites = unpack::extract(&file).map_err(ReadError::Format)?;
+        self.carry_animations(&file.data.records, file.data.animations);
+        self.fonts.extend(file.data.fonts);
+        self.credits.extend(file.data.credits);
+
+        let mut identical = 0;
+        for (record, sprite) in file.data.records.into_iter().zip(sprites) {
+            let mut data = sprite.data;
+            if file.data.premultiplied_alpha {
+                unpremultiply(&mut data);
+            }
+            if self.images.iter().any(|img| img.name == record.name && img.source_size == (sprite.width, sprite.height) && img.data == data) {
+                identical += 1;
+                continue;
+            }
+
+            let grayscale = data.chunks(4).all(|px| px[0] == px[1] && px[1] == px[2] && px[3] == 255);
+            let normal_map = self.normal_maps.iter().any(|pattern| pattern.is_match(&record.name));
+            self.images.push(Image {
+                path: path.join(&record.name),
+                name: record.name,
+                width: sprite.width,
+                height: sprite.height,
+                data,
+                grayscale,
+                color_chunks: ColorChunks::default(),
+                priority: 0,
+                rotated: false,
+                source_size: (sprite.width, sprite.height),
+                offset: (0, 0),
+                nine_slice: record.nine_slice,
+                pivot: Some((record.pivot_x, record.pivot_y)),
+                user_data: record.user_data,
+                outline: record.polygon,
+                aliases: Vec::new(),
+                variants: Vec::new(),
+                normal_map
+            });
+        }
+        Ok(identical)
+    }
+
+    // Flatten "name__layer.png" images in the same directory into one "name.png" image, stacking layers
+    // in suffix order with the first at the bottom. Returns the paths of layer groups that could not be
+    // flattened, which are dropped.
+    fn composite_layers(&mut self) -> Vec<(PathBuf, String)> {
+        let mut groups: BTreeMap<(PathBuf, String), Vec<Image>> = BTreeMap::new();
+        let mut rest = Vec::new();
+
+        for image in std::mem::take(&mut self.images) {
+            // Only the file part of relative names is split, directories may contain "__" too
+            let (dir, file) = image.name.split_at(image.name.rfind('/').map_or(0, |slash| slash + 1));
+            let split = file.split_once("__").map(|(base, layer)| match layer.rsplit_once('.') {
+                Some((_, ext)) => format!("{}{}.{}", dir, base, ext),
+                None => format!("{}{}", dir, base)
+            });
+
+            match split {
+                Some(name) => {
+                    let dir = image.path.parent().map(Path::to_path_buf).unwrap_or_default();
+                    groups.entry((dir, name)).or_default().push(image);
+                }
+                None => rest.push(image)
+            }
+        }
+
+        let mut failed = Vec::new();
+        for ((dir, name), mut layers) in groups {
+            layers.sort_by(|a, b| a.name.cmp(&b.name));
+
+            let (width, height) = (layers[0].width, layers[0].height);
+            if let Some(odd) = layers.iter().find(|img| (img.width, img.height) != (width, height)) {
+                failed.push((dir.join(Path::new(&name).file_name().unwrap()), format!("layer {} is {}x{} but {} is {}x{}",
+                    odd.name, odd.width, odd.height, layers[0].name, width, height)));
+                continue;
+            }
+
+            let mut data = layers[0].data.clone();
+            for layer in layers.iter().skip(1) {
+                for (dst, src) in data.chunks_mut(4).zip(layer.data.chunks(4)) {
+                    let over = effects::over([src[0], src[1], src[2], src[3]], [dst[0], dst[1], dst[2], dst[3]]);
+                    dst.copy_from_slice(&over);
+                }
+            }
+
+            let bottom = layers.swap_remove(0);
+            rest.push(Image {
+                name,
+                path: bottom.path,
+                width,
+                height,
+                grayscale: layers.iter().all(|img| img.grayscale) && bottom.grayscale,
+                data,
+                color_chunks: bottom.color_chunks,
+                priority: 0,
+                rotated: false,
+                source_size: (width, height),
+                offset: (0, 0),
+                nine_slice: None,
+                pivot: None,
+                user_data: None,
+                outline: None,
+                aliases: Vec::new(),
+                variants: Vec::new(),
+                normal_map: bottom.normal_map
+            });
+        }
+
+        self.images = rest;
+        failed
+    }
+
+    // Move every "<stem><suffix>.<ext>" image onto the "<stem>" image next to it as its variant for
+    // that suffix. Returns the variants that could not be attached, which are dropped.
+    fn attach_variants(&mut self) -> Vec<(PathBuf, String)> {
+        let count = self.variant_suffixes.len();
+        let mut variants = Vec::new();
+        let mut bases = Vec::new();
+        for mut image in std::mem::take(&mut self.images) {
+            let stem = image.path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+            let suffix = self.variant_suffixes.iter()
+                .position(|suffix| stem.len() > suffix.len() && stem.ends_with(suffix.as_str()));
+            match suffix {
+                Some(index) => {
+                    let base = image.path.with_file_name(&stem[..stem.len() - self.variant_suffixes[index].len()]);
+                    variants.push((index, base, image));
+                }
+                None => {
+                    image.variants = vec![None; count];
+                    bases.push(image);
+                }
+            }
+        }
+
+        let mut dropped = Vec::new();
+        for (index, base, variant) in variants {
+            let suffix = &self.variant_suffixes[index];
+            let problem = match bases.iter_mut().find(|image| image.path.with_extension("") == base) {
+                None => format!("no base image for the {} variant", suffix),
+                Some(image) if (image.width, image.height) != (variant.width, variant.height) => {
+                    format!("{}x{} variant of the {}x{} {:?}", variant.width, variant.height, image.width, image.height, image.path)
+                }
+                Some(image) if image.variants[index].is_some() => format!("second {} variant of {:?}", suffix, image.path),
+                Some(image) => {
+                    image.variants[index] = Some(variant);
+                    continue;
+                }
+            };
+            dropped.push((variant.path, problem));
+        }
+
+        self.images = bases;
+        dropped
+    }
+
+    // Drop images with the given record names, returning the names that matched nothing
+    fn exclude(&mut self, names: &[String]) -> Vec<String> {
+        let unmatched = names.iter()
+            .filter(|name| !self.images.iter().any(|img| img.name == **name))
+            .cloned()
+            .collect();
+
+        self.images.retain(|img| !names.contains(&img.name));
+        unmatched
+    }
+
+    // Give every image the priority of the first rule matching its path relative to `root`
+    fn assign_priorities(&mut self, root: &Path) {
+        for image in self.images.iter_mut() {
+            let relative = image.path.strip_prefix(root).unwrap_or(&image.path);
+            image.priority = self.priorities.iter()
+                .find(|(pattern, _)| pattern.is_match(relative))
+                .map_or(0, |&(_, priority)| priority);
+        }
+    }
+
+    // Resize every image under `root` by the scale and that of the first pattern its path matches,
+    // returning how many changed size
+    fn scale_images(&mut self, root: &Path) -> usize {
+        let mut scaled = 0;
+        for image in self.images.iter_mut() {
+            let relative = image.path.strip_prefix(root).unwrap_or(&image.path);
+            let factor = self.scale * self.sprite_scales.iter()
+                .find(|(pattern, _)| pattern.is_match(relative))
+                .map_or(1.0, |&(_, scale)| scale);
+            let size = |side: u32| ((side as f32 * factor).round() as u32).max(1);
+
+            let (width, height) = (size(image.width), size(image.height));
+            if (width, height) != (image.width, image.height) {
+                image.resize(width, height, self.scale_filter, self.linear_filtering);
+                scaled += 1;
+            }
+        }
+        scaled
+    }
+
+    // Read the attribution of every input from the sidecars under `root`, after the credits of
+    // appended sprites. Returns how many inputs have an attribution.
+    fn collect_credits(&mut self, root: &Path) -> Result<usize, String> {
+        let mut sprites: Vec<(String, Attribution)> = self.credits.drain(..)
+            .flat_map(|credit| credit.sprites.into_iter().map(move |name| (name, credit.attribution.clone())))
+            .collect();
+
+        let mut read: HashMap<PathBuf, Attribution> = HashMap::new();
+        let mut attributed = 0;
+        for image in self.images.iter() {
+            let file = match credits::sidecar(&image.path, root) {
+                Some(file) => file,
+                None => continue
+            };
+
+            if !read.contains_key(&file) {
+                let attribution = Attribution::read(&file).map_err(|err| format!("{:?} {}", file, err))?;
+                read.insert(file.clone(), attribution);
+            }
+            sprites.push((image.name.clone(), read[&file].clone()));
+            attributed += 1;
+        }
+
+        self.credits = credits::group(sprites);
+        Ok(attributed)
+    }
+
+    // Keep only the first image in path order for each name, returning the kept and dropped paths
+    fn drop_duplicate_names(&mut self) -> Vec<(String, PathBuf, Vec<PathBuf>)> {
+        let duplicates: Vec<(String, PathBuf, Vec<PathBuf>)> = self.duplicate_names().into_iter()
+            .map(|(name, mut paths)| {
+                let kept = paths.remove(0);
+                (name, kept, paths)
+            })
+            .collect();
+
+        self.images.retain(|img| {
+            !duplicates.iter().any(|(_, _, dropped)| dropped.contains(&img.path))
+        });
+
+        duplicates
+    }
+
+    // Names shared by several images with their paths, sorting the images by path
+    fn duplicate_names(&mut self) -> Vec<(String, Vec<PathBuf>)> {
+        self.images.sort_by(|a, b| a.path.cmp(&b.path));
+
+        let mut by_name: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+        for image in self.images.iter() {
+            by_name.entry(image.name.clone()).or_default().push(image.path.clone());
+        }
+
+        by_name.into_iter()
+            .filter(|(_, paths)| paths.len() > 1)
+            .collect()
+    }
+
+    // Give the image at `path` the first free name of the form "<stem>~2.<ext>", returning it
+    fn rename_image(&mut self, path: &Path) -> String {
+        let image = self.images.iter().position(|img| img.path == path).unwrap();
+        let name = &self.images[image].name;
+        let (stem, ext) = name.rsplit_once('.').map_or((name.as_str(), None), |(stem, ext)| (stem, Some(ext)));
+
+        let renamed = (2..)
+            .map(|n| match ext {
+                Some(ext) => format!("{}~{}.{}", stem, n, ext),
+                None => format!("{}~{}", stem, n)
+            })
+            .find(|candidate| !self.images.iter().any(|img| img.name == *candidate))
+            .unwrap();
+
+        self.images[image].name = renamed.clone();
+        renamed
+    }
+
+    // Images exceeding the size limits, largest first
+    fn oversized(&self) -> Vec<&Image> {
+        let total: u64 = self.images.iter().map(|img| img.area() as u64).sum();
+
+        let mut oversized: Vec<&Image> = self.images.iter()
+            .filter(|img| {
+                let share = img.area() as f64 / total as f64;
+                img.width > self.max_sprite_size || img.height > self.max_sprite_size ||
+                    self.max_sprite_share.is_some_and(|max| share > max)
+            })
+            .collect();
+
+        oversized.sort_by_key(|img| std::cmp::Reverse(img.area()));
+        oversized
+    }
+
+    // Largest sprite size that fits on a page, extrusion included, None where there is no limit
+    fn sprite_limits(&self) -> (Option<u32>, Option<u32>) {
+        let width = self.page_size.or(self.size_limits().0);
+        (width.map(|width| width.saturating_sub(2 * self.extrude)), self.page_limit().map(|height| height.saturating_sub(2 * self.extrude)))
+    }
+
+    // Whether the image fits on no page of the maximum size, turned or not
+    fn exceeds_limits(&self, image: &Image) -> bool {
+        let (max_width, max_height) = self.sprite_limits();
+        let fits = |width: u32, height: u32| max_width.is_none_or(|max| width <= max) && max_height.is_none_or(|max| height <= max);
+        !(fits(image.width, image.height) || self.may_turn(image) && fits(image.height, image.width))
+    }
+
+    // Scale the images that exceed the limits down to the largest size that fits, keeping their
+    // aspect ratio. Returns their paths with the sizes before and after.
+    fn downscale_oversized(&mut self) -> Vec<Downscaled> {
+        let (max_width, max_height) = self.sprite_limits();
+        let (max_width, max_height) = (max_width.unwrap_or(u32::MAX) as f64, max_height.unwrap_or(u32::MAX) as f64);
+        let scale = |width: u32, height: u32| (max_width / width as f64).min(max_height / height as f64);
+
+        let mut scaled = Vec::new();
+        for i in 0..self.images.len() {
+            if !self.exceeds_limits(&self.images[i]) {
+                continue;
+            }
+
+            let turnable = self.may_turn(&self.images[i]);
+            let image = &mut self.images[i];
+            let mut factor = scale(image.width, image.height);
+            if turnable {
+                factor = factor.max(scale(image.height, image.width));
+            }
+            let from = (image.width, image.height);
+            let to = (((from.0 as f64 * factor) as u32).max(1), ((from.1 as f64 * factor) as u32).max(1));
+            image.resize(to.0, to.1, self.scale_filter, self.linear_filtering);
+            scaled.push((image.path.clone(), from, to));
+        }
+        scaled
+    }
+
+    // Take out the images that exceed the limits, to be packed on their own
+    fn split_oversized(&mut self) -> Vec<Image> {
+        let (oversized, images) = std::mem::take(&mut self.images).into_iter().partition(|image| self.exceeds_limits(image));
+        self.images = images;
+        oversized
+    }
+
+    // Pixels of the written texture(s), counting padding of the last page
+    fn output_pixels(&self) -> u64 {
+        let (width, height) = self.texture_size();
+        let pages = self.page_height().map_or(1, |page| self.height().div_ceil(page).max(1));
+        width as u64 * height as u64 * pages as u64
+    }
+
+    fn stats(&self, output: &str, output_bytes: u64, timings: Timings) -> Stats {
+        let height = self.height();
+        let page_height = self.page_height().unwrap_or(height).max(1);
+        let page_count = height.div_ceil(page_height).max(1);
+        let (texture_width, texture_height) = self.texture_size();
+
+        let mut pages: Vec<PageStats> = (0..page_count)
+            .map(|_| PageStats {
+                sprite_pixels: 0,
+                total_pixels: texture_width as u64 * texture_height as u64,
+                fill: 0.0
+            })
+            .collect();
+        for rect in self.records.iter() {
+            pages[(rect.y / page_height) as usize].sprite_pixels += rect.width as u64 * rect.height as u64;
+        }
+        for page in pages.iter_mut() {
+            page.fill = page.sprite_pixels as f64 / page.total_pixels.max(1) as f64;
+        }
+
+        let sprite_pixels = pages.iter().map(|page| page.sprite_pixels).sum();
+        let total_pixels: u64 = pages.iter().map(|page| page.total_pixels).sum();
+        Stats {
+            output: output.to_string(),
+            sprites: self.sprite_count(),
+            width: texture_width,
+            height: texture_height,
+            pages,
+            sprite_pixels,
+            wasted_pixels: total_pixels.saturating_sub(sprite_pixels),
+            trim_saved_pixels: self.trimmed_pixels(),
+            output_bytes,
+            timings_ms: timings
+        }
+    }
+
+    // Fold images with identical pixels into the first of them, which is recorded under the names of
+    // all of them. Returns how many images were folded.
+    pub fn dedupe(&mut self) -> usize {
+        let before = self.images.len();
+        let identical = |a: &Image, b: &Image| {
+            a.data == b.data && a.source_size == b.source_size && a.offset == b.offset && a.color_chunks == b.color_chunks
+                && a.variants == b.variants
+        };
+
+        let mut first: HashMap<(u32, u32, u64), usize> = HashMap::new();
+        let mut kept: Vec<Image> = Vec::with_capacity(before);
+        for image in std::mem::take(&mut self.images) {
+            let mut hasher = DefaultHasher::new();
+            image.data.hash(&mut hasher);
+            let key = (image.width, image.height, hasher.finish());
+
+            match first.get(&key) {
+                Some(&index) if identical(&kept[index], &image) => {
+                    let original = &mut kept[index];
+                    original.priority = original.priority.max(image.priority);
+                    original.aliases.push(image.name);
+                    original.aliases.extend(image.aliases);
+                }
+                _ => {
+                    first.entry(key).or_insert(kept.len());
+                    kept.push(image);
+                }
+            }
+        }
+
+        self.images = kept;
+        before - self.images.len()
+    }
+
+    // Sprites recorded in the atlas, counting every name of a folded image
+    pub fn sprite_count(&self) -> usize {
+        self.images.iter().map(|image| 1 + image.aliases.len()).sum()
+    }
+
+    // Trim the transparent borders off every sprite, returning how many pixels that saved
+    pub fn trim(&mut self) -> u64 {
+        for image in self.images.iter_mut() {
+            image.trim();
+        }
+        self.trimmed_pixels()
+    }
+
+    fn trimmed_pixels(&self) -> u64 {
+        self.images.iter()
+            .map(|img| img.source_size.0 as u64 * img.source_size.1 as u64 - img.area() as u64)
+            .sum()
+    }
+
+    // The `count` sprites taking the most area, largest first, with their percentage of the sprite area
+    fn largest(&self, count: usize) -> Vec<(&Image, f64)> {
+        let total: u64 = self.images.iter().map(|img| img.area() as u64).sum();
+
+        let mut largest: Vec<&Image> = self.images.iter().collect();
+        largest.sort_by_key(|img| std::cmp::Reverse(img.area()));
+        largest.into_iter()
+            .take(count)
+            .map(|img| (img, 100.0 * img.area() as f64 / total.max(1) as f64))
+            .collect()
+    }
+
+    // Every image has to fit horizontally, beyond that aim for a roughly square atlas
+    fn pick_width(&self) -> u32 {
+        let widest = self.widest();
+
+        match self.fixed_width {
+            Some(width) if width >= widest => width,
+            Some(width) => {
+                warn!("requested width {} is narrower than the widest image, using {}", width, widest);
+                widest
+            }
+            None => {
+                let area: u64 = self.images.iter()
+                    .map(|img| (img.width + self.gutter()) as u64 * (img.height + self.gutter()) as u64)
+                    .sum();
+                widest.max((area as f64).sqrt().ceil() as u32)
+            }
+        }
+    }
+
+    // Whether the packer may turn `image`, which normal maps never are
+    fn may_turn(&self, image: &Image) -> bool {
+        self.allow_rotation && !image.holds_normals()
+    }
+
+    // Narrowest width every image fits across, sprites that may be turned with their shorter side
+    fn widest(&self) -> u32 {
+        self.images.iter()
+            .map(|img| if self.may_turn(img) { img.width.min(img.height) } else { img.width } + 2 * self.extrude)
+            .max().unwrap_or(0)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.records.iter()
+            .map(|rect| rect.y+rect.height+self.extrude)
+            .max().unwrap_or(0)
+    }
+
+    // Columns reached by the sprites and their extrusion
+    fn used_width(&self) -> u32 {
+        self.records.iter()
+            .map(|rect| rect.x+rect.width+self.extrude)
+            .max().unwrap_or(0)
+    }
+
+    // Largest width and page height, rounded down so that the texture still fits them once rounded
+    // up for --pot and --square
+    fn size_limits(&self) -> (Option<u32>, Option<u32>) {
+        let floor = |size: u32| if self.pot { 1 << size.ilog2() } else { size };
+        let (width, height) = (self.max_width.map(floor), self.max_height.map(floor));
+        if self.square {
+            let side = width.into_iter().chain(height).min();
+            (side, side)
+        } else {
+            (width, height)
+        }
+    }
+
+    // Height of the pages sprites are placed on, None when the atlas grows as tall as needed
+    fn page_limit(&self) -> Option<u32> {
+        self.page_size.or(self.size_limits().1)
+    }
+
+    // Height of the pages the atlas is written as, None for a single image. Only atlases taller
+    // than the maximum height are spilled onto pages.
+    fn page_height(&self) -> Option<u32> {
+        match self.size_limits().1 {
+            Some(max) if self.page_size.is_none() && self.height() > max => Some(max),
+            _ => self.page_size
+        }
+    }
+
+    // Size of the written texture, or of every page, with the transparent margin --pot and
+    // --square add to the right and bottom. An empty atlas still needs a pixel to be a valid png.
+    pub fn texture_size(&self) -> (u32, u32) {
+        let mut width = self.width.max(1);
+        let mut height = self.page_height().unwrap_or_else(|| self.height()).max(1);
+        if self.pot {
+            width = width.next_power_of_two();
+            height = height.next_power_of_two();
+        }
+        if self.square {
+            width = width.max(height);
+            height = width;
+        }
+        (width, height)
+    }
+
+    fn page_records(&self, pages: u32) -> Vec<PageRecord> {
+        if self.page_size.is_some() {
+            (0..pages).map(PageRecord::udim).collect()
+        } else {
+            (0..pages).map(PageRecord::numbered).collect()
+        }
+    }
+
+    // Pixels reserved across and down each sprite besides its own
+    fn gutter(&self) -> u32 {
+        2 * self.extrude + self.padding
+    }
+
+    // Fail for sprites that fit on no page or are wider than the atlas, extrusion included
+    fn check_size(&self, image: &Image) -> Result<(), PackError> {
+        let fail = |constraint| PackError {
+            sprite: image.name.clone(),
+            constraint
+        };
+        let (width, height) = (image.width + 2 * self.extrude, image.height + 2 * self.extrude);
+
+        if let Some(page) = self.page_limit() {
+            let fits = |width, height| width <= self.width && height <= page;
+            let turned_fits = self.may_turn(image) && fits(height, width);
+            if !fits(width, height) && !turned_fits {
+                return Err(fail(Constraint::Page { size: (width, height), page: (self.width, page) }));
+            }
+        }
+
+        let turned_fits = self.may_turn(image) && height <= self.width;
+        if width > self.width && !turned_fits {
+            return Err(fail(Constraint::Width { width, atlas_width: self.width }));
+        }
+
+        Ok(())
+    }
+
+    // First free position for `image` placed as a `width` x `height` rect including its padding, which must fit
+    // the atlas width. With `outlines`, the row spans of the placed sprites and of this one, rects may overlap
+    // as long as the spans stay apart.
+    fn next_slot(&self, image: &Image, width: u32, height: u32, outlines: Option<(&[Spans], &Spans)>,
+                 started: Instant, iterations: &mut u64) -> Result<Rect, PackError> {
+        let fail = |constraint| PackError {
+            sprite: image.name.clone(),
+            constraint
+        };
+
+        let mut pos = Rect {
+            x: 0,
+            y: 0,
+            width,
+            height
+        };
+
+        // Pages are stacked top to bottom, no sprite may straddle two of them, only its padding may
+        let straddles = |pos: &Rect| self.page_limit().is_some_and(|page| pos.y % page + pos.height > page + self.padding);
+
+        let taken = |pos: &Rect| self.records.iter().enumerate().any(|(i, rect)| {
+            rect.intersects(pos) && outlines.is_none_or(|(placed, spans)| {
+                polygon::overlap(&placed[i], (rect.x, rect.y), spans, (pos.x, pos.y), self.padding)
+            })
+        });
+
+        while taken(&pos) || pos.x+pos.width > self.width + self.padding || straddles(&pos) {
+            *iterations += 1;
+            if *iterations > self.max_iterations {
+                return Err(fail(Constraint::Iterations(self.max_iterations)));
+            }
+
+            // Checking the clock on every step would dominate the loop
+            if iterations.is_multiple_of(4096) {
+                if let Some(limit) = self.time_limit {
+                    if started.elapsed() > limit {
+                        return Err(fail(Constraint::Time(limit)));
+                    }
+                }
+            }
+
+            if pos.x == self.width-1 {
+                pos.x = 0;
+                pos.y += 1;
+            } else {
+                pos.x += 1;
+            }
+        }
+
+        Ok(pos)
+    }
+
+    // Composite all images into an RGBA buffer, returning its width and height
+    fn compose(&self) -> (u32, u32, Vec<u8>) {
+        self.compose_images(None)
+    }
+
+    // Composite the images, or their variants for the suffix at `variant`, into an RGBA buffer
+    fn compose_images(&self, variant: Option<usize>) -> (u32, u32, Vec<u8>) {
+        // Width and height of the buffer, pages are padded to their full height when written
+        let (width, height) = match self.page_height() {
+            Some(_) => (self.texture_size().0, self.height().max(1)),
+            None => self.texture_size()
+        };
+
+        // Rects placed around outlines overlap, the transparent pixels of one must not cover another
+        let overlapping = self.polygons.is_some() && self.packer == Packer::Scan;
+
+        // Buffer holding the composited RGBA atlas, or that of the previous build with the same
+        // layout when only some sprites changed
+        let canvas = self.cache.as_ref()
+            .filter(|_| variant.is_none() && !overlapping)
+            .and_then(|cache| cache.canvas(width, height, &self.records));
+        let (unchanged, mut pixels) = match canvas {
+            Some((sprites, pixels)) => (sprites, pixels),
+            None => (&[][..], vec![0; (width * 4 * height) as usize])
+        };
+
+        // Read all the images into the pixel buffer with proper placement
+        for (i, (image, rect)) in self.images.iter().zip(self.records.iter()).enumerate() {
+            // Sprites without the variant leave their rect transparent
+            let image = match variant {
+                Some(variant) => match image.variants.get(variant).and_then(Option::as_ref) {
+                    Some(image) => image,
+                    None => continue
+                },
+                None => image
+            };
+            if unchanged.get(i).is_some_and(|hash| *hash == Cache::hash_pixels(&image.data)) {
+                continue;
+            }
+
+            for row in 0..image.height {
+                for col in 0..image.width {
+                    let img_index = ((row * image.width + col) * 4) as usize;
+                    if overlapping && image.data[img_index + 3] == 0 {
+                        continue;
+                    }
+                    let buf_index = (((row+rect.y) * width + (col+rect.x))*4) as usize;
+                    pixels[buf_index..buf_index+4].copy_from_slice(&image.data[img_index..img_index+4]);
+                }
+            }
+
+            // Repeat the nearest edge pixel into the extrusion around the sprite
+            let extrude = self.extrude as i64;
+            for row in -extrude..(image.height as i64 + extrude) {
+                for col in -extrude..(image.width as i64 + extrude) {
+                    let inside = (0..image.height as i64).contains(&row) && (0..image.width as i64).contains(&col);
+                    let (x, y) = (rect.x as i64 + col, rect.y as i64 + row);
+                    if inside || x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+                        continue;
+                    }
+
+                    let src_row = row.clamp(0, image.height as i64 - 1);
+                    let src_col = col.clamp(0, image.width as i64 - 1);
+                    let img_index = ((src_row * image.width as i64 + src_col) * 4) as usize;
+                    let buf_index = ((y * width as i64 + x) * 4) as usize;
+                    pixels[buf_index..buf_index+4].copy_from_slice(&image.data[img_index..img_index+4]);
+                }
+            }
+        }
+
+        (width, height, pixels)
+    }
+
+    // Number of stacked layers, their height and number of pages of an atlas composed `height` tall
+    fn layout(&self, height: u32) -> (u32, u32, u32) {
+        // Layers are stacked in the composed image, which is exactly how KTX2 lays out an array level
+        let cube_faces = self.cubemap == Some(CubeLayout::Ktx2);
+        let (layers, layer_height) = match self.images.first() {
+            Some(image) if self.texture_array || cube_faces => (self.images.len() as u32, image.height),
+            _ => (0, height)
+        };
+
+        let pages = match self.page_height() {
+            Some(page) if !self.images.is_empty() => height.div_ceil(page),
+            _ => 0
+        };
+
+        (layers, layer_height, pages)
+    }
+
+    // Where every packed sprite ended up, as written to the atlas data
+    pub fn records(&self) -> Vec<AtlasRecord> {
+        let (layers, layer_height, pages) = self.layout(self.height().max(1));
+        let data_height = if pages > 0 { self.page_height().unwrap() } else { layer_height };
+
+        self.records.iter().zip(self.images.iter())
+            .flat_map(|(rect, image)| {
+                // Every stacked layer or page starts over at y = 0
+                let index = if layers > 0 || pages > 0 { rect.y / data_height } else { 0 };
+                let record = AtlasRecord {
+                    x: rect.x,
+                    y: rect.y - index * data_height,
+                    width: rect.width,
+                    height: rect.height,
+                    name: image.name.clone(),
+                    layer: if layers > 0 { index } else { 0 },
+                    page: if pages > 0 { index } else { 0 },
+                    rotated: image.rotated,
+                    source_width: image.source_size.0,
+                    source_height: image.source_size.1,
+                    offset_x: image.offset.0,
+                    offset_y: image.offset.1,
+                    nine_slice: image.nine_slice,
+                    pivot_x: image.pivot.unwrap_or(self.pivot).0,
+                    pivot_y: image.pivot.unwrap_or(self.pivot).1,
+                    user_data: image.user_data.clone(),
+                    polygon: image.outline.clone()
+                };
+
+                // Folded duplicates share the rect
+                let aliases: Vec<AtlasRecord> = image.aliases.iter()
+                    .map(|name| AtlasRecord { name: name.clone(), ..record.clone() })
+                    .collect();
+                std::iter::once(record).chain(aliases)
+            })
+            .collect()
+    }
+
+    pub fn write<P: AsRef<Path>>(&mut self, path: P) -> Result<(), AtlasError> {
+        // The archive is built in memory so it can be encrypted as a whole
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+        let (width, height, mut pixels) = self.compose();
+        if let Some(cache) = &mut self.cache {
+            let sprites = self.images.iter().map(|image| Cache::hash_pixels(&image.data)).collect();
+            cache.remember_canvas(width, height, &self.records, sprites, pixels.clone());
+        }
+        let (layers, layer_height, pages) = self.layout(height);
+        let cube_faces = self.cubemap == Some(CubeLayout::Ktx2);
+
+        // Variants are written next to every page as "<page><suffix>"
+        let mut variants: Vec<(&str, Vec<u8>)> = self.variant_suffixes.iter()
+            .enumerate()
+            .map(|(i, suffix)| (suffix.as_str(), self.compose_images(Some(i)).2))
+            .collect();
+
+        // Sprite owning every texel, which the mip levels are filtered and colors bled within
+        let mut owners = match self.mipmaps || self.alpha_bleed {
+            true => mipmap::owners(width, height, &self.records, self.extrude),
+            false => Vec::new()
+        };
+        if self.alpha_bleed {
+            bleed::bleed(width, height, &mut pixels, &owners);
+        }
+
+        let mut tile_table = None;
+        if pages > 0 {
+            // The last page is padded to the full page size
+            let page_height = self.page_height().unwrap();
+            let page_bytes = (width * page_height * 4) as usize;
+            pixels.resize(page_bytes * pages as usize, 0);
+            for (_, pixels) in variants.iter_mut() {
+                pixels.resize(page_bytes * pages as usize, 0);
+            }
+            if self.mipmaps {
+                owners.resize(page_bytes / 4 * pages as usize, 0);
+            }
+
+            for (i, record) in self.page_records(pages).iter().enumerate() {
+                let size = (width, page_height);
+                let page_owners = owners.get(i * page_bytes / 4..(i + 1) * page_bytes / 4).unwrap_or_default();
+                let page = pixels[i * page_bytes..(i + 1) * page_bytes].to_vec();
+                self.write_image(&mut zip, &record.stem, size, self.mip_levels(page, page_owners, size, 1, None), (0, 1), None)?;
+                for (variant, (suffix, pixels)) in variants.iter().enumerate() {
+                    let stem = format!("{}{}", record.stem, suffix);
+                    let page = pixels[i * page_bytes..(i + 1) * page_bytes].to_vec();
+                    self.write_image(&mut zip, &stem, size, self.mip_levels(page, page_owners, size, 1, Some(variant)), (0, 1), Some(variant))?;
+                }
+            }
+        } else if let Some(size) = self.tile_size {
+            let (table, entries) = tiles::split(&pixels, width, height, size);
+            for (entry, tile) in entries.into_iter().enumerate() {
+                self.write_image(&mut zip, &TileTable::stem(entry as u32), (size, size), vec![tile], (0, 1), None)?;
+            }
+            tile_table = Some(table);
+        } else {
+            // A single cube is a plain cubemap, several make a cubemap array
+            let (array_layers, faces) = if cube_faces {
+                (if layers > 6 { layers / 6 } else { 0 }, 6)
+            } else {
+                (layers, 1)
+            };
+            let size = (width, layer_height);
+            let levels = self.mip_levels(pixels, &owners, size, layers, None);
+            self.write_image(&mut zip, "atlas", size, levels, (array_layers, faces), None)?;
+            for (variant, (suffix, pixels)) in variants.into_iter().enumerate() {
+                let stem = format!("atlas{}", suffix);
+                let levels = self.mip_levels(pixels, &owners, size, layers, Some(variant));
+                self.write_image(&mut zip, &stem, size, levels, (array_layers, faces), Some(variant))?;
+            }
+        }
+
+        zip.start_file(reader::VERSION_ENTRY, entry_options())?;
+        zip.write_all(format!("{}\n", reader::FORMAT_VERSION).as_bytes())?;
+
+        // Create zip file for atlas metadata
+        let data_height = if pages > 0 { self.page_height().unwrap() } else { layer_height };
+        let records = self.records();
+        // Aseprite tags take precedence over numbered sprites grouped under the same name
+        let mut animations = self.tags.clone();
+        if let Some(pattern) = &self.animations {
+            for grouped in animation::group(&records, pattern, &self.frame_durations) {
+                if !animations.iter().any(|animation| animation.name == grouped.name) {
+                    animations.push(grouped);
+                }
+            }
+        }
+        animations.sort_by(|a, b| a.name.cmp(&b.name));
+        let data = AtlasData {
+            records,
+            width,
+            height: data_height,
+            fonts: self.fonts.clone(),
+            layers,
+            cubes: self.cubes.clone(),
+            pages: self.page_records(pages),
+            tiles: tile_table,
+            credits: self.credits.clone(),
+            premultiplied_alpha: self.premultiply_alpha,
+            animations
+        };
+
+        // Engine formats are written next to atlast's own data, which the other commands read
+        match self.data_format {
+            DataFormat::Godot => {
+                for (name, text) in godot::resources(&data, self.image_extension()).map_err(AtlasError::Write)? {
+                    zip.start_file(name, entry_options())?;
+                    zip.write_all(text.as_bytes())?;
+                }
+            }
+            DataFormat::Libgdx => {
+                zip.start_file(libgdx::ENTRY, entry_options())?;
+                zip.write_all(libgdx::write(&data, self.image_extension()).as_bytes())?;
+            }
+            _ => {}
+        }
+
+        let (entry, bytes) = match self.data_format {
+            DataFormat::Bincode | DataFormat::Godot | DataFormat::Libgdx | DataFormat::Rust => (reader::DATA_ENTRY, serialize(&data)?),
+            DataFormat::Json if self.pretty => (reader::JSON_ENTRY, serde_json::to_vec_pretty(&data)?),
+            DataFormat::Json => (reader::JSON_ENTRY, serde_json::to_vec(&data)?),
+            DataFormat::TpJsonHash | DataFormat::TpJsonArray => {
+                let image = format!("atlas.{}", self.image_extension());
+                let array = self.data_format == DataFormat::TpJsonArray;
+                (reader::JSON_ENTRY, texturepacker::write(&data, &image, self.texels().name(), array, self.pretty)?)
+            }
+        };
+        zip.start_file(entry, entry_options())?;
+        zip.write_all(&bytes)?;
+        let archive = integrity::seal(zip.finish()?.into_inner())?;
+        let archive = match &self.key {
+            Some(key) => crypt::encrypt(key, &archive),
+            None => archive
+        };
+        std::fs::write(&path, archive).map_err(|err| AtlasError::Io(path.as_ref().to_path_buf(), err))?;
+
+        // sprites.atlas gets sprites_gen.rs
+        if self.data_format == DataFormat::Rust {
+            let path = path.as_ref();
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let module = path.with_file_name(format!("{}_gen.rs", stem));
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            std::fs::write(&module, codegen::rust(&data, &name)).map_err(|err| AtlasError::Io(module, err))?;
+        }
+        Ok(())
+    }
+
+    // `pixels` followed by its mip levels with --mipmaps, each of the `images` of `size` stacked in it
+    // reduced on its own. `owners` tells their sprites apart, and `variant` whether the pixels are
+    // those of the sprites or of a variant.
+    fn mip_levels(&self, pixels: Vec<u8>, owners: &[u32], size: (u32, u32), images: u32, variant: Option<usize>) -> Vec<Vec<u8>> {
+        if !self.mipmaps {
+            return vec![pixels];
+        }
+
+        let normals: Vec<bool> = self.images.iter()
+            .map(|image| match variant {
+                Some(variant) => image.variants.get(variant).and_then(Option::as_ref).is_some_and(|variant| variant.normal_map),
+                None => image.normal_map
+            })
+            .collect();
+        // Variants hold data rather than colors
+        let linear = self.linear_filtering && variant.is_none();
+        let texels = (size.0 * size.1) as usize;
+        let chains: Vec<Vec<Vec<u8>>> = (0..images.max(1) as usize)
+            .map(|i| mipmap::chain(size, &pixels[i * texels * 4..(i + 1) * texels * 4], &owners[i * texels..(i + 1) * texels], &normals, linear))
+            .collect();
+
+        let mut levels = vec![pixels];
+        for level in 0..mipmap::level_count(size) - 1 {
+            levels.push(chains.iter().flat_map(|chain| chain[level].iter().copied()).collect());
+        }
+        levels
+    }
+
+    // Extension of the written images, which engine formats name next to their stems
+    fn image_extension(&self) -> &'static str {
+        match self.texture_format {
+            TextureFormat::Ktx2 => "ktx2",
+            TextureFormat::Dds => "dds",
+            TextureFormat::Basis => "basis",
+            TextureFormat::Png if self.pixel_format == PixelFormat::Rgba8888 => "png",
+            TextureFormat::Png => self.pixel_format.name()
+        }
+    }
+
+    // How the written images store their texels, compression only applies to KTX2 and DDS
+    fn texels(&self) -> Texels {
+        match self.compression {
+            _ if self.texture_format == TextureFormat::Basis => Texels::Basis,
+            Some(compression) if self.texture_format != TextureFormat::Png => Texels::Blocks(compression),
+            _ => Texels::Pixels(self.pixel_format)
+        }
+    }
+
+    // Write one `size` image as <stem>.png, <stem>.ktx2, <stem>.dds, <stem>.basis or raw <stem>.<format>
+    // depending on the output settings. `levels` holds its pixels and any mip levels below, written into the container or as
+    // <stem>_mip1.png, <stem>_mip2.png, ... `stack` holds the KTX2 array layers and faces, which follow
+    // each other in every level, and `variant` the suffix index when the levels hold variants rather
+    // than the sprites. Only the sprites themselves are premultiplied, variants hold data rather than colors.
+    fn write_image<W: Write + io::Seek>(&self, zip: &mut ZipWriter<W>, stem: &str, size: (u32, u32), mut levels: Vec<Vec<u8>>,
+                                        stack: (u32, u32), variant: Option<usize>) -> Result<(), AtlasError> {
+        let (layers, faces) = stack;
+        // Mip levels average by alpha, which matches averaging premultiplied colors, so every level
+        // can be premultiplied on its own
+        if self.premultiply_alpha && variant.is_none() {
+            levels.iter_mut().for_each(|level| premultiply(level));
+        }
+        let images: Vec<&Image> = match variant {
+            Some(variant) => self.images.iter().filter_map(|image| image.variants.get(variant)?.as_ref()).collect(),
+            None => self.images.iter().collect()
+        };
+        let level_stem = |level: usize| if level == 0 { stem.to_string() } else { format!("{}_mip{}", stem, level) };
+
+        let texels = self.texels();
+        if texels == Texels::Pixels(PixelFormat::Rgba8888) && self.texture_format == TextureFormat::Png {
+            // Single channel inputs get a single channel atlas
+            let grayscale = !images.is_empty() && images.iter().all(|img| img.grayscale);
+
+            let mut chunks = None;
+            if self.color_chunks == ChunkPolicy::Preserve && !images.is_empty() {
+                if images.iter().all(|img| img.color_chunks == images[0].color_chunks) {
+                    chunks = Some(&images[0].color_chunks);
+                } else {
+                    warn!("inputs carry differing color chunks, stripping them from the atlas");
+                }
+            }
+
+            for (level, mut pixels) in levels.into_iter().enumerate() {
+                let (width, height) = mipmap::level_size(size, level);
+
+                // Buffer that the png encoder writes to
+                let mut file_buffer = Vec::with_capacity((width*4*height) as usize);
+
+                {
+                    let w = Cursor::new(&mut file_buffer);
+
+                    // Png encoder
+                    let mut encoder = png::Encoder::new(w, width, height);
+                    if grayscale {
+                        encoder.set_color(png::ColorType::Grayscale);
+                    } else {
+                        encoder.set_color(png::ColorType::RGBA);
+                    }
+                    encoder.set_depth(png::BitDepth::Eight);
+                    let mut writer = encoder.write_header()?;
+
+                    if let Some(chunks) = chunks {
+                        chunks.write(&mut writer)?;
+                    }
+
+                    // Keep only one channel per pixel for grayscale atlases
+                    if grayscale {
+                        pixels = pixels.chunks(4).map(|px| px[0]).collect();
+                    }
+
+                    // Write the pixels into their encoded format in the file buffer
+                    writer.write_image_data(&pixels)?;
+                }
+
+                // Finally, write the file buffer into the zip file
+                zip.start_file(format!("{}.png", level_stem(level)), entry_options())?;
+                zip.write_all(&file_buffer)?;
+            }
+        } else {
+            let levels: Vec<Vec<u8>> = levels.into_iter()
+                .enumerate()
+                .map(|(level, pixels)| {
+                    let (width, height) = mipmap::level_size(size, level);
+                    texels.encode(&pixels, width, height, layers.max(1) * faces, self.dither)
+                })
+                .collect();
+
+            match self.texture_format {
+                TextureFormat::Ktx2 => {
+                    zip.start_file(format!("{}.ktx2", stem), entry_options())?;
+                    ktx2::write(zip, texels, size.0, size.1, layers, faces, &levels)?;
+                }
+                TextureFormat::Dds => {
+                    zip.start_file(format!("{}.dds", stem), entry_options())?;
+                    dds::write(zip, texels, size.0, size.1, layers, faces, &levels)?;
+                }
+                TextureFormat::Basis => {
+                    zip.start_file(format!("{}.basis", stem), entry_options())?;
+                    basis::write(zip, size.0, size.1, layers.max(1) * faces, &levels)?;
+                }
+                TextureFormat::Png => {
+                    for (level, data) in levels.iter().enumerate() {
+                        zip.start_file(format!("{}.{}", level_stem(level), self.pixel_format.name()), entry_options())?;
+                        zip.write_all(data)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Re-read a written atlas and compare every sprite against its source pixels
+    fn verify(&self, path: &str) -> Result<Vec<String>, ReadError> {
+        let mut problems = Vec::new();
+        match reader::verify(Path::new(path), self.key.as_ref()) {
+            Err(ReadError::Tampered(mismatches)) => problems.extend(mismatches),
+            result => result?
+        }
+
+        let file = reader::read(Path::new(path), self.key.as_ref())?;
+
+        if file.data.records.len() != self.sprite_count() {
+            problems.push(format!("expected {} records, found {}", self.sprite_count(), file.data.records.len()));
+        }
+
+        // Reduced formats only have to match within their quantization error, and block compressed
+        // and Basis ones can't be compared pixel by pixel at all
+        let bits = match file.texels {
+            Texels::Pixels(format) => format.bits(),
+            Texels::Blocks(_) | Texels::Basis => [0; 4]
+        };
+        let tolerance: Vec<Option<i32>> = bits.iter()
+            .map(|&bits| match bits {
+                0 => None,
+                8 => Some(0),
+                bits => Some(2 * 255 / ((1 << bits) - 1))
+            })
+            .collect();
+
+        for record in file.data.records.iter() {
+            let image = match self.images.iter().find(|img| img.name == record.name || img.aliases.contains(&record.name)) {
+                Some(image) => image,
+                None => {
+                    problems.push(format!("{}: no such source image", record.name));
+                    continue;
+                }
+            };
+
+            if record.rotated != image.rotated {
+                problems.push(format!("{}: recorded as {}rotated", record.name, if record.rotated { "" } else { "not " }));
+                continue;
+            }
+
+            if (record.width, record.height) != (image.width, image.height) {
+                problems.push(format!("{}: recorded as {}x{} but the source is {}x{}",
+                    record.name, record.width, record.height, image.width, image.height));
+                continue;
+            }
+
+            if record.x + record.width > file.width || record.y + record.height > file.height {
+                problems.push(format!("{}: rect lies outside the {}x{} atlas", record.name, file.width, file.height));
+                continue;
+            }
+
+            if record.layer >= file.layers.max(1) {
+                problems.push(format!("{}: layer {} lies outside the {} layer atlas", record.name, record.layer, file.layers));
+                continue;
+            }
+
+            if record.page >= file.pages.max(1) {
+                problems.push(format!("{}: page {} lies outside the {} page atlas", record.name, record.page, file.pages));
+                continue;
+            }
+
+            // Layers and pages are stacked top to bottom in the decoded pixels, only one of them is ever used
+            let top = (record.layer + record.page) * file.height + record.y;
+            let source = match file.data.premultiplied_alpha {
+                true => {
+                    let mut data = image.data.clone();
+                    premultiply(&mut data);
+                    Cow::Owned(data)
+                }
+                false => Cow::Borrowed(&image.data)
+            };
+
+            let mut mismatched = 0;
+            for row in 0..record.height {
+                for col in 0..record.width {
+                    let img_index = ((row * image.width + col) * 4) as usize;
+                    let atlas_index = (((row + top) * file.width + (col + record.x)) * 4) as usize;
+
+                    // Bled colors replace those of fully transparent texels
+                    let channels = if self.alpha_bleed && source[img_index + 3] == 0 { 3..4 } else { 0..4 };
+                    let differs = channels.into_iter().any(|c| match tolerance[c] {
+                        Some(max) => (file.pixels[atlas_index + c] as i32 - source[img_index + c] as i32).abs() > max,
+                        None => false
+                    });
+
+                    if differs {
+                        mismatched += 1;
+                    }
+                }
+            }
+
+            if mismatched > 0 {
+                problems.push(format!("{}: {} pixel(s) differ from the source", record.name, mismatched));
+            }
+        }
+
+        Ok(problems)
+    }
+
+    // Write the atlas with every rect outlined in its own color to `path`, and the sprite of each
+    // color to a legend beside it, returning the legend's path
+    fn debug_preview(&self, path: &str) -> Result<PathBuf, AtlasError> {
+        let (width, _, mut pixels) = self.compose();
+        let sprites: Vec<(&str, &Rect, (u32, u32))> = self.images.iter().zip(self.records.iter())
+            .map(|(image, rect)| (image.name.as_str(), rect, image.source_size))
+            .collect();
+        for (i, (_, rect, _)) in sprites.iter().enumerate() {
+            preview::outline(&mut pixels, width, rect, preview::color(i));
+        }
+
+        let height = (pixels.len() / (width.max(1) * 4) as usize) as u32;
+        write_png(path, width, height, &pixels)?;
+        let legend = Path::new(path).with_extension("txt");
+        std::fs::write(&legend, preview::legend(&sprites)).map_err(|err| AtlasError::Io(legend.clone(), err))?;
+        Ok(legend)
+    }
+
+    // Render every sprite with bilinear filtering, returning those that pull in color from their
+    // neighbors, worst first. With `out` the contaminated samples are marked on a copy of the atlas
+    // written there.
+    fn seam_test(&self, out: Option<&str>) -> Result<Vec<SeamReport>, AtlasError> {
+        let (width, height, pixels) = self.compose();
+        let sprites: Vec<(&str, &Rect)> = self.images.iter().zip(self.records.iter())
+            .map(|(image, rect)| (image.name.as_str(), rect))
+            .collect();
+
+        let mut marks = out.map(|_| pixels.clone());
+        let mut reports = seam::test(&pixels, width, height, &sprites, marks.as_mut());
+
+        reports.retain(|report| report.contaminated > 0);
+        reports.sort_by_key(|report| std::cmp::Reverse(report.max_error));
+
+        if let (Some(path), Some(marks)) = (out, marks) {
+            write_png(path, width, height, &marks)?;
+        }
+        Ok(reports)
+    }
+}
+
+impl Default for Atlas {
+    fn default() -> Atlas {
+        Atlas::new()
+    }
+}
+
+// Settings of an atlas packed from code, anything not set is the command line default
+#[derive(Default)]
+pub struct AtlasBuilder {
+    atlas: Atlas
+}
+
+impl AtlasBuilder {
+    pub fn new() -> AtlasBuilder {
+        AtlasBuilder::default()
+    }
+
+    // Fixed atlas width, otherwise picked when packing
+    pub fn width(mut self, width: u32) -> AtlasBuilder {
+        self.atlas.fixed_width = Some(width);
+        self
+    }
+
+    pub fn packer(mut self, packer: Packer) -> AtlasBuilder {
+        self.atlas.packer = packer;
+        self
+    }
+
+    pub fn heuristic(mut self, heuristic: Heuristic) -> AtlasBuilder {
+        self.atlas.heuristic = heuristic;
+        self
+    }
+
+    pub fn sort(mut self, sort: SortOrder) -> AtlasBuilder {
+        self.atlas.sort = sort;
+        self
+    }
+
+    pub fn allow_rotation(mut self, allow_rotation: bool) -> AtlasBuilder {
+        self.atlas.allow_rotation = allow_rotation;
+        self
+    }
+
+    pub fn padding(mut self, padding: u32) -> AtlasBuilder {
+        self.atlas.padding = padding;
+        self
+    }
+
+    pub fn extrude(mut self, extrude: u32) -> AtlasBuilder {
+        self.atlas.extrude = extrude;
+        self
+    }
+
+    // Split the atlas into square UDIM pages of this size
+    pub fn page_size(mut self, page_size: u32) -> AtlasBuilder {
+        self.atlas.page_size = Some(page_size);
+        self
+    }
+
+    // Largest atlas width, narrower atlases are picked when the sprites allow
+    pub fn max_width(mut self, max_width: u32) -> AtlasBuilder {
+        self.atlas.max_width = Some(max_width);
+        self
+    }
+
+    // Spill sprites onto further pages of this height rather than growing the atlas taller
+    pub fn max_height(mut self, max_height: u32) -> AtlasBuilder {
+        self.atlas.max_height = Some(max_height);
+        self
+    }
+
+    // Round the texture up to power-of-two dimensions
+    pub fn pot(mut self, pot: bool) -> AtlasBuilder {
+        self.atlas.pot = pot;
+        self
+    }
+
+    // Make the texture as tall as it is wide
+    pub fn square(mut self, square: bool) -> AtlasBuilder {
+        self.atlas.square = square;
+        self
+    }
+
+    // Trace an outline polygon around the opaque pixels of every sprite, straying at most `tolerance`
+    // pixels from them
+    pub fn polygons(mut self, tolerance: u32) -> AtlasBuilder {
+        self.atlas.polygons = Some(tolerance);
+        self
+    }
+
+    // Pivot of every sprite without a sidecar, as a fraction of its source from the top left corner
+    pub fn pivot(mut self, x: f32, y: f32) -> AtlasBuilder {
+        self.atlas.pivot = (x, y);
+        self
+    }
+
+    pub fn pixel_format(mut self, pixel_format: PixelFormat) -> AtlasBuilder {
+        self.atlas.pixel_format = pixel_format;
+        self
+    }
+
+    pub fn texture_format(mut self, texture_format: TextureFormat) -> AtlasBuilder {
+        self.atlas.texture_format = texture_format;
+        self
+    }
+
+    // Only applies to KTX2 and DDS textures
+    pub fn compression(mut self, compression: Compression) -> AtlasBuilder {
+        self.atlas.compression = Some(compression);
+        self
+    }
+
+    pub fn data_format(mut self, data_format: DataFormat) -> AtlasBuilder {
+        self.atlas.data_format = data_format;
+        self
+    }
+
+    pub fn pretty(mut self, pretty: bool) -> AtlasBuilder {
+        self.atlas.pretty = pretty;
+        self
+    }
+
+    pub fn max_iterations(mut self, max_iterations: u64) -> AtlasBuilder {
+        self.atlas.max_iterations = max_iterations;
+        self
+    }
+
+    pub fn time_limit(mut self, time_limit: Duration) -> AtlasBuilder {
+        self.atlas.time_limit = Some(time_limit);
+        self
+    }
+
+    pub fn build(self) -> Atlas {
+        self.atlas
+    }
+
+    // Pack every image under `asset_dir` into the atlas at `output`
+    pub fn pack(self, asset_dir: &Path, output: &Path) -> Result<Vec<AtlasRecord>, AtlasError> {
+        let mut atlas = self.build();
+        atlas.add_dir(asset_dir)?;
+        atlas.pack()?;
+        atlas.write(output)?;
+        Ok(atlas.records())
+    }
+}
+
+// Pack every image under `asset_dir` into the atlas at `output` with the default settings
+pub fn pack(asset_dir: &Path, output: &Path) -> Result<Vec<AtlasRecord>, AtlasError> {
+    AtlasBuilder::new().pack(asset_dir, output)
+}
+
+// Write an RGBA buffer to a standalone png file
+fn write_png(path: &str, width: u32, height: u32, rgba: &[u8]) -> Result<(), AtlasError> {
+    let file = File::create(path).map_err(|err| AtlasError::Io(PathBuf::from(path), err))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(rgba)?;
+    Ok(())
+}
+
+// Sprite name without the extension of its file, "ui/button.png" is "ui/button"
+fn strip_extension(name: &str) -> &str {
+    let file_start = name.rfind('/').map_or(0, |slash| slash + 1);
+    match name[file_start..].rfind('.') {
+        Some(dot) if dot > 0 => &name[..file_start + dot],
+        _ => name
+    }
+}
+
+// Fixed options for every archive entry so identical inputs give byte-identical archives
+fn entry_options() -> FileOptions {
+    FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .last_modified_time(DateTime::default())
+        .unix_permissions(0o644)
+}
+
+// Formats packed besides png, decoded with the image crate
+const IMAGE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "bmp", "tga", "gif"];
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.iter().any(|known| ext.eq_ignore_ascii_case(known)))
+}
+
+fn is_aseprite(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| aseprite::EXTENSIONS.iter().any(|known| ext.eq_ignore_ascii_case(known)))
+}
+
+// Decode a non-png image to RGBA, returning its size, pixels, whether it was a single gray
+// channel and whether it was gray at all (with or without alpha). Gifs use their first frame.
+fn decode_image(path: &Path, bytes: &[u8]) -> Result<(u32, u32, Vec<u8>, bool, bool), png::DecodingError> {
+    let format = image::ImageFormat::from_path(path).map_err(|err| err.to_string())?;
+    let decoded = image::load_from_memory_with_format(bytes, format).map_err(|err| err.to_string())?;
+
+    if decoded.width() == 0 || decoded.height() == 0 {
+        return Err(format!("zero-size image ({}x{})", decoded.width(), decoded.height()).into());
+    }
+
+    let color = decoded.color();
+    let grayscale = matches!(color, image::ColorType::L8 | image::ColorType::L16);
+    let heightmap = grayscale || matches!(color, image::ColorType::La8 | image::ColorType::La16);
+    let rgba = decoded.into_rgba8();
+    Ok((rgba.width(), rgba.height(), rgba.into_raw(), grayscale, heightmap))
+}
+
+// Png decoder turning every image into 8-bit gray, gray and alpha, RGB or RGBA for `to_rgba`, whatever
+// its color type and bit depth: palettes, bit depths below 8 and tRNS transparency are expanded and
+// 16-bit samples keep their high byte
+fn png_decoder(bytes: &[u8]) -> png::Decoder<&[u8]> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    decoder
+}
+
+// Expand png data from `png_decoder` into RGBA, `color_type` being the decoder's output color type
+fn to_rgba(buf: Vec<u8>, color_type: png::ColorType) -> Vec<u8> {
+    match color_type {
+        png::ColorType::RGBA => buf,
+        png::ColorType::RGB => buf.chunks(3)
+            .flat_map(|px| [px[0], px[1], px[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buf.chunks(2)
+            .flat_map(|px| [px[0], px[0], px[0], px[1]])
+            .collect(),
+        png::ColorType::Grayscale => buf.iter()
+            .flat_map(|&px| [px, px, px, 255])
+            .collect(),
+        // The decoder expands palettes
+        png::ColorType::Indexed => unreachable!()
+    }
+}
+
+// Multiply the color of every RGBA pixel by its alpha, rounding to nearest
+fn premultiply(rgba: &mut [u8]) {
+    for px in rgba.chunks_mut(4) {
+        let alpha = px[3] as u32;
+        for channel in px[..3].iter_mut() {
+            *channel = ((*channel as u32 * alpha + 127) / 255) as u8;
+        }
+    }
+}
+
+// Divide the color of every premultiplied RGBA pixel by its alpha, the inverse of premultiply
+fn unpremultiply(rgba: &mut [u8]) {
+    for px in rgba.chunks_mut(4).filter(|px| px[3] != 0) {
+        let alpha = px[3] as u32;
+        for channel in px[..3].iter_mut() {
+            *channel = ((*channel as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn rect(x: u32, y: u32, width: u32, height: u32) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    #[test]
+    fn crossing_rects_intersect() {
+        let wide = rect(0, 4, 10, 2);
+        let tall = rect(4, 0, 2, 10);
+        assert!(wide.intersects(&tall));
+        assert!(tall.intersects(&wide));
+    }
+
+    #[test]
+    fn contained_rect_intersects() {
+        let outer = rect(0, 0, 10, 10);
+        let inner = rect(2, 2, 3, 3);
+        assert!(outer.intersects(&inner));
+        assert!(inner.intersects(&outer));
+    }
+
+    #[test]
+    fn adjacent_rects_do_not_intersect() {
+        let a = rect(0, 0, 4, 4);
+        assert!(!a.intersects(&rect(4, 0, 4, 4)));
+        assert!(!a.intersects(&rect(0, 4, 4, 4)));
+        assert!(!a.intersects(&rect(4, 4, 4, 4)));
+        assert!(a.intersects(&rect(3, 3, 4, 4)));
+    }
+
+    fn image(width: u32, height: u32) -> Image {
+        Image {
+            name: format!("{}x{}", width, height),
+            path: PathBuf::new(),
+            width,
+            height,
+            data: vec![0; (width * height * 4) as usize],
+            grayscale: false,
+            color_chunks: ColorChunks::default(),
+            priority: 0,
+            rotated: false,
+            source_size: (width, height),
+            offset: (0, 0),
+            nine_slice: None,
+            pivot: None,
+            user_data: None,
+            outline: None,
+            aliases: Vec::new(),
+            variants: Vec::new(),
+            normal_map: false
+        }
+    }
+
+    // Data of a 16x16 atlas holding nothing but `records`
+    fn data(records: Vec<AtlasRecord>) -> AtlasData {
+        AtlasData {
+            records,
+            width: 16,
+            height: 16,
+            fonts: Vec::new(),
+            layers: 0,
+            cubes: Vec::new(),
+            pages: Vec::new(),
+            tiles: None,
+            credits: Vec::new(),
+            premultiplied_alpha: false,
+            animations: Vec::new()
+        }
+    }
+
+    fn packed(sizes: &[(u32, u32)], fixed_width: Option<u32>) -> Atlas {
+        let mut atlas = Atlas::new();
+        atlas.fixed_width = fixed_width;
+        for &(width, height) in sizes {
+            atlas.images.push(image(width, height));
+        }
+        atlas.pack().unwrap();
+        atlas
+    }
+
+    #[test]
+    fn max_height_spills_onto_numbered_pages() {
+        let mut atlas = AtlasBuilder::new().max_width(16).max_height(16).build();
+        for _ in 0..5 {
+            atlas.images.push(image(8, 8));
+        }
+        atlas.pack().unwrap();
+
+        assert_eq!(atlas.page_height(), Some(16));
+        let records = atlas.records();
+        assert_eq!(records.iter().map(|record| record.page).max(), Some(1));
+        assert!(records.iter().all(|record| record.x + record.width <= 16 && record.y + record.height <= 16));
+        let stems: Vec<String> = atlas.page_records(2).into_iter().map(|page| page.stem).collect();
+        assert_eq!(stems, ["atlas_0", "atlas_1"]);
+
+        // Sprites fitting under the maximum height stay a single image
+        let mut atlas = AtlasBuilder::new().max_height(64).build();
+        atlas.images.push(image(8, 8));
+        atlas.pack().unwrap();
+        assert_eq!(atlas.page_height(), None);
+    }
+
+    #[test]
+    fn dedupe_records_identical_images_at_one_rect() {
+        let mut atlas = Atlas::new();
+        for name in ["a", "b", "c"] {
+            atlas.images.push(Image { name: name.to_string(), ..image(4, 4) });
+        }
+        let mut other = image(4, 4);
+        other.data[3] = 255;
+        atlas.images.push(other);
+
+        assert_eq!(atlas.dedupe(), 2);
+        atlas.pack().unwrap();
+        assert_eq!(atlas.sprite_count(), 4);
+
+        let records = atlas.records();
+        let rect = |name: &str| records.iter().find(|record| record.name == name).map(|record| (record.x, record.y)).unwrap();
+        assert_eq!(rect("a"), rect("b"));
+        assert_eq!(rect("a"), rect("c"));
+        assert_ne!(rect("a"), rect("4x4"));
+    }
+
+    #[test]
+    fn unreadable_images_name_the_file() {
+        let path = std::env::temp_dir().join(format!("atlast-{}-broken.png", std::process::id()));
+        std::fs::write(&path, b"not a png").unwrap();
+        let mut atlas = Atlas::new();
+        let err = atlas.add_image(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(err, AtlasError::Decode(..)), "{:?}", err);
+        assert_eq!(err.path(), Some(path.as_path()));
+
+        let missing = path.with_file_name("atlast-missing.png");
+        assert!(matches!(atlas.add_image(&missing), Err(AtlasError::Io(..))));
+        assert!(atlas.images.is_empty());
+    }
+
+    #[test]
+    fn png_inputs_of_every_color_type_decode_to_rgba8() {
+        use png::{BitDepth, ColorType};
+
+        // Two pixels of every color type and bit depth, with the palette and tRNS chunk they need
+        let palette = vec![255, 0, 0, 0, 0, 255];
+        let cases = vec![
+            (ColorType::Grayscale, BitDepth::One, vec![0b1000_0000], None, None, [255u8, 255, 255, 255, 0, 0, 0, 255]),
+            (ColorType::Grayscale, BitDepth::Four, vec![0x3c], None, None, [51, 51, 51, 255, 204, 204, 204, 255]),
+            (ColorType::Grayscale, BitDepth::Eight, vec![10, 200], None, None, [10, 10, 10, 255, 200, 200, 200, 255]),
+            (ColorType::Grayscale, BitDepth::Sixteen, vec![0x12, 0x34, 0xab, 0xcd], None, None, [18, 18, 18, 255, 171, 171, 171, 255]),
+            (ColorType::Grayscale, BitDepth::Eight, vec![10, 200], None, Some(vec![0, 10]), [10, 10, 10, 0, 200, 200, 200, 255]),
+            (ColorType::GrayscaleAlpha, BitDepth::Eight, vec![10, 128, 200, 255], None, None, [10, 10, 10, 128, 200, 200, 200, 255]),
+            (ColorType::GrayscaleAlpha, BitDepth::Sixteen, vec![0x12, 0x34, 0x80, 0x00, 0xab, 0xcd, 0xff, 0xff], None, None,
+             [18, 18, 18, 128, 171, 171, 171, 255]),
+            (ColorType::RGB, BitDepth::Eight, vec![1, 2, 3, 4, 5, 6], None, None, [1, 2, 3, 255, 4, 5, 6, 255]),
+            (ColorType::RGB, BitDepth::Sixteen, vec![0x12, 0, 0x34, 0, 0x56, 0, 0xab, 0, 0xcd, 0, 0xef, 0], None, None,
+             [18, 52, 86, 255, 171, 205, 239, 255]),
+            (ColorType::RGB, BitDepth::Eight, vec![1, 2, 3, 4, 5, 6], None, Some(vec![0, 1, 0, 2, 0, 3]), [1, 2, 3, 0, 4, 5, 6, 255]),
+            (ColorType::RGBA, BitDepth::Eight, vec![1, 2, 3, 4, 5, 6, 7, 8], None, None, [1, 2, 3, 4, 5, 6, 7, 8]),
+            (ColorType::RGBA, BitDepth::Sixteen, vec![0x12, 0, 0x34, 0, 0x56, 0, 0x80, 0, 0xab, 0, 0xcd, 0, 0xef, 0, 0xff, 0xff], None, None,
+             [18, 52, 86, 128, 171, 205, 239, 255]),
+            (ColorType::Indexed, BitDepth::Eight, vec![1, 0], Some(palette.clone()), None, [0, 0, 255, 255, 255, 0, 0, 255]),
+            (ColorType::Indexed, BitDepth::Two, vec![0b0001_0000], Some(palette.clone()), None, [255, 0, 0, 255, 0, 0, 255, 255]),
+            (ColorType::Indexed, BitDepth::Eight, vec![1, 0], Some(palette.clone()), Some(vec![255, 64]), [0, 0, 255, 64, 255, 0, 0, 255])
+        ];
+
+        let path = std::env::temp_dir().join(format!("atlast-{}-color-type.png", std::process::id()));
+        for (color, depth, data, palette, trns, expected) in cases {
+            let mut bytes = Vec::new();
+            {
+                let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+                encoder.set_color(color);
+                encoder.set_depth(depth);
+                if let Some(palette) = palette {
+                    encoder.set_palette(palette);
+                }
+                if let Some(trns) = trns.clone() {
+                    encoder.set_trns(trns);
+                }
+                encoder.write_header().unwrap().write_image_data(&data).unwrap();
+            }
+            std::fs::write(&path, &bytes).unwrap();
+
+            let mut atlas = Atlas::new();
+            atlas.add_image(&path).unwrap();
+            let image = &atlas.images[0];
+            assert_eq!((image.width, image.height), (2, 1));
+            assert_eq!(image.data, expected, "{:?} at {:?} bits, tRNS {:?}", color, depth, trns);
+        }
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn add_images_keeps_the_given_order() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-order", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths: Vec<PathBuf> = (1..=8).map(|n| dir.join(format!("{}.png", n))).collect();
+        for (n, path) in paths.iter().enumerate() {
+            write_png(path.to_str().unwrap(), n as u32 + 1, 2, &vec![255; (n + 1) * 2 * 4]).unwrap();
+        }
+        let mut inputs = paths.clone();
+        inputs.insert(3, dir.join("missing.png"));
+
+        let mut atlas = Atlas::new();
+        let failed = atlas.add_images(&inputs);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].path(), Some(dir.join("missing.png").as_path()));
+        assert_eq!(atlas.images.iter().map(|img| img.width).collect::<Vec<_>>(), (1..=8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn sprites_are_named_by_their_path_under_the_directory() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-names", std::process::id()));
+        for file in ["enemies/slime.png", "ui/slime.png", "top.png"] {
+            std::fs::create_dir_all(dir.join(file).parent().unwrap()).unwrap();
+            write_png(dir.join(file).to_str().unwrap(), 2, 2, &[255; 16]).unwrap();
+        }
+
+        let mut atlas = Atlas::new();
+        atlas.add_dir(&dir).unwrap();
+        let mut names: Vec<&str> = atlas.images.iter().map(|img| img.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, ["enemies/slime.png", "top.png", "ui/slime.png"]);
+
+        atlas.name_style = NameStyle::Stem;
+        assert_eq!(atlas.sprite_name(&dir.join("ui").join("slime.png")), ("slime".to_string(), false));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn godot_resources_reach_the_atlas_from_their_directory() {
+        let record = |name: &str, source_width| AtlasRecord {
+            x: 4, y: 2, width: 6, height: 5, name: name.to_string(), layer: 0, page: 0, rotated: false,
+            source_width, source_height: 5, offset_x: 1, offset_y: 0, nine_slice: None, pivot_x: 0.5, pivot_y: 0.5, user_data: None, polygon: None
+        };
+        let data = data(vec![record("ui/icons/gem.png", 8), record("top", 6)]);
+
+        let resources = godot::resources(&data, "png").unwrap();
+        assert_eq!(resources[0].0, "ui/icons/gem.tres");
+        assert!(resources[0].1.contains("path=\"../../atlas.png\""));
+        assert!(resources[0].1.contains("region = Rect2(4, 2, 6, 5)\nmargin = Rect2(1, 0, 2, 0)\n"));
+        assert_eq!(resources[1].0, "top.tres");
+        assert!(resources[1].1.contains("path=\"atlas.png\"") && !resources[1].1.contains("margin"));
+        assert!(godot::resources(&data, "ktx2").unwrap()[1].1.contains("path=\"atlas.ktx2\""));
+
+        let clash = AtlasData { records: vec![record("a.png", 6), record("a.jpg", 6)], ..data };
+        assert!(godot::resources(&clash, "png").is_err());
+    }
+
+    #[test]
+    fn libgdx_offsets_count_from_the_bottom() {
+        let data = data(vec![AtlasRecord {
+                x: 4, y: 2, width: 6, height: 5, name: "ui/gem.png".to_string(), layer: 0, page: 0, rotated: false,
+                source_width: 8, source_height: 9, offset_x: 1, offset_y: 3, nine_slice: None, pivot_x: 0.5, pivot_y: 0.5, user_data: None, polygon: None
+            }]);
+
+        let text = libgdx::write(&data, "png");
+        assert!(text.starts_with("\natlas.png\nsize: 16, 16\n"));
+        assert!(libgdx::write(&data, "dds").starts_with("\natlas.dds\n"));
+        assert!(text.contains("\nui/gem\n  rotate: false\n  xy: 4, 2\n  size: 6, 5\n  orig: 8, 9\n  offset: 1, 1\n  index: -1\n"));
+    }
+
+    #[test]
+    fn rust_constants_are_unique_and_sorted() {
+        let record = |name: &str, x| AtlasRecord {
+            x, y: 0, width: 2, height: 2, name: name.to_string(), layer: 0, page: 0, rotated: false,
+            source_width: 2, source_height: 2, offset_x: 0, offset_y: 0, nine_slice: None, pivot_x: 0.5, pivot_y: 0.5, user_data: None, polygon: None
+        };
+        let data = AtlasData { width: 8, height: 2, ..data(vec![record("ui/play-button.png", 0), record("9lives.png", 2), record("ui/play_button.png", 4)]) };
+
+        let text = codegen::rust(&data, "sprites.atlas");
+        assert!(text.contains("pub const _9LIVES: SpriteRect = SpriteRect { x: 2, y: 0, w: 2, h: 2, page: 0, rotated: false };"));
+        assert!(text.contains("pub const UI_PLAY_BUTTON: SpriteRect = SpriteRect { x: 0,"));
+        assert!(text.contains("pub const UI_PLAY_BUTTON_2: SpriteRect = SpriteRect { x: 4,"));
+        assert!(text.contains("[(&str, SpriteRect); 3] = [\n    (\"9lives.png\", _9LIVES),\n    (\"ui/play-button.png\", UI_PLAY_BUTTON),\n"));
+    }
+
+    #[test]
+    fn cache_keeps_the_layout_and_recomposes_changed_sprites() {
+        let cache = std::env::temp_dir().join(format!("atlast-{}-layout.cache", std::process::id()));
+        let output = cache.with_extension("atlas");
+        let build = |fill: u8, cached: bool| {
+            let mut atlas = Atlas::new();
+            if cached {
+                atlas.cache = Some(Cache::open(&cache, 0));
+            }
+            for (width, height) in [(8, 4), (3, 9), (5, 5)] {
+                atlas.images.push(image(width, height));
+            }
+            atlas.images[1].data.fill(fill);
+            atlas.pack().unwrap();
+            atlas
+        };
+
+        let mut first = build(1, true);
+        first.write(&output).unwrap();
+        first.cache.take().unwrap().save().unwrap();
+
+        let second = build(2, true);
+        assert!(second.cache.as_ref().unwrap().kept_layout);
+        assert_eq!(second.records, first.records);
+        assert_eq!(second.compose(), build(2, false).compose());
+
+        std::fs::remove_file(&cache).unwrap();
+        std::fs::remove_file(&output).unwrap();
+    }
+
+    #[test]
+    fn largest_sprites_come_first_with_their_share() {
+        let atlas = packed(&[(2, 2), (4, 4), (2, 6)], None);
+        let largest: Vec<(String, f64)> = atlas.largest(2).into_iter().map(|(img, share)| (img.name.clone(), share)).collect();
+        assert_eq!(largest, [("4x4".to_string(), 50.0), ("2x6".to_string(), 37.5)]);
+    }
+
+    #[test]
+    fn seam_test_reports_neighbors_bleeding_in() {
+        let sprites = |extrude| {
+            let mut atlas = AtlasBuilder::new().width(8).extrude(extrude).build();
+            atlas.add(Image::new("white", 4, 4, vec![255; 4 * 4 * 4]).unwrap());
+            atlas.add(Image::new("black", 4, 4, [0, 0, 0, 255].repeat(4 * 4)).unwrap());
+            atlas.pack().unwrap();
+            atlas.seam_test(None).unwrap().into_iter().map(|report| report.name).collect::<Vec<_>>()
+        };
+        assert_eq!(sprites(0).len(), 2);
+        assert!(sprites(2).is_empty());
+    }
+
+    #[test]
+    fn pot_and_square_round_the_texture_size() {
+        let mut atlas = AtlasBuilder::new().width(20).pot(true).build();
+        atlas.images.push(image(20, 5));
+        atlas.pack().unwrap();
+        assert_eq!(atlas.texture_size(), (32, 8));
+
+        atlas.square = true;
+        assert_eq!(atlas.texture_size(), (32, 32));
+        assert_eq!(atlas.compose().2.len(), 32 * 32 * 4);
+
+        // Limits are rounded down so the rounded up pages fit them
+        let mut atlas = AtlasBuilder::new().max_width(50).max_height(40).pot(true).square(true).build();
+        for _ in 0..3 {
+            atlas.images.push(image(20, 20));
+        }
+        atlas.pack().unwrap();
+        assert_eq!(atlas.page_height(), Some(32));
+        assert_eq!(atlas.texture_size(), (32, 32));
+    }
+
+    fn assert_valid_layout(atlas: &Atlas) {
+        for (i, a) in atlas.records.iter().enumerate() {
+            assert!(a.x + a.width <= atlas.width, "{:?} exceeds width {}", a, atlas.width);
+            for b in atlas.records.iter().skip(i + 1) {
+                assert!(!a.intersects(b), "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn square_sprites_pack_square() {
+        let atlas = packed(&[(8, 8); 16], None);
+        assert_valid_layout(&atlas);
+        assert_eq!((atlas.width, atlas.height()), (32, 32));
+    }
+
+    #[test]
+    fn tall_sprites_share_rows() {
+        let atlas = packed(&[(2, 50); 10], None);
+        assert_valid_layout(&atlas);
+        assert_eq!(atlas.height(), 50);
+    }
+
+    #[test]
+    fn wide_sprite_sets_width() {
+        let mut sizes = vec![(100, 2)];
+        sizes.extend([(4, 4); 10]);
+        let atlas = packed(&sizes, None);
+        assert_valid_layout(&atlas);
+        assert_eq!((atlas.width, atlas.height()), (100, 6));
+    }
+
+    #[test]
+    fn mixed_sprites_pack_without_overlap() {
+        let atlas = packed(&[(40, 3), (3, 40), (10, 10), (1, 1), (25, 7), (7, 25), (16, 16)], None);
+        assert_valid_layout(&atlas);
+        assert!(atlas.height() < 40 + 25 + 16);
+    }
+
+    #[test]
+    fn fixed_width_is_respected() {
+        let atlas = packed(&[(8, 8); 16], Some(64));
+        assert_valid_layout(&atlas);
+        assert_eq!((atlas.width, atlas.height()), (64, 16));
+    }
+
+    #[test]
+    fn fixed_width_grows_to_widest_sprite() {
+        let atlas = packed(&[(30, 4), (4, 4)], Some(16));
+        assert_valid_layout(&atlas);
+        assert_eq!(atlas.width, 30);
+    }
+
+    #[test]
+    fn watchdog_stops_placement_and_names_the_sprite() {
+        let sprites = |count: usize| {
+            let mut atlas = Atlas::new();
+            for i in 0..count {
+                atlas.images.push(Image { name: format!("sprite{}", i), ..image(2, 2) });
+            }
+            atlas
+        };
+
+        let mut atlas = sprites(5);
+        atlas.max_iterations = 2;
+        let err = atlas.pack().unwrap_err();
+        assert!(matches!(err, AtlasError::Pack(PackError { constraint: Constraint::Iterations(2), .. })));
+        assert!(err.to_string().starts_with("could not place sprite"), "{}", err);
+
+        // The clock is only looked at every few thousand steps
+        let mut atlas = sprites(400);
+        atlas.time_limit = Some(Duration::ZERO);
+        let err = atlas.pack().unwrap_err();
+        assert_eq!(err.to_string().split(": ").nth(1), Some("packing exceeded the 0ns time limit"));
+
+        let mut atlas = sprites(5);
+        atlas.time_limit = Some(Duration::from_secs(60));
+        atlas.pack().unwrap();
+        assert_valid_layout(&atlas);
+    }
+
+    #[test]
+    fn rotation_turns_pixels_clockwise() {
+        let mut img = image(2, 2);
+        img.data = [1, 2, 3, 4].iter().flat_map(|&v| [v; 4]).collect();
+        img.rotate();
+        assert!(img.rotated);
+        assert_eq!(img.data.chunks(4).map(|px| px[0]).collect::<Vec<_>>(), [3, 1, 4, 2]);
+    }
+
+    #[test]
+    fn rotation_fits_wide_sprites_in_a_narrow_atlas() {
+        let mut atlas = Atlas::new();
+        atlas.fixed_width = Some(10);
+        atlas.allow_rotation = true;
+        atlas.images.push(image(30, 4));
+        atlas.pack().unwrap();
+        assert_eq!(atlas.width, 10);
+        assert_eq!((atlas.records[0].width, atlas.records[0].height), (4, 30));
+        assert!(atlas.images[0].rotated);
+    }
+
+    #[test]
+    fn linear_filtering_keeps_the_brightness() {
+        // Half black and half white is half as bright, 188 in sRGB rather than 128
+        let mut checker = image(2, 1);
+        checker.data = vec![0, 0, 0, 255, 255, 255, 255, 255];
+        for (linear, gray) in [(false, 128), (true, 188)] {
+            let mut resized = checker.clone();
+            resized.resize(1, 1, FilterType::Triangle, linear);
+            assert_eq!(resized.data, [gray, gray, gray, 255]);
+            assert_eq!(mipmap::chain((2, 1), &checker.data, &[1, 1], &[], linear)[0], [gray, gray, gray, 255]);
+        }
+    }
+
+    #[test]
+    fn normal_maps_stay_upright_and_unit_length() {
+        // Normals tilted 45° left and right average to one pointing straight out, but shortened
+        let mut normals = image(2, 1);
+        normals.data = vec![37, 128, 218, 255, 218, 128, 218, 255];
+        normals.normal_map = true;
+        let mut resized = normals.clone();
+        resized.resize(1, 1, FilterType::Triangle, true);
+        assert_eq!(resized.data, [128, 128, 255, 255]);
+        let levels = mipmap::chain((2, 1), &normals.data, &[1, 1], &[true], true);
+        assert_eq!(levels[0], [128, 128, 255, 255]);
+        assert_eq!(mipmap::chain((2, 1), &normals.data, &[1, 1], &[false], false)[0][2], 218);
+
+        let mut atlas = Atlas::new();
+        atlas.fixed_width = Some(10);
+        atlas.allow_rotation = true;
+        atlas.images.push(image(30, 4));
+        atlas.images[0].normal_map = true;
+        atlas.pack().unwrap();
+        assert_eq!((atlas.width, atlas.records[0].width), (30, 30));
+        assert!(!atlas.images[0].rotated);
+    }
+
+    #[test]
+    fn variants_are_placed_at_the_rect_of_their_sprite() {
+        let mut atlas = Atlas::new();
+        atlas.fixed_width = Some(4);
+        atlas.allow_rotation = true;
+        atlas.variant_suffixes = vec!["_n".to_string()];
+        for (name, fill) in [("hero.png", 1), ("hero_n.png", 2), ("rock_n.png", 3)] {
+            let mut sprite = image(8, 2);
+            sprite.name = name.to_string();
+            sprite.path = Path::new("sprites").join(name);
+            sprite.data.fill(fill);
+            atlas.images.push(sprite);
+        }
+
+        let dropped = atlas.attach_variants();
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].0, Path::new("sprites/rock_n.png"));
+
+        atlas.pack().unwrap();
+        assert_eq!((atlas.records[0].width, atlas.records[0].height), (2, 8));
+        assert!(atlas.images[0].variants[0].as_ref().is_some_and(|variant| variant.rotated));
+        let (_, _, pixels) = atlas.compose_images(Some(0));
+        assert_eq!(pixels.iter().filter(|&&value| value == 2).count(), 2 * 8 * 4);
+    }
+
+    #[test]
+    fn trim_crops_transparent_borders() {
+        let mut img = image(4, 3);
+        for x in [1, 2] {
+            img.data[((4 + x) * 4 + 3) as usize] = 255;
+        }
+        img.trim();
+        assert_eq!((img.width, img.height, img.offset, img.source_size), (2, 1, (1, 1), (4, 3)));
+        assert!(img.data.chunks(4).all(|px| px[3] == 255));
+
+        let mut empty = image(5, 5);
+        empty.trim();
+        assert_eq!((empty.width, empty.height, empty.offset), (1, 1, (0, 0)));
+    }
+
+    #[test]
+    fn unpack_restores_rotated_and_trimmed_sprites() {
+        let mut sources = Vec::new();
+        for (width, height) in [(30, 4), (6, 5)] {
+            let mut sprite = image(width, height);
+            sprite.name = format!("{}x{}.png", width, height);
+            for (i, px) in sprite.data.chunks_mut(4).enumerate() {
+                px.copy_from_slice(&[i as u8, 7, 9, 255]);
+            }
+            sources.push(sprite);
+        }
+        // Transparent border around the second sprite
+        for (i, px) in sources[1].data.chunks_mut(4).enumerate() {
+            if i % 6 == 0 || i < 6 {
+                px.copy_from_slice(&[0; 4]);
+            }
+        }
+
+        let mut atlas = Atlas::new();
+        atlas.fixed_width = Some(10);
+        atlas.allow_rotation = true;
+        atlas.images = sources.clone();
+        atlas.trim();
+        atlas.pack().unwrap();
+        assert!(atlas.images.iter().any(|image| image.rotated));
+
+        let path = std::env::temp_dir().join(format!("atlast-{}-unpack.atlas", std::process::id()));
+        atlas.write(&path).unwrap();
+        let file = reader::read(&path, None).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let sprites = unpack::extract(&file).unwrap();
+        assert_eq!(sprites.len(), 2);
+        for source in sources.iter() {
+            let sprite = sprites.iter().find(|sprite| sprite.name == source.name).unwrap();
+            assert_eq!((sprite.width, sprite.height), (source.width, source.height));
+            assert!(sprite.data == source.data, "{} differs", source.name);
+        }
+    }
+
+    #[test]
+    fn inspect_counts_shared_rects_once() {
+        let mut atlas = Atlas::new();
+        atlas.fixed_width = Some(8);
+        for name in ["a", "b"] {
+            let mut sprite = image(4, 4);
+            sprite.name = name.to_string();
+            atlas.images.push(sprite);
+        }
+        atlas.dedupe();
+        atlas.pack().unwrap();
+
+        let data = AtlasData { width: 8, height: 4, ..data(atlas.records()) };
+        let inspection = inspect::inspect(&data);
+        assert_eq!(inspection.sprites.len(), 2);
+        assert_eq!(inspection.sprites[1].uv, [0.0, 0.0, 0.5, 1.0]);
+        assert_eq!((inspection.sprite_pixels, inspection.total_pixels), (16, 32));
+        assert_eq!(inspection.wasted_percent, 50.0);
+    }
+
+    #[test]
+    fn loaded_atlases_find_sprites_by_name() {
+        let output = std::env::temp_dir().join(format!("atlast-{}-loaded.atlas", std::process::id()));
+        let mut atlas = AtlasBuilder::new().width(8).build();
+        atlas.add(Image::new("a", 4, 4, vec![255; 4 * 4 * 4]).unwrap());
+        atlas.add(Image::new("b", 4, 2, vec![128; 4 * 2 * 4]).unwrap());
+        assert!(Image::new("c", 4, 4, vec![0; 4 * 2 * 4]).is_err_and(|err| err.path() == Some(Path::new("c"))));
+        atlas.pack().unwrap();
+        atlas.write(&output).unwrap();
+
+        let bytes = std::fs::read(&output).unwrap();
+        let loaded = LoadedAtlas::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.pixels().len(), (loaded.width() * loaded.height() * 4) as usize);
+        let b = loaded.get("b").unwrap();
+        let [u0, v0, u1, v1] = loaded.get_uv("b").unwrap();
+        assert_eq!((u0, v0), (b.x as f32 / loaded.width() as f32, b.y as f32 / loaded.height() as f32));
+        assert_eq!((u1 - u0, v1 - v0), (4.0 / loaded.width() as f32, 2.0 / loaded.height() as f32));
+        assert!(loaded.get_uv("c").is_none());
+        assert_eq!(load(&output).unwrap().iter().map(|(record, _)| record.name.clone()).collect::<Vec<_>>(), ["a", "b"]);
+        std::fs::remove_file(&output).ok();
+    }
+
+    #[test]
+    fn archives_do_not_depend_on_the_order_images_are_added_in() {
+        let output = std::env::temp_dir().join(format!("atlast-{}-order.atlas", std::process::id()));
+        let build = |names: [&str; 3]| {
+            let mut atlas = AtlasBuilder::new().width(8).build();
+            for name in names {
+                atlas.add(Image::new(name, 4, 4, vec![name.as_bytes()[0]; 4 * 4 * 4]).unwrap());
+            }
+            atlas.pack().unwrap();
+            atlas.write(&output).unwrap();
+            std::fs::read(&output).unwrap()
+        };
+
+        assert_eq!(build(["a", "b", "c"]), build(["c", "a", "b"]));
+        std::fs::remove_file(&output).ok();
+    }
+
+    #[test]
+    fn previews_outline_every_rect_in_its_legend_color() {
+        let preview = std::env::temp_dir().join(format!("atlast-{}-preview.png", std::process::id()));
+        let mut atlas = Atlas::new();
+        atlas.fixed_width = Some(8);
+        atlas.images.push(image(4, 4));
+        atlas.images.push(image(4, 2));
+        atlas.images[1].source_size = (6, 3);
+        atlas.pack().unwrap();
+
+        let legend = atlas.debug_preview(&preview.to_string_lossy()).unwrap();
+        let (_, _, pixels) = reader::decode_png(&std::fs::read(&preview).unwrap()).unwrap();
+        for (i, rect) in atlas.records.iter().enumerate() {
+            let [r, g, b] = preview::color(i);
+            let corner = ((rect.y * 8 + rect.x) * 4) as usize;
+            assert_eq!(pixels[corner..corner + 4], [r, g, b, 255]);
+        }
+        assert_ne!(preview::color(0), preview::color(1));
+
+        let text = std::fs::read_to_string(&legend).unwrap();
+        let lines: Vec<&str> = text.lines().skip(1).collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].ends_with(" 6 3 4x2"));
+        std::fs::remove_file(&preview).ok();
+        std::fs::remove_file(&legend).ok();
+    }
+
+    #[test]
+    fn mip_levels_stay_within_their_sprite() {
+        use std::io::Read;
+
+        // Red 3x4 and blue 4x4 with a transparent column of padding between them
+        let rects = [Rect { x: 0, y: 0, width: 3, height: 4 }, Rect { x: 4, y: 0, width: 4, height: 4 }];
+        let mut pixels = vec![0; 8 * 4 * 4];
+        for (i, px) in pixels.chunks_mut(4).enumerate() {
+            match i % 8 {
+                0..=2 => px.copy_from_slice(&[255, 0, 0, 255]),
+                4.. => px.copy_from_slice(&[0, 0, 255, 255]),
+                _ => {}
+            }
+        }
+        let levels = mipmap::chain((8, 4), &pixels, &mipmap::owners(8, 4, &rects, 0), &[], false);
+        assert_eq!(levels.iter().map(Vec::len).collect::<Vec<_>>(), [4 * 2 * 4, 2 * 4, 4]);
+        // The texel over the last red column and the padding stays opaque red
+        assert_eq!(levels[0][4..8], [255, 0, 0, 255]);
+        assert_eq!(levels[0][8..12], [0, 0, 255, 255]);
+
+        let output = std::env::temp_dir().join(format!("atlast-{}-mipmaps.atlas", std::process::id()));
+        let mut atlas = AtlasBuilder::new().width(8).build();
+        atlas.mipmaps = true;
+        atlas.add(Image::new("a", 4, 4, vec![255; 4 * 4 * 4]).unwrap());
+        atlas.pack().unwrap();
+        atlas.write(&output).unwrap();
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&output).unwrap()).unwrap();
+        let (width, height) = atlas.texture_size();
+        for level in 1..mipmap::level_count((width, height)) {
+            assert!(zip.by_name(&format!("atlas_mip{}.png", level)).is_ok());
+        }
+
+        atlas.texture_format = TextureFormat::Ktx2;
+        atlas.write(&output).unwrap();
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&output).unwrap()).unwrap();
+        let mut ktx = Vec::new();
+        zip.by_name("atlas.ktx2").unwrap().read_to_end(&mut ktx).unwrap();
+        assert_eq!(ktx[40..44], (mipmap::level_count((width, height)) as u32).to_le_bytes());
+        std::fs::remove_file(&output).ok();
+    }
+
+    #[test]
+    fn compressed_textures_decode_close_to_their_source() {
+        // Not a whole number of blocks, and half of it translucent
+        let (width, height) = (6, 5);
+        let rgba: Vec<u8> = (0..width * height)
+            .flat_map(|i| {
+                let (x, y) = (i % width, i / width);
+                [x * 8, y * 10, 200 - x * 6, if y < 2 { 255 } else { 128 }].map(|v| v as u8)
+            })
+            .collect();
+
+        for compression in [Compression::Bc1, Compression::Bc3, Compression::Bc7, Compression::Etc2, Compression::Astc] {
+            let blocks = compression.compress(&rgba, width, height);
+            assert_eq!(blocks.len(), Texels::Blocks(compression).size(width, height));
+            let decoded = compression.decompress(&blocks, width, height).unwrap();
+
+            // BC1 and ETC2 drop alpha
+            let channels = if matches!(compression, Compression::Bc1 | Compression::Etc2) { 3 } else { 4 };
+            let error = rgba.chunks(4).zip(decoded.chunks(4))
+                .flat_map(|(source, decoded)| (0..channels).map(|c| (source[c] as f32 - decoded[c] as f32).abs()))
+                .sum::<f32>() / (width * height) as f32 / channels as f32;
+            assert!(error < 8.0, "{:?} is off by {} on average", compression, error);
+        }
+
+        let output = std::env::temp_dir().join(format!("atlast-{}-compressed.atlas", std::process::id()));
+        for (texture_format, compression) in [(TextureFormat::Dds, Compression::Bc7), (TextureFormat::Ktx2, Compression::Astc)] {
+            let mut atlas = AtlasBuilder::new().width(8).texture_format(texture_format).compression(compression).build();
+            atlas.mipmaps = true;
+            atlas.add(Image::new("a", 5, 3, vec![255; 5 * 3 * 4]).unwrap());
+            atlas.pack().unwrap();
+            atlas.write(&output).unwrap();
+
+            let file = reader::read(&output, None).unwrap();
+            assert_eq!(file.texels, Texels::Blocks(compression));
+            assert_eq!((file.width, file.height), atlas.texture_size());
+            assert!(file.pixels[..4] == [255; 4]);
+        }
+        std::fs::remove_file(&output).ok();
+    }
+
+    #[test]
+    fn premultiplied_atlases_record_it_and_still_verify() {
+        let output = std::env::temp_dir().join(format!("atlast-{}-premultiplied.atlas", std::process::id()));
+        let mut atlas = AtlasBuilder::new().build();
+        atlas.premultiply_alpha = true;
+        atlas.add(Image::new("a", 2, 1, vec![200, 100, 50, 128, 255, 255, 255, 0]).unwrap());
+        atlas.pack().unwrap();
+        atlas.write(&output).unwrap();
+
+        let file = reader::read(&output, None).unwrap();
+        assert!(file.data.premultiplied_alpha);
+        assert_eq!(file.pixels[..8], [100, 50, 25, 128, 0, 0, 0, 0]);
+        assert_eq!(atlas.verify(output.to_str().unwrap()).unwrap(), Vec::<String>::new());
+        std::fs::remove_file(&output).ok();
+    }
+
+    #[test]
+    fn bled_colors_stay_within_their_sprite() {
+        // A red and a blue sprite with a transparent texel of padding and of the blue sprite between them
+        let mut pixels = vec![255, 0, 0, 255, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 255, 255];
+        bleed::bleed(4, 1, &mut pixels, &[1, 0, 2, 2]);
+        assert_eq!(pixels, [255, 0, 0, 255, 255, 0, 0, 0, 0, 0, 255, 0, 0, 0, 255, 255]);
+
+        // Every transparent texel of the canvas is filled, however far from a sprite
+        let mut pixels = vec![0; 5 * 3 * 4];
+        pixels[..4].copy_from_slice(&[10, 20, 30, 255]);
+        bleed::bleed(5, 3, &mut pixels, &[0; 5 * 3]);
+        assert!(pixels.chunks(4).skip(1).all(|px| px == [10, 20, 30, 0]));
+    }
+
+    #[test]
+    fn basis_textures_transcode_back_with_their_layers() {
+        let output = std::env::temp_dir().join(format!("atlast-{}-basis.atlas", std::process::id()));
+        let mut atlas = AtlasBuilder::new().texture_format(TextureFormat::Basis).build();
+        atlas.texture_array = true;
+        atlas.mipmaps = true;
+        atlas.add(Image::new("a", 8, 8, vec![255; 8 * 8 * 4]).unwrap());
+        atlas.add(Image::new("b", 8, 8, [40, 90, 160, 255].repeat(8 * 8)).unwrap());
+        atlas.pack().unwrap();
+        atlas.write(&output).unwrap();
+
+        let file = reader::read(&output, None).unwrap();
+        std::fs::remove_file(&output).ok();
+        assert_eq!(file.texels, Texels::Basis);
+        assert_eq!((file.width, file.height, file.layers), (8, 8, 2));
+        // Flat colors survive UASTC almost untouched
+        for (layer, color) in [[255u8; 4], [40, 90, 160, 255]].iter().enumerate() {
+            let texel = &file.pixels[layer * 8 * 8 * 4..][..4];
+            assert!(texel.iter().zip(color).all(|(&a, &b)| (a as i32 - b as i32).abs() <= 2), "layer {} is {:?}", layer, texel);
+        }
+    }
+
+    #[test]
+    fn oversized_sprites_are_scaled_or_split_off() {
+        let mut atlas = Atlas::new();
+        atlas.max_width = Some(16);
+        atlas.max_height = Some(16);
+        atlas.images.extend([image(40, 10), image(8, 8), image(10, 40)]);
+
+        let mut split = Atlas { images: atlas.images.clone(), ..Atlas::new() };
+        split.max_width = Some(16);
+        split.max_height = Some(16);
+        let own: Vec<String> = split.split_oversized().into_iter().map(|image| image.name).collect();
+        assert_eq!(own, ["40x10", "10x40"]);
+        assert_eq!(split.images.len(), 1);
+
+        let scaled = atlas.downscale_oversized();
+        assert_eq!(scaled.iter().map(|(_, from, to)| (*from, *to)).collect::<Vec<_>>(), [((40, 10), (16, 4)), ((10, 40), (4, 16))]);
+        assert_eq!((atlas.images[0].width, atlas.images[0].height, atlas.images[0].data.len()), (16, 4, 16 * 4 * 4));
+        assert_eq!(atlas.images[0].source_size, (16, 4));
+        atlas.pack().unwrap();
+    }
+
+    #[test]
+    fn sprites_scale_by_the_global_and_first_matching_factor() {
+        let mut atlas = Atlas::new();
+        atlas.scale = 0.5;
+        atlas.sprite_scales = vec![(globset::Glob::new("ui/*").unwrap().compile_matcher(), 0.5)];
+        atlas.scale_filter = FilterType::Nearest;
+        for (path, width) in [("ui/a.png", 16), ("b.png", 16), ("c.png", 1)] {
+            let mut sprite = image(width, 8);
+            sprite.path = PathBuf::from("art").join(path);
+            atlas.images.push(sprite);
+        }
+
+        assert_eq!(atlas.scale_images(Path::new("art")), 3);
+        let sizes: Vec<(u32, u32)> = atlas.images.iter().map(|image| (image.width, image.height)).collect();
+        assert_eq!(sizes, [(4, 2), (8, 4), (1, 4)]);
+        assert_eq!(atlas.images[0].source_size, (4, 2));
+    }
+
+    #[test]
+    fn builder_packs_images_added_in_code() {
+        let mut atlas = AtlasBuilder::new().width(16).padding(2).packer(Packer::MaxRects).build();
+        atlas.add(Image::new("a", 6, 4, vec![255; 6 * 4 * 4]).unwrap());
+        atlas.add(Image::new("b", 6, 4, vec![255; 6 * 4 * 4]).unwrap());
+        atlas.pack().unwrap();
+
+        let records = atlas.records();
+        assert_eq!(records.iter().map(|record| record.name.as_str()).collect::<Vec<_>>(), ["a", "b"]);
+        let (a, b) = (&records[0], &records[1]);
+        assert!(a.x + a.width + 2 <= b.x || b.x + b.width + 2 <= a.x || a.y + a.height + 2 <= b.y || b.y + b.height + 2 <= a.y);
+        assert_eq!(atlas.width(), 16);
+    }
+
+    proptest! {
+        #[test]
+        fn every_packer_places_rects_without_overlap(sizes in prop::collection::vec((1u32..24, 1u32..24), 1..24),
+                                                     rotate in any::<bool>(), padding in 0u32..3, extrude in 0u32..3) {
+            for packer in Packer::ALL {
+                let mut atlas = Atlas::new();
+                atlas.packer = packer;
+                atlas.allow_rotation = rotate;
+                atlas.padding = padding;
+                atlas.extrude = extrude;
+                for &(width, height) in sizes.iter() {
+                    atlas.images.push(image(width, height));
+                }
+                atlas.pack().unwrap();
+
+                // Each sprite's extrusion, and its padding to the right and below
+                let padded = |rect: &Rect| Rect {
+                    x: rect.x - extrude,
+                    y: rect.y - extrude,
+                    width: rect.width + 2 * extrude + padding,
+                    height: rect.height + 2 * extrude + padding
+                };
+                for (i, a) in atlas.records.iter().enumerate() {
+                    prop_assert!(a.x >= extrude && a.y >= extrude);
+                    prop_assert!(a.x + a.width + extrude <= atlas.width);
+                    prop_assert!(a.y + a.height + extrude <= atlas.height());
+                    for b in atlas.records.iter().skip(i + 1) {
+                        prop_assert!(!padded(a).intersects(&padded(b)), "{:?} is too close to {:?}", a, b);
+                    }
+                }
+                for (img, rect) in atlas.images.iter().zip(atlas.records.iter()) {
+                    prop_assert_eq!((img.width, img.height), (rect.width, rect.height));
+                    prop_assert!(rotate || !img.rotated);
+                }
+            }
+        }
+
+        #[test]
+        fn placed_rects_never_overlap(sizes in prop::collection::vec((1u32..24, 1u32..24), 1..24)) {
+            let atlas = packed(&sizes, None);
+
+            for (i, a) in atlas.records.iter().enumerate() {
+                prop_assert!(a.x + a.width <= atlas.width);
+                for b in atlas.records.iter().skip(i + 1) {
+                    prop_assert!(!a.intersects(b), "{:?} overlaps {:?}", a, b);
+                }
+            }
+        }
+
+        #[test]
+        fn traced_outlines_cover_their_pixels(width in 1u32..12, height in 1u32..12, seed in any::<u64>(), tolerance in 0u32..4) {
+            let pixels: Vec<u8> = (0..width * height)
+                .flat_map(|i| if (seed.rotate_left(i % 64) ^ i as u64).is_multiple_of(3) { [255; 4] } else { [0; 4] })
+                .collect();
+            let outline = polygon::trace(width, height, &pixels, tolerance);
+            prop_assert!(outline.vertices.iter().all(|&(x, y)| x <= width && y <= height));
+
+            let spans = polygon::spans(&outline.vertices, width, height);
+            for (i, _) in pixels.chunks(4).enumerate().filter(|(_, pixel)| pixel[3] != 0) {
+                let (x, y) = (i as u32 % width, i as u32 / width);
+                prop_assert!((spans[y as usize].0..spans[y as usize].1).contains(&x), "{},{} is outside {:?}", x, y, outline);
+            }
+
+            // The triangles tile the outline without overlapping
+            let cross = |(ax, ay): (u32, u32), (bx, by): (u32, u32)| ax as i64 * by as i64 - bx as i64 * ay as i64;
+            let count = outline.vertices.len();
+            let area: i64 = (0..count).map(|i| cross(outline.vertices[i], outline.vertices[(i + 1) % count])).sum();
+            let covered: i64 = outline.triangles.iter()
+                .map(|&[a, b, c]| [a, b, c, a].map(|i| outline.vertices[i as usize]))
+                .map(|corners| corners.windows(2).map(|pair| cross(pair[0], pair[1])).sum::<i64>().abs())
+                .sum();
+            prop_assert_eq!(area.abs(), covered, "{:?}", outline);
+        }
+
+        #[test]
+        fn polygon_packing_keeps_opaque_pixels_apart(sprites in prop::collection::vec((any::<u64>(), 1u32..10, 1u32..10), 1..12),
+                                                     padding in 0u32..3, rotate in any::<bool>()) {
+            let mut atlas = AtlasBuilder::new().width(24).padding(padding).polygons(1).build();
+            atlas.allow_rotation = rotate;
+            for (i, &(seed, width, height)) in sprites.iter().enumerate() {
+                let pixels = (0..width * height).flat_map(|j| if seed.rotate_left(j % 64).is_multiple_of(3) { [255; 4] } else { [0; 4] }).collect();
+                atlas.add(Image::new(&i.to_string(), width, height, pixels).unwrap());
+            }
+            atlas.pack().unwrap();
+
+            let opaque: Vec<Vec<(u32, u32)>> = atlas.images.iter().zip(atlas.records.iter())
+                .map(|(image, rect)| {
+                    image.data.chunks(4).enumerate().filter(|(_, pixel)| pixel[3] != 0)
+                        .map(|(j, _)| (rect.x + j as u32 % image.width, rect.y + j as u32 / image.width))
+                        .collect()
+                })
+                .collect();
+            for (i, a) in opaque.iter().enumerate() {
+                for b in opaque.iter().skip(i + 1) {
+                    for (&(ax, ay), &(bx, by)) in a.iter().flat_map(|a| b.iter().map(move |b| (a, b))) {
+                        prop_assert!(ax.abs_diff(bx) > padding || ay.abs_diff(by) > padding, "{},{} is too close to {},{}", ax, ay, bx, by);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn rects_intersect_exactly_when_they_share_a_pixel(a in (0u32..8, 0u32..8, 1u32..8, 1u32..8),
+                                                           b in (0u32..8, 0u32..8, 1u32..8, 1u32..8)) {
+            let (a, b) = (rect(a.0, a.1, a.2, a.3), rect(b.0, b.1, b.2, b.3));
+            let pixels = |r: Rect| (r.y..r.y + r.height).flat_map(move |y| (r.x..r.x + r.width).map(move |x| (x, y)));
+            let shared = pixels(a).any(|pixel| pixels(b).any(|other| other == pixel));
+            prop_assert_eq!(a.intersects(&b), shared);
+            prop_assert_eq!(b.intersects(&a), shared);
+
+            let inside = pixels(b).all(|pixel| pixels(a).any(|other| other == pixel));
+            prop_assert_eq!(a.contains(&b), inside);
+            prop_assert!(!a.contains(&b) || a.intersects(&b));
+        }
+
+        #[test]
+        fn rects_touching_edges_do_not_intersect(a in (0u32..8, 0u32..8, 1u32..8, 1u32..8), along in 0u32..8, length in 1u32..8) {
+            let a = rect(a.0, a.1, a.2, a.3);
+            // Beside the right and bottom edges, starting anywhere along them
+            let right = rect(a.x + a.width, a.y + along % a.height, length, length);
+            let below = rect(a.x + along % a.width, a.y + a.height, length, length);
+            for b in [right, below] {
+                prop_assert!(!a.intersects(&b) && !b.intersects(&a), "{:?} and {:?} only touch", a, b);
+            }
+            // One pixel closer they do
+            prop_assert!(a.intersects(&rect(right.x - 1, right.y, length, length)));
+            prop_assert!(a.intersects(&rect(below.x, below.y - 1, length, length)));
+        }
+    }
+
+    #[test]
+    fn numbered_sprites_group_into_animations() {
+        let output = std::env::temp_dir().join(format!("atlast-{}-animations.atlas", std::process::id()));
+        let mut atlas = AtlasBuilder::new().build();
+        atlas.animations = Some(regex::Regex::new(animation::DEFAULT_PATTERN).unwrap());
+        atlas.frame_durations = vec![(globset::Glob::new("run").unwrap().compile_matcher(), 80)];
+        for name in ["run_10.png", "run_9.png", "run_0.png", "idle-1.png", "idle-0.png", "logo.png"] {
+            atlas.add(Image::new(name, 2, 2, vec![255; 2 * 2 * 4]).unwrap());
+        }
+        atlas.pack().unwrap();
+        atlas.data_format = DataFormat::Libgdx;
+        atlas.write(&output).unwrap();
+
+        let file = reader::read(&output, None).unwrap();
+        assert_eq!(file.data.animations, [
+            AnimationRecord {
+                name: "idle".to_string(),
+                frames: vec!["idle-0.png".to_string(), "idle-1.png".to_string()],
+                duration: None
+            },
+            AnimationRecord {
+                name: "run".to_string(),
+                frames: vec!["run_0.png".to_string(), "run_9.png".to_string(), "run_10.png".to_string()],
+                duration: Some(80)
+            }
+        ]);
+
+        // libGDX finds the frames by the animation name and their index
+        let text = libgdx::write(&file.data, "png");
+        assert!(text.contains("\nrun\n") && text.contains("  index: 2\n") && text.contains("\nlogo\n"));
+        assert!(!text.contains("run_10"));
+        std::fs::remove_file(&output).ok();
+    }
+
+    #[test]
+    fn aseprite_frames_and_tags_become_sprites_and_animations() {
+        // A 2x1 sprite with a layer of red, green and blue frames, and a ping-pong and a single frame tag
+        let words = |words: &[u16]| words.iter().flat_map(|word| word.to_le_bytes()).collect::<Vec<u8>>();
+        let string = |text: &str| [words(&[text.len() as u16]), text.as_bytes().to_vec()].concat();
+        let chunk = |kind: u16, data: Vec<u8>| [(data.len() as u32 + 6).to_le_bytes().to_vec(), words(&[kind]), data].concat();
+        let layer = chunk(0x2004, [words(&[1, 0, 0, 0, 0, 0]), vec![255, 0, 0, 0], string("art")].concat());
+        let tags = chunk(0x2018, [
+            words(&[2, 0, 0, 0, 0]),
+            words(&[0, 2]), vec![2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], string("walk"),
+            words(&[2, 2]), vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], string("hit")
+        ].concat());
+
+        let mut bytes = [words(&[0, 0, 0xA5E0, 3, 2, 1, 32, 1, 0, 100]), vec![0; 12], words(&[0]), vec![1, 1], vec![0; 92]].concat();
+        for (frame, (color, duration)) in [([255, 0, 0, 255], 100), ([0, 255, 0, 255], 100), ([0, 0, 255, 255], 150)].into_iter().enumerate() {
+            let cel = chunk(0x2005, [words(&[0, 0, 0]), vec![255], words(&[0]), vec![0; 7], words(&[2, 1]), color.repeat(2)].concat());
+            let chunks = if frame == 0 { [layer.clone(), tags.clone(), cel].concat() } else { cel };
+            let count = if frame == 0 { 3 } else { 1 };
+            bytes.extend((16 + chunks.len() as u32).to_le_bytes());
+            bytes.extend([words(&[0xF1FA, count, duration, 0, 0, 0]), chunks].concat());
+        }
+        let length = bytes.len() as u32;
+        bytes[..4].copy_from_slice(&length.to_le_bytes());
+
+        let path = std::env::temp_dir().join(format!("atlast-{}-hero.aseprite", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+        let mut atlas = Atlas::new();
+        atlas.name_style = NameStyle::Stem;
+        atlas.add_aseprite(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let stem = format!("atlast-{}-hero", std::process::id());
+        let names: Vec<&str> = atlas.images.iter().map(|image| image.name.as_str()).collect();
+        assert_eq!(names, [format!("{}_0", stem), format!("{}_1", stem), format!("{}_2", stem)]);
+        assert_eq!((atlas.images[1].width, atlas.images[1].height), (2, 1));
+        assert_eq!(atlas.images[1].data, [0, 255, 0, 255, 0, 255, 0, 255]);
+
+        let frames = |frames: &[u32]| frames.iter().map(|frame| format!("{}_{}", stem, frame)).collect::<Vec<_>>();
+        assert_eq!(atlas.tags, [
+            AnimationRecord { name: format!("{}/walk", stem), frames: frames(&[0, 1, 2, 1]), duration: Some(100) },
+            AnimationRecord { name: format!("{}/hit", stem), frames: frames(&[2]), duration: Some(150) }
+        ]);
+    }
+
+    #[test]
+    fn nine_slices_come_from_borders_sidecars_and_rules() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-nine-slice", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // A 4x3 panel inside a nine-patch border marking its two middle columns and middle row
+        let (clear, black, white) = ([0, 0, 0, 0], [0, 0, 0, 255], [255, 255, 255, 255]);
+        let mut patch = Vec::new();
+        for y in 0..5 {
+            for x in 0..6 {
+                let marked = (y == 0 && (2..=3).contains(&x)) || (x == 0 && y == 2);
+                let inside = (1..5).contains(&x) && (1..4).contains(&y);
+                patch.extend(if marked { black } else if inside { white } else { clear });
+            }
+        }
+        write_png(dir.join("panel.9.png").to_str().unwrap(), 6, 5, &patch).unwrap();
+        write_png(dir.join("button.png").to_str().unwrap(), 8, 8, &[255; 8 * 8 * 4]).unwrap();
+        std::fs::write(dir.join("button.9.json"), r#"{"left": 2, "top": 3, "right": 2, "bottom": 1}"#).unwrap();
+        write_png(dir.join("frame.png").to_str().unwrap(), 8, 8, &[255; 8 * 8 * 4]).unwrap();
+        write_png(dir.join("icon.png").to_str().unwrap(), 8, 8, &[255; 8 * 8 * 4]).unwrap();
+
+        let mut atlas = Atlas::new();
+        atlas.nine_slices = vec![(globset::Glob::new("*.png").unwrap().compile_matcher(), NineSlice::parse("1,1,1,1").unwrap())];
+        atlas.nine_slices.insert(0, (globset::Glob::new("icon*").unwrap().compile_matcher(), NineSlice::parse("5,0,4,0").unwrap()));
+        let err = atlas.add_dir(&dir).unwrap_err();
+        assert!(err.reason().contains("do not fit 8x8"), "{}", err.reason());
+
+        atlas = Atlas::new();
+        atlas.nine_slices = vec![(globset::Glob::new("*.png").unwrap().compile_matcher(), NineSlice::parse("1,1,1,1").unwrap())];
+        atlas.add_dir(&dir).unwrap();
+        atlas.pack().unwrap();
+        let records = atlas.records();
+        std::fs::remove_dir_all(&dir).ok();
+
+        let borders = |name: &str| records.iter().find(|record| record.name == name).unwrap().nine_slice;
+        let panel = records.iter().find(|record| record.name == "panel.png").unwrap();
+        assert_eq!((panel.width, panel.height), (4, 3));
+        assert_eq!(borders("panel.png"), Some(NineSlice { left: 1, top: 1, right: 1, bottom: 1 }));
+        assert_eq!(borders("button.png"), Some(NineSlice { left: 2, top: 3, right: 2, bottom: 1 }));
+        assert_eq!(borders("frame.png"), Some(NineSlice { left: 1, top: 1, right: 1, bottom: 1 }));
+
+        // libGDX splits are left, right, top, bottom and TexturePacker gives the stretchable center
+        let data = data(records.clone());
+        assert!(libgdx::write(&data, "png").contains("\nbutton\n  rotate: false\n  xy: 0, 0\n  size: 8, 8\n  split: 2, 2, 3, 1\n"));
+        let sheet = texturepacker::write(&data, "atlas.png", "rgba8888", false, false).unwrap();
+        assert!(String::from_utf8_lossy(&sheet).contains(r#""scale9Borders":{"x":2,"y":3,"w":4,"h":4}"#));
+        let read = texturepacker::read(serde_json::from_slice(&sheet).unwrap()).unwrap();
+        assert!(read.records.iter().all(|record| record.nine_slice == borders(&record.name)));
+    }
+
+    #[test]
+    fn pivots_come_from_sidecars_rules_and_the_default() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-pivots", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["feet.png", "hand.png", "head.png"] {
+            write_png(dir.join(name).to_str().unwrap(), 4, 4, &[255; 4 * 4 * 4]).unwrap();
+        }
+        std::fs::write(dir.join("feet.pivot.json"), r#"{"x": 0.5, "y": 1.0}"#).unwrap();
+
+        let mut atlas = AtlasBuilder::new().pivot(0.0, 0.0).build();
+        atlas.sprite_pivots = vec![(globset::Glob::new("h*").unwrap().compile_matcher(), pivot::parse("0.25, 0.75").unwrap())];
+        atlas.sprite_pivots.push((globset::Glob::new("head*").unwrap().compile_matcher(), (1.0, 1.0)));
+        atlas.add_dir(&dir).unwrap();
+        atlas.add(Image::new("made.png", 2, 2, vec![255; 2 * 2 * 4]).unwrap());
+        atlas.pack().unwrap();
+        let records = atlas.records();
+        std::fs::remove_dir_all(&dir).ok();
+
+        let pivot = |name: &str| records.iter().find(|record| record.name == name).map(|record| (record.pivot_x, record.pivot_y)).unwrap();
+        assert_eq!(pivot("feet.png"), (0.5, 1.0));
+        assert_eq!(pivot("hand.png"), (0.25, 0.75));
+        assert_eq!(pivot("head.png"), (0.25, 0.75));
+        assert_eq!(pivot("made.png"), (0.0, 0.0));
+
+        // TexturePacker sheets carry them, sheets without pivots center the sprites
+        let data = AtlasData { width: 8, height: 8, ..data(records.clone()) };
+        let sheet = texturepacker::write(&data, "atlas.png", "rgba8888", true, false).unwrap();
+        assert!(String::from_utf8_lossy(&sheet).contains(r#""pivot":{"x":0.25,"y":0.75}"#));
+        let read = texturepacker::read(serde_json::from_slice(&sheet).unwrap()).unwrap();
+        assert!(read.records.iter().all(|record| (record.pivot_x, record.pivot_y) == pivot(&record.name)));
+        let unpivoted = String::from_utf8_lossy(&sheet).replace(r#","pivot":{"x":0.25,"y":0.75}"#, "");
+        let read = texturepacker::read(serde_json::from_str(&unpivoted).unwrap()).unwrap();
+        assert_eq!(read.records.iter().filter(|record| (record.pivot_x, record.pivot_y) == (0.5, 0.5)).count(), 2);
+    }
+
+    #[test]
+    fn user_data_passes_through_untouched() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-user-data", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["hero.png", "slime.png", "rock.png"] {
+            write_png(dir.join(name).to_str().unwrap(), 4, 4, &[255; 4 * 4 * 4]).unwrap();
+        }
+        let hero = r#"{"hitbox": [1, 2, 3, 4], "material": "wood"}"#;
+        std::fs::write(dir.join("hero.meta.json"), format!("{}\n", hero)).unwrap();
+        std::fs::write(dir.join("rock.meta.json"), "{hitbox").unwrap();
+
+        let mut atlas = Atlas::new();
+        assert!(atlas.add_dir(&dir).unwrap_err().reason().contains("rock.meta.json"));
+        std::fs::remove_file(dir.join("rock.meta.json")).unwrap();
+
+        let output = dir.with_extension("atlas");
+        let mut atlas = AtlasBuilder::new().data_format(DataFormat::Json).build();
+        atlas.user_data = vec![(globset::Glob::new("*.png").unwrap().compile_matcher(), r#"{"team": "red"}"#.to_string())];
+        atlas.add_dir(&dir).unwrap();
+        atlas.pack().unwrap();
+        atlas.write(&output).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        let file = reader::read(&output, None).unwrap();
+        std::fs::remove_file(&output).ok();
+        let user_data = |name: &str| file.data.records.iter().find(|record| record.name == name).unwrap().user_data.clone();
+        assert_eq!(user_data("hero.png").as_deref(), Some(hero));
+        assert_eq!(user_data("slime.png").as_deref(), Some(r#"{"team": "red"}"#));
+
+        let sheet = texturepacker::write(&file.data, "atlas.png", "rgba8888", false, false).unwrap();
+        assert!(String::from_utf8_lossy(&sheet).contains(r#""userData":{"hitbox":[1,2,3,4],"material":"wood"}"#));
+    }
+
+    #[test]
+    fn polygons_hug_opaque_pixels_and_let_rects_overlap() {
+        // Right triangles filling the lower left and the upper right of 16x16
+        let triangle = |lower: bool| -> Vec<u8> {
+            (0..16u32).flat_map(|y| (0..16u32).map(move |x| (x, y)))
+                .flat_map(|(x, y)| if (x <= y) == lower { [255, 0, 0, 255] } else { [0; 4] })
+                .collect()
+        };
+
+        // Every opaque pixel lies inside, and the triangles cover the outline exactly
+        let outline = polygon::trace(16, 16, &triangle(true), 1);
+        assert_eq!(outline.vertices.len(), 5, "{:?}", outline.vertices);
+        let spans = polygon::spans(&outline.vertices, 16, 16);
+        assert!(spans.iter().enumerate().all(|(y, &(left, right))| left == 0 && right > y as u32));
+        let cross = |(ax, ay): (u32, u32), (bx, by): (u32, u32)| ax as i64 * by as i64 - bx as i64 * ay as i64;
+        let area: i64 = (0..5).map(|i| cross(outline.vertices[i], outline.vertices[(i + 1) % 5])).sum();
+        let covered: i64 = outline.triangles.iter()
+            .map(|&[a, b, c]| [a, b, c, a].map(|i| outline.vertices[i as usize]))
+            .map(|corners| corners.windows(2).map(|pair| cross(pair[0], pair[1])).sum::<i64>().abs())
+            .sum();
+        assert_eq!(area.abs(), covered);
+
+        let pack = |polygons: bool| {
+            let builder = AtlasBuilder::new().width(20);
+            let mut atlas = if polygons { builder.polygons(0) } else { builder }.build();
+            atlas.add(Image::new("lower.png", 16, 16, triangle(true)).unwrap());
+            atlas.add(Image::new("upper.png", 16, 16, triangle(false)).unwrap());
+            atlas.pack().unwrap();
+            atlas
+        };
+        assert_eq!(pack(false).height(), 32);
+        let atlas = pack(true);
+        assert_eq!(atlas.height(), 16);
+        assert!(atlas.records[0].intersects(&atlas.records[1]));
+
+        // The transparent pixels of one sprite leave those of the other alone
+        let (width, _, pixels) = atlas.compose();
+        for (image, rect) in atlas.images.iter().zip(atlas.records.iter()) {
+            for (i, pixel) in image.data.chunks(4).enumerate().filter(|(_, pixel)| pixel[3] != 0) {
+                let at = (((rect.y + i as u32 / 16) * width + rect.x + i as u32 % 16) * 4) as usize;
+                assert_eq!(&pixels[at..at + 4], pixel);
+            }
+        }
+
+        // TexturePacker's polygon mode gives the corners in the source and in the image
+        let records = atlas.records();
+        let data = AtlasData { width: atlas.width(), height: 16, ..data(records.clone()) };
+        let sheet: serde_json::Value = serde_json::from_slice(&texturepacker::write(&data, "atlas.png", "rgba8888", false, false).unwrap()).unwrap();
+        let upper = records.iter().find(|record| record.name == "upper.png").unwrap();
+        let (x, y) = upper.polygon.as_ref().unwrap().vertices[0];
+        assert_eq!(sheet["frames"]["upper.png"]["vertices"][0], serde_json::json!([x, y]));
+        assert_eq!(sheet["frames"]["upper.png"]["verticesUV"][0], serde_json::json!([upper.x + x, upper.y + y]));
+        let read = texturepacker::read(sheet).unwrap();
+        for record in read.records {
+            let packed = records.iter().find(|packed| packed.name == record.name).unwrap();
+            assert!(record.polygon.is_some() && record.polygon == packed.polygon);
+        }
+    }
+
+    #[test]
+    fn optimize_keeps_the_smallest_layout() {
+        let sizes: Vec<(u32, u32)> = (0..24u32).map(|i| (3 + i * 7 % 19, 2 + i * 11 % 23)).collect();
+        let pack = |optimize: Option<u32>| {
+            let mut atlas = packed(&[], None);
+            atlas.optimize = optimize;
+            for (i, &(width, height)) in sizes.iter().enumerate() {
+                atlas.add(Image::new(&format!("{:02}", i), width, height, vec![255; (width * height * 4) as usize]).unwrap());
+            }
+            atlas.pack().unwrap();
+            atlas
+        };
+
+        let configured = pack(None);
+        let optimized = pack(Some(40));
+        assert!(optimized.output_pixels() < configured.output_pixels());
+        for (i, a) in optimized.records.iter().enumerate() {
+            assert!(a.x + a.width <= optimized.width);
+            assert!(optimized.records.iter().skip(i + 1).all(|b| !a.intersects(b)));
+        }
+        for (image, rect) in optimized.images.iter().zip(optimized.records.iter()) {
+            assert_eq!((image.width, image.height), (rect.width, rect.height));
+        }
+
+        // The search is the same every time
+        let again = pack(Some(40));
+        assert_eq!(again.records, optimized.records);
+        assert_eq!(again.images.iter().map(Image::name).collect::<Vec<_>>(), optimized.images.iter().map(Image::name).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn validate_catches_overlaps_stray_uvs_and_changed_pixels() {
+        let mut atlas = Atlas::new();
+        atlas.fixed_width = Some(8);
+        for (name, color) in [("a", 40), ("b", 90)] {
+            atlas.add(Image::new(name, 4, 3, vec![color; 4 * 3 * 4]).unwrap());
+        }
+        atlas.pack().unwrap();
+        let path = std::env::temp_dir().join(format!("atlast-{}-validate.atlas", std::process::id()));
+        atlas.write(&path).unwrap();
+        let mut file = reader::read(&path, None).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let sprites = unpack::extract(&file).unwrap();
+        assert!(validate::check(&file).is_empty());
+        assert!(validate::compare(&file, &sprites, &atlas.images).unwrap().is_empty());
+
+        // A source that changed since packing
+        let mut sources = atlas.images.clone();
+        sources[1].data[0] ^= 1;
+        assert_eq!(validate::compare(&file, &sprites, &sources).unwrap().len(), 1);
+
+        // A rect crossing its neighbour and one leaving the image
+        file.data.records[1].x = file.data.records[0].x + 1;
+        assert_eq!(validate::check(&file), vec!["a: rect overlaps b".to_string()]);
+        file.data.records[1].x = file.data.width - 2;
+        let problems = validate::check(&file);
+        assert!(problems.len() == 1 && problems[0].starts_with("b: texture coordinates"), "{:?}", problems);
+    }
+
+    #[test]
+    fn merged_atlases_keep_their_sprites_and_share_identical_ones() {
+        let write = |name: &str, sprites: &[(&str, u8)], premultiplied: bool| {
+            let mut atlas = Atlas::new();
+            atlas.premultiply_alpha = premultiplied;
+            atlas.allow_rotation = true;
+            for &(sprite, color) in sprites {
+                let mut image = Image::new(sprite, 5, 2, vec![color; 5 * 2 * 4]).unwrap();
+                image.pivot = Some((0.25, 1.0));
+                atlas.add(image);
+            }
+            atlas.pack().unwrap();
+            let path = std::env::temp_dir().join(format!("atlast-{}-merge-{}.atlas", std::process::id(), name));
+            atlas.write(&path).unwrap();
+            path
+        };
+        let a = write("a", &[("a", 255), ("shared", 255)], false);
+        let b = write("b", &[("b", 255), ("shared", 255)], true);
+
+        let mut merged = Atlas::new();
+        assert_eq!(merged.add_atlas(&a).unwrap(), 0);
+        assert_eq!(merged.add_atlas(&b).unwrap(), 1);
+        std::fs::remove_file(&a).unwrap();
+        std::fs::remove_file(&b).unwrap();
+
+        assert_eq!(merged.images.iter().map(Image::name).collect::<Vec<_>>(), ["a", "shared", "b"]);
+        for image in merged.images.iter() {
+            assert_eq!((image.width, image.height, image.pivot), (5, 2, Some((0.25, 1.0))));
+            assert!(image.data.iter().all(|&channel| channel == 255));
+        }
+        assert!(merged.duplicate_names().is_empty());
+        merged.pack().unwrap();
+    }
+
+    #[test]
+    fn diff_reports_added_removed_moved_and_changed_sprites() {
+        let write = |name: &str, sprites: &[(&str, u8)]| {
+            let mut atlas = Atlas::new();
+            atlas.fixed_width = Some(8);
+            for &(sprite, color) in sprites {
+                atlas.add(Image::new(sprite, 4, 4, vec![color; 4 * 4 * 4]).unwrap());
+            }
+            atlas.pack().unwrap();
+            let path = std::env::temp_dir().join(format!("atlast-{}-diff-{}.atlas", std::process::id(), name));
+            atlas.write(&path).unwrap();
+            let file = reader::read(&path, None).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            file
+        };
+        // Sprites of the same size go in name order, so "0" pushes "a" and "b" along
+        let old = write("old", &[("a", 10), ("b", 20), ("c", 30)]);
+        let new = write("new", &[("0", 40), ("a", 10), ("b", 21), ("d", 30)]);
+
+        let same = diff::diff(&old, &old).unwrap();
+        assert!(same.is_empty());
+
+        let changes = diff::diff(&old, &new).unwrap();
+        assert_eq!(changes.added, ["0", "d"]);
+        assert_eq!(changes.removed, ["c"]);
+        assert_eq!(changes.moved.iter().map(|moved| moved.name.as_str()).collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!((changes.moved[0].from.x, changes.moved[0].to.x), (0, 4));
+        assert_eq!(changes.changed, ["b"]);
+        assert!(!changes.is_empty());
+
+        // Sprites sharing a name cannot be told apart
+        let mut new = new;
+        new.data.records[1].name = new.data.records[0].name.clone();
+        assert!(diff::diff(&old, &new).is_err_and(|err| err.contains("more than one sprite named \"0\"")));
+    }
+
+    #[test]
+    fn appending_keeps_straight_colors_credits_and_animations() {
+        let mut shipped = Atlas::new();
+        shipped.premultiply_alpha = true;
+        shipped.add(Image::new("a", 2, 2, [200, 100, 50, 128].repeat(4)).unwrap());
+        shipped.tags.push(AnimationRecord { name: "idle".to_string(), frames: vec!["a".to_string()], duration: None });
+        shipped.credits.push(Credit {
+            attribution: Attribution { author: Some("Ada".to_string()), license: None, source: None },
+            sprites: vec!["a".to_string()]
+        });
+        shipped.pack().unwrap();
+        let path = std::env::temp_dir().join(format!("atlast-{}-append.atlas", std::process::id()));
+        shipped.write(&path).unwrap();
+
+        let mut atlas = Atlas::new();
+        atlas.premultiply_alpha = true;
+        atlas.credits.push(Credit { attribution: Attribution { author: None, license: None, source: None }, sprites: vec!["b".to_string()] });
+        atlas.keep_existing(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // Premultiplied once when written again, not twice
+        let kept = &atlas.base[0].0;
+        for px in kept.data.chunks(4) {
+            assert!(px.iter().zip([200, 100, 50, 128]).all(|(&channel, straight)| channel.abs_diff(straight) <= 1), "{:?}", px);
+        }
+        assert_eq!(atlas.tags.iter().map(|tag| tag.name.as_str()).collect::<Vec<_>>(), ["idle"]);
+        assert_eq!(atlas.credits.len(), 2);
+    }
+
+    // A TrueType font with a single glyph, a 500 unit square for "A"
+    fn square_font() -> Vec<u8> {
+        let words = |values: &[i32]| -> Vec<u8> { values.iter().flat_map(|&v| (v as u16).to_be_bytes()).collect() };
+        // One contour of four on-curve points given as 16-bit deltas
+        let mut glyph = words(&[1, 100, 100, 600, 600, 3, 0]);
+        glyph.extend([1; 4]);
+        glyph.extend(words(&[100, 500, 0, -500, 100, 0, 500, 0]));
+        let tables = [
+            (b"cmap", words(&[0, 1, 3, 1, 0, 12, 6, 12, 0, 'A' as i32, 1, 1])),
+            (b"head", words(&[1, 0, 1, 0, 0, 0, 0x5f0f, 0x3cf5, 0, 1000, 0, 0, 0, 0, 0, 0, 0, 0, 100, 100, 600, 600, 0, 8, 2, 0, 0])),
+            (b"hhea", words(&[1, 0, 800, -200, 0, 700, 0, 0, 600, 1, 0, 0, 0, 0, 0, 0, 0, 2])),
+            (b"hmtx", words(&[700, 0, 700, 100])),
+            (b"loca", words(&[0, 0, glyph.len() as i32 / 2])),
+            (b"maxp", words(&[0, 0x5000, 2])),
+            (b"glyf", glyph)
+        ];
+
+        let mut font = words(&[1, 0, tables.len() as i32, 0, 0, 0]);
+        let mut data = Vec::new();
+        let mut records: Vec<_> = tables.iter()
+            .map(|(tag, table)| {
+                let offset = 12 + 16 * tables.len() + data.len();
+                data.extend(table);
+                data.resize(data.len().next_multiple_of(4), 0);
+                (*tag, offset, table.len())
+            })
+            .collect();
+        records.sort();
+        for (tag, offset, length) in records {
+            font.extend(tag);
+            font.extend([0; 4]);
+            font.extend((offset as u32).to_be_bytes());
+            font.extend((length as u32).to_be_bytes());
+        }
+        font.extend(data);
+        font
+    }
+
+    #[test]
+    fn font_glyphs_pack_as_distance_fields_with_their_metrics() {
+        let path = std::env::temp_dir().join(format!("atlast-{}-square.ttf", std::process::id()));
+        std::fs::write(&path, square_font()).unwrap();
+
+        let output = std::env::temp_dir().join(format!("atlast-{}-msdf.atlas", std::process::id()));
+        let mut atlas = AtlasBuilder::new().build();
+        // The font has no glyph for B
+        atlas.charset = "AB".to_string();
+        atlas.add_font(&path).unwrap();
+        atlas.pack().unwrap();
+        atlas.write(&output).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        let file = reader::read(&output, None).unwrap();
+        std::fs::remove_file(&output).ok();
+        let font = &file.data.fonts[0];
+        assert_eq!((font.size, font.distance_range, font.ascender, font.descender), (32.0, 4.0, 0.8, -0.2));
+        assert_eq!(font.glyphs.len(), 1);
+        let glyph = &font.glyphs[0];
+        assert_eq!((glyph.codepoint, glyph.advance), ('A' as u32, 0.7));
+        // The square from 0.1 to 0.6 em with the distance range of 4 texels, 1/8 em, on either side
+        assert_eq!(glyph.plane_bounds, Some([-0.025, -0.025, 0.725, 0.725]));
+
+        let sprites = unpack::extract(&file).unwrap();
+        let sprite = sprites.iter().find(|sprite| Some(&sprite.name) == glyph.sprite.as_ref()).unwrap();
+        assert_eq!((sprite.width, sprite.height), (24, 24));
+        // The median of the channels is the distance, over half inside the square and under it outside
+        let median = |x: u32, y: u32| {
+            let mut px = sprite.data[((y * 24 + x) * 4) as usize..][..3].to_vec();
+            px.sort();
+            px[1]
+        };
+        assert!(median(12, 12) > 128 && median(0, 0) < 128);
+    }
+
+
+    #[test]
+    fn grayscale_heightmaps_pack_as_normal_maps() {
+        let dir = std::env::temp_dir();
+        let (heightmap, color) = (
+            dir.join(format!("atlast-{}-height.png", std::process::id())),
+            dir.join(format!("atlast-{}-height-color.png", std::process::id()))
+        );
+        // Heights rising to the right, and a gray RGBA sprite that is no heightmap
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, 3, 3);
+            encoder.set_color(png::ColorType::Grayscale);
+            encoder.write_header().unwrap().write_image_data(&[0, 128, 255].repeat(3)).unwrap();
+        }
+        std::fs::write(&heightmap, &bytes).unwrap();
+        write_png(color.to_str().unwrap(), 3, 3, &[128; 36]).unwrap();
+
+        let output = dir.join(format!("atlast-{}-height.atlas", std::process::id()));
+        let mut atlas = Atlas::new();
+        atlas.normal_strength = Some(2.0);
+        atlas.add_image(&heightmap).unwrap();
+        atlas.add_image(&color).unwrap();
+        atlas.pack().unwrap();
+        atlas.write(&output).unwrap();
+        std::fs::remove_file(&heightmap).ok();
+        std::fs::remove_file(&color).ok();
+
+        let file = reader::read(&output, None).unwrap();
+        std::fs::remove_file(&output).ok();
+        let sprites = unpack::extract(&file).unwrap();
+        let sprite = |name: &str| sprites.iter().find(|sprite| sprite.name.ends_with(name)).unwrap();
+        // A slope of 1 at strength 2 tilts the normal 45 degrees to the left, alpha stays opaque
+        assert_eq!(sprite("height.png").data[16..20], [37, 128, 218, 255]);
+        assert_eq!(sprite("height-color.png").data, [128; 36]);
+    }
+
+
+    #[test]
+    fn effects_sidecars_are_baked_into_their_sprites() {
+        let path = std::env::temp_dir().join(format!("atlast-{}-effects.png", std::process::id()));
+        write_png(path.to_str().unwrap(), 2, 2, &[255; 16]).unwrap();
+        std::fs::write(Effects::sidecar(&path), "# 1 pixel red outline\noutline 1 ff0000\n").unwrap();
+
+        let output = std::env::temp_dir().join(format!("atlast-{}-effects.atlas", std::process::id()));
+        let mut atlas = Atlas::new();
+        atlas.add_image(&path).unwrap();
+        atlas.pack().unwrap();
+        atlas.write(&output).unwrap();
+
+        let file = reader::read(&output, None).unwrap();
+        std::fs::remove_file(&output).ok();
+        let sprite = &unpack::extract(&file).unwrap()[0];
+        // The outline grows the sprite by its width on every side, its disk missing the corners
+        assert_eq!((sprite.width, sprite.height), (4, 4));
+        let px = |x: usize, y: usize| &sprite.data[(y * 4 + x) * 4..][..4];
+        assert_eq!((px(0, 0), px(1, 0), px(0, 2), px(1, 1)), (&[0; 4][..], &[255, 0, 0, 255][..], &[255, 0, 0, 255][..], &[255; 4][..]));
+
+        std::fs::write(Effects::sidecar(&path), "outline wide ff0000\n").unwrap();
+        assert!(Atlas::new().add_image(&path).is_err());
+        std::fs::remove_file(Effects::sidecar(&path)).ok();
+        std::fs::remove_file(&path).ok();
+    }
+
+
+    #[test]
+    fn texture_arrays_stack_one_sprite_per_layer() {
+        let output = std::env::temp_dir().join(format!("atlast-{}-array.atlas", std::process::id()));
+        // Layers need a KTX2 container, which the cli switches to for --texture-array
+        let mut atlas = AtlasBuilder::new().texture_format(TextureFormat::Ktx2).build();
+        atlas.texture_array = true;
+        for (name, color) in [("c", [0, 0, 255, 255]), ("a", [255, 0, 0, 255]), ("b", [0, 255, 0, 128])] {
+            atlas.add(Image::new(name, 4, 4, color.repeat(16)).unwrap());
+        }
+        atlas.pack().unwrap();
+        atlas.write(&output).unwrap();
+
+        let file = reader::read(&output, None).unwrap();
+        std::fs::remove_file(&output).ok();
+        assert_eq!((file.width, file.height, file.layers), (4, 4, 3));
+        // Layers follow the sprite names, every sprite covering all of its layer
+        let placed: Vec<_> = file.data.records.iter().map(|r| (r.name.as_str(), r.layer, r.x, r.y, r.width, r.height)).collect();
+        assert_eq!(placed, [("a", 0, 0, 0, 4, 4), ("b", 1, 0, 0, 4, 4), ("c", 2, 0, 0, 4, 4)]);
+        let sprites: Vec<_> = unpack::extract(&file).unwrap().into_iter().map(|sprite| sprite.data).collect();
+        assert_eq!(sprites, [[255, 0, 0, 255].repeat(16), [0, 255, 0, 128].repeat(16), [0, 0, 255, 255].repeat(16)]);
+
+        // The odd sprite out is named against the size most sprites share
+        atlas.add(Image::new("d", 4, 2, vec![255; 4 * 2 * 4]).unwrap());
+        let err = atlas.pack().unwrap_err().to_string();
+        assert_eq!(err, "could not place d: it is 4x2 but the texture array layers are 4x4");
+    }
+
+
+    #[test]
+    fn cube_faces_pack_as_crosses_or_ktx2_cubemaps() {
+        let output = std::env::temp_dir().join(format!("atlast-{}-cube.atlas", std::process::id()));
+        // Faces added out of order, each a color of its own
+        let faces = |atlas: &mut Atlas| {
+            for face in [5, 2, 0, 3, 1, 4] {
+                let name = format!("sky_{}", cubemap::FACES[face]);
+                atlas.add(Image::new(&name, 2, 2, [face as u8 * 40, 0, 0, 255].repeat(4)).unwrap());
+            }
+        };
+
+        for layout in [CubeLayout::Cross, CubeLayout::Ktx2] {
+            let format = if layout == CubeLayout::Ktx2 { TextureFormat::Ktx2 } else { TextureFormat::Png };
+            let mut atlas = AtlasBuilder::new().texture_format(format).build();
+            atlas.cubemap = Some(layout);
+            faces(&mut atlas);
+            atlas.pack().unwrap();
+            atlas.write(&output).unwrap();
+
+            let file = reader::read(&output, None).unwrap();
+            let names: Vec<String> = cubemap::FACES.iter().map(|face| format!("sky_{}", face)).collect();
+            assert_eq!(file.data.cubes.len(), 1);
+            assert_eq!((file.data.cubes[0].name.as_str(), &file.data.cubes[0].faces), ("sky", &names));
+            let placed: Vec<_> = file.data.records.iter().map(|r| (r.name.clone(), r.layer, r.x, r.y)).collect();
+            match layout {
+                // +Y over -X, +Z, +X, -Z over -Y
+                CubeLayout::Cross => {
+                    assert_eq!((file.width, file.height, file.layers), (8, 6, 0));
+                    let cross = cubemap::CROSS.iter().zip(&names).map(|(&(col, row), name)| (name.clone(), 0, col * 2, row * 2));
+                    assert_eq!(placed, cross.collect::<Vec<_>>());
+                }
+                CubeLayout::Ktx2 => {
+                    assert_eq!((file.width, file.height, file.layers), (2, 2, 6));
+                    assert_eq!(placed, (0..6).map(|face| (names[face].clone(), face as u32, 0, 0)).collect::<Vec<_>>());
+                }
+            }
+            for (face, sprite) in unpack::extract(&file).unwrap().iter().enumerate() {
+                assert_eq!(sprite.data, [face as u8 * 40, 0, 0, 255].repeat(4), "{:?} face {}", layout, face);
+            }
+        }
+        std::fs::remove_file(&output).ok();
+
+        let mut atlas = Atlas::new();
+        atlas.cubemap = Some(CubeLayout::Cross);
+        faces(&mut atlas);
+        atlas.images.retain(|image| image.name != "sky_nz");
+        assert_eq!(atlas.pack().unwrap_err().to_string(), "could not place sky: it has no _nz face");
+    }
+
+
+    #[test]
+    fn udim_tiles_hold_what_fits_on_each_page() {
+        let output = std::env::temp_dir().join(format!("atlast-{}-udim.atlas", std::process::id()));
+        let mut atlas = AtlasBuilder::new().page_size(8).build();
+        // Only one sprite fits on every 8x8 tile
+        for (name, color) in [("a", [255, 0, 0, 255]), ("b", [0, 255, 0, 255]), ("c", [0, 0, 255, 255])] {
+            atlas.add(Image::new(name, 8, 6, color.repeat(8 * 6)).unwrap());
+        }
+        atlas.pack().unwrap();
+        atlas.write(&output).unwrap();
+
+        let file = reader::read(&output, None).unwrap();
+        std::fs::remove_file(&output).ok();
+        assert_eq!((file.width, file.height, file.pages), (8, 8, 3));
+        let pages: Vec<_> = file.data.pages.iter().map(|page| (page.stem.as_str(), page.tile, page.u_offset, page.v_offset)).collect();
+        assert_eq!(pages, [("atlas.1001", 1001, 0, 0), ("atlas.1002", 1002, 1, 0), ("atlas.1003", 1003, 2, 0)]);
+        let mut placed: Vec<_> = file.data.records.iter().map(|r| r.page).collect();
+        placed.sort();
+        assert_eq!(placed, [0, 1, 2]);
+        // Texture coordinates point into the tile of the sprite's page
+        assert!(validate::check(&file).is_empty());
+        for (record, sprite) in file.data.records.iter().zip(unpack::extract(&file).unwrap()) {
+            let color = match record.name.as_str() { "a" => [255, 0, 0, 255], "b" => [0, 255, 0, 255], _ => [0, 0, 255, 255] };
+            assert_eq!(sprite.data, color.repeat(8 * 6), "{}", record.name);
+        }
+
+        let mut atlas = AtlasBuilder::new().page_size(8).build();
+        atlas.add(Image::new("wide", 9, 2, vec![255; 9 * 2 * 4]).unwrap());
+        assert!(atlas.pack().is_err());
+    }
+
+
+    #[test]
+    fn virtual_tiles_share_identical_entries_and_skip_transparent_ones() {
+        let output = std::env::temp_dir().join(format!("atlast-{}-tiles.atlas", std::process::id()));
+        let mut atlas = Atlas::new();
+        atlas.fixed_width = Some(16);
+        atlas.tile_size = Some(4);
+        // Two red tiles, a blue one and a transparent column left over
+        for (name, color) in [("a", [255, 0, 0, 255]), ("b", [255, 0, 0, 255]), ("c", [0, 0, 255, 255])] {
+            atlas.add(Image::new(name, 4, 4, color.repeat(16)).unwrap());
+        }
+        atlas.pack().unwrap();
+        atlas.write(&output).unwrap();
+
+        let file = reader::read(&output, None).unwrap();
+        let entries = zip::ZipArchive::new(std::fs::File::open(&output).unwrap()).unwrap()
+            .file_names()
+            .filter(|name| name.starts_with("tile."))
+            .count();
+        std::fs::remove_file(&output).ok();
+        let table = file.data.tiles.as_ref().unwrap();
+        assert_eq!((table.size, table.columns, table.rows, table.entries, entries), (4, 4, 1, 2, 2));
+        let mut shared = table.table.clone();
+        shared.sort();
+        assert_eq!(shared, [None, Some(0), Some(0), Some(1)]);
+
+        // The tiles reassemble into the whole atlas
+        assert_eq!((file.width, file.height), (16, 4));
+        for (record, sprite) in file.data.records.iter().zip(unpack::extract(&file).unwrap()) {
+            let color = if record.name == "c" { [0, 0, 255, 255] } else { [255, 0, 0, 255] };
+            assert_eq!(sprite.data, color.repeat(16), "{}", record.name);
+        }
+    }
+
+
+    #[test]
+    fn encrypted_atlases_only_open_with_their_key() {
+        let output = std::env::temp_dir().join(format!("atlast-{}-crypt.atlas", std::process::id()));
+        let key = crypt::parse_key(&"0123456789abcdef".repeat(4)).unwrap();
+        let mut atlas = Atlas::new();
+        atlas.key = Some(key);
+        atlas.add(Image::new("a", 2, 2, [10, 20, 30, 255].repeat(4)).unwrap());
+        atlas.pack().unwrap();
+        atlas.write(&output).unwrap();
+
+        let bytes = std::fs::read(&output).unwrap();
+        assert!(crypt::is_encrypted(&bytes));
+        let file = reader::read(&output, Some(&key)).unwrap();
+        assert_eq!(unpack::extract(&file).unwrap()[0].data, [10, 20, 30, 255].repeat(4));
+        assert_eq!(reader::read_bytes(&bytes, Some(&key)).unwrap().pixels, file.pixels);
+
+        let wrong = crypt::parse_key(&"f".repeat(64)).unwrap();
+        let err = |key: Option<&crypt::Key>| reader::read(&output, key).err().map(|err| err.to_string());
+        assert_eq!(err(None).as_deref(), Some("atlas is encrypted and no key was given"));
+        assert_eq!(err(Some(&wrong)).as_deref(), Some("wrong key or corrupted atlas"));
+
+        // The nonce comes from the key and the archive, so writing again gives the same file
+        atlas.write(&output).unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), bytes);
+        std::fs::remove_file(&output).ok();
+        assert!(crypt::parse_key("0123").is_err());
+    }
+
+    #[test]
+    fn padding_stays_transparent_and_only_separates_sprites() {
+        for packer in Packer::ALL {
+            let mut atlas = AtlasBuilder::new().padding(4).packer(packer).build();
+            for (name, width, height) in [("a", 1, 1), ("b", 1, 5), ("c", 4, 6), ("d", 4, 6)] {
+                atlas.add(Image::new(name, width, height, [255, 0, 0, 255].repeat((width * height) as usize)).unwrap());
+            }
+            atlas.pack().unwrap();
+
+            // The width picked from the padded area is cut back to the columns the sprites reach
+            assert_eq!((atlas.width(), atlas.height()), (12, 15), "{:?}", packer);
+            let records = atlas.records();
+            for (i, a) in records.iter().enumerate() {
+                for b in records.iter().skip(i + 1) {
+                    assert!(a.x + a.width + 4 <= b.x || b.x + b.width + 4 <= a.x || a.y + a.height + 4 <= b.y || b.y + b.height + 4 <= a.y,
+                            "{:?}: {} is too close to {}", packer, a.name, b.name);
+                }
+            }
+
+            // Every texel is a sprite's or transparent padding
+            let (width, height, pixels) = atlas.compose();
+            for (y, x) in (0..height).flat_map(|y| (0..width).map(move |x| (y, x))) {
+                let inside = records.iter().any(|r| (r.x..r.x + r.width).contains(&x) && (r.y..r.y + r.height).contains(&y));
+                let alpha = pixels[((y * width + x) * 4 + 3) as usize];
+                assert_eq!(alpha, if inside { 255 } else { 0 }, "{:?} at {},{}", packer, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn extrusion_repeats_the_edge_texels_up_to_the_padding() {
+        // Every texel of every sprite has its own color
+        let sprites = [("a", 3, 2), ("b", 2, 3), ("c", 1, 1)];
+        let texel = |sprite: usize, col: u32, row: u32| [sprite as u8 + 1, col as u8 * 40 + 10, row as u8 * 40 + 10, 255];
+        for packer in Packer::ALL {
+            let mut atlas = AtlasBuilder::new().padding(1).extrude(2).packer(packer).build();
+            for (i, &(name, width, height)) in sprites.iter().enumerate() {
+                let data = (0..height).flat_map(|row| (0..width).flat_map(move |col| texel(i, col, row))).collect();
+                atlas.add(Image::new(name, width, height, data).unwrap());
+            }
+            atlas.pack().unwrap();
+
+            // Texels within two of a sprite copy its nearest texel, the padding between extrusions stays transparent
+            let records = atlas.records();
+            let (width, height, pixels) = atlas.compose();
+            for (y, x) in (0..height as i64).flat_map(|y| (0..width as i64).map(move |x| (y, x))) {
+                let expected = records.iter()
+                    .find(|r| (r.x as i64 - 2..(r.x + r.width) as i64 + 2).contains(&x) && (r.y as i64 - 2..(r.y + r.height) as i64 + 2).contains(&y))
+                    .map_or([0; 4], |r| {
+                        let sprite = sprites.iter().position(|&(name, _, _)| name == r.name).unwrap();
+                        let col = (x - r.x as i64).clamp(0, r.width as i64 - 1) as u32;
+                        let row = (y - r.y as i64).clamp(0, r.height as i64 - 1) as u32;
+                        texel(sprite, col, row)
+                    });
+                let at = ((y * width as i64 + x) * 4) as usize;
+                assert_eq!(pixels[at..at + 4], expected, "{:?} at {},{}", packer, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn single_channel_inputs_pack_into_a_grayscale_png() {
+        use std::io::Read;
+
+        let dir = std::env::temp_dir().join(format!("atlast-{}-grayscale", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, width, data) in [("mask.png", 2, vec![10, 200]), ("dot.png", 1, vec![90])] {
+            let mut bytes = Vec::new();
+            let mut encoder = png::Encoder::new(&mut bytes, width, 1);
+            encoder.set_color(png::ColorType::Grayscale);
+            encoder.write_header().unwrap().write_image_data(&data).unwrap();
+            std::fs::write(dir.join(name), bytes).unwrap();
+        }
+        let color_type = |atlas: &Path| {
+            let mut zip = zip::ZipArchive::new(File::open(atlas).unwrap()).unwrap();
+            let mut png = Vec::new();
+            zip.by_name("atlas.png").unwrap().read_to_end(&mut png).unwrap();
+            png::Decoder::new(png.as_slice()).read_info().unwrap().0.color_type
+        };
+
+        let output = dir.join("gray.atlas");
+        let mut atlas = Atlas::new();
+        atlas.add_dir(&dir).unwrap();
+        atlas.pack().unwrap();
+        atlas.write(&output).unwrap();
+        assert_eq!(color_type(&output), png::ColorType::Grayscale);
+
+        // The single channel reads back as gray RGBA
+        let file = reader::read(&output, None).unwrap();
+        let sprites = unpack::extract(&file).unwrap();
+        let mask = sprites.iter().find(|sprite| sprite.name == "mask.png").unwrap();
+        assert_eq!(mask.data, [10, 10, 10, 255, 200, 200, 200, 255]);
+
+        // One colored input keeps all four channels
+        write_png(dir.join("red.png").to_str().unwrap(), 1, 1, &[255, 0, 0, 255]).unwrap();
+        let output = dir.join("color.atlas");
+        let mut atlas = Atlas::new();
+        atlas.add_dir(&dir).unwrap();
+        atlas.pack().unwrap();
+        atlas.write(&output).unwrap();
+        assert_eq!(color_type(&output), png::ColorType::RGBA);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn degenerate_images_follow_the_placeholder_policy_or_fail() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-degenerate", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dot = dir.join("dot.png");
+        write_png(dot.to_str().unwrap(), 1, 1, &[255; 4]).unwrap();
+
+        let added = |placeholders| {
+            let mut atlas = Atlas::new();
+            atlas.placeholders = placeholders;
+            atlas.add_image(&dot).map(|_| atlas.images.len()).map_err(|err| err.to_string())
+        };
+        assert_eq!(added(Placeholders::Keep), Ok(1));
+        assert_eq!(added(Placeholders::Skip), Ok(0));
+        assert!(added(Placeholders::Reject).unwrap_err().contains("1x1 placeholder"));
+
+        // A header claiming 0x4 pixels, with the CRC png checks
+        let crc = |data: &[u8]| !data.iter().fold(!0u32, |crc, &byte| {
+            (0..8).fold(crc ^ byte as u32, |crc, _| if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 })
+        });
+        let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+        let ihdr = [0u32.to_be_bytes(), 4u32.to_be_bytes()].concat();
+        for (kind, data) in [(b"IHDR", [ihdr.as_slice(), &[8, 6, 0, 0, 0]].concat()), (b"IDAT", vec![0x78, 0x9c, 3, 0, 0, 0, 0, 1]), (b"IEND", vec![])] {
+            let chunk = [kind.as_slice(), &data].concat();
+            bytes.extend((data.len() as u32).to_be_bytes());
+            bytes.extend(&chunk);
+            bytes.extend(crc(&chunk).to_be_bytes());
+        }
+        let empty = dir.join("empty.png");
+        std::fs::write(&empty, bytes).unwrap();
+        let err = Atlas::new().add_image(&empty).unwrap_err();
+        assert!(matches!(err, AtlasError::Decode(..)), "{:?}", err);
+        assert!(err.to_string().contains("zero-size image (0x4)"), "{}", err);
+        assert_eq!(err.path(), Some(empty.as_path()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn oversized_sources_are_listed_largest_first() {
+        let mut atlas = Atlas::new();
+        atlas.max_sprite_size = 32;
+        atlas.images.extend([image(20, 20), image(4, 4), image(64, 8), image(4, 4), image(4, 4)]);
+        let names = |atlas: &Atlas| atlas.oversized().iter().map(|image| image.name.clone()).collect::<Vec<_>>();
+
+        // 64 wide is over the size, the 20x20 sprite is 400 of the 960 texels
+        assert_eq!(names(&atlas), ["64x8"]);
+        atlas.max_sprite_share = Some(0.4);
+        assert_eq!(names(&atlas), ["64x8", "20x20"]);
+        atlas.max_sprite_share = Some(0.5);
+        assert_eq!(names(&atlas), ["64x8"]);
+    }
+
+
+    #[test]
+    fn duplicate_names_list_every_source_and_renames_stay_unique() {
+        let mut atlas = Atlas::new();
+        for path in ["b/icon.png", "a/icon.png", "a/other.png", "c/icon.png"] {
+            let mut img = image(1, 1);
+            img.name = Path::new(path).file_name().unwrap().to_string_lossy().into_owned();
+            img.path = PathBuf::from(path);
+            atlas.images.push(img);
+        }
+
+        // Both source paths are listed, in path order, so the diagnostic is stable
+        let duplicates = atlas.duplicate_names();
+        let icons = vec![PathBuf::from("a/icon.png"), PathBuf::from("b/icon.png"), PathBuf::from("c/icon.png")];
+        assert_eq!(duplicates, [("icon.png".to_string(), icons)]);
+
+        // Renaming skips names that are already taken
+        atlas.images.push(Image { name: "icon~2.png".to_string(), path: PathBuf::from("d/icon~2.png"), ..image(1, 1) });
+        assert_eq!(atlas.rename_image(Path::new("b/icon.png")), "icon~3.png");
+        assert_eq!(atlas.rename_image(Path::new("c/icon.png")), "icon~4.png");
+        assert!(atlas.duplicate_names().is_empty());
+    }
+
+
+    #[test]
+    fn names_are_recorded_in_nfc_unless_normalizing_is_off() {
+        let mut atlas = Atlas::new();
+        atlas.name_root = Some(PathBuf::from("art"));
+
+        // "é" decomposed, as macOS stores it, and precomposed, as Windows does
+        let decomposed = Path::new("art/ui/cafe\u{301}.png");
+        let precomposed = Path::new("art/ui/caf\u{e9}.png");
+        assert_eq!(atlas.image_name(decomposed), "ui/caf\u{e9}.png");
+        assert_eq!(atlas.image_name(decomposed), atlas.image_name(precomposed));
+
+        atlas.normalize_names = false;
+        assert_eq!(atlas.image_name(decomposed), "ui/cafe\u{301}.png");
+        assert_ne!(atlas.image_name(decomposed), atlas.image_name(precomposed));
+    }
+
+
+    #[test]
+    fn prioritized_sprites_are_pinned_to_the_first_page() {
+        let mut atlas = AtlasBuilder::new().max_width(16).max_height(16).build();
+        atlas.priorities.push((globset::Glob::new("hud/**").unwrap().compile_matcher(), 1));
+        for name in ["a", "b", "c", "d", "hud/health"] {
+            atlas.images.push(Image { name: name.to_string(), path: PathBuf::from("art").join(name), ..image(8, 8) });
+        }
+        atlas.assign_priorities(Path::new("art"));
+        atlas.pack().unwrap();
+
+        let page = |name: &str| atlas.records().into_iter().find(|record| record.name == name).unwrap().page;
+        assert_eq!(page("hud/health"), 0);
+        assert_eq!(atlas.records().iter().filter(|record| record.page == 0).count(), 4);
+
+        // More pinned sprites than the first page holds is an error, not a silent spill
+        let mut atlas = AtlasBuilder::new().max_width(16).max_height(16).build();
+        for i in 0..5 {
+            atlas.images.push(Image { name: format!("hud/{}", i), priority: 1, ..image(8, 8) });
+        }
+        let err = atlas.pack().unwrap_err().to_string();
+        assert!(err.ends_with("it has priority 1 but page 0 is full"), "{}", err);
+    }
+
+
+    #[test]
+    fn appending_keeps_every_sprite_in_place_and_packs_new_ones_around_them() {
+        let mut shipped = Atlas::new();
+        shipped.add(Image::new("a", 8, 8, [255, 0, 0, 255].repeat(8 * 8)).unwrap());
+        shipped.add(Image::new("b", 4, 4, [0, 0, 255, 255].repeat(4 * 4)).unwrap());
+        shipped.pack().unwrap();
+        let path = std::env::temp_dir().join(format!("atlast-{}-append-only.atlas", std::process::id()));
+        shipped.write(&path).unwrap();
+        let before = shipped.records();
+        let (width, height, _) = shipped.compose();
+
+        let mut atlas = Atlas::new();
+        atlas.add(Image::new("a", 2, 2, [0; 2 * 2 * 4].to_vec()).unwrap());
+        atlas.add(Image::new("c", 4, 4, [0, 255, 0, 255].repeat(4 * 4)).unwrap());
+        atlas.add(Image::new("d", 8, 8, [0, 255, 0, 255].repeat(8 * 8)).unwrap());
+        // Inputs named like a kept sprite are dropped
+        assert_eq!(atlas.keep_existing(&path).unwrap(), 1);
+        std::fs::remove_file(&path).unwrap();
+        atlas.pack().unwrap();
+
+        let records = atlas.records();
+        for kept in before.iter() {
+            let record = records.iter().find(|record| record.name == kept.name).unwrap();
+            assert_eq!((record.x, record.y, record.width, record.height), (kept.x, kept.y, kept.width, kept.height));
+        }
+        for (i, a) in records.iter().enumerate() {
+            for b in records[i + 1..].iter() {
+                let apart = a.x + a.width <= b.x || b.x + b.width <= a.x || a.y + a.height <= b.y || b.y + b.height <= a.y;
+                assert!(apart, "{} overlaps {}", a.name, b.name);
+            }
+        }
+
+        // The width stays, the height grows only as far as the new sprites need
+        let (new_width, new_height, pixels) = atlas.compose();
+        assert_eq!(new_width, width);
+        assert!(new_height > height && new_height <= height + 8, "{} to {}", height, new_height);
+        let a = records.iter().find(|record| record.name == "a").unwrap();
+        assert_eq!(pixels[((a.y * new_width + a.x) * 4) as usize..][..4], [255, 0, 0, 255]);
+    }
+
+
+    #[test]
+    fn layers_are_flattened_over_each_other_in_suffix_order() {
+        let layer = |name: &str, dir: &str, size: u32, px: [u8; 4]| Image {
+            name: name.to_string(),
+            path: PathBuf::from(dir).join(name.rsplit('/').next().unwrap()),
+            ..Image::new(name, size, size, px.repeat((size * size) as usize)).unwrap()
+        };
+        let mut atlas = Atlas::new();
+        // Out of order, the suffixes decide the stacking and not the input order
+        atlas.add(layer("chars/hero__2_armor.png", "art/chars", 2, [0, 0, 255, 128]));
+        atlas.add(layer("chars/hero__1_base.png", "art/chars", 2, [255, 0, 0, 255]));
+        atlas.add(layer("chars/ogre__1_base.png", "art/chars", 2, [0; 4]));
+        atlas.add(layer("chars/ogre__2_club.png", "art/chars", 4, [0; 4]));
+        atlas.add(layer("chars/plain.png", "art/chars", 2, [0; 4]));
+
+        let failed = atlas.composite_layers();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, Path::new("art/chars/ogre.png"));
+        assert!(failed[0].1.contains("is 4x4 but chars/ogre__1_base.png is 2x2"), "{}", failed[0].1);
+
+        let mut names: Vec<&str> = atlas.images.iter().map(|img| img.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, ["chars/hero.png", "chars/plain.png"]);
+        let hero = atlas.images.iter().find(|img| img.name == "chars/hero.png").unwrap();
+        assert_eq!((hero.width, hero.height), (2, 2));
+        assert_eq!(hero.data[..4], effects::over([0, 0, 255, 128], [255, 0, 0, 255]));
+        assert_eq!(hero.data[..4], [127, 0, 128, 255]);
+    }
+
+
+    #[test]
+    fn every_supported_format_is_decoded_to_rgba() {
+        let dir = std::env::temp_dir().join(format!("atlast-{}-formats", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let red = image::RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255]));
+        for name in ["a.bmp", "b.gif", "c.tga", "d.jpg", "E.JPEG"] {
+            image::DynamicImage::ImageRgba8(red.clone()).to_rgb8().save(dir.join(name)).unwrap();
+        }
+        let gray = image::GrayImage::from_pixel(4, 4, image::Luma([128]));
+        gray.save(dir.join("f.jpg")).unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a sprite").unwrap();
+
+        let mut atlas = Atlas::new();
+        assert_eq!(atlas.add_dir(&dir).unwrap(), 6);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        for image in atlas.images.iter() {
+            assert_eq!((image.width, image.height, image.data.len()), (4, 4, 4 * 4 * 4), "{}", image.name);
+            // Jpeg is lossy, the rest is exact
+            let expected = if image.name == "f.jpg" { [128, 128, 128, 255] } else { [255, 0, 0, 255] };
+            assert!(image.data.chunks(4).all(|px| px.iter().zip(expected).all(|(&got, want)| got.abs_diff(want) <= 2)), "{}", image.name);
+        }
+        let gray: Vec<&str> = atlas.images.iter().filter(|image| image.grayscale).map(|image| image.name.as_str()).collect();
+        assert_eq!(gray, ["f.jpg"]);
+    }
+
+
+    #[test]
+    fn json_format_writes_atlas_json_instead_of_atlas_data() {
+        use std::io::Read;
+
+        let output = std::env::temp_dir().join(format!("atlast-{}-json.atlas", std::process::id()));
+        for pretty in [false, true] {
+            let mut atlas = AtlasBuilder::new().data_format(DataFormat::Json).pretty(pretty).build();
+            atlas.add(image(2, 2));
+            atlas.pack().unwrap();
+            atlas.write(&output).unwrap();
+
+            let mut zip = zip::ZipArchive::new(File::open(&output).unwrap()).unwrap();
+            assert!(zip.by_name(reader::DATA_ENTRY).is_err());
+            let mut json = String::new();
+            zip.by_name(reader::JSON_ENTRY).unwrap().read_to_string(&mut json).unwrap();
+            assert_eq!(json.contains("\n  \""), pretty);
+            let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+            assert_eq!(value["records"][0]["name"], "2x2");
+        }
+        std::fs::remove_file(&output).unwrap();
+    }
+}
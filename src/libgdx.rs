@@ -0,0 +1,62 @@
+// Atlas data in the libGDX TextureAtlas text format, also read by the Spine runtimes
+//
+// Each page names its image followed by its regions. Regions are named without their extension,
+// `size` and `orig` are the packed and source sizes and `offset` is measured from the bottom left
+// corner of the source, as libGDX has its y axis up. Animation frames are named after their
+// animation and numbered by `index`, so `findRegions` returns them in order. Nine-slice borders are
+// written as `split` in libGDX's left, right, top, bottom order for `NinePatch`.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::{strip_extension, AtlasData};
+
+pub const ENTRY: &str = "atlas.atlas";
+
+// The pack file for `data`, its images having `extension`
+pub fn write(data: &AtlasData, extension: &str) -> String {
+    let pages: Vec<String> = if data.pages.is_empty() {
+        vec![format!("atlas.{}", extension)]
+    } else {
+        data.pages.iter().map(|page| format!("{}.{}", page.stem, extension)).collect()
+    };
+
+    let frames: HashMap<&str, (&str, usize)> = data.animations.iter()
+        .flat_map(|animation| animation.frames.iter()
+            .enumerate()
+            .map(|(index, frame)| (frame.as_str(), (animation.name.as_str(), index))))
+        .collect();
+
+    let mut text = String::new();
+    for (index, image) in pages.iter().enumerate() {
+        // A blank line starts every page
+        writeln!(text).unwrap();
+        writeln!(text, "{}", image).unwrap();
+        writeln!(text, "size: {}, {}", data.width, data.height).unwrap();
+        writeln!(text, "format: RGBA8888").unwrap();
+        writeln!(text, "filter: Linear, Linear").unwrap();
+        writeln!(text, "repeat: none").unwrap();
+        if data.premultiplied_alpha {
+            writeln!(text, "pma: true").unwrap();
+        }
+
+        for record in data.records.iter().filter(|record| record.page as usize == index) {
+            let bottom = record.source_height - record.offset_y - record.height;
+            let (name, frame) = match frames.get(record.name.as_str()) {
+                Some(&(animation, frame)) => (animation, frame as i64),
+                None => (strip_extension(&record.name), -1)
+            };
+            writeln!(text, "{}", name).unwrap();
+            writeln!(text, "  rotate: false").unwrap();
+            writeln!(text, "  xy: {}, {}", record.x, record.y).unwrap();
+            writeln!(text, "  size: {}, {}", record.width, record.height).unwrap();
+            if let Some(borders) = record.nine_slice {
+                writeln!(text, "  split: {}, {}, {}, {}", borders.left, borders.right, borders.top, borders.bottom).unwrap();
+            }
+            writeln!(text, "  orig: {}, {}", record.source_width, record.source_height).unwrap();
+            writeln!(text, "  offset: {}, {}", record.offset_x, bottom).unwrap();
+            writeln!(text, "  index: {}", frame).unwrap();
+        }
+    }
+    text
+}
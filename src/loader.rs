@@ -0,0 +1,180 @@
+// Opening packed atlases at runtime, for games that draw from them
+//
+// The image is decoded to RGBA8 whatever it was stored as, and sprites are looked up by name along
+// with their texture coordinates. `LoadedAtlas::from_bytes` reads an atlas embedded with
+// `include_bytes!` in place, only the decoded pixels and the records are allocated.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::inspect;
+use crate::reader::{self, AtlasFile, ReadError};
+use crate::{AnimationRecord, AtlasRecord};
+
+pub struct LoadedAtlas {
+    width: u32,
+    height: u32,
+    // Pages or texture array layers of `width` x `height`, 1 for a single image
+    images: u32,
+    // The pixels' colors are multiplied by their alpha
+    premultiplied_alpha: bool,
+    pixels: Vec<u8>,
+    sprites: Vec<AtlasRecord>,
+    animations: Vec<AnimationRecord>,
+    // Left, top, right and bottom of every sprite in texture coordinates
+    uvs: Vec<[f32; 4]>,
+    // Position of every sprite by name
+    index: HashMap<String, usize>
+}
+
+// Open the atlas at `path`
+pub fn load(path: &Path) -> Result<LoadedAtlas, ReadError> {
+    Ok(LoadedAtlas::new(reader::read(path, None)?))
+}
+
+// Open the atlas at `path` once every entry matches the hash stored with it, failing with
+// `ReadError::Tampered` naming the entries that do not, or `ReadError::Unsealed` when it has no hashes
+pub fn load_verified(path: &Path) -> Result<LoadedAtlas, ReadError> {
+    LoadedAtlas::from_verified_bytes(&std::fs::read(path)?)
+}
+
+impl LoadedAtlas {
+    fn new(file: AtlasFile) -> LoadedAtlas {
+        let uvs = inspect::inspect(&file.data).sprites.iter()
+            .map(|sprite| sprite.uv.map(|uv| uv as f32))
+            .collect();
+        let index = file.data.records.iter()
+            .enumerate()
+            .map(|(i, record)| (record.name.clone(), i))
+            .collect();
+
+        LoadedAtlas {
+            width: file.width,
+            height: file.height,
+            images: file.layers.max(file.pages).max(1),
+            premultiplied_alpha: file.data.premultiplied_alpha,
+            pixels: file.pixels,
+            sprites: file.data.records,
+            animations: file.data.animations,
+            uvs,
+            index
+        }
+    }
+
+    // Read an atlas held in memory
+    pub fn from_bytes(bytes: &[u8]) -> Result<LoadedAtlas, ReadError> {
+        Ok(LoadedAtlas::new(reader::read_bytes(bytes, None)?))
+    }
+
+    // Read an atlas held in memory after checking it against its hashes
+    pub fn from_verified_bytes(bytes: &[u8]) -> Result<LoadedAtlas, ReadError> {
+        reader::verify_bytes(bytes, None)?;
+        LoadedAtlas::from_bytes(bytes)
+    }
+
+    // Read an atlas encrypted with --key
+    pub fn from_encrypted_bytes(bytes: &[u8], key: &[u8; 32]) -> Result<LoadedAtlas, ReadError> {
+        Ok(LoadedAtlas::new(reader::read_bytes(bytes, Some(key))?))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn images(&self) -> u32 {
+        self.images
+    }
+
+    // Whether the atlas was packed with --premultiply-alpha, so it is drawn with ONE, ONE_MINUS_SRC_ALPHA
+    // blending rather than SRC_ALPHA, ONE_MINUS_SRC_ALPHA
+    pub fn premultiplied_alpha(&self) -> bool {
+        self.premultiplied_alpha
+    }
+
+    // RGBA8 pixels, pages and layers stacked top to bottom
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AtlasRecord> {
+        self.index.get(name).map(|&i| &self.sprites[i])
+    }
+
+    // Left, top, right and bottom of the sprite's rect in texture coordinates, UDIM tiles offset by
+    // their tile. The rect of a rotated sprite holds it turned 90° clockwise.
+    pub fn get_uv(&self, name: &str) -> Option<[f32; 4]> {
+        self.index.get(name).map(|&i| self.uvs[i])
+    }
+
+    // Frames and frame duration of an animation grouped with --animations
+    pub fn animation(&self, name: &str) -> Option<&AnimationRecord> {
+        self.animations.iter().find(|animation| animation.name == name)
+    }
+
+    pub fn animations(&self) -> &[AnimationRecord] {
+        &self.animations
+    }
+
+    // Every sprite with its texture coordinates, in the order they were packed
+    pub fn iter(&self) -> impl Iterator<Item = (&AtlasRecord, [f32; 4])> {
+        self.sprites.iter().zip(self.uvs.iter().copied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Write};
+
+    use zip::write::FileOptions;
+    use zip::{ZipArchive, ZipWriter};
+
+    use crate::{load_verified, AtlasBuilder, Image, LoadedAtlas, ReadError};
+
+    #[test]
+    fn verified_loads_reject_modified_entries() {
+        let path = std::env::temp_dir().join(format!("atlast-{}-load-verified.atlas", std::process::id()));
+        let mut atlas = AtlasBuilder::new().build();
+        atlas.add(Image::new("a", 2, 2, vec![255; 2 * 2 * 4]).unwrap());
+        atlas.pack().unwrap();
+        atlas.write(&path).unwrap();
+        assert_eq!(load_verified(&path).unwrap().len(), 1);
+
+        // The same archive with one more byte in its data entry
+        let mut sealed = ZipArchive::new(Cursor::new(std::fs::read(&path).unwrap())).unwrap();
+        let mut tampered = ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..sealed.len() {
+            let mut entry = sealed.by_index(i).unwrap();
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents).unwrap();
+            if entry.name() == "atlas.data" {
+                contents.push(0);
+            }
+            tampered.start_file(entry.name(), FileOptions::default()).unwrap();
+            tampered.write_all(&contents).unwrap();
+        }
+        let tampered = tampered.finish().unwrap().into_inner();
+        std::fs::write(&path, &tampered).unwrap();
+
+        // The unchecked load still reads it, trailing bytes and all
+        assert!(LoadedAtlas::from_bytes(&tampered).is_ok());
+        for result in [load_verified(&path), LoadedAtlas::from_verified_bytes(&tampered)] {
+            match result {
+                Err(ReadError::Tampered(problems)) => assert_eq!(problems, ["atlas.data does not match its hash"]),
+                result => panic!("expected a tampered atlas, got {:?}", result.err())
+            }
+        }
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,111 @@
+// MaxRects bin packing with the best short side fit heuristic
+//
+// The bin keeps every maximal free rectangle, possibly overlapping each other. A sprite goes
+// into the free rectangle leaving the least room along its shorter side, then every free
+// rectangle it overlaps is split into the up to four maximal rectangles around it.
+
+use crate::{Bin, Rect};
+
+pub struct MaxRects {
+    free: Vec<Rect>
+}
+
+impl MaxRects {
+    pub fn new(width: u32, height: u32) -> MaxRects {
+        MaxRects {
+            free: vec![Rect { x: 0, y: 0, width, height }]
+        }
+    }
+
+    // Best short side fit, ties broken by long side fit and then top-left position
+    fn fit(&self, width: u32, height: u32) -> Option<((u32, u32, u32, u32), Rect)> {
+        self.free.iter()
+            .filter(|free| free.width >= width && free.height >= height)
+            .map(|free| {
+                let (dx, dy) = (free.width - width, free.height - height);
+                ((dx.min(dy), dx.max(dy), free.y, free.x), Rect { x: free.x, y: free.y, width, height })
+            })
+            .min_by_key(|(score, _)| *score)
+    }
+}
+
+impl Bin for MaxRects {
+    fn insert(&mut self, width: u32, height: u32, rotate: bool) -> Option<Rect> {
+        let turned = if rotate && width != height { self.fit(height, width) } else { None };
+        let (_, rect) = self.fit(width, height).into_iter().chain(turned).min_by_key(|(score, _)| *score)?;
+        self.occupy(&rect);
+        Some(rect)
+    }
+
+    // Remove `used` from the free space
+    fn occupy(&mut self, used: &Rect) {
+        let mut split = Vec::new();
+        self.free.retain(|free| {
+            if !free.intersects(used) {
+                return true;
+            }
+
+            if used.x > free.x {
+                split.push(Rect { width: used.x - free.x, ..*free });
+            }
+            if used.x + used.width < free.x + free.width {
+                split.push(Rect { x: used.x + used.width, width: free.x + free.width - used.x - used.width, ..*free });
+            }
+            if used.y > free.y {
+                split.push(Rect { height: used.y - free.y, ..*free });
+            }
+            if used.y + used.height < free.y + free.height {
+                split.push(Rect { y: used.y + used.height, height: free.y + free.height - used.y - used.height, ..*free });
+            }
+            false
+        });
+        self.free.extend(split);
+
+        // Drop free rectangles contained in another one
+        let mut i = 0;
+        while i < self.free.len() {
+            let contained = self.free.iter().enumerate()
+                .any(|(j, other)| j != i && other.contains(&self.free[i]) && (other != &self.free[i] || j < i));
+            if contained {
+                self.free.swap_remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MaxRects;
+    use crate::{Bin, Rect};
+
+    #[test]
+    fn sprites_fill_the_bin_without_overlap() {
+        let mut bin = MaxRects::new(8, 8);
+        let rects: Vec<Rect> = [(4, 4), (4, 4), (4, 2), (2, 2), (2, 2), (4, 4)].iter()
+            .map(|&(width, height)| bin.insert(width, height, false).unwrap())
+            .collect();
+
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.x + a.width <= 8 && a.y + a.height <= 8, "{:?} leaves the bin", a);
+            for b in rects.iter().skip(i + 1) {
+                assert!(!a.intersects(b), "{:?} overlaps {:?}", a, b);
+            }
+        }
+        // Together they cover all 64 texels, leaving room for nothing
+        assert_eq!(bin.insert(1, 1, false), None);
+    }
+
+    #[test]
+    fn the_free_rect_with_the_shortest_leftover_side_wins() {
+        let mut bin = MaxRects::new(10, 10);
+        bin.occupy(&Rect { x: 0, y: 0, width: 10, height: 3 });
+        bin.occupy(&Rect { x: 0, y: 3, width: 5, height: 3 });
+        // Free are 5x7 to the right and 10x4 at the bottom, a 5x3 sprite fits the right one exactly across
+        assert_eq!(bin.insert(5, 3, false), Some(Rect { x: 5, y: 3, width: 5, height: 3 }));
+        // A sprite that only fits turned is turned
+        assert_eq!(bin.insert(2, 10, true), Some(Rect { x: 0, y: 6, width: 10, height: 2 }));
+        assert_eq!(bin.insert(2, 10, false), None);
+    }
+}
@@ -0,0 +1,112 @@
+// Mip levels of the composed atlas, filtered so no sprite picks up its neighbors or the padding
+//
+// Every texel of the base level belongs to the sprite whose rect, extrusion included, covers it,
+// or to none. A texel of the next level belongs to the sprite owning most of the 2x2 texels under
+// it, sprites winning over padding, and averages only the texels of that sprite. Colors are
+// weighted by alpha so transparent texels don't darken the edges, and averaged evenly where all
+// texels are transparent so colors bled into them carry down. Sprites holding normal maps get their
+// averaged vectors scaled back to unit length. Filtering in linear light averages the colors of the
+// other sprites and the padding after decoding them from sRGB.
+
+use crate::color;
+use crate::normal;
+use crate::Rect;
+
+// Size of `level` for a base level of `size`
+pub fn level_size(size: (u32, u32), level: usize) -> (u32, u32) {
+    ((size.0 >> level).max(1), (size.1 >> level).max(1))
+}
+
+// Number of levels down to 1x1, the base level included
+pub fn level_count(size: (u32, u32)) -> usize {
+    (32 - size.0.max(size.1).max(1).leading_zeros()) as usize
+}
+
+// Owner of every texel of a `width` x `height` canvas, the index of its rect plus one or 0
+pub fn owners(width: u32, height: u32, rects: &[Rect], extrude: u32) -> Vec<u32> {
+    let mut owners = vec![0; (width * height) as usize];
+    for (i, rect) in rects.iter().enumerate() {
+        let (left, top) = (rect.x.saturating_sub(extrude), rect.y.saturating_sub(extrude));
+        let right = (rect.x + rect.width + extrude).min(width);
+        let bottom = (rect.y + rect.height + extrude).min(height);
+        for y in top..bottom {
+            for x in left..right {
+                owners[(y * width + x) as usize] = i as u32 + 1;
+            }
+        }
+    }
+    owners
+}
+
+// Every level below the base of the RGBA `pixels`, smallest last. `normals` tells by rect index
+// which sprites are normal maps, the colors of the rest are filtered in linear light if `linear`.
+pub fn chain(size: (u32, u32), pixels: &[u8], owners: &[u32], normals: &[bool], linear: bool) -> Vec<Vec<u8>> {
+    let normal = |owner: u32| owner.checked_sub(1).is_some_and(|rect| normals.get(rect as usize) == Some(&true));
+    let mut levels: Vec<Vec<u8>> = Vec::new();
+    let (mut pixels, mut owners) = (pixels.to_vec(), owners.to_vec());
+    for level in 1..level_count(size) {
+        let (width, height) = level_size(size, level - 1);
+        let (mut next_pixels, next_owners) = reduce((width, height), &pixels, &owners, |owner| linear && !normal(owner));
+        for (texel, &owner) in next_pixels.chunks_exact_mut(4).zip(next_owners.iter()) {
+            if normal(owner) {
+                normal::renormalize(texel);
+            }
+        }
+        levels.push(next_pixels.clone());
+        pixels = next_pixels;
+        owners = next_owners;
+    }
+    levels
+}
+
+// Halve a level, returning the pixels and owners of the next one. The colors of the owners `linear`
+// holds for are averaged in linear light.
+fn reduce(size: (u32, u32), pixels: &[u8], owners: &[u32], linear: impl Fn(u32) -> bool) -> (Vec<u8>, Vec<u32>) {
+    let (width, height) = size;
+    let (next_width, next_height) = level_size(size, 1);
+    let mut next_pixels = vec![0; (next_width * next_height * 4) as usize];
+    let mut next_owners = vec![0; (next_width * next_height) as usize];
+
+    for y in 0..next_height {
+        for x in 0..next_width {
+            // A side of 1 stays 1, and its texel is read twice
+            let texels: Vec<usize> = [(0, 0), (1, 0), (0, 1), (1, 1)].iter()
+                .map(|&(dx, dy)| (((2 * y + dy).min(height - 1)) * width + (2 * x + dx).min(width - 1)) as usize)
+                .collect();
+
+            let owner = texels.iter()
+                .map(|&i| owners[i])
+                .max_by_key(|&owner| (owner != 0, texels.iter().filter(|&&i| owners[i] == owner).count()))
+                .unwrap();
+
+            let linear = linear(owner);
+            let decode = |c: u8| if linear { color::to_linear(c) as f64 } else { c as f64 };
+            let encode = |c: f64| if linear { color::from_linear(c as f32) } else { c.round() as u8 };
+
+            let mut sum = [0.0; 3];
+            let mut plain = [0.0; 3];
+            let (mut alpha, mut count) = (0u64, 0u64);
+            for &i in texels.iter().filter(|&&i| owners[i] == owner) {
+                let texel_alpha = pixels[i * 4 + 3] as u64;
+                for channel in 0..3 {
+                    sum[channel] += decode(pixels[i * 4 + channel]) * texel_alpha as f64;
+                    plain[channel] += decode(pixels[i * 4 + channel]);
+                }
+                alpha += texel_alpha;
+                count += 1;
+            }
+
+            let next = ((y * next_width + x) * 4) as usize;
+            for channel in 0..3 {
+                next_pixels[next + channel] = match alpha {
+                    0 => encode(plain[channel] / count as f64),
+                    alpha => encode(sum[channel] / alpha as f64)
+                };
+            }
+            next_pixels[next + 3] = ((alpha + count / 2) / count) as u8;
+            next_owners[(y * next_width + x) as usize] = owner;
+        }
+    }
+
+    (next_pixels, next_owners)
+}
@@ -0,0 +1,118 @@
+// Multi-channel signed distance fields for font glyphs
+
+use fdsm::bezier::scanline::FillRule;
+use fdsm::correct_error::{ErrorCorrectionConfig, correct_error_msdf};
+use fdsm::generate::generate_msdf;
+use fdsm::render::correct_sign_msdf;
+use fdsm::shape::Shape;
+use fdsm::transform::Transform;
+use image::{ImageBuffer, Rgb};
+use nalgebra::{Affine2, Similarity2, Vector2};
+use ttf_parser::Face;
+
+// Fixed so repeated runs color edges identically
+const COLORING_SEED: u64 = 69441337420;
+
+pub struct Glyph {
+    pub codepoint: u32,
+    // Advance and plane bounds in ems, y pointing up from the baseline
+    pub advance: f32,
+    pub plane: Option<[f32; 4]>,
+    // RGBA distance field, None for glyphs without an outline such as space
+    pub image: Option<(u32, u32, Vec<u8>)>
+}
+
+pub struct Font {
+    pub ascender: f32,
+    pub descender: f32,
+    pub line_height: f32,
+    pub glyphs: Vec<Glyph>
+}
+
+// Render every character of `charset` the font has a glyph for.
+// `size` is pixels per em and `range` the distance range in pixels.
+pub fn generate(data: &[u8], charset: &str, size: f64, range: f64) -> Result<Font, ttf_parser::FaceParsingError> {
+    let face = Face::parse(data, 0)?;
+    let units_per_em = face.units_per_em() as f64;
+    // Font units per texel
+    let shrinkage = units_per_em / size;
+    let em = |units: f64| (units / units_per_em) as f32;
+
+    let mut glyphs = Vec::new();
+
+    for c in charset.chars() {
+        let id = match face.glyph_index(c) {
+            Some(id) => id,
+            None => continue
+        };
+
+        let advance = em(face.glyph_hor_advance(id).unwrap_or(0) as f64);
+        let outline = face.glyph_bounding_box(id)
+            .and_then(|bbox| fdsm_ttf_parser::load_shape_from_face(&face, id).map(|shape| (bbox, shape)));
+
+        let (bbox, mut shape) = match outline {
+            Some(outline) => outline,
+            None => {
+                glyphs.push(Glyph { codepoint: c as u32, advance, plane: None, image: None });
+                continue;
+            }
+        };
+
+        // Move the glyph into pixel space with a margin of `range` texels
+        let transformation = nalgebra::convert::<_, Affine2<f64>>(Similarity2::new(
+            Vector2::new(
+                range - bbox.x_min as f64 / shrinkage,
+                range - bbox.y_min as f64 / shrinkage
+            ),
+            0.0,
+            1.0 / shrinkage
+        ));
+        shape.transform(&transformation);
+
+        let width = ((bbox.x_max as f64 - bbox.x_min as f64) / shrinkage + 2.0 * range).ceil() as u32;
+        let height = ((bbox.y_max as f64 - bbox.y_min as f64) / shrinkage + 2.0 * range).ceil() as u32;
+
+        let colored = Shape::edge_coloring_simple(shape, 0.03, COLORING_SEED);
+        let prepared = colored.prepare();
+
+        let mut msdf: ImageBuffer<Rgb<f32>, Vec<f32>> = ImageBuffer::new(width, height);
+        generate_msdf(&prepared, range, &mut msdf);
+        correct_error_msdf(&mut msdf, &colored, &prepared, range, &ErrorCorrectionConfig::default());
+        correct_sign_msdf(&mut msdf, &prepared, FillRule::Nonzero);
+
+        // Fonts point y up, images point it down
+        let mut rgba = Vec::with_capacity((width * height * 4) as usize);
+        for y in (0..height).rev() {
+            for x in 0..width {
+                let px = msdf.get_pixel(x, y);
+                for c in 0..3 {
+                    rgba.push((px[c].clamp(0.0, 1.0) * 255.0).round() as u8);
+                }
+                rgba.push(255);
+            }
+        }
+
+        let left = bbox.x_min as f64 / shrinkage - range;
+        let bottom = bbox.y_min as f64 / shrinkage - range;
+        let plane = [
+            (left / size) as f32,
+            (bottom / size) as f32,
+            ((left + width as f64) / size) as f32,
+            ((bottom + height as f64) / size) as f32
+        ];
+
+        glyphs.push(Glyph {
+            codepoint: c as u32,
+            advance,
+            plane: Some(plane),
+            image: Some((width, height, rgba))
+        });
+    }
+
+    Ok(Font {
+        ascender: em(face.ascender() as f64),
+        descender: em(face.descender() as f64),
+        line_height: em((face.ascender() - face.descender() + face.line_gap()) as f64),
+        glyphs
+    })
+}
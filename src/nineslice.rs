@@ -0,0 +1,119 @@
+// Nine-slice borders, the insets of a sprite's corners and edges that keep their size when a UI
+// panel drawn from it is stretched
+//
+// Borders come from, in order of precedence, an Android style nine-patch (`panel.9.png`) whose
+// outer pixels mark the stretchable area in black, a sidecar next to the sprite (`panel.png` uses
+// `panel.9.json`):
+//
+//     {"left": 4, "top": 4, "right": 4, "bottom": 6}
+//
+// or the first --nine-slice pattern matching the sprite's path. Insets are in pixels of the
+// untrimmed, unrotated source.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, Deserialize};
+
+use crate::strip_extension;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NineSlice {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32
+}
+
+impl NineSlice {
+    // Parse LEFT,TOP,RIGHT,BOTTOM
+    pub fn parse(value: &str) -> Result<NineSlice, String> {
+        let insets: Vec<u32> = value.split(',')
+            .map(|inset| inset.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("expected LEFT,TOP,RIGHT,BOTTOM in pixels, got {:?}", value))?;
+        match insets[..] {
+            [left, top, right, bottom] => Ok(NineSlice { left, top, right, bottom }),
+            _ => Err(format!("expected LEFT,TOP,RIGHT,BOTTOM in pixels, got {:?}", value))
+        }
+    }
+
+    // Whether the corners fit in a `width` x `height` source without overlapping
+    pub fn fits(&self, width: u32, height: u32) -> bool {
+        self.left + self.right <= width && self.top + self.bottom <= height
+    }
+
+    // The borders of the source resized from `from` to `to`
+    pub fn scale(&self, from: (u32, u32), to: (u32, u32)) -> NineSlice {
+        let scale = |inset: u32, from: u32, to: u32| (inset as f64 * to as f64 / from as f64).round() as u32;
+        NineSlice {
+            left: scale(self.left, from.0, to.0),
+            top: scale(self.top, from.1, to.1),
+            right: scale(self.right, from.0, to.0),
+            bottom: scale(self.bottom, from.1, to.1)
+        }
+    }
+}
+
+impl fmt::Display for NineSlice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.left, self.top, self.right, self.bottom)
+    }
+}
+
+pub fn sidecar(path: &Path) -> PathBuf {
+    path.with_extension("9.json")
+}
+
+// Read the sidecar of the sprite at `path`, None without one
+pub fn read(path: &Path) -> Result<Option<NineSlice>, String> {
+    let text = match std::fs::read_to_string(sidecar(path)) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.to_string())
+    };
+    serde_json::from_str(&text).map(Some).map_err(|err| format!("{:?}: {}", sidecar(path), err))
+}
+
+// Whether the sprite at `path` is a nine-patch, named like `panel.9.png`
+pub fn is_nine_patch(path: &Path) -> bool {
+    path.file_stem().is_some_and(|stem| stem.to_string_lossy().ends_with(".9"))
+}
+
+// The name of a nine-patch sprite without its ".9", `ui/panel.9.png` is `ui/panel.png`
+pub fn sprite_name(name: &str) -> String {
+    let stem = strip_extension(name);
+    match stem.strip_suffix(".9") {
+        Some(base) => format!("{}{}", base, &name[stem.len()..]),
+        None => name.strip_suffix(".9").unwrap_or(name).to_string()
+    }
+}
+
+// Cut the marked outer pixels off a `width` x `height` RGBA nine-patch, returning the size and
+// pixels of the sprite inside with its borders. The top row marks the columns and the left column
+// the rows that stretch; the bottom row and right column only mark where content goes and are ignored.
+pub fn strip_border(width: u32, height: u32, pixels: &[u8]) -> Result<(u32, u32, Vec<u8>, NineSlice), String> {
+    if width < 3 || height < 3 {
+        return Err(format!("a {}x{} nine-patch has no room inside its border", width, height));
+    }
+
+    let pixel = |x: u32, y: u32| &pixels[((y * width + x) * 4) as usize..((y * width + x) * 4 + 4) as usize];
+    let mut border = (0..width).flat_map(|x| [(x, 0), (x, height - 1)])
+        .chain((0..height).flat_map(|y| [(0, y), (width - 1, y)]));
+    if let Some((x, y)) = border.find(|&(x, y)| !matches!(pixel(x, y), [_, _, _, 0] | [0, 0, 0, 255])) {
+        return Err(format!("nine-patch border pixel at {},{} is neither transparent nor black", x, y));
+    }
+
+    // The marked span of the inner pixels along the top row and left column
+    let marked = |length: u32, black: &dyn Fn(u32) -> bool| {
+        let marks: Vec<u32> = (1..length - 1).filter(|&i| black(i)).map(|i| i - 1).collect();
+        Some((*marks.first()?, length - 3 - *marks.last()?))
+    };
+    let (left, right) = marked(width, &|x| pixel(x, 0)[3] == 255).ok_or("nine-patch marks no stretchable columns")?;
+    let (top, bottom) = marked(height, &|y| pixel(0, y)[3] == 255).ok_or("nine-patch marks no stretchable rows")?;
+
+    let inner: Vec<u8> = (1..height - 1)
+        .flat_map(|y| pixels[((y * width + 1) * 4) as usize..((y * width + width - 1) * 4) as usize].iter().copied())
+        .collect();
+    Ok((width - 2, height - 2, inner, NineSlice { left, top, right, bottom }))
+}
@@ -0,0 +1,52 @@
+// Tangent-space normal maps from grayscale heightmaps, and keeping the vectors of normal maps unit
+// length once resampling has averaged them
+
+// Convert a heightmap (height in the red channel, white is high) into an RGBA normal map
+// with +Y pointing up the image (OpenGL convention). Alpha is kept from the heightmap.
+// `strength` scales the height slopes, larger values give steeper normals.
+pub fn from_height(rgba: &[u8], width: u32, height: u32, strength: f32) -> Vec<u8> {
+    let (w, h) = (width as i64, height as i64);
+
+    // Heights clamp at the sprite edges so borders stay flat outward
+    let sample = |x: i64, y: i64| {
+        let x = x.clamp(0, w - 1);
+        let y = y.clamp(0, h - 1);
+        rgba[((y * w + x) * 4) as usize] as f32 / 255.0
+    };
+
+    let mut out = Vec::with_capacity(rgba.len());
+    for y in 0..h {
+        for x in 0..w {
+            // Sobel gradients with y pointing down the image
+            let dx = (sample(x + 1, y - 1) + 2.0 * sample(x + 1, y) + sample(x + 1, y + 1))
+                - (sample(x - 1, y - 1) + 2.0 * sample(x - 1, y) + sample(x - 1, y + 1));
+            let dy = (sample(x - 1, y + 1) + 2.0 * sample(x, y + 1) + sample(x + 1, y + 1))
+                - (sample(x - 1, y - 1) + 2.0 * sample(x, y - 1) + sample(x + 1, y - 1));
+
+            // Sobel weights sum to 8 over a two pixel span
+            let nx = -dx / 8.0 * strength;
+            let ny = dy / 8.0 * strength;
+            let length = (nx * nx + ny * ny + 1.0).sqrt();
+
+            for n in [nx / length, ny / length, 1.0 / length] {
+                out.push(((n * 0.5 + 0.5) * 255.0).round() as u8);
+            }
+            out.push(rgba[((y * w + x) * 4 + 3) as usize]);
+        }
+    }
+
+    out
+}
+
+// Scale the XYZ vector of every RGBA texel back to unit length, a vector averaged away to nothing
+// pointing straight out of the surface
+pub fn renormalize(rgba: &mut [u8]) {
+    for px in rgba.chunks_exact_mut(4) {
+        let n: Vec<f32> = px[..3].iter().map(|&c| c as f32 / 255.0 * 2.0 - 1.0).collect();
+        let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+        let unit = if length > 0.0 { [n[0] / length, n[1] / length, n[2] / length] } else { [0.0, 0.0, 1.0] };
+        for (c, n) in px[..3].iter_mut().zip(unit) {
+            *c = ((n * 0.5 + 0.5) * 255.0).round() as u8;
+        }
+    }
+}
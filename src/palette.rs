@@ -0,0 +1,122 @@
+// Palette swaps for generating recolored variants of the same sprites
+//
+// A palette is either a text file of "old new" RRGGBB pairs, one per line:
+//
+//     # red team -> blue team
+//     c03030 3050c0
+//
+// or a png two pixels tall whose top row holds the old colors and bottom row the new ones.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+pub struct Palette {
+    map: HashMap<[u8; 3], [u8; 3]>
+}
+
+fn parse_color(value: &str) -> Option<[u8; 3]> {
+    if value.len() != 6 || !value.is_ascii() {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&value[i..i+2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+impl Palette {
+    pub fn read(path: &Path) -> Result<Palette, String> {
+        let bytes = std::fs::read(path).map_err(|err| err.to_string())?;
+        let mut map = HashMap::new();
+
+        if path.extension().is_some_and(|ext| ext == "png") {
+            let (info, mut reader) = crate::png_decoder(&bytes).read_info().map_err(|err| err.to_string())?;
+            if info.height != 2 {
+                return Err(format!("palette image must be 2 pixels tall, not {}", info.height));
+            }
+
+            let mut buf = vec![0; info.buffer_size()];
+            reader.next_frame(&mut buf).map_err(|err| err.to_string())?;
+            let rgba = crate::to_rgba(buf, info.color_type);
+
+            let (old, new) = rgba.split_at(rgba.len() / 2);
+            for (old, new) in old.chunks(4).zip(new.chunks(4)) {
+                map.insert([old[0], old[1], old[2]], [new[0], new[1], new[2]]);
+            }
+        } else {
+            let text = String::from_utf8(bytes).map_err(|err| err.to_string())?;
+            for (number, line) in text.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+
+                let colors: Vec<Option<[u8; 3]>> = line.split_whitespace().map(parse_color).collect();
+                match colors.as_slice() {
+                    [Some(old), Some(new)] => {
+                        map.insert(*old, *new);
+                    }
+                    _ => return Err(format!("line {}: expected two RRGGBB colors, got {:?}", number + 1, line))
+                }
+            }
+        }
+
+        Ok(Palette { map })
+    }
+
+    // Replace every pixel whose color is in the palette, alpha is left alone
+    pub fn remap(&self, rgba: &mut [u8]) {
+        for px in rgba.chunks_mut(4) {
+            if let Some(new) = self.map.get(&[px[0], px[1], px[2]]) {
+                px[..3].copy_from_slice(new);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Palette;
+
+    #[test]
+    fn text_and_image_palettes_swap_the_same_colors() {
+        let dir = std::env::temp_dir();
+        let text = dir.join(format!("atlast-{}-palette.txt", std::process::id()));
+        std::fs::write(&text, "# red team -> blue team\nc03030 3050c0\n\n  00ff00   00c000\n").unwrap();
+
+        // Old colors on the top row, new ones below them
+        let image = dir.join(format!("atlast-{}-palette.png", std::process::id()));
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, 2, 2);
+        encoder.set_color(png::ColorType::RGB);
+        encoder.write_header().unwrap()
+            .write_image_data(&[0xc0, 0x30, 0x30, 0x00, 0xff, 0x00, 0x30, 0x50, 0xc0, 0x00, 0xc0, 0x00])
+            .unwrap();
+        std::fs::write(&image, png).unwrap();
+
+        for path in [&text, &image] {
+            let palette = Palette::read(path).unwrap();
+            // Alpha and colors missing from the palette are left alone
+            let mut pixels = [0xc0, 0x30, 0x30, 128, 0, 255, 0, 255, 1, 2, 3, 4];
+            palette.remap(&mut pixels);
+            assert_eq!(pixels, [0x30, 0x50, 0xc0, 128, 0, 0xc0, 0, 255, 1, 2, 3, 4], "{:?}", path);
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn malformed_palettes_are_rejected() {
+        let path = std::env::temp_dir().join(format!("atlast-{}-broken.txt", std::process::id()));
+        std::fs::write(&path, "c03030 3050c0\nc03030 blue\n").unwrap();
+        assert_eq!(Palette::read(&path).err().unwrap(), "line 2: expected two RRGGBB colors, got \"c03030 blue\"");
+
+        let path = path.with_extension("png");
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, 2, 3);
+        encoder.set_color(png::ColorType::RGB);
+        encoder.write_header().unwrap().write_image_data(&[0; 2 * 3 * 3]).unwrap();
+        std::fs::write(&path, png).unwrap();
+        assert_eq!(Palette::read(&path).err().unwrap(), "palette image must be 2 pixels tall, not 3");
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(path.with_extension("txt")).unwrap();
+    }
+}
@@ -0,0 +1,27 @@
+// Paths given on the command line, which on Windows may be extended-length (\\?\C:\art),
+// UNC (\\server\share\art) or extended-length UNC (\\?\UNC\server\share\art)
+//
+// Windows passes extended-length paths to the file system untouched, so a forward slash in one
+// is part of a file name rather than a separator: `\\?\C:\art/ui` would name a single directory
+// "art/ui" and sprites found through it would be named after the wrong component.
+
+use std::borrow::Cow;
+
+// Backslashes for every separator of an extended-length path, other paths are left alone since
+// Windows accepts both separators in them
+pub fn normalize_extended(path: &str) -> Cow<'_, str> {
+    if path.starts_with(r"\\?\") && path.contains('/') {
+        Cow::Owned(path.replace('/', "\\"))
+    } else {
+        Cow::Borrowed(path)
+    }
+}
+
+// A path from the command line ready to use on this platform
+pub fn normalize(path: &str) -> Cow<'_, str> {
+    if cfg!(windows) {
+        normalize_extended(path)
+    } else {
+        Cow::Borrowed(path)
+    }
+}
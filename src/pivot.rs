@@ -0,0 +1,44 @@
+// Pivots, the point of a sprite engines place at its position and rotate it around
+//
+// Pivots are fractions of the untrimmed, unrotated source, 0,0 being its top left and 1,1 its
+// bottom right corner, so they survive trimming and scaling. A sprite takes its pivot from a
+// sidecar next to it (`hero.png` uses `hero.pivot.json`):
+//
+//     {"x": 0.5, "y": 1.0}
+//
+// or the first --sprite-pivot pattern matching its path, and otherwise from --pivot.
+
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Pivot {
+    x: f32,
+    y: f32
+}
+
+// Parse X,Y
+pub fn parse(value: &str) -> Result<(f32, f32), String> {
+    let invalid = || format!("expected X,Y as fractions of the sprite, got {:?}", value);
+    let (x, y) = value.split_once(',').ok_or_else(invalid)?;
+    match (x.trim().parse::<f32>(), y.trim().parse::<f32>()) {
+        (Ok(x), Ok(y)) if x.is_finite() && y.is_finite() => Ok((x, y)),
+        _ => Err(invalid())
+    }
+}
+
+pub fn sidecar(path: &Path) -> PathBuf {
+    path.with_extension("pivot.json")
+}
+
+// Read the sidecar of the sprite at `path`, None without one
+pub fn read(path: &Path) -> Result<Option<(f32, f32)>, String> {
+    let text = match std::fs::read_to_string(sidecar(path)) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.to_string())
+    };
+    let pivot: Pivot = serde_json::from_str(&text).map_err(|err| format!("{:?}: {}", sidecar(path), err))?;
+    Ok(Some((pivot.x, pivot.y)))
+}
@@ -0,0 +1,203 @@
+// Outline polygons, tight meshes around the opaque pixels of a sprite that engines draw instead of
+// its whole rect to save overdraw
+//
+// The outline runs down the right edge of the opaque pixels of every row and back up their left
+// edge, rows without any between others bridging their neighbours, so it has no holes. Each side
+// is then straightened greedily into as few edges as keep it within the tolerance of the pixels,
+// never cutting into them. A polygon made of two sides running top to bottom is monotone, which
+// lets it be cut into triangles in a single sweep.
+
+use serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Polygon {
+    // Corners in pixels, clockwise on screen
+    pub vertices: Vec<(u32, u32)>,
+    // Indices of the corners of every triangle
+    pub triangles: Vec<[u32; 3]>
+}
+
+impl Polygon {
+    // The polygon moved right by `dx` and down by `dy`
+    pub fn translate(&self, dx: u32, dy: u32) -> Polygon {
+        Polygon {
+            vertices: self.vertices.iter().map(|&(x, y)| (x + dx, y + dy)).collect(),
+            triangles: self.triangles.clone()
+        }
+    }
+}
+
+// Trace the opaque pixels of a `width` x `height` RGBA sprite, straying at most `tolerance` pixels
+// from them. A sprite without any is outlined whole.
+pub fn trace(width: u32, height: u32, pixels: &[u8], tolerance: u32) -> Polygon {
+    let opaque = |x: u32, y: u32| pixels[((y * width + x) * 4 + 3) as usize] != 0;
+    let rows: Vec<Option<(u32, u32)>> = (0..height)
+        .map(|y| Some(((0..width).find(|&x| opaque(x, y))?, (0..width).rfind(|&x| opaque(x, y))? + 1)))
+        .collect();
+    let (first, last) = match (rows.iter().position(Option::is_some), rows.iter().rposition(Option::is_some)) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return trace(width, height, &vec![255; pixels.len()], tolerance)
+    };
+
+    let mut spans: Vec<(u32, u32)> = Vec::with_capacity(last - first + 1);
+    for (y, row) in rows.iter().enumerate().take(last + 1).skip(first) {
+        let span = match row {
+            Some(span) => *span,
+            None => {
+                let (above, below) = (spans[spans.len() - 1], rows[y..].iter().flatten().next().unwrap());
+                (above.0.min(below.0), above.1.max(below.1))
+            }
+        };
+        spans.push(span);
+    }
+
+    // How far out either side has to be at each boundary between rows, both rows touching it
+    let rows = spans.len();
+    let touching = |y: usize| &spans[y.max(1) - 1..(y + 1).min(rows)];
+    let right: Vec<i64> = (0..=rows).map(|y| touching(y).iter().map(|span| span.1 as i64).max().unwrap()).collect();
+    // Negated, so pushing the left side outwards makes it larger as well
+    let left: Vec<i64> = (0..=rows).map(|y| -touching(y).iter().map(|span| span.0 as i64).min().unwrap()).collect();
+
+    let corner = |x: i64, y: usize| (x.unsigned_abs() as u32, (first + y) as u32);
+    let right_side: Vec<(u32, u32)> = straighten(&right, tolerance).into_iter().map(|y| corner(right[y], y)).collect();
+    let left_side: Vec<(u32, u32)> = straighten(&left, tolerance).into_iter().map(|y| corner(left[y], y)).collect();
+
+    let triangles = triangulate(&right_side, &left_side);
+    let vertices = right_side.into_iter().chain(left_side.into_iter().rev()).collect();
+    Polygon { vertices, triangles }
+}
+
+// Boundaries where a side of the outline turns, `reach` being how far out it has to be at each. Every
+// edge runs from one boundary as far down as it can while staying out of the pixels and within
+// `tolerance` of them.
+fn straighten(reach: &[i64], tolerance: u32) -> Vec<usize> {
+    // Whether the edge from `from` to `to` keeps to the reach of the boundaries in between, scaled by
+    // the edge's height so it stays in integers
+    let fits = |from: usize, to: usize| {
+        let length = (to - from) as i64;
+        (from + 1..to).all(|y| {
+            let gap = reach[from] * length + (reach[to] - reach[from]) * (y - from) as i64 - reach[y] * length;
+            (0..=tolerance as i64 * length).contains(&gap)
+        })
+    };
+
+    let mut corners = vec![0];
+    let mut from = 0;
+    while from < reach.len() - 1 {
+        let mut to = from + 1;
+        while to + 1 < reach.len() && fits(from, to + 1) {
+            to += 1;
+        }
+        corners.push(to);
+        from = to;
+    }
+    corners
+}
+
+// Triangles of the polygon made of the `right` side top to bottom followed by the `left` side
+// bottom to top, as indices into its corners, sweeping down both sides
+fn triangulate(right: &[(u32, u32)], left: &[(u32, u32)]) -> Vec<[u32; 3]> {
+    // Corners from the top down, the left one first where both sides have one, with their side and
+    // index. Both sides start and end at the same heights, so the top left and bottom right corners
+    // start and end the sweep.
+    let (mut r, mut l) = (0, 0);
+    let mut sweep = Vec::with_capacity(right.len() + left.len());
+    while r < right.len() || l < left.len() {
+        if l == left.len() || (r < right.len() && right[r].1 < left[l].1) {
+            sweep.push((true, right[r], r as u32));
+            r += 1;
+        } else {
+            sweep.push((false, left[l], (right.len() + left.len() - 1 - l) as u32));
+            l += 1;
+        }
+    }
+
+    // Twice the signed area of the triangle, positive when `c` is right of `a` to `b` on screen
+    let turn = |a: usize, b: usize, c: usize| {
+        let ((ax, ay), (bx, by), (cx, cy)) = (sweep[a].1, sweep[b].1, sweep[c].1);
+        (bx as i64 - ax as i64) * (cy as i64 - ay as i64) - (by as i64 - ay as i64) * (cx as i64 - ax as i64)
+    };
+    let mut triangles = Vec::new();
+    let mut emit = |a: usize, b: usize, c: usize| {
+        if turn(a, b, c) != 0 {
+            triangles.push([sweep[a].2, sweep[b].2, sweep[c].2]);
+        }
+    };
+
+    let mut stack = vec![0, 1];
+    for j in 2..sweep.len() - 1 {
+        let top = *stack.last().unwrap();
+        if sweep[j].0 != sweep[top].0 {
+            // Across to the other side everything waiting on the stack can be seen
+            for pair in stack.windows(2) {
+                emit(j, pair[0], pair[1]);
+            }
+            stack = vec![top, j];
+        } else {
+            // Along the same side only the corners bulging outwards can be cut off
+            let mut last = stack.pop().unwrap();
+            while let Some(&next) = stack.last() {
+                let bulge = turn(next, last, j);
+                if (sweep[j].0 && bulge <= 0) || (!sweep[j].0 && bulge >= 0) {
+                    break;
+                }
+                emit(j, last, next);
+                last = stack.pop().unwrap();
+            }
+            stack.push(last);
+            stack.push(j);
+        }
+    }
+    for pair in stack.windows(2) {
+        emit(sweep.len() - 1, pair[0], pair[1]);
+    }
+    triangles
+}
+
+// Horizontal extent of the polygon with corners `points` in each of `height` rows of pixels, as the
+// first column and the one past the last, clamped to `width`
+pub fn spans(points: &[(u32, u32)], width: u32, height: u32) -> Vec<(u32, u32)> {
+    let mut spans = vec![(width, 0); height as usize];
+    for (i, &(x0, y0)) in points.iter().enumerate() {
+        let (x1, y1) = points[(i + 1) % points.len()];
+        let (top, bottom) = (y0.min(y1), y0.max(y1));
+        let x_at = |y: u32| match y1 == y0 {
+            true => (x0.min(x1) as f64, x0.max(x1) as f64),
+            false => {
+                let x = x0 as f64 + (x1 as f64 - x0 as f64) * (y as f64 - y0 as f64) / (y1 as f64 - y0 as f64);
+                (x, x)
+            }
+        };
+        for row in top..bottom.max(top + 1).min(height) {
+            let (a, b) = (x_at(row.max(top)), x_at((row + 1).min(bottom)));
+            let span = &mut spans[row as usize];
+            span.0 = span.0.min(a.0.min(b.0).floor() as u32);
+            span.1 = span.1.max((a.1.max(b.1).ceil() as u32).min(width));
+        }
+    }
+    spans
+}
+
+// Row spans of `polygon` around a sprite trimmed to `offset` within its source and `width` x
+// `height` as placed, upright or `turned` 90° clockwise
+pub fn placed_spans(polygon: &Polygon, offset: (u32, u32), (width, height): (u32, u32), turned: bool) -> Vec<(u32, u32)> {
+    // Height of the trimmed pixels before turning them
+    let upright = if turned { width } else { height };
+    let points: Vec<(u32, u32)> = polygon.vertices.iter()
+        .map(|&(x, y)| (x.saturating_sub(offset.0), y.saturating_sub(offset.1)))
+        .map(|(x, y)| if turned { (upright - y.min(upright), x) } else { (x, y) })
+        .collect();
+    spans(&points, width, height)
+}
+
+// Whether sprites covering the row `spans` `a` and `b`, with their top left corners at `a_at` and
+// `b_at`, come closer than `gap` pixels
+pub fn overlap(a: &[(u32, u32)], a_at: (u32, u32), b: &[(u32, u32)], b_at: (u32, u32), gap: u32) -> bool {
+    b.iter().enumerate().filter(|(_, span)| span.0 < span.1).any(|(row, &(left, right))| {
+        let y = b_at.1 + row as u32;
+        let near = y.saturating_sub(gap).max(a_at.1)..(y + gap + 1).min(a_at.1 + a.len() as u32);
+        near.map(|y| a[(y - a_at.1) as usize]).filter(|span| span.0 < span.1).any(|(a_left, a_right)| {
+            a_at.0 + a_left < b_at.0 + right + gap && b_at.0 + left < a_at.0 + a_right + gap
+        })
+    })
+}
@@ -0,0 +1,63 @@
+// Bundles of limits and output formats suited to common targets
+
+use crate::quantize::{PixelFormat, Dither};
+
+pub struct Preset {
+    // Largest texture dimension the target samples reliably
+    pub max_atlas_size: u32,
+    pub max_sprite_size: u32,
+    pub pixel_format: PixelFormat,
+    pub dither: Dither,
+    pub ktx: bool,
+    // Larger textures are more likely to be sampled from mip levels, which bleed further
+    pub padding: u32,
+    // Round the atlas up to power-of-two dimensions
+    pub pot: bool
+}
+
+impl Preset {
+    pub fn from_name(name: &str) -> Option<Preset> {
+        match name {
+            // WebGL 1 only guarantees 4096, only mipmaps power-of-two textures and browsers decode png natively
+            "web" => Some(Preset {
+                max_atlas_size: 4096,
+                max_sprite_size: 2048,
+                pixel_format: PixelFormat::Rgba8888,
+                dither: Dither::None,
+                ktx: false,
+                padding: 2,
+                pot: true
+            }),
+            // Half the memory and bandwidth of RGBA8, dithered to hide banding. Older GLES drivers
+            // expect power-of-two textures.
+            "mobile" => Some(Preset {
+                max_atlas_size: 2048,
+                max_sprite_size: 1024,
+                pixel_format: PixelFormat::Rgba4444,
+                dither: Dither::FloydSteinberg,
+                ktx: true,
+                padding: 2,
+                pot: true
+            }),
+            "desktop" => Some(Preset {
+                max_atlas_size: 8192,
+                max_sprite_size: 4096,
+                pixel_format: PixelFormat::Rgba8888,
+                dither: Dither::None,
+                ktx: true,
+                padding: 4,
+                pot: false
+            }),
+            "console" => Some(Preset {
+                max_atlas_size: 16384,
+                max_sprite_size: 8192,
+                pixel_format: PixelFormat::Rgba8888,
+                dither: Dither::None,
+                ktx: true,
+                padding: 4,
+                pot: false
+            }),
+            _ => None
+        }
+    }
+}
@@ -0,0 +1,51 @@
+// Debug previews: the atlas with every rect outlined in a color of its own, and a legend naming the
+// sprite of each color, so placement, padding and trimming can be checked by eye
+
+use std::fmt::Write;
+
+use crate::Rect;
+
+// Color of the `index`th sprite, hues a golden angle apart so neighbors rarely look alike
+pub fn color(index: usize) -> [u8; 3] {
+    let hue = (index as f64 * 137.508) % 360.0 / 60.0;
+    let fall = (1.0 - (hue % 2.0 - 1.0).abs()) * 255.0;
+    let (r, g, b) = match hue as u32 {
+        0 => (255.0, fall, 0.0),
+        1 => (fall, 255.0, 0.0),
+        2 => (0.0, 255.0, fall),
+        3 => (0.0, fall, 255.0),
+        4 => (fall, 0.0, 255.0),
+        _ => (255.0, 0.0, fall)
+    };
+    [r as u8, g as u8, b as u8]
+}
+
+// Draw the 1px border of `rect` over the RGBA `pixels` of an image `width` wide, inside the rect so
+// it never covers a neighbor
+pub fn outline(pixels: &mut [u8], width: u32, rect: &Rect, color: [u8; 3]) {
+    if rect.width == 0 || rect.height == 0 {
+        return;
+    }
+
+    let (right, bottom) = (rect.x + rect.width - 1, rect.y + rect.height - 1);
+    for y in rect.y..=bottom {
+        for x in rect.x..=right {
+            if x == rect.x || x == right || y == rect.y || y == bottom {
+                let i = ((y * width + x) * 4) as usize;
+                pixels[i..i + 4].copy_from_slice(&[color[0], color[1], color[2], 255]);
+            }
+        }
+    }
+}
+
+// One line per sprite with its color as hex, rect and source size, then its name last as it may
+// hold spaces. Trimmed sprites have a source larger than their rect.
+pub fn legend(sprites: &[(&str, &Rect, (u32, u32))]) -> String {
+    let mut text = String::from("# color x y width height source_width source_height name\n");
+    for (i, (name, rect, (source_width, source_height))) in sprites.iter().enumerate() {
+        let [r, g, b] = color(i);
+        writeln!(text, "#{:02x}{:02x}{:02x} {} {} {} {} {} {} {}",
+                 r, g, b, rect.x, rect.y, rect.width, rect.height, source_width, source_height, name).unwrap();
+    }
+    text
+}
@@ -0,0 +1,69 @@
+// Log output and progress bars of the command line tool
+//
+// Messages go through `log`, so --quiet and -v decide which are shown: errors and warnings keep
+// their "error: " and "warning: " prefixes and go to stderr, so they never mix into reports on
+// stdout, and everything else prints as it is. Bars are drawn on stderr with log lines printed
+// above them, and are hidden with --quiet or when stderr is not a terminal, so piped output is
+// the same as before.
+
+use std::io::{self, Write};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressFinish, ProgressStyle};
+use log::{Level, LevelFilter};
+
+fn bars() -> &'static MultiProgress {
+    static BARS: OnceLock<MultiProgress> = OnceLock::new();
+    BARS.get_or_init(MultiProgress::new)
+}
+
+// Prints log lines to stdout with the bars cleared from under them
+struct AboveBars;
+
+impl Write for AboveBars {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        bars().suspend(|| io::stdout().write_all(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
+
+// Show messages up to `level`, RUST_LOG still overrides it
+pub fn init(level: LevelFilter) {
+    if level < LevelFilter::Info {
+        bars().set_draw_target(ProgressDrawTarget::hidden());
+    }
+
+    env_logger::Builder::new()
+        .filter_level(LevelFilter::Off)
+        .filter_module("atlast", level)
+        .parse_default_env()
+        .format(|buf, record| match record.level() {
+            Level::Error => bars().suspend(|| writeln!(io::stderr(), "error: {}", record.args())),
+            Level::Warn => bars().suspend(|| writeln!(io::stderr(), "warning: {}", record.args())),
+            _ => writeln!(buf, "{}", record.args())
+        })
+        .target(env_logger::Target::Pipe(Box::new(AboveBars)))
+        .init();
+}
+
+// A bar counting the `len` steps of `phase`, cleared when dropped
+pub fn bar(phase: &str, len: u64) -> ProgressBar {
+    let style = ProgressStyle::with_template("{msg:>9} [{bar:30}] {pos}/{len} {elapsed}")
+        .unwrap()
+        .progress_chars("=> ");
+    let bar = ProgressBar::new(len).with_style(style).with_message(phase.to_string()).with_finish(ProgressFinish::AndClear);
+    bars().add(bar)
+}
+
+// A spinner for a phase without steps to count, cleared when dropped
+pub fn spinner(phase: &str) -> ProgressBar {
+    let style = ProgressStyle::with_template("{msg:>9} {spinner} {elapsed}").unwrap();
+    let bar = bars().add(ProgressBar::new_spinner().with_style(style).with_message(phase.to_string()).with_finish(ProgressFinish::AndClear));
+    bar.enable_steady_tick(Duration::from_millis(100));
+    bar
+}
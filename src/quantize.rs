@@ -0,0 +1,179 @@
+// Reduced bit-depth pixel formats for memory constrained targets
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    Rgba8888,
+    Rgba4444,
+    Rgb565
+}
+
+impl PixelFormat {
+    pub fn from_name(name: &str) -> Option<PixelFormat> {
+        match name {
+            "rgba8888" => Some(PixelFormat::Rgba8888),
+            "rgba4444" => Some(PixelFormat::Rgba4444),
+            "rgb565" => Some(PixelFormat::Rgb565),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PixelFormat::Rgba8888 => "rgba8888",
+            PixelFormat::Rgba4444 => "rgba4444",
+            PixelFormat::Rgb565 => "rgb565"
+        }
+    }
+
+    // Bits per channel in RGBA order, 0 for dropped channels
+    pub fn bits(&self) -> [u32; 4] {
+        match self {
+            PixelFormat::Rgba8888 => [8, 8, 8, 8],
+            PixelFormat::Rgba4444 => [4, 4, 4, 4],
+            PixelFormat::Rgb565 => [5, 6, 5, 0]
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dither {
+    None,
+    Ordered,
+    FloydSteinberg
+}
+
+impl Dither {
+    pub fn from_name(name: &str) -> Option<Dither> {
+        match name {
+            "none" => Some(Dither::None),
+            "ordered" => Some(Dither::Ordered),
+            "floyd-steinberg" => Some(Dither::FloydSteinberg),
+            _ => None
+        }
+    }
+}
+
+const BAYER: [[f32; 4]; 4] = [
+    [0.0, 8.0, 2.0, 10.0],
+    [12.0, 4.0, 14.0, 6.0],
+    [3.0, 11.0, 1.0, 9.0],
+    [15.0, 7.0, 13.0, 5.0]
+];
+
+// Quantize an RGBA8 buffer into 16-bit pixels stored little endian
+pub fn quantize(rgba: &[u8], width: u32, height: u32, format: PixelFormat, dither: Dither) -> Vec<u8> {
+    let bits = format.bits();
+    let width = width as usize;
+    let height = height as usize;
+
+    // Working copy in floats so error diffusion can carry fractions
+    let mut values: Vec<f32> = rgba.iter().map(|&v| v as f32).collect();
+    let mut out = Vec::with_capacity(width * height * 2);
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut pixel: u16 = 0;
+
+            for c in 0..4 {
+                if bits[c] == 0 {
+                    continue;
+                }
+
+                let max = ((1 << bits[c]) - 1) as f32;
+                let step = 255.0 / max;
+                let i = (y * width + x) * 4 + c;
+
+                let mut value = values[i];
+                if dither == Dither::Ordered {
+                    value += (BAYER[y % 4][x % 4] / 16.0 - 0.5) * step;
+                }
+
+                let level = (value / step).round().clamp(0.0, max);
+
+                if dither == Dither::FloydSteinberg {
+                    let error = values[i] - level * step;
+                    let mut spread = |dx: isize, dy: usize, weight: f32| {
+                        let nx = x as isize + dx;
+                        let ny = y + dy;
+                        if nx >= 0 && (nx as usize) < width && ny < height {
+                            values[(ny * width + nx as usize) * 4 + c] += error * weight;
+                        }
+                    };
+                    spread(1, 0, 7.0 / 16.0);
+                    spread(-1, 1, 3.0 / 16.0);
+                    spread(0, 1, 5.0 / 16.0);
+                    spread(1, 1, 1.0 / 16.0);
+                }
+
+                // Channels are packed with red in the high bits
+                let shift: u32 = bits[c+1..].iter().sum();
+                pixel |= (level as u16) << shift;
+            }
+
+            out.extend_from_slice(&pixel.to_le_bytes());
+        }
+    }
+
+    out
+}
+
+// Expand pixels in the given format back to RGBA8, dropped channels become opaque
+pub fn expand(data: &[u8], format: PixelFormat) -> Vec<u8> {
+    if format == PixelFormat::Rgba8888 {
+        return data.to_vec();
+    }
+
+    let bits = format.bits();
+
+    data.chunks(2)
+        .flat_map(|px| {
+            let pixel = u16::from_le_bytes([px[0], px[1]]) as u32;
+            let mut rgba = [255u8; 4];
+
+            for c in 0..4 {
+                if bits[c] == 0 {
+                    continue;
+                }
+
+                let max = (1 << bits[c]) - 1;
+                let shift: u32 = bits[c+1..].iter().sum();
+                let level = (pixel >> shift) & max;
+                rgba[c] = ((level * 255 + max / 2) / max) as u8;
+            }
+
+            rgba
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{quantize, Dither, PixelFormat};
+
+    #[test]
+    fn channels_pack_with_red_in_the_high_bits() {
+        let rgba4444 = quantize(&[255, 0, 136, 17], 1, 1, PixelFormat::Rgba4444, Dither::None);
+        assert_eq!(u16::from_le_bytes([rgba4444[0], rgba4444[1]]), 0xf081);
+
+        // RGB565 drops alpha, green keeps the extra bit
+        let rgb565 = quantize(&[255, 130, 0, 0], 1, 1, PixelFormat::Rgb565, Dither::None);
+        assert_eq!(u16::from_le_bytes([rgb565[0], rgb565[1]]), 0xfc00);
+    }
+
+    #[test]
+    fn dithering_keeps_the_average_between_two_levels() {
+        // 8 is just under halfway between the 4-bit levels 0 and 17
+        let gray = [8, 8, 8, 255].repeat(4 * 4);
+        let lit = |dither: Dither| {
+            quantize(&gray, 4, 4, PixelFormat::Rgba4444, dither)
+                .chunks(2)
+                .filter(|px| px[1] >> 4 == 1)
+                .count()
+        };
+
+        assert_eq!(lit(Dither::None), 0);
+        // 8 of 16 would be 8.5
+        assert_eq!(lit(Dither::Ordered), 7);
+        assert!((7..=8).contains(&lit(Dither::FloydSteinberg)), "{}", lit(Dither::FloydSteinberg));
+    }
+}
@@ -0,0 +1,1021 @@
+// Reading back atlas archives written by atlast
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+
+use bincode::deserialize;
+use zip::ZipArchive;
+use zip::result::ZipError;
+
+use serde::Deserialize;
+
+use crate::{AnimationRecord, AtlasData, AtlasRecord, CubeRecord, FontRecord, NineSlice, PageRecord};
+use crate::basis;
+use crate::compress::Texels;
+use crate::credits::Credit;
+use crate::crypt::{self, Key};
+use crate::dds;
+use crate::integrity;
+use crate::ktx2;
+use crate::texturepacker;
+use crate::tiles::{self, TileTable};
+use crate::quantize::{PixelFormat, expand};
+
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    Zip(ZipError),
+    Decode(png::DecodingError),
+    Data(bincode::Error),
+    Format(String),
+    // Entries that do not match the hashes stored with them
+    Tampered(Vec<String>),
+    // No hashes to check against, as in atlases written before they were sealed
+    Unsealed
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReadError::Io(err) => write!(f, "{}", err),
+            ReadError::Zip(err) => write!(f, "invalid archive: {}", err),
+            ReadError::Decode(err) => write!(f, "invalid atlas png: {}", err),
+            ReadError::Data(err) => write!(f, "invalid atlas data: {}", err),
+            ReadError::Format(msg) => write!(f, "{}", msg),
+            ReadError::Tampered(problems) => write!(f, "atlas failed its integrity check: {}", problems.join(", ")),
+            ReadError::Unsealed => write!(f, "archive has no {}", integrity::ENTRY)
+        }
+    }
+}
+
+impl Error for ReadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReadError::Io(err) => Some(err),
+            ReadError::Zip(err) => Some(err),
+            ReadError::Decode(err) => Some(err),
+            ReadError::Data(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> ReadError {
+        ReadError::Io(err)
+    }
+}
+
+impl From<ZipError> for ReadError {
+    fn from(err: ZipError) -> ReadError {
+        ReadError::Zip(err)
+    }
+}
+
+impl From<png::DecodingError> for ReadError {
+    fn from(err: png::DecodingError) -> ReadError {
+        ReadError::Decode(err)
+    }
+}
+
+impl From<bincode::Error> for ReadError {
+    fn from(err: bincode::Error) -> ReadError {
+        ReadError::Data(err)
+    }
+}
+
+pub struct AtlasFile {
+    pub data: AtlasData,
+    // How the texels were stored before decoding
+    pub texels: Texels,
+    pub width: u32,
+    pub height: u32,
+    // Texture array layers (times faces for cubemaps), 0 for a regular atlas
+    pub layers: u32,
+    // Number of pages, 0 for a single page atlas
+    pub pages: u32,
+    // Decoded atlas expanded to RGBA8, array layers and pages stacked top to bottom
+    pub pixels: Vec<u8>
+}
+
+fn read_entry<R: Read + io::Seek>(zip: &mut ZipArchive<R>, name: &str) -> Result<Option<Vec<u8>>, ReadError> {
+    let mut entry = match zip.by_name(name) {
+        Ok(entry) => entry,
+        Err(ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err.into())
+    };
+
+    let mut buf = Vec::new();
+    entry.read_to_end(&mut buf)?;
+    Ok(Some(buf))
+}
+
+pub fn decode_png(bytes: &[u8]) -> Result<(u32, u32, Vec<u8>), ReadError> {
+    let (info, mut reader) = crate::png_decoder(bytes).read_info()?;
+
+    let mut buf = vec![0; info.buffer_size()];
+    reader.next_frame(&mut buf)?;
+
+    Ok((info.width, info.height, crate::to_rgba(buf, info.color_type)))
+}
+
+// Read the image stored as `<stem>.png`, `<stem>.ktx2`, `<stem>.dds`, `<stem>.basis` or raw `<stem>.<format>`,
+// raw pixels carry no dimensions so they are taken to be `raw_size`
+fn read_image<R: Read + io::Seek>(zip: &mut ZipArchive<R>, stem: &str, raw_size: (u32, u32)) -> Result<(Texels, u32, u32, u32, Vec<u8>), ReadError> {
+    let container = |name: &str, texture: Option<(Texels, u32, u32, u32, Vec<u8>)>| {
+        let unsupported = || ReadError::Format(format!("unsupported {}.{}", stem, name));
+        let (texels, width, height, layers, raw) = texture.ok_or_else(unsupported)?;
+        let pixels = texels.decode(&raw, width, height, layers.max(1)).ok_or_else(unsupported)?;
+        Ok::<_, ReadError>((texels, width, height, layers, pixels))
+    };
+
+    let image = if let Some(png) = read_entry(zip, &format!("{}.png", stem))? {
+        let (width, height, pixels) = decode_png(&png)?;
+        (Texels::Pixels(PixelFormat::Rgba8888), width, height, 0, pixels)
+    } else if let Some(ktx) = read_entry(zip, &format!("{}.ktx2", stem))? {
+        container("ktx2", ktx2::read(&ktx))?
+    } else if let Some(dds) = read_entry(zip, &format!("{}.dds", stem))? {
+        container("dds", dds::read(&dds))?
+    } else if let Some(texture) = read_entry(zip, &format!("{}.basis", stem))? {
+        let texture = basis::read(&texture).map(|(width, height, layers, pixels)| (Texels::Basis, width, height, layers, pixels));
+        container("basis", texture)?
+    } else {
+        let format = [PixelFormat::Rgba4444, PixelFormat::Rgb565].iter()
+            .find(|format| zip.by_name(&format!("{}.{}", stem, format.name())).is_ok())
+            .copied()
+            .ok_or_else(|| ReadError::Format(format!("archive has no {} image", stem)))?;
+        let raw = read_entry(zip, &format!("{}.{}", stem, format.name()))?.unwrap();
+        (Texels::Pixels(format), raw_size.0, raw_size.1, 0, expand(&raw, format))
+    };
+
+    let (_, width, height, layers, ref pixels) = image;
+    if pixels.len() != (width * height * layers.max(1) * 4) as usize {
+        return Err(ReadError::Format(format!("{} image size does not match its dimensions", stem)));
+    }
+
+    Ok(image)
+}
+
+// Version of the atlas data written by this release, stored as text in `atlas.version`.
+// Atlases from before versioning have no such entry and are version 1.
+pub const FORMAT_VERSION: u32 = 11;
+pub const VERSION_ENTRY: &str = "atlas.version";
+
+// The atlas data is bincode in atlas.data, or JSON in atlas.json since version 4
+pub const DATA_ENTRY: &str = "atlas.data";
+pub const JSON_ENTRY: &str = "atlas.json";
+
+// Version 1 records, a single atlas image without layers or pages
+#[derive(Deserialize)]
+struct RecordV1 {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    name: String
+}
+
+#[derive(Deserialize)]
+struct DataV1 {
+    records: Vec<RecordV1>,
+    width: u32,
+    height: u32
+}
+
+fn migrate_v1(data: DataV1) -> AtlasData {
+    let records = data.records.into_iter()
+        .map(|record| migrate_v3_record(RecordV3 {
+            x: record.x,
+            y: record.y,
+            width: record.width,
+            height: record.height,
+            name: record.name,
+            layer: 0,
+            page: 0,
+            rotated: false
+        }))
+        .collect();
+
+    AtlasData {
+        records,
+        width: data.width,
+        height: data.height,
+        fonts: Vec::new(),
+        layers: 0,
+        cubes: Vec::new(),
+        pages: Vec::new(),
+        tiles: None,
+        credits: Vec::new(),
+        premultiplied_alpha: false,
+        animations: Vec::new()
+    }
+}
+
+// Version 2 records, from before sprites could be rotated
+#[derive(Deserialize)]
+struct RecordV2 {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    name: String,
+    layer: u32,
+    page: u32
+}
+
+// Version 3 records, from before sprites could be trimmed
+#[derive(Deserialize)]
+struct RecordV3 {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    name: String,
+    layer: u32,
+    page: u32,
+    rotated: bool
+}
+
+// Version 4 to 7 records, from before sprites had nine-slice borders
+#[derive(Deserialize)]
+struct RecordV4 {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    name: String,
+    layer: u32,
+    page: u32,
+    rotated: bool,
+    source_width: u32,
+    source_height: u32,
+    offset_x: u32,
+    offset_y: u32
+}
+
+// Version 8 records, from before sprites had pivots
+#[derive(Deserialize)]
+struct RecordV8 {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    name: String,
+    layer: u32,
+    page: u32,
+    rotated: bool,
+    source_width: u32,
+    source_height: u32,
+    offset_x: u32,
+    offset_y: u32,
+    nine_slice: Option<NineSlice>
+}
+
+// Version 9 records, from before sprites carried user data
+#[derive(Deserialize)]
+struct RecordV9 {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    name: String,
+    layer: u32,
+    page: u32,
+    rotated: bool,
+    source_width: u32,
+    source_height: u32,
+    offset_x: u32,
+    offset_y: u32,
+    nine_slice: Option<NineSlice>,
+    pivot_x: f32,
+    pivot_y: f32
+}
+
+// Version 10 records, from before sprites had outline polygons
+#[derive(Deserialize)]
+struct RecordV10 {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    name: String,
+    layer: u32,
+    page: u32,
+    rotated: bool,
+    source_width: u32,
+    source_height: u32,
+    offset_x: u32,
+    offset_y: u32,
+    nine_slice: Option<NineSlice>,
+    pivot_x: f32,
+    pivot_y: f32,
+    user_data: Option<String>
+}
+
+// Version 2 to 4 pages, which could only be UDIM tiles
+#[derive(Deserialize)]
+struct PageRecordV2 {
+    tile: u32,
+    u_offset: u32,
+    v_offset: u32
+}
+
+// Version 2 to 4 data, which only differ in their records
+#[derive(Deserialize)]
+struct DataV2<R> {
+    records: Vec<R>,
+    width: u32,
+    height: u32,
+    fonts: Vec<FontRecord>,
+    layers: u32,
+    cubes: Vec<CubeRecord>,
+    pages: Vec<PageRecordV2>,
+    tiles: Option<TileTable>,
+    credits: Vec<Credit>
+}
+
+fn migrate_v2<R>(data: DataV2<R>, record: impl Fn(R) -> AtlasRecord) -> AtlasData {
+    AtlasData {
+        records: data.records.into_iter().map(record).collect(),
+        width: data.width,
+        height: data.height,
+        fonts: data.fonts,
+        layers: data.layers,
+        cubes: data.cubes,
+        pages: data.pages.into_iter()
+            .map(|page| PageRecord {
+                stem: format!("atlas.{}", page.tile),
+                tile: page.tile,
+                u_offset: page.u_offset,
+                v_offset: page.v_offset
+            })
+            .collect(),
+        tiles: data.tiles,
+        credits: data.credits,
+        premultiplied_alpha: false,
+        animations: Vec::new()
+    }
+}
+
+// Version 5 data, from before atlases could be premultiplied
+#[derive(Deserialize)]
+struct DataV5 {
+    records: Vec<RecordV4>,
+    width: u32,
+    height: u32,
+    fonts: Vec<FontRecord>,
+    layers: u32,
+    cubes: Vec<CubeRecord>,
+    pages: Vec<PageRecord>,
+    tiles: Option<TileTable>,
+    credits: Vec<Credit>
+}
+
+fn migrate_v5(data: DataV5) -> AtlasData {
+    migrate_v6(DataV6 {
+        records: data.records,
+        width: data.width,
+        height: data.height,
+        fonts: data.fonts,
+        layers: data.layers,
+        cubes: data.cubes,
+        pages: data.pages,
+        tiles: data.tiles,
+        credits: data.credits,
+        premultiplied_alpha: false
+    })
+}
+
+// Version 6 data, from before numbered sprites were grouped into animations
+#[derive(Deserialize)]
+struct DataV6 {
+    records: Vec<RecordV4>,
+    width: u32,
+    height: u32,
+    fonts: Vec<FontRecord>,
+    layers: u32,
+    cubes: Vec<CubeRecord>,
+    pages: Vec<PageRecord>,
+    tiles: Option<TileTable>,
+    credits: Vec<Credit>,
+    premultiplied_alpha: bool
+}
+
+fn migrate_v6(data: DataV6) -> AtlasData {
+    migrate_v7(DataV7 {
+        records: data.records,
+        width: data.width,
+        height: data.height,
+        fonts: data.fonts,
+        layers: data.layers,
+        cubes: data.cubes,
+        pages: data.pages,
+        tiles: data.tiles,
+        credits: data.credits,
+        premultiplied_alpha: data.premultiplied_alpha,
+        animations: Vec::new()
+    })
+}
+
+// Version 7 data, from before records had nine-slice borders
+#[derive(Deserialize)]
+struct DataV7 {
+    records: Vec<RecordV4>,
+    width: u32,
+    height: u32,
+    fonts: Vec<FontRecord>,
+    layers: u32,
+    cubes: Vec<CubeRecord>,
+    pages: Vec<PageRecord>,
+    tiles: Option<TileTable>,
+    credits: Vec<Credit>,
+    premultiplied_alpha: bool,
+    animations: Vec<AnimationRecord>
+}
+
+fn migrate_v7(data: DataV7) -> AtlasData {
+    AtlasData {
+        records: data.records.into_iter().map(migrate_v4_record).collect(),
+        width: data.width,
+        height: data.height,
+        fonts: data.fonts,
+        layers: data.layers,
+        cubes: data.cubes,
+        pages: data.pages,
+        tiles: data.tiles,
+        credits: data.credits,
+        premultiplied_alpha: data.premultiplied_alpha,
+        animations: data.animations
+    }
+}
+
+// Version 8 data, from before records had pivots
+#[derive(Deserialize)]
+struct DataV8 {
+    records: Vec<RecordV8>,
+    width: u32,
+    height: u32,
+    fonts: Vec<FontRecord>,
+    layers: u32,
+    cubes: Vec<CubeRecord>,
+    pages: Vec<PageRecord>,
+    tiles: Option<TileTable>,
+    credits: Vec<Credit>,
+    premultiplied_alpha: bool,
+    animations: Vec<AnimationRecord>
+}
+
+fn migrate_v8(data: DataV8) -> AtlasData {
+    AtlasData {
+        records: data.records.into_iter().map(migrate_v8_record).collect(),
+        width: data.width,
+        height: data.height,
+        fonts: data.fonts,
+        layers: data.layers,
+        cubes: data.cubes,
+        pages: data.pages,
+        tiles: data.tiles,
+        credits: data.credits,
+        premultiplied_alpha: data.premultiplied_alpha,
+        animations: data.animations
+    }
+}
+
+// Version 9 data, from before records carried user data
+#[derive(Deserialize)]
+struct DataV9 {
+    records: Vec<RecordV9>,
+    width: u32,
+    height: u32,
+    fonts: Vec<FontRecord>,
+    layers: u32,
+    cubes: Vec<CubeRecord>,
+    pages: Vec<PageRecord>,
+    tiles: Option<TileTable>,
+    credits: Vec<Credit>,
+    premultiplied_alpha: bool,
+    animations: Vec<AnimationRecord>
+}
+
+fn migrate_v9(data: DataV9) -> AtlasData {
+    AtlasData {
+        records: data.records.into_iter().map(migrate_v9_record).collect(),
+        width: data.width,
+        height: data.height,
+        fonts: data.fonts,
+        layers: data.layers,
+        cubes: data.cubes,
+        pages: data.pages,
+        tiles: data.tiles,
+        credits: data.credits,
+        premultiplied_alpha: data.premultiplied_alpha,
+        animations: data.animations
+    }
+}
+
+// Version 10 data, from before records carried outline polygons
+#[derive(Deserialize)]
+struct DataV10 {
+    records: Vec<RecordV10>,
+    width: u32,
+    height: u32,
+    fonts: Vec<FontRecord>,
+    layers: u32,
+    cubes: Vec<CubeRecord>,
+    pages: Vec<PageRecord>,
+    tiles: Option<TileTable>,
+    credits: Vec<Credit>,
+    premultiplied_alpha: bool,
+    animations: Vec<AnimationRecord>
+}
+
+fn migrate_v10(data: DataV10) -> AtlasData {
+    AtlasData {
+        records: data.records.into_iter().map(migrate_v10_record).collect(),
+        width: data.width,
+        height: data.height,
+        fonts: data.fonts,
+        layers: data.layers,
+        cubes: data.cubes,
+        pages: data.pages,
+        tiles: data.tiles,
+        credits: data.credits,
+        premultiplied_alpha: data.premultiplied_alpha,
+        animations: data.animations
+    }
+}
+
+fn migrate_v2_record(record: RecordV2) -> AtlasRecord {
+    migrate_v3_record(RecordV3 {
+        x: record.x,
+        y: record.y,
+        width: record.width,
+        height: record.height,
+        name: record.name,
+        layer: record.layer,
+        page: record.page,
+        rotated: false
+    })
+}
+
+fn migrate_v3_record(record: RecordV3) -> AtlasRecord {
+    // Untrimmed, so the source is the rect turned back
+    let (source_width, source_height) = if record.rotated {
+        (record.height, record.width)
+    } else {
+        (record.width, record.height)
+    };
+
+    migrate_v4_record(RecordV4 {
+        x: record.x,
+        y: record.y,
+        width: record.width,
+        height: record.height,
+        name: record.name,
+        layer: record.layer,
+        page: record.page,
+        rotated: record.rotated,
+        source_width,
+        source_height,
+        offset_x: 0,
+        offset_y: 0
+    })
+}
+
+fn migrate_v4_record(record: RecordV4) -> AtlasRecord {
+    migrate_v8_record(RecordV8 {
+        x: record.x,
+        y: record.y,
+        width: record.width,
+        height: record.height,
+        name: record.name,
+        layer: record.layer,
+        page: record.page,
+        rotated: record.rotated,
+        source_width: record.source_width,
+        source_height: record.source_height,
+        offset_x: record.offset_x,
+        offset_y: record.offset_y,
+        nine_slice: None
+    })
+}
+
+// Sprites were placed by their center before pivots could be set
+fn migrate_v8_record(record: RecordV8) -> AtlasRecord {
+    migrate_v9_record(RecordV9 {
+        x: record.x,
+        y: record.y,
+        width: record.width,
+        height: record.height,
+        name: record.name,
+        layer: record.layer,
+        page: record.page,
+        rotated: record.rotated,
+        source_width: record.source_width,
+        source_height: record.source_height,
+        offset_x: record.offset_x,
+        offset_y: record.offset_y,
+        nine_slice: record.nine_slice,
+        pivot_x: 0.5,
+        pivot_y: 0.5
+    })
+}
+
+fn migrate_v9_record(record: RecordV9) -> AtlasRecord {
+    migrate_v10_record(RecordV10 {
+        x: record.x,
+        y: record.y,
+        width: record.width,
+        height: record.height,
+        name: record.name,
+        layer: record.layer,
+        page: record.page,
+        rotated: record.rotated,
+        source_width: record.source_width,
+        source_height: record.source_height,
+        offset_x: record.offset_x,
+        offset_y: record.offset_y,
+        nine_slice: record.nine_slice,
+        pivot_x: record.pivot_x,
+        pivot_y: record.pivot_y,
+        user_data: None
+    })
+}
+
+fn migrate_v10_record(record: RecordV10) -> AtlasRecord {
+    AtlasRecord {
+        x: record.x,
+        y: record.y,
+        width: record.width,
+        height: record.height,
+        name: record.name,
+        layer: record.layer,
+        page: record.page,
+        rotated: record.rotated,
+        source_width: record.source_width,
+        source_height: record.source_height,
+        offset_x: record.offset_x,
+        offset_y: record.offset_y,
+        nine_slice: record.nine_slice,
+        pivot_x: record.pivot_x,
+        pivot_y: record.pivot_y,
+        user_data: record.user_data,
+        polygon: None
+    }
+}
+
+fn read_records<R: Read + io::Seek>(zip: &mut ZipArchive<R>) -> Result<AtlasData, ReadError> {
+    let version = match read_entry(zip, VERSION_ENTRY)? {
+        Some(text) => String::from_utf8_lossy(&text).trim().parse::<u32>()
+            .map_err(|_| ReadError::Format(format!("invalid {}", VERSION_ENTRY)))?,
+        None => 1
+    };
+
+    // JSON data was introduced with version 4
+    if let Some(json) = read_entry(zip, JSON_ENTRY)? {
+        if !(4..=FORMAT_VERSION).contains(&version) {
+            return Err(ReadError::Format(format!(
+                "atlas format version {} is not supported for {}, this release reads versions 4 to {}", version, JSON_ENTRY, FORMAT_VERSION)));
+        }
+        let invalid = |err: String| ReadError::Format(format!("invalid {}: {}", JSON_ENTRY, err));
+        let json: serde_json::Value = serde_json::from_slice(&json).map_err(|err| invalid(err.to_string()))?;
+        return if texturepacker::is_sheet(&json) {
+            texturepacker::read(json).map_err(invalid)
+        } else if version == 4 {
+            serde_json::from_value(json).map(|data| migrate_v2(data, migrate_v4_record)).map_err(|err| invalid(err.to_string()))
+        } else if version == 5 {
+            serde_json::from_value(json).map(migrate_v5).map_err(|err| invalid(err.to_string()))
+        } else if version == 6 {
+            serde_json::from_value(json).map(migrate_v6).map_err(|err| invalid(err.to_string()))
+        } else if version == 7 {
+            serde_json::from_value(json).map(migrate_v7).map_err(|err| invalid(err.to_string()))
+        } else if version == 8 {
+            serde_json::from_value(json).map(migrate_v8).map_err(|err| invalid(err.to_string()))
+        } else if version == 9 {
+            serde_json::from_value(json).map(migrate_v9).map_err(|err| invalid(err.to_string()))
+        } else if version == 10 {
+            serde_json::from_value(json).map(migrate_v10).map_err(|err| invalid(err.to_string()))
+        } else {
+            serde_json::from_value(json).map_err(|err| invalid(err.to_string()))
+        };
+    }
+
+    let data = read_entry(zip, DATA_ENTRY)?
+        .ok_or_else(|| ReadError::Format(format!("archive has no {} or {}", DATA_ENTRY, JSON_ENTRY)))?;
+    match version {
+        1 => Ok(migrate_v1(deserialize(&data)?)),
+        2 => Ok(migrate_v2(deserialize(&data)?, migrate_v2_record)),
+        3 => Ok(migrate_v2(deserialize(&data)?, migrate_v3_record)),
+        4 => Ok(migrate_v2(deserialize(&data)?, migrate_v4_record)),
+        5 => Ok(migrate_v5(deserialize(&data)?)),
+        6 => Ok(migrate_v6(deserialize(&data)?)),
+        7 => Ok(migrate_v7(deserialize(&data)?)),
+        8 => Ok(migrate_v8(deserialize(&data)?)),
+        9 => Ok(migrate_v9(deserialize(&data)?)),
+        10 => Ok(migrate_v10(deserialize(&data)?)),
+        FORMAT_VERSION => Ok(deserialize(&data)?),
+        _ => Err(ReadError::Format(format!(
+            "atlas format version {} is not supported, this release reads versions 1 to {}", version, FORMAT_VERSION)))
+    }
+}
+
+// Open the archive at `path`, decrypting it with `key` when it is encrypted
+fn open(path: &Path, key: Option<&Key>) -> Result<ZipArchive<Cursor<Vec<u8>>>, ReadError> {
+    let mut bytes = std::fs::read(path)?;
+    if crypt::is_encrypted(&bytes) {
+        let key = key.ok_or_else(|| ReadError::Format("atlas is encrypted and no key was given".to_string()))?;
+        bytes = crypt::decrypt(key, &bytes).map_err(ReadError::Format)?;
+    }
+
+    Ok(ZipArchive::new(Cursor::new(bytes))?)
+}
+
+// Read only the records of an atlas, without decoding its images
+pub fn read_data(path: &Path, key: Option<&Key>) -> Result<AtlasData, ReadError> {
+    let mut zip = open(path, key)?;
+    read_records(&mut zip)
+}
+
+// Check every entry of the atlas against the hashes written with it, cheap enough to run at load time
+pub fn verify(path: &Path, key: Option<&Key>) -> Result<(), ReadError> {
+    verify_archive(&mut open(path, key)?)
+}
+
+// Check an atlas held in memory like `verify`
+pub fn verify_bytes(bytes: &[u8], key: Option<&Key>) -> Result<(), ReadError> {
+    if !crypt::is_encrypted(bytes) {
+        return verify_archive(&mut ZipArchive::new(Cursor::new(bytes))?);
+    }
+
+    let key = key.ok_or_else(|| ReadError::Format("atlas is encrypted and no key was given".to_string()))?;
+    let bytes = crypt::decrypt(key, bytes).map_err(ReadError::Format)?;
+    verify_archive(&mut ZipArchive::new(Cursor::new(bytes))?)
+}
+
+fn verify_archive<R: Read + io::Seek>(zip: &mut ZipArchive<R>) -> Result<(), ReadError> {
+    let stored = read_entry(zip, integrity::ENTRY)?.ok_or(ReadError::Unsealed)?;
+
+    let problems = integrity::check(zip, &stored).map_err(ReadError::Format)?;
+    if !problems.is_empty() {
+        return Err(ReadError::Tampered(problems));
+    }
+    Ok(())
+}
+
+pub fn read(path: &Path, key: Option<&Key>) -> Result<AtlasFile, ReadError> {
+    read_archive(&mut open(path, key)?)
+}
+
+// Read an atlas held in memory, unencrypted archives are read in place without copying them
+pub fn read_bytes(bytes: &[u8], key: Option<&Key>) -> Result<AtlasFile, ReadError> {
+    if !crypt::is_encrypted(bytes) {
+        return read_archive(&mut ZipArchive::new(Cursor::new(bytes))?);
+    }
+
+    let key = key.ok_or_else(|| ReadError::Format("atlas is encrypted and no key was given".to_string()))?;
+    let bytes = crypt::decrypt(key, bytes).map_err(ReadError::Format)?;
+    read_archive(&mut ZipArchive::new(Cursor::new(bytes))?)
+}
+
+fn read_archive<R: Read + io::Seek>(zip: &mut ZipArchive<R>) -> Result<AtlasFile, ReadError> {
+    let data = read_records(zip)?;
+
+    if let Some(table) = &data.tiles {
+        let mut texels = Texels::Pixels(PixelFormat::Rgba8888);
+        let mut entries = Vec::new();
+        for entry in 0..table.entries {
+            let (format, width, height, _, pixels) = read_image(zip, &TileTable::stem(entry), (table.size, table.size))?;
+            if (width, height) != (table.size, table.size) {
+                return Err(ReadError::Format(format!("{} is {}x{} instead of {}x{}", TileTable::stem(entry), width, height, table.size, table.size)));
+            }
+            texels = format;
+            entries.push(pixels);
+        }
+
+        if table.table.iter().flatten().any(|&entry| entry >= table.entries) {
+            return Err(ReadError::Format("tile table refers to a missing entry".to_string()));
+        }
+
+        return Ok(AtlasFile {
+            texels,
+            width: data.width,
+            height: data.height,
+            layers: 0,
+            pages: 0,
+            pixels: tiles::assemble(table, &entries, data.width, data.height),
+            data
+        });
+    }
+
+    if data.pages.is_empty() {
+        let (texels, width, height, layers, pixels) = read_image(zip, "atlas", (data.width, data.height))?;
+        return Ok(AtlasFile {
+            data,
+            texels,
+            width,
+            height,
+            layers,
+            pages: 0,
+            pixels
+        });
+    }
+
+    // Pages are stacked like array layers
+    let mut pixels = Vec::new();
+    let mut texels = Texels::Pixels(PixelFormat::Rgba8888);
+    for page in data.pages.iter() {
+        let (format, width, height, _, page_pixels) = read_image(zip, &page.stem, (data.width, data.height))?;
+        if (width, height) != (data.width, data.height) {
+            return Err(ReadError::Format(format!("{} is {}x{} instead of {}x{}", page.stem, width, height, data.width, data.height)));
+        }
+        texels = format;
+        pixels.extend(page_pixels);
+    }
+
+    Ok(AtlasFile {
+        texels,
+        width: data.width,
+        height: data.height,
+        layers: 0,
+        pages: data.pages.len() as u32,
+        pixels,
+        data
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+    use std::path::Path;
+
+    use zip::write::FileOptions;
+    use zip::{ZipArchive, ZipWriter};
+
+    use super::{read, read_bytes, read_records, ReadError, DATA_ENTRY, FORMAT_VERSION, JSON_ENTRY, VERSION_ENTRY};
+    use crate::{ktx2, AtlasData};
+    use crate::compress::Texels;
+    use crate::quantize::{expand, quantize, Dither, PixelFormat};
+
+    const PIXELS: [u8; 8] = [200, 100, 50, 255, 10, 20, 30, 128];
+
+    // Write an atlas of a 2x1 image stored in `entry`, without any records
+    fn write_archive(path: &Path, entry: &str, image: &[u8], width: u32) {
+        let mut zip = ZipWriter::new(std::fs::File::create(path).unwrap());
+        zip.start_file("atlas.data", FileOptions::default()).unwrap();
+        let data = AtlasData {
+            records: Vec::new(),
+            width,
+            height: 1,
+            fonts: Vec::new(),
+            layers: 0,
+            cubes: Vec::new(),
+            pages: Vec::new(),
+            tiles: None,
+            credits: Vec::new(),
+            premultiplied_alpha: false,
+            animations: Vec::new()
+        };
+        zip.write_all(&bincode::serialize(&data).unwrap()).unwrap();
+        zip.start_file(entry, FileOptions::default()).unwrap();
+        zip.write_all(image).unwrap();
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn every_container_is_expanded_to_rgba8() {
+        let path = std::env::temp_dir().join(format!("atlast-{}-containers.atlas", std::process::id()));
+
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, 2, 1);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.write_header().unwrap().write_image_data(&PIXELS).unwrap();
+        write_archive(&path, "atlas.png", &png, 2);
+        let file = read(&path, None).unwrap();
+        assert_eq!((file.texels, file.width, file.height), (Texels::Pixels(PixelFormat::Rgba8888), 2, 1));
+        assert_eq!(file.pixels, PIXELS);
+
+        let rgba4444 = quantize(&PIXELS, 2, 1, PixelFormat::Rgba4444, Dither::None);
+        let mut ktx = Vec::new();
+        ktx2::write(&mut ktx, Texels::Pixels(PixelFormat::Rgba4444), 2, 1, 0, 1, std::slice::from_ref(&rgba4444)).unwrap();
+        write_archive(&path, "atlas.ktx2", &ktx, 2);
+        let file = read(&path, None).unwrap();
+        assert_eq!(file.texels, Texels::Pixels(PixelFormat::Rgba4444));
+        assert_eq!(file.pixels, expand(&rgba4444, PixelFormat::Rgba4444));
+        // Within half a 4-bit step of the source
+        assert!(file.pixels.iter().zip(PIXELS).all(|(&read, source)| read.abs_diff(source) <= 8), "{:?}", file.pixels);
+
+        // Raw pixels take their size from the atlas data, RGB565 has no alpha to keep
+        let rgb565 = quantize(&PIXELS, 2, 1, PixelFormat::Rgb565, Dither::None);
+        write_archive(&path, "atlas.rgb565", &rgb565, 2);
+        let file = read(&path, None).unwrap();
+        assert_eq!((file.texels, file.pixels[3], file.pixels[7]), (Texels::Pixels(PixelFormat::Rgb565), 255, 255));
+        write_archive(&path, "atlas.rgb565", &rgb565, 3);
+        match read(&path, None) {
+            Err(ReadError::Format(msg)) => assert_eq!(msg, "atlas image size does not match its dimensions"),
+            result => panic!("expected a size mismatch, got {:?}", result.err())
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    // Written by the first release, before atlases had a version entry
+    const V1_ATLAS: &[u8] = include_bytes!("../tests/fixtures/v1.atlas");
+
+    fn archive(entries: &[(&str, &[u8])]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in entries {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(contents).unwrap();
+        }
+        ZipArchive::new(Cursor::new(zip.finish().unwrap().into_inner())).unwrap()
+    }
+
+    #[test]
+    fn version_1_atlases_read_back_through_every_migration() {
+        let atlas = read_bytes(V1_ATLAS, None).unwrap();
+        assert_eq!((atlas.width, atlas.height, atlas.layers, atlas.pages), (4, 2, 0, 0));
+        assert_eq!(&atlas.pixels[..4], [255, 0, 0, 255]);
+        assert_eq!(&atlas.pixels[8..12], [0, 0, 255, 255]);
+
+        let records: Vec<_> = atlas.data.records.iter()
+            .map(|r| (r.name.as_str(), r.x, r.y, r.width, r.height, r.source_width, r.source_height))
+            .collect();
+        assert_eq!(records, [("red", 0, 0, 2, 2, 2, 2), ("blue", 2, 0, 2, 2, 2, 2)]);
+        for record in atlas.data.records.iter() {
+            assert!(!record.rotated && record.nine_slice.is_none() && record.user_data.is_none() && record.polygon.is_none());
+            assert_eq!((record.offset_x, record.offset_y, record.pivot_x, record.pivot_y), (0, 0, 0.5, 0.5));
+        }
+    }
+
+    #[test]
+    fn every_version_migrates_to_the_current_records() {
+        use bincode::serialize;
+
+        // Each version appends fields to the records and data of the one before, and empty lists
+        // are written the same whatever they hold, so nested tuples stand in for the old structs
+        let none = None::<u8>;
+        let empty = Vec::<u8>::new();
+        let v2 = (2u32, 0u32, 2u32, 2u32, "blue", 0u32, 0u32);
+        let v3 = (v2, false);
+        let v4 = (v3, 2u32, 2u32, 0u32, 0u32);
+        let v8 = (v4, none);
+        let v9 = (v8, 0.25f32, 0.75f32);
+        let v10 = (v9, Some("{\"hp\":3}"));
+        let v11 = (v10, none);
+        let v2_data = serialize(&(4u32, 2u32, &empty, 0u32, &empty, &empty, none, &empty)).unwrap();
+        let v6_data = [v2_data.clone(), serialize(&false).unwrap()].concat();
+        let v7_data = [v6_data.clone(), serialize(&empty).unwrap()].concat();
+
+        let cases = [
+            (2, serialize(&v2).unwrap(), &v2_data),
+            (3, serialize(&v3).unwrap(), &v2_data),
+            (4, serialize(&v4).unwrap(), &v2_data),
+            (5, serialize(&v4).unwrap(), &v2_data),
+            (6, serialize(&v4).unwrap(), &v6_data),
+            (7, serialize(&v4).unwrap(), &v7_data),
+            (8, serialize(&v8).unwrap(), &v7_data),
+            (9, serialize(&v9).unwrap(), &v7_data),
+            (10, serialize(&v10).unwrap(), &v7_data),
+            (FORMAT_VERSION, serialize(&v11).unwrap(), &v7_data)
+        ];
+        for (version, record, rest) in cases {
+            let mut data = serialize(&1u64).unwrap();
+            data.extend(record);
+            data.extend(rest);
+            let version_text = version.to_string();
+            let mut zip = archive(&[(VERSION_ENTRY, version_text.as_bytes()), (DATA_ENTRY, &data)]);
+
+            let data = read_records(&mut zip).unwrap_or_else(|err| panic!("version {}: {}", version, err));
+            assert_eq!((data.width, data.height, data.records.len()), (4, 2, 1), "version {}", version);
+            let record = &data.records[0];
+            assert_eq!((record.name.as_str(), record.x, record.y, record.width, record.height), ("blue", 2, 0, 2, 2), "version {}", version);
+            assert_eq!((record.source_width, record.source_height, record.offset_x, record.offset_y), (2, 2, 0, 0), "version {}", version);
+            assert!(!record.rotated && record.nine_slice.is_none() && record.polygon.is_none(), "version {}", version);
+
+            // Sprites were centered before version 9 and had no user data before version 10
+            let pivot = if version < 9 { (0.5, 0.5) } else { (0.25, 0.75) };
+            assert_eq!((record.pivot_x, record.pivot_y), pivot, "version {}", version);
+            assert_eq!(record.user_data.as_deref(), if version < 10 { None } else { Some("{\"hp\":3}") }, "version {}", version);
+        }
+    }
+
+    #[test]
+    fn json_data_reads_back_like_bincode() {
+        let bincode = read_bytes(V1_ATLAS, None).unwrap().data;
+        let version = FORMAT_VERSION.to_string();
+
+        for json in [serde_json::to_vec(&bincode).unwrap(), serde_json::to_vec_pretty(&bincode).unwrap()] {
+            let mut zip = archive(&[(VERSION_ENTRY, version.as_bytes()), (JSON_ENTRY, &json)]);
+            let data = read_records(&mut zip).unwrap();
+            assert_eq!(serde_json::to_value(&data).unwrap(), serde_json::to_value(&bincode).unwrap());
+        }
+
+        // Atlases before version 4 could not hold JSON
+        let json = serde_json::to_vec(&bincode).unwrap();
+        let err = read_records(&mut archive(&[(VERSION_ENTRY, b"3"), (JSON_ENTRY, &json)])).unwrap_err();
+        assert!(err.to_string().contains("atlas format version 3 is not supported for atlas.json"), "{}", err);
+        let err = read_records(&mut archive(&[(VERSION_ENTRY, version.as_bytes()), (JSON_ENTRY, b"{\"records\": 1}")])).unwrap_err();
+        assert!(err.to_string().starts_with("invalid atlas.json: "), "{}", err);
+    }
+}
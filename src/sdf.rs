@@ -0,0 +1,147 @@
+// Signed distance field generation for monochrome and alpha-masked sprites
+
+const INF: f32 = 1e20;
+
+// Squared euclidean distance transform of a single row or column (Felzenszwalb & Huttenlocher)
+fn edt_1d(f: &[f32], d: &mut [f32], v: &mut [usize], z: &mut [f32]) {
+    let n = f.len();
+    let mut k = 0;
+    v[0] = 0;
+    z[0] = -INF;
+    z[1] = INF;
+
+    for q in 1..n {
+        let parabola = |r: usize| {
+            ((f[q] + (q * q) as f32) - (f[r] + (r * r) as f32)) / (2.0 * q as f32 - 2.0 * r as f32)
+        };
+
+        // z[0] is -INF so this always stops at the first parabola
+        let mut s = parabola(v[k]);
+        while s <= z[k] {
+            k -= 1;
+            s = parabola(v[k]);
+        }
+
+        k += 1;
+        v[k] = q;
+        z[k] = s;
+        z[k+1] = INF;
+    }
+
+    k = 0;
+    for (q, out) in d.iter_mut().enumerate() {
+        while z[k+1] < q as f32 {
+            k += 1;
+        }
+        let r = v[k];
+        let dq = q as f32 - r as f32;
+        *out = dq * dq + f[r];
+    }
+}
+
+// Distance from every pixel to the nearest pixel where `target` is set
+fn distance(target: &[bool], width: usize, height: usize) -> Vec<f32> {
+    let mut grid: Vec<f32> = target.iter().map(|&t| if t { 0.0 } else { INF }).collect();
+
+    let n = width.max(height);
+    let mut f = vec![0.0; n];
+    let mut d = vec![0.0; n];
+    let mut v = vec![0; n];
+    let mut z = vec![0.0; n + 1];
+
+    for x in 0..width {
+        for y in 0..height {
+            f[y] = grid[y * width + x];
+        }
+        edt_1d(&f[..height], &mut d[..height], &mut v, &mut z);
+        for y in 0..height {
+            grid[y * width + x] = d[y];
+        }
+    }
+
+    for y in 0..height {
+        f[..width].copy_from_slice(&grid[y * width..(y + 1) * width]);
+        edt_1d(&f[..width], &mut d[..width], &mut v, &mut z);
+        grid[y * width..(y + 1) * width].copy_from_slice(&d[..width]);
+    }
+
+    grid.iter().map(|d| d.sqrt()).collect()
+}
+
+// Convert an RGBA sprite into a gray RGBA distance field grown by `spread` pixels on every side.
+// The shape is the alpha channel when the sprite has transparency, otherwise its luminance.
+pub fn generate(rgba: &[u8], width: u32, height: u32, spread: u32) -> (u32, u32, Vec<u8>) {
+    let out_w = (width + 2 * spread) as usize;
+    let out_h = (height + 2 * spread) as usize;
+
+    let use_alpha = rgba.chunks(4).any(|px| px[3] < 255);
+
+    let mut inside = vec![false; out_w * out_h];
+    for y in 0..height as usize {
+        for x in 0..width as usize {
+            let px = &rgba[(y * width as usize + x) * 4..][..4];
+            let value = if use_alpha {
+                px[3] as u32
+            } else {
+                (px[0] as u32 * 299 + px[1] as u32 * 587 + px[2] as u32 * 114) / 1000
+            };
+            inside[(y + spread as usize) * out_w + x + spread as usize] = value >= 128;
+        }
+    }
+
+    let outside: Vec<bool> = inside.iter().map(|&i| !i).collect();
+    let to_inside = distance(&inside, out_w, out_h);
+    let to_outside = distance(&outside, out_w, out_h);
+
+    let data = (0..out_w * out_h)
+        .flat_map(|i| {
+            // Distances are between pixel centers, the edge lies half a pixel in between
+            let signed = if inside[i] {
+                to_outside[i] - 0.5
+            } else {
+                0.5 - to_inside[i]
+            };
+
+            let value = (0.5 + signed / (2.0 * spread as f32)).clamp(0.0, 1.0);
+            let value = (value * 255.0).round() as u8;
+            [value, value, value, 255]
+        })
+        .collect();
+
+    (out_w as u32, out_h as u32, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate;
+
+    // An 8x8 sprite with a 4x4 square in the middle, drawn with `inside` on `outside`
+    fn square(inside: [u8; 4], outside: [u8; 4]) -> Vec<u8> {
+        (0..8 * 8)
+            .flat_map(|i| if (2..6).contains(&(i % 8)) && (2..6).contains(&(i / 8)) { inside } else { outside })
+            .collect()
+    }
+
+    #[test]
+    fn fields_grow_by_the_spread_and_cross_the_middle_at_the_edge() {
+        let (width, height, field) = generate(&square([255; 4], [0; 4]), 8, 8, 2);
+        assert_eq!((width, height), (12, 12));
+        assert!(field.chunks(4).all(|px| px[0] == px[1] && px[1] == px[2] && px[3] == 255));
+
+        // Half a pixel from the edge on either side, and clamped further than the spread
+        let value = |x: usize, y: usize| field[(y * 12 + x) * 4];
+        assert_eq!((value(3, 6), value(4, 6)), (96, 159));
+        assert_eq!((value(0, 0), value(6, 6)), (0, 223));
+    }
+
+    #[test]
+    fn opaque_sprites_are_shaped_by_their_luminance() {
+        let masked = generate(&square([255; 4], [0; 4]), 8, 8, 2);
+        let opaque = generate(&square([255; 4], [0, 0, 0, 255]), 8, 8, 2);
+        assert_eq!(masked, opaque);
+
+        // Transparency decides as soon as there is any, whatever the colors
+        let dark = generate(&square([0, 0, 0, 255], [255, 255, 255, 0]), 8, 8, 2);
+        assert_eq!(masked, dark);
+    }
+}
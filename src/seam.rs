@@ -0,0 +1,118 @@
+// Seam testing: render sprites out of the atlas with bilinear filtering and
+// measure how much color is pulled in from outside their rects
+
+use crate::Rect;
+
+// Render scales, magnification is where neighbors bleed in
+const SCALES: [f32; 3] = [0.5, 1.5, 2.0];
+
+// Channel difference that counts as contamination
+const THRESHOLD: u8 = 2;
+
+pub struct SeamReport {
+    pub name: String,
+    // Largest channel difference against the isolated sprite
+    pub max_error: u8,
+    pub contaminated: usize,
+    pub samples: usize
+}
+
+// Bilinear sample at texel coordinates, `fetch` resolves integer texel positions
+fn bilinear<F: Fn(i64, i64) -> [u8; 4]>(fetch: F, tx: f32, ty: f32) -> [f32; 4] {
+    let x0 = tx.floor();
+    let y0 = ty.floor();
+    let fx = tx - x0;
+    let fy = ty - y0;
+
+    let mut out = [0.0; 4];
+    for (dx, dy, weight) in [(0, 0, (1.0 - fx) * (1.0 - fy)), (1, 0, fx * (1.0 - fy)), (0, 1, (1.0 - fx) * fy), (1, 1, fx * fy)] {
+        let texel = fetch(x0 as i64 + dx, y0 as i64 + dy);
+        for c in 0..4 {
+            out[c] += texel[c] as f32 * weight;
+        }
+    }
+
+    out
+}
+
+fn texel(pixels: &[u8], width: u32, x: i64, y: i64) -> [u8; 4] {
+    let i = ((y as usize) * width as usize + x as usize) * 4;
+    [pixels[i], pixels[i+1], pixels[i+2], pixels[i+3]]
+}
+
+// Test every sprite, optionally marking contaminated edge texels in `marks` (an RGBA copy of the atlas)
+pub fn test(pixels: &[u8], width: u32, height: u32, sprites: &[(&str, &Rect)], mut marks: Option<&mut Vec<u8>>) -> Vec<SeamReport> {
+    let mut reports = Vec::new();
+
+    for &(name, rect) in sprites {
+        let mut report = SeamReport {
+            name: name.to_string(),
+            max_error: 0,
+            contaminated: 0,
+            samples: 0
+        };
+
+        if rect.width == 0 || rect.height == 0 {
+            reports.push(report);
+            continue;
+        }
+
+        // Clamp to the whole texture, as a GPU sampling the atlas would
+        let atlas = |x: i64, y: i64| {
+            texel(pixels, width, x.clamp(0, width as i64 - 1), y.clamp(0, height as i64 - 1))
+        };
+
+        for &scale in SCALES.iter() {
+            // Each sprite is drawn once stretched and once tiled twice in each direction
+            for tiled in [false, true] {
+                let repeats = if tiled { 2.0 } else { 1.0 };
+                let out_w = ((rect.width as f32 * scale * repeats).round() as u32).max(1);
+                let out_h = ((rect.height as f32 * scale * repeats).round() as u32).max(1);
+
+                // The sprite on its own, clamped or wrapped at its edges
+                let isolated = |x: i64, y: i64| {
+                    let (w, h) = (rect.width as i64, rect.height as i64);
+                    let (x, y) = if tiled {
+                        (x.rem_euclid(w), y.rem_euclid(h))
+                    } else {
+                        (x.clamp(0, w - 1), y.clamp(0, h - 1))
+                    };
+                    atlas(rect.x as i64 + x, rect.y as i64 + y)
+                };
+
+                for oy in 0..out_h {
+                    for ox in 0..out_w {
+                        // Position inside the sprite in texels
+                        let sx = ((ox as f32 + 0.5) / out_w as f32 * repeats * rect.width as f32) % rect.width as f32;
+                        let sy = ((oy as f32 + 0.5) / out_h as f32 * repeats * rect.height as f32) % rect.height as f32;
+
+                        let expected = bilinear(isolated, sx - 0.5, sy - 0.5);
+                        let actual = bilinear(atlas, rect.x as f32 + sx - 0.5, rect.y as f32 + sy - 0.5);
+
+                        let error = (0..4)
+                            .map(|c| (expected[c] - actual[c]).abs().round() as u8)
+                            .max().unwrap();
+
+                        report.samples += 1;
+                        report.max_error = report.max_error.max(error);
+
+                        if error > THRESHOLD {
+                            report.contaminated += 1;
+
+                            if let Some(marks) = marks.as_mut() {
+                                let mx = rect.x + (sx as u32).min(rect.width - 1);
+                                let my = rect.y + (sy as u32).min(rect.height - 1);
+                                let i = ((my * width + mx) * 4) as usize;
+                                marks[i..i+4].copy_from_slice(&[255, 0, 255, 255]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        reports.push(report);
+    }
+
+    reports
+}
@@ -0,0 +1,173 @@
+// Skyline bin packing
+//
+// The bin only remembers the lowest free row in every column, kept as segments of equal height.
+// Sprites sit on top of the skyline, so space below an overhang is never used again, which keeps
+// placement cheap and suits rows of long thin sprites.
+
+use crate::{Bin, Rect};
+
+// How the position of a sprite is chosen among those on the skyline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Heuristic {
+    // Lowest bottom edge, then leftmost
+    BottomLeft,
+    // Least area left unusable below the sprite among the positions growing the used height the
+    // least, then lowest bottom edge
+    MinWaste
+}
+
+impl Heuristic {
+    pub fn from_name(name: &str) -> Option<Heuristic> {
+        match name {
+            "bl" => Some(Heuristic::BottomLeft),
+            "min-waste" => Some(Heuristic::MinWaste),
+            _ => None
+        }
+    }
+}
+
+// Columns x..x+width are free from row y down
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    x: u32,
+    y: u32,
+    width: u32
+}
+
+pub struct Skyline {
+    width: u32,
+    height: u32,
+    heuristic: Heuristic,
+    segments: Vec<Segment>
+}
+
+impl Skyline {
+    pub fn new(width: u32, height: u32, heuristic: Heuristic) -> Skyline {
+        Skyline {
+            width,
+            height,
+            heuristic,
+            segments: vec![Segment { x: 0, y: 0, width }]
+        }
+    }
+
+    fn fit(&self, width: u32, height: u32) -> Option<((u64, u64, u64, u32), Rect)> {
+        let mut best: Option<((u64, u64, u64, u32), Rect)> = None;
+        let top = self.segments.iter().map(|seg| seg.y).max().unwrap_or(0) as u64;
+
+        for (i, start) in self.segments.iter().enumerate() {
+            if start.x + width > self.width {
+                break;
+            }
+
+            // The sprite rests on the highest segment below it
+            let end = start.x + width;
+            let under = self.segments[i..].iter().take_while(|seg| seg.x < end);
+            let y = under.clone().map(|seg| seg.y).max().unwrap();
+            if y + height > self.height {
+                continue;
+            }
+
+            let waste: u64 = under
+                .map(|seg| (y - seg.y) as u64 * (seg.x + seg.width).min(end).saturating_sub(seg.x) as u64)
+                .sum();
+            let bottom = (y + height) as u64;
+            let score = match self.heuristic {
+                Heuristic::BottomLeft => (bottom, 0, 0, start.x),
+                Heuristic::MinWaste => (bottom.max(top), waste, bottom, start.x)
+            };
+
+            if best.as_ref().is_none_or(|(best, _)| score < *best) {
+                best = Some((score, Rect { x: start.x, y, width, height }));
+            }
+        }
+
+        best
+    }
+}
+
+impl Bin for Skyline {
+    fn insert(&mut self, width: u32, height: u32, rotate: bool) -> Option<Rect> {
+        let turned = if rotate && width != height { self.fit(height, width) } else { None };
+        let (_, rect) = self.fit(width, height).into_iter().chain(turned).min_by_key(|(score, _)| *score)?;
+        self.occupy(&rect);
+        Some(rect)
+    }
+
+    // Raise the skyline to the bottom of `used` across its columns
+    fn occupy(&mut self, used: &Rect) {
+        let (left, right, bottom) = (used.x, used.x + used.width, used.y + used.height);
+        let mut segments: Vec<Segment> = Vec::with_capacity(self.segments.len() + 2);
+
+        for seg in self.segments.iter() {
+            let seg_right = seg.x + seg.width;
+            let parts = [
+                (seg.x, seg_right.min(left), seg.y),
+                (seg.x.max(left), seg_right.min(right), seg.y.max(bottom)),
+                (seg.x.max(right), seg_right, seg.y)
+            ];
+
+            for (x, end, y) in parts {
+                if x >= end {
+                    continue;
+                }
+                match segments.last_mut() {
+                    Some(last) if last.y == y => last.width += end - x,
+                    _ => segments.push(Segment { x, y, width: end - x })
+                }
+            }
+        }
+
+        self.segments = segments;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Heuristic, Skyline};
+    use crate::{Bin, Rect};
+
+    #[test]
+    fn sprites_rest_on_the_skyline_without_overlap() {
+        for heuristic in [Heuristic::BottomLeft, Heuristic::MinWaste] {
+            let mut bin = Skyline::new(10, 20, heuristic);
+            let rects: Vec<Rect> = [(6, 2), (4, 5), (3, 3), (7, 1), (2, 6), (5, 4)].iter()
+                .map(|&(width, height)| bin.insert(width, height, false).unwrap())
+                .collect();
+
+            for (i, a) in rects.iter().enumerate() {
+                assert!(a.x + a.width <= 10 && a.y + a.height <= 20, "{:?} leaves the bin", a);
+                for b in rects.iter().skip(i + 1) {
+                    assert!(!a.intersects(b), "{:?}: {:?} overlaps {:?}", heuristic, a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn heuristics_pick_their_own_position() {
+        // Columns 0 to 3 are free from row 3, 4 and 5 from the top and 6 to 9 from row 1
+        let bin = |heuristic| {
+            let mut bin = Skyline::new(10, 20, heuristic);
+            bin.occupy(&Rect { x: 0, y: 0, width: 4, height: 3 });
+            bin.occupy(&Rect { x: 6, y: 0, width: 4, height: 1 });
+            bin
+        };
+
+        // Bottom-left takes the leftmost of the lowest bottom edges, leaving a gap under the sprite,
+        // min waste the one with nothing left under it
+        assert_eq!(bin(Heuristic::BottomLeft).insert(3, 1, false), Some(Rect { x: 4, y: 1, width: 3, height: 1 }));
+        assert_eq!(bin(Heuristic::MinWaste).insert(3, 1, false), Some(Rect { x: 6, y: 1, width: 3, height: 1 }));
+    }
+
+    #[test]
+    fn space_under_an_overhang_is_never_used_again() {
+        let mut bin = Skyline::new(10, 20, Heuristic::BottomLeft);
+        bin.occupy(&Rect { x: 0, y: 0, width: 6, height: 2 });
+        bin.occupy(&Rect { x: 4, y: 2, width: 6, height: 1 });
+
+        // Columns 6 to 9 are empty down to the top, but the sprite sits on the overhang
+        assert_eq!(bin.insert(4, 2, false), Some(Rect { x: 0, y: 2, width: 4, height: 2 }));
+        assert_eq!(bin.insert(4, 2, false), Some(Rect { x: 4, y: 3, width: 4, height: 2 }));
+    }
+}
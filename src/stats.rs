@@ -0,0 +1,48 @@
+// Per-run metrics written with --stats-out for tracking atlases over time
+
+use std::path::Path;
+
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PageStats {
+    pub sprite_pixels: u64,
+    pub total_pixels: u64,
+    // Share of the page covered by sprites, 0 to 1
+    pub fill: f64
+}
+
+// Milliseconds spent in each phase of a build
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Timings {
+    pub load: u64,
+    pub pack: u64,
+    pub write: u64,
+    pub total: u64
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Stats {
+    pub output: String,
+    pub sprites: usize,
+    pub width: u32,
+    pub height: u32,
+    pub pages: Vec<PageStats>,
+    pub sprite_pixels: u64,
+    // Pixels of the output covered by no sprite
+    pub wasted_pixels: u64,
+    // Transparent border pixels removed before packing
+    pub trim_saved_pixels: u64,
+    pub output_bytes: u64,
+    pub timings_ms: Timings
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    atlases: &'a [Stats]
+}
+
+pub fn write(path: &Path, atlases: &[Stats]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(&Report { atlases }).map_err(|err| err.to_string())?;
+    std::fs::write(path, json + "\n").map_err(|err| err.to_string())
+}
@@ -0,0 +1,312 @@
+// Atlas data in TexturePacker's "JSON (Hash)" and "JSON (Array)" layouts, read natively by Phaser,
+// PixiJS and many other engines
+//
+// Rotated frames are turned 90° clockwise in the image and their `frame` keeps the unrotated size,
+// so the area they cover in the image is `frame.h` wide and `frame.w` tall. Animations are listed
+// by name with their frames the way PixiJS reads them, frame durations have no place there.
+// Nine-slice borders are given as the stretchable center of the source, `scale9Borders`, and
+// pivots as fractions of the source from its top left corner. Outline polygons are written the way
+// TexturePacker's polygon mode does, corners as `vertices` in the source and `verticesUV` in the image
+// along with the `triangles` between them.
+
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+
+use crate::{AnimationRecord, AtlasData, AtlasRecord, NineSlice, Polygon};
+
+#[derive(Serialize, Deserialize)]
+struct Area {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32
+}
+
+#[derive(Serialize, Deserialize)]
+struct Size {
+    w: u32,
+    h: u32
+}
+
+#[derive(Serialize, Deserialize)]
+struct Point {
+    x: f32,
+    y: f32
+}
+
+impl Point {
+    // Sheets without pivots place sprites by their center
+    fn center() -> Point {
+        Point { x: 0.5, y: 0.5 }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Frame {
+    // Only in the array layout, the hash layout keys frames by name
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    filename: Option<String>,
+    frame: Area,
+    rotated: bool,
+    trimmed: bool,
+    #[serde(rename = "spriteSourceSize")]
+    sprite_source_size: Area,
+    #[serde(rename = "sourceSize")]
+    source_size: Size,
+    #[serde(default = "Point::center")]
+    pivot: Point,
+    // Attached to the sprite by the user, written as the JSON it is
+    #[serde(rename = "userData", skip_serializing_if = "Option::is_none", default)]
+    user_data: Option<serde_json::Value>,
+    // The stretchable center of nine-slice sprites within the source, as Phaser reads it
+    #[serde(rename = "scale9Borders", skip_serializing_if = "Option::is_none", default)]
+    scale9_borders: Option<Area>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    vertices: Option<Vec<(u32, u32)>>,
+    #[serde(rename = "verticesUV", skip_serializing_if = "Option::is_none", default)]
+    vertices_uv: Option<Vec<(u32, u32)>>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    triangles: Option<Vec<[u32; 3]>>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Frames {
+    Hash(BTreeMap<String, Frame>),
+    Array(Vec<Frame>)
+}
+
+#[derive(Serialize, Deserialize)]
+struct Meta {
+    app: String,
+    version: String,
+    image: String,
+    format: String,
+    size: Size,
+    scale: String,
+    // Only written for premultiplied atlases
+    #[serde(rename = "premultipliedAlpha", skip_serializing_if = "std::ops::Not::not", default)]
+    premultiplied_alpha: bool
+}
+
+#[derive(Serialize, Deserialize)]
+struct Sheet {
+    frames: Frames,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    animations: BTreeMap<String, Vec<String>>,
+    meta: Meta
+}
+
+fn frame(record: &AtlasRecord) -> Frame {
+    let (width, height) = if record.rotated { (record.height, record.width) } else { (record.width, record.height) };
+    Frame {
+        filename: None,
+        frame: Area { x: record.x, y: record.y, w: width, h: height },
+        rotated: record.rotated,
+        trimmed: (width, height) != (record.source_width, record.source_height),
+        sprite_source_size: Area { x: record.offset_x, y: record.offset_y, w: width, h: height },
+        source_size: Size { w: record.source_width, h: record.source_height },
+        pivot: Point { x: record.pivot_x, y: record.pivot_y },
+        user_data: record.user_data.as_ref().and_then(|data| serde_json::from_str(data).ok()),
+        scale9_borders: record.nine_slice.map(|borders| Area {
+            x: borders.left,
+            y: borders.top,
+            w: record.source_width - borders.left - borders.right,
+            h: record.source_height - borders.top - borders.bottom
+        }),
+        vertices: record.polygon.as_ref().map(|polygon| polygon.vertices.clone()),
+        vertices_uv: record.polygon.as_ref().map(|polygon| polygon.vertices.iter().map(|&corner| texel(record, corner)).collect()),
+        triangles: record.polygon.as_ref().map(|polygon| polygon.triangles.clone())
+    }
+}
+
+// Where the corner `x`, `y` of the source of `record` lies in the image
+fn texel(record: &AtlasRecord, (x, y): (u32, u32)) -> (u32, u32) {
+    let (x, y) = (x.saturating_sub(record.offset_x), y.saturating_sub(record.offset_y));
+    if record.rotated {
+        (record.x + record.width.saturating_sub(y), record.y + x)
+    } else {
+        (record.x + x, record.y + y)
+    }
+}
+
+// The sheet for `data` with the image stored as `image` in `format`, frames keyed by name or listed
+pub fn write(data: &AtlasData, image: &str, format: &str, array: bool, pretty: bool) -> serde_json::Result<Vec<u8>> {
+    let frames = if array {
+        Frames::Array(data.records.iter()
+            .map(|record| Frame { filename: Some(record.name.clone()), ..frame(record) })
+            .collect())
+    } else {
+        Frames::Hash(data.records.iter().map(|record| (record.name.clone(), frame(record))).collect())
+    };
+
+    let sheet = Sheet {
+        frames,
+        animations: data.animations.iter()
+            .map(|animation| (animation.name.clone(), animation.frames.clone()))
+            .collect(),
+        meta: Meta {
+            app: "atlast".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            image: image.to_string(),
+            format: format.to_uppercase(),
+            size: Size { w: data.width, h: data.height },
+            scale: "1".to_string(),
+            premultiplied_alpha: data.premultiplied_alpha
+        }
+    };
+
+    if pretty {
+        serde_json::to_vec_pretty(&sheet)
+    } else {
+        serde_json::to_vec(&sheet)
+    }
+}
+
+// Whether a JSON document is a TexturePacker sheet rather than atlast's own data
+pub fn is_sheet(json: &serde_json::Value) -> bool {
+    json.get("frames").is_some() && json.get("meta").is_some()
+}
+
+// Records and size of a sheet, everything else atlast writes has no place in it
+pub fn read(json: serde_json::Value) -> Result<AtlasData, String> {
+    let sheet: Sheet = serde_json::from_value(json).map_err(|err| err.to_string())?;
+
+    let frames: Vec<(String, Frame)> = match sheet.frames {
+        Frames::Hash(frames) => frames.into_iter().collect(),
+        Frames::Array(frames) => frames.into_iter()
+            .map(|frame| Ok((frame.filename.clone().ok_or("array frame without a filename")?, frame)))
+            .collect::<Result<_, String>>()?
+    };
+
+    let records = frames.into_iter()
+        .map(|(name, frame)| {
+            let (width, height) = if frame.rotated { (frame.frame.h, frame.frame.w) } else { (frame.frame.w, frame.frame.h) };
+            AtlasRecord {
+                x: frame.frame.x,
+                y: frame.frame.y,
+                width,
+                height,
+                name,
+                layer: 0,
+                page: 0,
+                rotated: frame.rotated,
+                source_width: frame.source_size.w,
+                source_height: frame.source_size.h,
+                offset_x: frame.sprite_source_size.x,
+                offset_y: frame.sprite_source_size.y,
+                nine_slice: frame.scale9_borders.map(|center| NineSlice {
+                    left: center.x,
+                    top: center.y,
+                    right: frame.source_size.w.saturating_sub(center.x + center.w),
+                    bottom: frame.source_size.h.saturating_sub(center.y + center.h)
+                }),
+                pivot_x: frame.pivot.x,
+                pivot_y: frame.pivot.y,
+                user_data: frame.user_data.map(|data| data.to_string()),
+                polygon: frame.vertices.zip(frame.triangles).map(|(vertices, triangles)| Polygon { vertices, triangles })
+            }
+        })
+        .collect();
+
+    Ok(AtlasData {
+        records,
+        width: sheet.meta.size.w,
+        height: sheet.meta.size.h,
+        fonts: Vec::new(),
+        layers: 0,
+        cubes: Vec::new(),
+        pages: Vec::new(),
+        tiles: None,
+        credits: Vec::new(),
+        premultiplied_alpha: sheet.meta.premultiplied_alpha,
+        animations: sheet.animations.into_iter()
+            .map(|(name, frames)| AnimationRecord { name, frames, duration: None })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{read, write};
+    use crate::{AtlasData, AtlasRecord};
+
+    fn record(name: &str, (x, y, width, height): (u32, u32, u32, u32), rotated: bool, source: (u32, u32), offset: (u32, u32)) -> AtlasRecord {
+        AtlasRecord {
+            x,
+            y,
+            width,
+            height,
+            name: name.to_string(),
+            layer: 0,
+            page: 0,
+            rotated,
+            source_width: source.0,
+            source_height: source.1,
+            offset_x: offset.0,
+            offset_y: offset.1,
+            nine_slice: None,
+            pivot_x: 0.5,
+            pivot_y: 0.5,
+            user_data: None,
+            polygon: None
+        }
+    }
+
+    #[test]
+    fn frames_follow_texturepacker_for_rotated_and_trimmed_sprites() {
+        // An untrimmed 4x4 coin and a hero trimmed from 6x4 to 5x3 at 1,0 and stored turned, 3 wide and 5 tall
+        let data = AtlasData {
+            records: vec![
+                record("coin", (0, 0, 4, 4), false, (4, 4), (0, 0)),
+                record("hero", (4, 0, 3, 5), true, (6, 4), (1, 0))
+            ],
+            width: 7,
+            height: 5,
+            fonts: Vec::new(),
+            layers: 0,
+            cubes: Vec::new(),
+            pages: Vec::new(),
+            tiles: None,
+            credits: Vec::new(),
+            premultiplied_alpha: false,
+            animations: Vec::new()
+        };
+
+        let sheet: serde_json::Value = serde_json::from_slice(&write(&data, "atlas.png", "rgba8888", false, false).unwrap()).unwrap();
+        let fields = |name: &str| ["frame", "rotated", "trimmed", "spriteSourceSize", "sourceSize"].map(|field| sheet["frames"][name][field].clone());
+        assert_eq!(fields("coin"), [
+            json!({"x": 0, "y": 0, "w": 4, "h": 4}),
+            json!(false),
+            json!(false),
+            json!({"x": 0, "y": 0, "w": 4, "h": 4}),
+            json!({"w": 4, "h": 4})
+        ]);
+        // The frame keeps the unrotated size of the trimmed pixels
+        assert_eq!(fields("hero"), [
+            json!({"x": 4, "y": 0, "w": 5, "h": 3}),
+            json!(true),
+            json!(true),
+            json!({"x": 1, "y": 0, "w": 5, "h": 3}),
+            json!({"w": 6, "h": 4})
+        ]);
+        assert_eq!(sheet["meta"]["size"], json!({"w": 7, "h": 5}));
+
+        // The array layout has the same frames, named by filename, and both read back to the records
+        let array: serde_json::Value = serde_json::from_slice(&write(&data, "atlas.png", "rgba8888", true, false).unwrap()).unwrap();
+        assert_eq!(array["frames"][1]["filename"], json!("hero"));
+        assert_eq!(array["frames"][1]["frame"], sheet["frames"]["hero"]["frame"]);
+        for sheet in [sheet, array] {
+            let records: Vec<_> = read(sheet).unwrap().records.into_iter()
+                .map(|r| (r.name, r.x, r.y, r.width, r.height, r.rotated, r.source_width, r.source_height, r.offset_x, r.offset_y))
+                .collect();
+            assert_eq!(records, [
+                ("coin".to_string(), 0, 0, 4, 4, false, 4, 4, 0, 0),
+                ("hero".to_string(), 4, 0, 3, 5, true, 6, 4, 1, 0)
+            ]);
+        }
+    }
+}
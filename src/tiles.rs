@@ -0,0 +1,91 @@
+// Splitting the atlas into fixed-size tiles for virtual texturing
+//
+// Every tile of the atlas is looked up through an indirection table, so fully transparent
+// tiles need no entry at all and identical tiles share one.
+
+use std::collections::HashMap;
+
+use serde::{Serialize, Deserialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TileTable {
+    pub size: u32,
+    pub columns: u32,
+    pub rows: u32,
+    // Entry holding each tile, row by row, None for fully transparent tiles
+    pub table: Vec<Option<u32>>,
+    pub entries: u32
+}
+
+impl TileTable {
+    pub fn stem(entry: u32) -> String {
+        format!("tile.{}", entry)
+    }
+}
+
+// Cut an RGBA image into size x size tiles, padding the last row and column with transparency.
+// Returns the table and the pixels of every distinct entry.
+pub fn split(pixels: &[u8], width: u32, height: u32, size: u32) -> (TileTable, Vec<Vec<u8>>) {
+    let columns = width.div_ceil(size);
+    let rows = height.div_ceil(size);
+
+    let mut entries: Vec<Vec<u8>> = Vec::new();
+    let mut seen: HashMap<Vec<u8>, u32> = HashMap::new();
+    let mut table = Vec::with_capacity((columns * rows) as usize);
+
+    for row in 0..rows {
+        for column in 0..columns {
+            let mut tile = vec![0; (size * size * 4) as usize];
+            for y in 0..size.min(height - row * size) {
+                let x_count = size.min(width - column * size);
+                let src = (((row * size + y) * width + column * size) * 4) as usize;
+                let dst = (y * size * 4) as usize;
+                tile[dst..dst + (x_count * 4) as usize].copy_from_slice(&pixels[src..src + (x_count * 4) as usize]);
+            }
+
+            if tile.chunks(4).all(|px| px[3] == 0) {
+                table.push(None);
+                continue;
+            }
+
+            let entry = *seen.entry(tile.clone()).or_insert_with(|| {
+                entries.push(tile);
+                entries.len() as u32 - 1
+            });
+            table.push(Some(entry));
+        }
+    }
+
+    let table = TileTable {
+        size,
+        columns,
+        rows,
+        table,
+        entries: entries.len() as u32
+    };
+    (table, entries)
+}
+
+// Rebuild the width x height atlas from its tile entries
+pub fn assemble(table: &TileTable, entries: &[Vec<u8>], width: u32, height: u32) -> Vec<u8> {
+    let size = table.size;
+    let mut pixels = vec![0; (width * height * 4) as usize];
+
+    for row in 0..table.rows {
+        for column in 0..table.columns {
+            let tile = match table.table[(row * table.columns + column) as usize] {
+                Some(entry) => &entries[entry as usize],
+                None => continue
+            };
+
+            for y in 0..size.min(height - row * size) {
+                let x_count = size.min(width - column * size);
+                let dst = (((row * size + y) * width + column * size) * 4) as usize;
+                let src = (y * size * 4) as usize;
+                pixels[dst..dst + (x_count * 4) as usize].copy_from_slice(&tile[src..src + (x_count * 4) as usize]);
+            }
+        }
+    }
+
+    pixels
+}
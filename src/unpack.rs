@@ -0,0 +1,52 @@
+// Recovering the sprites of a packed atlas
+//
+// Every record is cut out of the decoded pixels, turned back counterclockwise if it was rotated and
+// placed at its offset in a transparent image of its source size, which undoes --trim.
+
+use crate::reader::AtlasFile;
+use crate::AtlasRecord;
+
+pub struct Sprite {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>
+}
+
+// The sprite of every record as it was before packing
+pub fn extract(file: &AtlasFile) -> Result<Vec<Sprite>, String> {
+    file.data.records.iter().map(|record| sprite(file, record)).collect()
+}
+
+fn sprite(file: &AtlasFile, record: &AtlasRecord) -> Result<Sprite, String> {
+    let stacked = file.layers.max(1).max(file.pages);
+    let index = record.layer.max(record.page);
+    if record.x + record.width > file.width || record.y + record.height > file.height || index >= stacked {
+        return Err(format!("{}: rect lies outside the atlas", record.name));
+    }
+
+    // Unrotated size of the packed pixels
+    let (width, height) = if record.rotated { (record.height, record.width) } else { (record.width, record.height) };
+    if record.offset_x + width > record.source_width || record.offset_y + height > record.source_height {
+        return Err(format!("{}: trimmed pixels lie outside the {}x{} source", record.name, record.source_width, record.source_height));
+    }
+
+    // Layers and pages are stacked top to bottom in the decoded pixels
+    let top = index * file.height + record.y;
+    let mut data = vec![0; (record.source_width * record.source_height * 4) as usize];
+    for y in 0..height {
+        for x in 0..width {
+            let (col, row) = if record.rotated { (record.width - 1 - y, x) } else { (x, y) };
+            let src = (((top + row) * file.width + record.x + col) * 4) as usize;
+            let dst = (((record.offset_y + y) * record.source_width + record.offset_x + x) * 4) as usize;
+            data[dst..dst + 4].copy_from_slice(&file.pixels[src..src + 4]);
+        }
+    }
+
+    Ok(Sprite {
+        name: record.name.clone(),
+        width: record.source_width,
+        height: record.source_height,
+        data
+    })
+}
@@ -0,0 +1,28 @@
+// Custom data attached to sprites and passed through to the atlas data untouched
+//
+// Games carry hitboxes, material types and the like along with their sprites, from a sidecar next
+// to the sprite (`hero.png` uses `hero.meta.json`) or the first --user-data pattern matching its
+// path. The data only has to be valid JSON and is recorded as its text, bincode having no way to
+// store arbitrary JSON values.
+
+use std::path::{Path, PathBuf};
+
+// The JSON `text` without surrounding whitespace, if it is valid
+pub fn check(text: &str) -> Result<&str, String> {
+    serde_json::from_str::<serde_json::Value>(text).map_err(|err| format!("invalid user data: {}", err))?;
+    Ok(text.trim())
+}
+
+pub fn sidecar(path: &Path) -> PathBuf {
+    path.with_extension("meta.json")
+}
+
+// Read the sidecar of the sprite at `path`, None without one
+pub fn read(path: &Path) -> Result<Option<String>, String> {
+    let text = match std::fs::read_to_string(sidecar(path)) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.to_string())
+    };
+    check(&text).map(|data| Some(data.to_string())).map_err(|err| format!("{:?}: {}", sidecar(path), err))
+}
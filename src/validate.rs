@@ -0,0 +1,113 @@
+// Consistency checks of an atlas read back from disk, run by the verify subcommand
+//
+// Every rect has to keep clear of the others on its page or layer, except for folded duplicates
+// sharing one and sprites with outline polygons, whose rects may overlap as long as the polygons
+// do not. Texture coordinates have to stay within their image, UDIM tiles within their tile. Given
+// the sources, every sprite's pixels are hashed and compared against the source of the same name,
+// transparent texels counting as black and texels outside an outline polygon as transparent, since
+// alpha bleeding and overlapping neighbours fill them in.
+
+use sha2::{Digest, Sha256};
+
+use crate::compress::Texels;
+use crate::inspect;
+use crate::polygon::{self, Polygon};
+use crate::reader::AtlasFile;
+use crate::unpack::Sprite;
+use crate::{premultiply, AtlasRecord, Image, Rect};
+
+// Problems with the rects and texture coordinates of `file`
+pub fn check(file: &AtlasFile) -> Vec<String> {
+    let mut problems = Vec::new();
+    let records = &file.data.records;
+
+    for (i, a) in records.iter().enumerate() {
+        for b in records.iter().skip(i + 1) {
+            if overlap(a, b) {
+                problems.push(format!("{}: rect overlaps {}", a.name, b.name));
+            }
+        }
+    }
+
+    for (record, sprite) in records.iter().zip(inspect::inspect(&file.data).sprites) {
+        let (u, v) = match file.data.pages.get(record.page as usize) {
+            Some(page) if page.tile > 0 => (page.u_offset as f64, page.v_offset as f64),
+            _ => (0.0, 0.0)
+        };
+        let [left, top, right, bottom] = sprite.uv;
+        if !(0.0..=1.0).contains(&(left - u)) || !(0.0..=1.0).contains(&(right - u)) ||
+            !(0.0..=1.0).contains(&(top - v)) || !(0.0..=1.0).contains(&(bottom - v)) {
+            problems.push(format!("{}: texture coordinates {:?} lie outside their image", record.name, sprite.uv));
+        }
+    }
+    problems
+}
+
+// Whether two records cover the same texels
+fn overlap(a: &AtlasRecord, b: &AtlasRecord) -> bool {
+    let rect = |record: &AtlasRecord| Rect { x: record.x, y: record.y, width: record.width, height: record.height };
+    if (a.page, a.layer) != (b.page, b.layer) || rect(a) == rect(b) || !rect(a).intersects(&rect(b)) {
+        return false;
+    }
+    match (&a.polygon, &b.polygon) {
+        (Some(a_outline), Some(b_outline)) => {
+            polygon::overlap(&spans(a, a_outline), (a.x, a.y), &spans(b, b_outline), (b.x, b.y), 0)
+        }
+        _ => true
+    }
+}
+
+fn spans(record: &AtlasRecord, outline: &Polygon) -> Vec<(u32, u32)> {
+    polygon::placed_spans(outline, (record.offset_x, record.offset_y), (record.width, record.height), record.rotated)
+}
+
+// Sprites of `file` whose pixels differ from the source of the same name, and sources missing from
+// it. Only atlases storing full RGBA8 texels can be compared.
+pub fn compare(file: &AtlasFile, sprites: &[Sprite], sources: &[Image]) -> Result<Vec<String>, String> {
+    if !matches!(file.texels, Texels::Pixels(format) if format.bits() == [8; 4]) {
+        return Err("only RGBA8 atlases can be compared against their sources".to_string());
+    }
+
+    let mut problems = Vec::new();
+    for (record, sprite) in file.data.records.iter().zip(sprites) {
+        let source = match sources.iter().find(|source| source.name == record.name) {
+            Some(source) => source,
+            None => {
+                problems.push(format!("{}: no such source image", record.name));
+                continue;
+            }
+        };
+
+        let mut pixels = source.data.clone();
+        if file.data.premultiplied_alpha {
+            premultiply(&mut pixels);
+        }
+        let outline = record.polygon.as_ref();
+        if (source.width, source.height) != (sprite.width, sprite.height) ||
+            hash(sprite.width, sprite.height, &sprite.data, outline) != hash(source.width, source.height, &pixels, outline) {
+            problems.push(format!("{}: pixels differ from {:?}", record.name, source.path));
+        }
+    }
+
+    for source in sources.iter().filter(|source| !file.data.records.iter().any(|record| record.name == source.name)) {
+        problems.push(format!("{}: {:?} is not in the atlas", source.name, source.path));
+    }
+    Ok(problems)
+}
+
+// SHA-256 of the visible texels of a `width` x `height` sprite within its `outline`
+pub(crate) fn hash(width: u32, height: u32, pixels: &[u8], outline: Option<&Polygon>) -> [u8; 32] {
+    let spans = match outline {
+        Some(outline) => polygon::spans(&outline.vertices, width, height),
+        None => vec![(0, width); height as usize]
+    };
+
+    let mut hasher = Sha256::new();
+    for (y, row) in pixels.chunks_exact(width as usize * 4).enumerate() {
+        for (x, px) in row.chunks_exact(4).enumerate() {
+            let inside = (spans[y].0..spans[y].1).contains(&(x as u32));
+            hasher.update(if inside && px[3] != 0 { px } else { &[0; 4] });
+        }
+    }
+    hasher.finalize().into()
+}